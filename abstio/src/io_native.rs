@@ -50,7 +50,13 @@ pub fn maybe_read_binary<T: DeserializeOwned>(path: String, timer: &mut Timer) -
     }
 
     timer.read_file(&path)?;
-    bincode::deserialize_from(timer).map_err(|err| err.into())
+    // bincode has no way to handle missing fields, so any change to the format breaks old files
+    bincode::deserialize_from(timer).map_err(|err| {
+        anyhow!(
+            "{}. If this file is from an older version, it needs to be regenerated",
+            err
+        )
+    })
 }
 
 // TODO Idea: Have a wrapper type DotJSON(...) and DotBin(...) to distinguish raw path strings
@@ -101,7 +101,13 @@ pub fn slurp_file<I: AsRef<str>>(path: I) -> Result<Vec<u8>> {
 
 pub fn maybe_read_binary<T: DeserializeOwned>(path: String, _: &mut Timer) -> Result<T> {
     if let Some(raw) = SYSTEM_DATA.get_file(path.trim_start_matches("../data/system/")) {
-        bincode::deserialize(raw.contents()).map_err(|err| err.into())
+        // bincode has no way to handle missing fields, so any change to the format breaks old files
+        bincode::deserialize(raw.contents()).map_err(|err| {
+            anyhow!(
+                "{}. If this file is from an older version, it needs to be regenerated",
+                err
+            )
+        })
     } else {
         bail!("Can't maybe_read_binary {}, it doesn't exist", path)
     }
@@ -7,7 +7,9 @@ use abstutil::{retain_btreemap, Tags, Timer};
 use geom::{Distance, FindClosest, HashablePt2D, Polygon, Pt2D, Ring};
 use kml::{ExtraShape, ExtraShapes};
 use map_model::raw::{RawArea, RawBuilding, RawMap, RawParkingLot, RawRoad, RestrictionType};
-use map_model::{osm, Amenity, AreaType, Direction, DrivingSide, NamePerLanguage};
+use map_model::{
+    osm, parse_turn_lanes, Amenity, AreaType, CrossingType, Direction, DrivingSide, NamePerLanguage,
+};
 
 use crate::osm_geom::{get_multipolygon_members, glue_multipolygon, multipoly_geometry};
 use crate::{transit, Options};
@@ -17,6 +19,8 @@ pub struct OsmExtract {
     pub roads: Vec<(WayID, RawRoad)>,
     /// Traffic signals to the direction they apply
     pub traffic_signals: HashMap<HashablePt2D, Direction>,
+    /// Pedestrian crossings, which might be in the middle of a block
+    pub crossings: HashMap<HashablePt2D, CrossingType>,
     pub osm_node_ids: HashMap<HashablePt2D, NodeID>,
     /// (ID, restriction type, from way ID, via node ID, to way ID)
     pub simple_turn_restrictions: Vec<(RestrictionType, WayID, NodeID, WayID)>,
@@ -57,6 +61,7 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
     let mut out = OsmExtract {
         roads: Vec::new(),
         traffic_signals: HashMap::new(),
+        crossings: HashMap::new(),
         osm_node_ids: HashMap::new(),
         simple_turn_restrictions: Vec::new(),
         complicated_turn_restrictions: Vec::new(),
@@ -76,6 +81,9 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
             };
             out.traffic_signals.insert(node.pt.to_hashable(), dir);
         }
+        if let Some(crossing) = get_crossing_type(&node.tags) {
+            out.crossings.insert(node.pt.to_hashable(), crossing);
+        }
         for amenity in get_bldg_amenities(&node.tags) {
             out.amenities.push((node.pt, amenity));
         }
@@ -101,6 +109,22 @@ pub fn extract_osm(map: &mut RawMap, opts: &Options, timer: &mut Timer) -> OsmEx
                 way.tags.insert(osm::SIDEWALK, "right");
             }
 
+            // Lanes are only restricted to certain turns if all of the markings make sense.
+            // Complain once here, not every time the map is built.
+            let mut problems = Vec::new();
+            for key in &["turn:lanes", "turn:lanes:forward", "turn:lanes:backward"] {
+                if let Some(value) = way.tags.get(key) {
+                    if let Err(err) = parse_turn_lanes(value, opts.map_config.driving_side) {
+                        problems.push(format!("{}={}: {}", key, value, err));
+                    }
+                }
+            }
+            if !problems.is_empty() {
+                let problems = problems.join("; ");
+                warn!("Ignoring turn lanes on {}. {}", id, problems);
+                way.tags.insert(osm::INVALID_TURN_LANES, problems);
+            }
+
             out.roads.push((
                 id,
                 RawRoad {
@@ -537,6 +561,30 @@ fn is_bldg(tags: &Tags) -> bool {
     tags.contains_key("building") && !tags.contains_key("abandoned:man_made")
 }
 
+/// See https://wiki.openstreetmap.org/wiki/Key:crossing. Unmarked crossings are ignored; vehicles
+/// don't have to yield there.
+fn get_crossing_type(tags: &Tags) -> Option<CrossingType> {
+    // Pedestrian signals are sometimes tagged as a traffic signal on the node
+    if !tags.is(osm::HIGHWAY, "crossing")
+        && !(tags.is(osm::HIGHWAY, "traffic_signals") && tags.contains_key("crossing"))
+    {
+        return None;
+    }
+    if tags.is("crossing", "traffic_signals")
+        || tags.is_any(
+            "crossing_ref",
+            vec!["pelican", "puffin", "toucan", "pegasus"],
+        )
+    {
+        return Some(CrossingType::Signalized);
+    }
+    if tags.is_any("crossing", vec!["no", "unmarked", "informal"]) {
+        return None;
+    }
+    // zebra, marked, uncontrolled, or nothing more specific
+    Some(CrossingType::Zebra)
+}
+
 fn get_bldg_amenities(tags: &Tags) -> Vec<Amenity> {
     let mut amenities = Vec::new();
     for key in vec!["amenity", "shop", "craft", "office", "tourism", "leisure"] {
@@ -644,3 +692,65 @@ fn service_road_crosses_parking_lot(map: &RawMap, road: &RawRoad, candidates: Ve
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(kv: Vec<&str>) -> Tags {
+        let mut tags = Tags::empty();
+        for pair in kv {
+            let parts = pair.split('=').collect::<Vec<_>>();
+            tags.insert(parts[0], parts[1]);
+        }
+        tags
+    }
+
+    #[test]
+    fn test_get_crossing_type() {
+        let mut ok = true;
+        for (input, expected) in vec![
+            (vec!["highway=crossing"], Some(CrossingType::Zebra)),
+            (
+                vec!["highway=crossing", "crossing=zebra"],
+                Some(CrossingType::Zebra),
+            ),
+            (
+                vec!["highway=crossing", "crossing=marked"],
+                Some(CrossingType::Zebra),
+            ),
+            (
+                vec!["highway=crossing", "crossing=uncontrolled"],
+                Some(CrossingType::Zebra),
+            ),
+            (
+                vec!["highway=crossing", "crossing=traffic_signals"],
+                Some(CrossingType::Signalized),
+            ),
+            (
+                vec!["highway=crossing", "crossing_ref=puffin"],
+                Some(CrossingType::Signalized),
+            ),
+            (
+                vec!["highway=traffic_signals", "crossing=traffic_signals"],
+                Some(CrossingType::Signalized),
+            ),
+            // Pedestrians can cross here, but nothing tells vehicles to stop
+            (vec!["highway=crossing", "crossing=unmarked"], None),
+            (vec!["highway=crossing", "crossing=no"], None),
+            (vec!["highway=crossing", "crossing=informal"], None),
+            // A normal traffic signal for vehicles
+            (vec!["highway=traffic_signals"], None),
+            (vec!["crossing=zebra"], None),
+        ] {
+            let actual = get_crossing_type(&tags(input.clone()));
+            if actual != expected {
+                ok = false;
+                println!("For input {:?}:", input);
+                println!("    Got {:?}", actual);
+                println!("    Expected {:?}", expected);
+            }
+        }
+        assert!(ok);
+    }
+}
@@ -3,10 +3,14 @@ use std::collections::HashMap;
 use abstutil::{Counter, Timer};
 use geom::{Distance, HashablePt2D, PolyLine, Pt2D};
 use map_model::raw::{OriginalRoad, RawIntersection, RawMap, RawRoad};
-use map_model::{osm, Amenity, Direction, IntersectionType};
+use map_model::{osm, Amenity, CrossingType, Direction, IntersectionType};
 
 use crate::extract::OsmExtract;
 
+/// Crossings mapped closer than this to a junction are just the crosswalks of that junction, which
+/// already exist.
+const MIN_CROSSING_DIST_FROM_INTERSECTION: Distance = Distance::const_meters(20.0);
+
 /// Returns amenities and a mapping of all points to split road. (Some internal points on roads get
 /// removed in this call, so this mapping isn't redundant.)
 pub fn split_up_roads(
@@ -55,18 +59,27 @@ pub fn split_up_roads(
         }
     }
 
+    let mid_block_crossings = find_mid_block_crossings(&input, &pt_to_intersection);
+    for pt in mid_block_crossings.keys() {
+        pt_to_intersection.insert(*pt, input.osm_node_ids[pt]);
+    }
+
     for (pt, id) in &pt_to_intersection {
+        let crossing = mid_block_crossings.get(pt).cloned();
         map.intersections.insert(
             *id,
             RawIntersection {
                 point: pt.to_pt2d(),
-                intersection_type: if input.traffic_signals.remove(pt).is_some() {
+                intersection_type: if input.traffic_signals.remove(pt).is_some()
+                    || crossing == Some(CrossingType::Signalized)
+                {
                     IntersectionType::TrafficSignal
                 } else {
                     IntersectionType::StopSign
                 },
                 // Filled out later
                 elevation: Distance::ZERO,
+                crossing,
             },
         );
     }
@@ -80,6 +93,7 @@ pub fn split_up_roads(
                 intersection_type: IntersectionType::StopSign,
                 // Filled out later
                 elevation: Distance::ZERO,
+                crossing: None,
             },
         );
     }
@@ -236,6 +250,49 @@ pub fn split_up_roads(
     (input.amenities, pt_to_road)
 }
 
+/// Find crossings along roads, far enough away from any intersection that the road should be split
+/// there. Pedestrians can't otherwise cross in the middle of a long block.
+fn find_mid_block_crossings(
+    input: &OsmExtract,
+    pt_to_intersection: &HashMap<HashablePt2D, osm::NodeID>,
+) -> HashMap<HashablePt2D, CrossingType> {
+    let mut results = HashMap::new();
+    for (_, r) in &input.roads {
+        // The distance along the road of every point
+        let mut dists = vec![Distance::ZERO];
+        for pair in r.center_points.windows(2) {
+            let last = *dists.last().unwrap();
+            dists.push(last + pair[0].dist_to(pair[1]));
+        }
+
+        // The first point of a road is always an intersection
+        let mut last_split = Distance::ZERO;
+        for (idx, pt) in r.center_points.iter().enumerate() {
+            let pt = pt.to_hashable();
+            if pt_to_intersection.contains_key(&pt) {
+                last_split = dists[idx];
+                continue;
+            }
+            let crossing = if let Some(c) = input.crossings.get(&pt) {
+                *c
+            } else {
+                continue;
+            };
+            let next_split = (idx..r.center_points.len())
+                .find(|i| pt_to_intersection.contains_key(&r.center_points[*i].to_hashable()))
+                .map(|i| dists[i])
+                .unwrap();
+            if dists[idx] - last_split >= MIN_CROSSING_DIST_FROM_INTERSECTION
+                && next_split - dists[idx] >= MIN_CROSSING_DIST_FROM_INTERSECTION
+            {
+                results.insert(pt, crossing);
+                last_split = dists[idx];
+            }
+        }
+    }
+    results
+}
+
 // TODO Consider doing this in PolyLine::new always. extend() there does this too.
 fn dedupe_angles(pts: Vec<Pt2D>) -> Vec<Pt2D> {
     let mut result = Vec::new();
@@ -266,3 +323,60 @@ fn should_collapse_roundabout(r: &RawRoad) -> bool {
         && r.center_points[0] == *r.center_points.last().unwrap()
         && PolyLine::unchecked_new(r.center_points.clone()).length() < Distance::meters(30.0)
 }
+
+#[cfg(test)]
+mod tests {
+    use abstutil::Tags;
+    use map_model::osm::{NodeID, WayID};
+
+    use super::*;
+
+    #[test]
+    fn test_find_mid_block_crossings() {
+        // A straight road along the x-axis, with junctions at x = 0, 60, and 100
+        let xs = vec![0.0, 10.0, 30.0, 45.0, 50.0, 60.0, 80.0, 100.0];
+        let pt = |x: f64| Pt2D::new(x, 0.0).to_hashable();
+        let mut input = OsmExtract {
+            roads: vec![(
+                WayID(1),
+                RawRoad {
+                    center_points: xs.iter().map(|x| Pt2D::new(*x, 0.0)).collect(),
+                    osm_tags: Tags::empty(),
+                    turn_restrictions: Vec::new(),
+                    complicated_turn_restrictions: Vec::new(),
+                    percent_incline: 0.0,
+                },
+            )],
+            traffic_signals: HashMap::new(),
+            crossings: HashMap::new(),
+            osm_node_ids: HashMap::new(),
+            simple_turn_restrictions: Vec::new(),
+            complicated_turn_restrictions: Vec::new(),
+            amenities: Vec::new(),
+        };
+        let mut pt_to_intersection = HashMap::new();
+        for (idx, x) in xs.iter().enumerate() {
+            input.osm_node_ids.insert(pt(*x), NodeID(idx as i64));
+            if *x == 0.0 || *x == 60.0 || *x == 100.0 {
+                pt_to_intersection.insert(pt(*x), NodeID(idx as i64));
+            }
+        }
+        // Only 10m from the junction at 0
+        input.crossings.insert(pt(10.0), CrossingType::Zebra);
+        input.crossings.insert(pt(30.0), CrossingType::Signalized);
+        // Only 15m from the last crossing
+        input.crossings.insert(pt(45.0), CrossingType::Zebra);
+        // Only 10m from the junction at 60
+        input.crossings.insert(pt(50.0), CrossingType::Zebra);
+        // Exactly 20m from the junctions on both sides
+        input.crossings.insert(pt(80.0), CrossingType::Zebra);
+
+        let mut expected = HashMap::new();
+        expected.insert(pt(30.0), CrossingType::Signalized);
+        expected.insert(pt(80.0), CrossingType::Zebra);
+        assert_eq!(
+            find_mid_block_crossings(&input, &pt_to_intersection),
+            expected
+        );
+    }
+}
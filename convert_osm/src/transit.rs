@@ -135,6 +135,10 @@ pub fn extract_route(
         is_bus,
         osm_rel_id: rel_id,
         gtfs_trip_marker: rel.tags.get("gtfs:trip_marker").cloned(),
+        capacity: rel
+            .tags
+            .get("capacity")
+            .and_then(|x| x.parse::<usize>().ok()),
         stops: keep_stops,
         border_start: None,
         border_end: None,
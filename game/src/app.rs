@@ -76,6 +76,10 @@ impl App {
                     "- bus_arrivals : {} bytes",
                     prettyprint_usize(serialized_size_bytes(&a.bus_arrivals))
                 );
+                println!(
+                    "- bus_departures : {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.bus_departures))
+                );
                 println!(
                     "- passengers_boarding: {} bytes",
                     prettyprint_usize(serialized_size_bytes(&a.passengers_boarding))
@@ -84,6 +88,14 @@ impl App {
                     "- passengers_alighting: {} bytes",
                     prettyprint_usize(serialized_size_bytes(&a.passengers_alighting))
                 );
+                println!(
+                    "- denied_boardings: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.denied_boardings))
+                );
+                println!(
+                    "- transit_loads: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.transit_loads))
+                );
                 println!(
                     "- started_trips: {} bytes",
                     prettyprint_usize(serialized_size_bytes(&a.started_trips))
@@ -108,6 +120,14 @@ impl App {
                     "- parking_lot_changes: {} bytes",
                     prettyprint_usize(serialized_size_bytes(&a.parking_lot_changes))
                 );
+                println!(
+                    "- parking_searches: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.parking_searches))
+                );
+                println!(
+                    "- delivery_stops: {} bytes",
+                    prettyprint_usize(serialized_size_bytes(&a.delivery_stops))
+                );
             }
         }
     }
@@ -361,8 +361,8 @@ pub fn cmp_duration_shorter(app: &App, after: Duration, before: Duration) -> Vec
 pub fn color_for_mode(app: &App, m: TripMode) -> Color {
     match m {
         TripMode::Walk => app.cs.unzoomed_pedestrian,
-        TripMode::Bike => app.cs.unzoomed_bike,
-        TripMode::Transit => app.cs.unzoomed_bus,
+        TripMode::Bike | TripMode::Micromobility => app.cs.unzoomed_bike,
+        TripMode::Transit | TripMode::ParkAndRide | TripMode::BikeAndRide => app.cs.unzoomed_bus,
         TripMode::Drive => app.cs.unzoomed_car,
     }
 }
@@ -246,6 +246,20 @@ fn params_to_controls(ctx: &mut EventCtx, mode: TripMode, params: &RoutingParams
                 0.1,
             ),
         ]));
+        rows.push(Widget::row(vec![
+            "Arterial penalty:".text_widget(ctx).margin_right(20),
+            Spinner::widget(
+                ctx,
+                "arterial penalty",
+                (0.0, 5.0),
+                params.arterial_penalty,
+                0.1,
+            ),
+        ]));
+        rows.push(Widget::row(vec![
+            "Fitness:".text_widget(ctx).margin_right(20),
+            Spinner::widget(ctx, "fitness", (0.1, 3.0), params.fitness, 0.1),
+        ]));
     }
     Widget::col(rows)
 }
@@ -263,6 +277,8 @@ fn controls_to_params(panel: &Panel) -> (TripMode, RoutingParams) {
     params.bike_lane_penalty = panel.spinner("bike lane penalty");
     params.bus_lane_penalty = panel.spinner("bus lane penalty");
     params.driving_lane_penalty = panel.spinner("driving lane penalty");
+    params.arterial_penalty = panel.spinner("arterial penalty");
+    params.fitness = panel.spinner("fitness");
     (TripMode::Bike, params)
 }
 
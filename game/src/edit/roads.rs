@@ -1,13 +1,14 @@
-use geom::{CornerRadii, Distance};
+use geom::{CornerRadii, Distance, Duration};
 use map_gui::render::{Renderable, OUTLINE_THICKNESS};
 use map_gui::ID;
 use map_model::{
-    Direction, EditCmd, EditRoad, LaneID, LaneSpec, LaneType, Road, RoadID, NORMAL_LANE_THICKNESS,
+    Direction, EditCmd, EditRoad, LaneID, LaneSpec, LaneType, ParkingPolicy, Road, RoadID,
+    NORMAL_LANE_THICKNESS,
 };
 use widgetry::{
     lctrl, Choice, Color, ControlState, Drawable, EventCtx, GeomBatch, GeomBatchStack, GfxCtx,
-    HorizontalAlignment, Image, Key, Line, Outcome, Panel, State, Text, TextExt, VerticalAlignment,
-    Widget, DEFAULT_CORNER_RADIUS,
+    HorizontalAlignment, Image, Key, Line, Outcome, Panel, State, Text, TextExt, Toggle,
+    VerticalAlignment, Widget, DEFAULT_CORNER_RADIUS,
 };
 
 use crate::app::{App, Transition};
@@ -209,6 +210,24 @@ impl State<App> for RoadEditor {
                         new.lanes_ltr[idx].width = width;
                     });
                 }
+                "parking price" | "parking time limit" | "residents only" | "loading zone" => {
+                    let policy = ParkingPolicy {
+                        hourly_price: self.main_panel.dropdown_value("parking price"),
+                        time_limit: self.main_panel.dropdown_value("parking time limit"),
+                        permit_only: self.main_panel.is_checked("residents only"),
+                        loading_zone: self.main_panel.is_checked("loading zone"),
+                    };
+                    let mut edits = app.primary.map.get_edits().clone();
+                    edits
+                        .commands
+                        .push(app.primary.map.edit_road_cmd(self.r, |new| {
+                            new.parking_policy = policy.clone();
+                        }));
+                    apply_map_edits(ctx, app, edits);
+                    self.redo_stack.clear();
+
+                    self.recalc_all_panels(ctx, app);
+                }
                 _ => unreachable!(),
             },
             _ => {}
@@ -476,14 +495,86 @@ fn make_main_panel(
         ),
     ]);
 
-    Panel::new(Widget::col(vec![
+    let mut col = vec![
         modify_lane,
         available_lane_types_row,
         current_lanes_ltr,
         road_settings,
-    ]))
-    .aligned(HorizontalAlignment::Left, VerticalAlignment::Center)
-    .build(ctx)
+    ];
+    if road
+        .lanes_ltr()
+        .into_iter()
+        .any(|(_, _, lt)| lt == LaneType::Parking)
+    {
+        let policy = &road.parking_policy;
+        col.push(Widget::row(vec![
+            Line("Parking").secondary().into_widget(ctx).centered_vert(),
+            Widget::dropdown(
+                ctx,
+                "parking price",
+                policy.hourly_price,
+                parking_price_choices(policy.hourly_price),
+            ),
+            Widget::dropdown(
+                ctx,
+                "parking time limit",
+                policy.time_limit,
+                parking_time_limit_choices(app, policy.time_limit),
+            ),
+            Toggle::switch(ctx, "residents only", None, policy.permit_only),
+            Toggle::switch(ctx, "loading zone", None, policy.loading_zone),
+        ]));
+    }
+
+    Panel::new(Widget::col(col))
+        .aligned(HorizontalAlignment::Left, VerticalAlignment::Center)
+        .build(ctx)
+}
+
+fn parking_price_choices(preset: f64) -> Vec<Choice<f64>> {
+    let mut prices = vec![0.0, 1.0, 2.0, 3.0, 5.0, 8.0];
+    if !prices.contains(&preset) {
+        prices.push(preset);
+        prices.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    }
+    prices
+        .into_iter()
+        .map(|x| {
+            if x == 0.0 {
+                Choice::new("free", x)
+            } else {
+                Choice::new(format!("${:.2} per hour", x), x)
+            }
+        })
+        .collect()
+}
+
+fn parking_time_limit_choices(
+    app: &App,
+    preset: Option<Duration>,
+) -> Vec<Choice<Option<Duration>>> {
+    let mut limits = vec![
+        None,
+        Some(Duration::minutes(30)),
+        Some(Duration::hours(1)),
+        Some(Duration::hours(2)),
+        Some(Duration::hours(4)),
+    ];
+    if !limits.contains(&preset) {
+        // None sorts first, so "no time limit" stays at the top
+        let idx = limits
+            .iter()
+            .position(|x| *x > preset)
+            .unwrap_or(limits.len());
+        limits.insert(idx, preset);
+    }
+    limits
+        .into_iter()
+        .map(|x| match x {
+            Some(dt) => Choice::new(format!("{} limit", dt.to_string(&app.opts.units)), x),
+            None => Choice::new("no time limit", x),
+        })
+        .collect()
 }
 
 fn highlight_current_selection(
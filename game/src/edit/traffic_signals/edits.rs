@@ -2,6 +2,7 @@ use geom::Duration;
 use map_gui::tools::{ChooseSomething, PopupMsg};
 use map_model::{
     ControlStopSign, ControlTrafficSignal, EditCmd, EditIntersection, IntersectionID, StageType,
+    TransitPriority,
 };
 use widgetry::{
     Choice, DrawBaselayer, EventCtx, Key, Line, Panel, SimpleState, Spinner, State, Text, TextExt,
@@ -14,7 +15,10 @@ use crate::edit::{apply_map_edits, check_sidewalk_connectivity, StopSignEditor};
 use crate::sandbox::GameplayMode;
 
 pub struct ChangeDuration {
+    plan: usize,
     idx: usize,
+    // Keep actuated stages actuated, instead of turning them into variable ones
+    actuated: bool,
 }
 
 impl ChangeDuration {
@@ -22,8 +26,25 @@ impl ChangeDuration {
         ctx: &mut EventCtx,
         app: &App,
         signal: &ControlTrafficSignal,
+        plan: usize,
         idx: usize,
     ) -> Box<dyn State<App>> {
+        let stage = &signal.plan_stages(plan)[idx];
+        let actuated = matches!(stage.stage_type, StageType::Actuated(_, _, _));
+        // Actuated stages reuse the same spinners, but they mean something a bit different
+        let (duration_label, additional_label, delay_label) = if actuated {
+            (
+                "Minimum duration:",
+                "How much longer can vehicles extend this stage?",
+                "How long of a gap between vehicles ends the stage?",
+            )
+        } else {
+            (
+                "Duration:",
+                "How much additional time can this stage last?",
+                "How long with no demand before the stage ends?",
+            )
+        };
         let panel = Panel::new(Widget::col(vec![
             Widget::row(vec![
                 Line("How long should this stage last?")
@@ -32,12 +53,15 @@ impl ChangeDuration {
                 ctx.style().btn_close_widget(ctx),
             ]),
             Widget::row(vec![
-                "Duration:".text_widget(ctx).centered_vert(),
+                duration_label.text_widget(ctx).centered_vert(),
                 Spinner::widget(
                     ctx,
                     "duration",
-                    (signal.get_min_crossing_time(idx), Duration::minutes(5)),
-                    signal.stages[idx].stage_type.simple_duration(),
+                    (
+                        signal.get_min_crossing_time(plan, idx),
+                        Duration::minutes(5),
+                    ),
+                    stage.stage_type.simple_duration(),
                     Duration::seconds(1.0),
                 ),
             ]),
@@ -45,7 +69,7 @@ impl ChangeDuration {
                 .secondary()
                 .into_widget(ctx),
             Widget::col(vec![
-                Text::from_all(match signal.stages[idx].stage_type {
+                Text::from_all(match stage.stage_type {
                     StageType::Fixed(_) => vec![
                         Line("Fixed timing").small_heading(),
                         Line(" (Adjust both values below to enable variable timing)"),
@@ -54,35 +78,37 @@ impl ChangeDuration {
                         Line("Variable timing").small_heading(),
                         Line(" (Set either values below to 0 to use fixed timing."),
                     ],
+                    StageType::Actuated(_, _, _) => vec![
+                        Line("Actuated timing").small_heading(),
+                        Line(" (Set either values below to 0 to use fixed timing."),
+                    ],
                 })
                 .into_widget(ctx)
                 .named("timing type"),
                 Widget::row(vec![
-                    "How much additional time can this stage last?"
-                        .text_widget(ctx)
-                        .centered_vert(),
+                    additional_label.text_widget(ctx).centered_vert(),
                     Spinner::widget(
                         ctx,
                         "additional",
                         (Duration::ZERO, Duration::minutes(5)),
-                        match signal.stages[idx].stage_type {
+                        match stage.stage_type {
                             StageType::Fixed(_) => Duration::ZERO,
                             StageType::Variable(_, _, additional) => additional,
+                            StageType::Actuated(min, max, _) => max - min,
                         },
                         Duration::seconds(1.0),
                     ),
                 ]),
                 Widget::row(vec![
-                    "How long with no demand before the stage ends?"
-                        .text_widget(ctx)
-                        .centered_vert(),
+                    delay_label.text_widget(ctx).centered_vert(),
                     Spinner::widget(
                         ctx,
                         "delay",
                         (Duration::ZERO, Duration::seconds(300.0)),
-                        match signal.stages[idx].stage_type {
+                        match stage.stage_type {
                             StageType::Fixed(_) => Duration::ZERO,
                             StageType::Variable(_, delay, _) => delay,
+                            StageType::Actuated(_, _, passage) => passage,
                         },
                         Duration::seconds(1.0),
                     ),
@@ -98,7 +124,14 @@ impl ChangeDuration {
                 .build_def(ctx),
         ]))
         .build(ctx);
-        <dyn SimpleState<_>>::new(panel, Box::new(ChangeDuration { idx }))
+        <dyn SimpleState<_>>::new(
+            panel,
+            Box::new(ChangeDuration {
+                plan,
+                idx,
+                actuated,
+            }),
+        )
     }
 }
 
@@ -112,16 +145,19 @@ impl SimpleState<App> for ChangeDuration {
                 let additional = panel.spinner("additional");
                 let new_type = if delay == Duration::ZERO || additional == Duration::ZERO {
                     StageType::Fixed(dt)
+                } else if self.actuated {
+                    StageType::Actuated(dt, dt + additional, delay)
                 } else {
                     StageType::Variable(dt, delay, additional)
                 };
+                let plan = self.plan;
                 let idx = self.idx;
                 Transition::Multi(vec![
                     Transition::Pop,
                     Transition::ModifyState(Box::new(move |state, ctx, app| {
                         let editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
                         editor.add_new_edit(ctx, app, idx, |ts| {
-                            ts.stages[idx].stage_type = new_type.clone();
+                            ts.plan_stages_mut(plan)[idx].stage_type = new_type.clone();
                         });
                     })),
                 ])
@@ -144,6 +180,11 @@ impl SimpleState<App> for ChangeDuration {
                     Line("Fixed timing").small_heading(),
                     Line(" (Adjust both values below to enable variable timing)"),
                 ]
+            } else if self.actuated {
+                vec![
+                    Line("Actuated timing").small_heading(),
+                    Line(" (Set either values below to 0 to use fixed timing."),
+                ]
             } else {
                 vec![
                     Line("Variable timing").small_heading(),
@@ -185,6 +226,8 @@ pub fn edit_entire_signal(
     let use_template = "use template";
     let all_walk = "add an all-walk stage at the end";
     let major_minor_timing = "use timing pattern for a major/minor intersection";
+    let actuated = "use actuated control with loop detectors";
+    let transit_priority = "give priority to buses and trains";
     let stop_sign = "convert to stop signs";
     let close = "close intersection for construction";
     let reset = "reset to default";
@@ -195,6 +238,8 @@ pub fn edit_entire_signal(
         choices.push(all_walk);
     }
     choices.push(major_minor_timing);
+    choices.push(actuated);
+    choices.push(transit_priority);
     // TODO Conflating stop signs and construction here
     if mode.can_edit_stop_signs() {
         choices.push(stop_sign);
@@ -273,6 +318,53 @@ pub fn edit_entire_signal(
                     }
                 }),
             )),
+            x if x == actuated => Transition::Multi(vec![
+                Transition::Pop,
+                Transition::ModifyState(Box::new(move |state, ctx, app| {
+                    let mut new_signal = app.primary.map.get_traffic_signal(i).clone();
+                    new_signal.make_actuated();
+                    let editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+                    editor.add_new_edit(ctx, app, 0, |ts| {
+                        *ts = new_signal.clone();
+                    });
+                })),
+            ]),
+            x if x == transit_priority => Transition::Replace(ChooseSomething::new(
+                ctx,
+                "How much can approaching buses and trains change the timing?",
+                vec![
+                    Choice::new("no transit priority", None),
+                    Choice::new(
+                        "extend or cut short stages by up to 5s",
+                        Some(TransitPriority {
+                            max_extension: Duration::seconds(5.0),
+                            max_truncation: Duration::seconds(5.0),
+                        }),
+                    ),
+                    Choice::new(
+                        "extend or cut short stages by up to 10s",
+                        Some(TransitPriority::default()),
+                    ),
+                    Choice::new(
+                        "extend or cut short stages by up to 20s",
+                        Some(TransitPriority {
+                            max_extension: Duration::seconds(20.0),
+                            max_truncation: Duration::seconds(20.0),
+                        }),
+                    ),
+                ],
+                Box::new(move |tsp, _, _| {
+                    Transition::Multi(vec![
+                        Transition::Pop,
+                        Transition::ModifyState(Box::new(move |state, ctx, app| {
+                            let editor = state.downcast_mut::<TrafficSignalEditor>().unwrap();
+                            editor.add_new_edit(ctx, app, 0, |ts| {
+                                ts.transit_priority = tsp;
+                            });
+                        })),
+                    ])
+                }),
+            )),
             x if x == stop_sign => {
                 original.apply(app);
 
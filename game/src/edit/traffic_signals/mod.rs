@@ -34,6 +34,8 @@ pub struct TrafficSignalEditor {
 
     mode: GameplayMode,
     members: BTreeSet<IntersectionID>,
+    // Which time-of-day plan of the signals is being edited
+    current_plan: usize,
     current_stage: usize,
 
     movements: Vec<DrawMovement>,
@@ -72,9 +74,10 @@ impl TrafficSignalEditor {
         synced.apply(app);
 
         let mut editor = TrafficSignalEditor {
-            side_panel: make_side_panel(ctx, app, &members, 0),
+            side_panel: make_side_panel(ctx, app, &members, 0, 0),
             top_panel: make_top_panel(ctx, app, false, false),
             mode,
+            current_plan: 0,
             current_stage: 0,
             movements: Vec::new(),
             movement_selected: None,
@@ -92,18 +95,43 @@ impl TrafficSignalEditor {
     }
 
     fn change_stage(&mut self, ctx: &mut EventCtx, app: &App, idx: usize) {
+        // Replacing the entire signal might remove plans
+        let num_plans = num_plans(app, &self.members);
+        if self.current_plan >= num_plans {
+            self.current_plan = num_plans - 1;
+        }
+
         if self.current_stage == idx {
-            let mut new = make_side_panel(ctx, app, &self.members, self.current_stage);
+            let mut new = make_side_panel(
+                ctx,
+                app,
+                &self.members,
+                self.current_plan,
+                self.current_stage,
+            );
             new.restore(ctx, &self.side_panel);
             self.side_panel = new;
         } else {
             self.current_stage = idx;
-            self.side_panel = make_side_panel(ctx, app, &self.members, self.current_stage);
+            self.side_panel = make_side_panel(
+                ctx,
+                app,
+                &self.members,
+                self.current_plan,
+                self.current_stage,
+            );
         }
 
         self.recalc_draw_current(ctx, app);
     }
 
+    fn change_plan(&mut self, ctx: &mut EventCtx, app: &App, plan: usize) {
+        self.current_plan = plan;
+        self.current_stage = 0;
+        self.side_panel = make_side_panel(ctx, app, &self.members, plan, 0);
+        self.recalc_draw_current(ctx, app);
+    }
+
     fn add_new_edit<F: Fn(&mut ControlTrafficSignal)>(
         &mut self,
         ctx: &mut EventCtx,
@@ -127,12 +155,17 @@ impl TrafficSignalEditor {
         let mut batch = GeomBatch::new();
         let mut movements = Vec::new();
         for i in &self.members {
-            let stage = &app.primary.map.get_traffic_signal(*i).stages[self.current_stage];
+            let stage = &app
+                .primary
+                .map
+                .get_traffic_signal(*i)
+                .plan_stages(self.current_plan)[self.current_stage];
             for (m, draw) in DrawMovement::for_i(
                 ctx.prerender,
                 &app.primary.map,
                 &app.cs,
                 *i,
+                self.current_plan,
                 self.current_stage,
             ) {
                 if self
@@ -196,7 +229,8 @@ impl State<App> for TrafficSignalEditor {
             .primary
             .map
             .get_traffic_signal(*self.members.iter().next().unwrap());
-        let num_stages = canonical_signal.stages.len();
+        let plan = self.current_plan;
+        let num_stages = canonical_signal.plan_stages(plan).len();
 
         match self.side_panel.event(ctx) {
             Outcome::Clicked(x) => match x.as_ref() {
@@ -218,7 +252,7 @@ impl State<App> for TrafficSignalEditor {
                 }
                 "Add a new stage" => {
                     self.add_new_edit(ctx, app, num_stages, |ts| {
-                        ts.stages.push(Stage::new());
+                        ts.plan_stages_mut(plan).push(Stage::new());
                     });
                     return Transition::Keep;
                 }
@@ -227,32 +261,37 @@ impl State<App> for TrafficSignalEditor {
                         ctx,
                         app,
                         &canonical_signal,
+                        plan,
                         self.current_stage,
                     ));
                 }
                 "delete stage" => {
                     let idx = self.current_stage;
                     self.add_new_edit(ctx, app, 0, |ts| {
-                        ts.stages.remove(idx);
+                        ts.plan_stages_mut(plan).remove(idx);
                     });
                     return Transition::Keep;
                 }
                 "move stage left" => {
                     let idx = self.current_stage;
                     self.add_new_edit(ctx, app, idx - 1, |ts| {
-                        ts.stages.swap(idx, idx - 1);
+                        ts.plan_stages_mut(plan).swap(idx, idx - 1);
                     });
                     return Transition::Keep;
                 }
                 "move stage right" => {
                     let idx = self.current_stage;
                     self.add_new_edit(ctx, app, idx + 1, |ts| {
-                        ts.stages.swap(idx, idx + 1);
+                        ts.plan_stages_mut(plan).swap(idx, idx + 1);
                     });
                     return Transition::Keep;
                 }
                 x => {
-                    if let Some(x) = x.strip_prefix("stage ") {
+                    if let Some(x) = x.strip_prefix("plan ") {
+                        let idx = x.parse::<usize>().unwrap() - 1;
+                        self.change_plan(ctx, app, idx);
+                        return Transition::Keep;
+                    } else if let Some(x) = x.strip_prefix("stage ") {
                         // 123, Intersection #456
                         let parts = x.split(", Intersection #").collect::<Vec<_>>();
                         let idx = parts[0].parse::<usize>().unwrap() - 1;
@@ -352,6 +391,7 @@ impl State<App> for TrafficSignalEditor {
                         ctx,
                         app,
                         self.members.clone(),
+                        self.current_plan,
                         self.current_stage,
                     ));
                 }
@@ -394,7 +434,7 @@ impl State<App> for TrafficSignalEditor {
                 for m in &self.movements {
                     let signal = app.primary.map.get_traffic_signal(m.id.parent);
                     if m.hitbox.contains_pt(pt) {
-                        let stage = &signal.stages[self.current_stage];
+                        let stage = &signal.plan_stages(plan)[self.current_stage];
                         let next_priority = match stage.get_priority_of_movement(m.id) {
                             TurnPriority::Banned => {
                                 if stage.could_be_protected(m.id, &signal.movements) {
@@ -431,7 +471,8 @@ impl State<App> for TrafficSignalEditor {
                 let mut txt = Text::new();
                 txt.add_line(Line(format!(
                     "{} {}",
-                    match signal.stages[self.current_stage].get_priority_of_movement(id) {
+                    match signal.plan_stages(plan)[self.current_stage].get_priority_of_movement(id)
+                    {
                         TurnPriority::Protected => "Protected",
                         TurnPriority::Yield => "Yielding",
                         TurnPriority::Banned => "Forbidden",
@@ -454,7 +495,7 @@ impl State<App> for TrafficSignalEditor {
                     ctx,
                     format!(
                         "toggle from {:?} to {:?}",
-                        signal.stages[self.current_stage].get_priority_of_movement(id),
+                        signal.plan_stages(plan)[self.current_stage].get_priority_of_movement(id),
                         pri
                     ),
                 ) {
@@ -462,7 +503,8 @@ impl State<App> for TrafficSignalEditor {
                     let signal = signal.clone();
                     self.add_new_edit(ctx, app, idx, |ts| {
                         if ts.id == id.parent {
-                            ts.stages[idx].edit_movement(&signal.movements[&id], pri);
+                            ts.plan_stages_mut(plan)[idx]
+                                .edit_movement(&signal.movements[&id], pri);
                         }
                     });
                     return Transition::KeepWithMouseover;
@@ -593,11 +635,13 @@ fn make_side_panel(
     ctx: &mut EventCtx,
     app: &App,
     members: &BTreeSet<IntersectionID>,
+    plan: usize,
     selected: usize,
 ) -> Panel {
     let map = &app.primary.map;
     // Use any member for stage duration
     let canonical_signal = map.get_traffic_signal(*members.iter().next().unwrap());
+    let stages = canonical_signal.plan_stages(plan);
 
     let mut txt = Text::new();
     if members.len() == 1 {
@@ -629,8 +673,36 @@ fn make_side_panel(
             .secondary(),
         );
     }
+    if let Some(tsp) = canonical_signal.transit_priority {
+        txt.add_line(
+            Line(format!(
+                "Transit priority: extend stages by up to {}, cut short by up to {}",
+                tsp.max_extension, tsp.max_truncation
+            ))
+            .secondary(),
+        );
+    }
     let mut col = vec![txt.into_widget(ctx)];
 
+    // Each time-of-day plan has its own stages
+    let num_plans = num_plans(app, members);
+    if num_plans > 1 {
+        let mut plans_row = vec!["Timing plan:".text_widget(ctx).centered_vert()];
+        for idx in 0..num_plans {
+            plans_row.push(
+                ctx.style()
+                    .btn_outline
+                    .text(format!(
+                        "From {}",
+                        canonical_signal.plan_start_time(idx).ampm_tostring()
+                    ))
+                    .disabled(idx == plan)
+                    .build_widget(ctx, &format!("plan {}", idx + 1)),
+            );
+        }
+        col.push(Widget::row(plans_row));
+    }
+
     // Stage controls
     col.push(
         Widget::row(vec![
@@ -646,14 +718,18 @@ fn make_side_panel(
                 .icon_bytes(include_labeled_bytes!(
                     "../../../../widgetry/icons/arrow_right.svg"
                 ))
-                .disabled(selected == canonical_signal.stages.len() - 1)
+                .disabled(selected == stages.len() - 1)
                 .build_widget(ctx, "move stage right"),
-            match canonical_signal.stages[selected].stage_type {
+            match stages[selected].stage_type {
                 StageType::Fixed(d) => format!("Stage duration: {}", d),
                 StageType::Variable(min, delay, additional) => format!(
                     "Stage duration: {}, {}, {} (variable)",
                     min, delay, additional
                 ),
+                StageType::Actuated(min, max, passage) => format!(
+                    "Stage duration: {} to {}, passage {} (actuated)",
+                    min, max, passage
+                ),
             }
             .text_widget(ctx)
             .centered_vert(),
@@ -662,7 +738,7 @@ fn make_side_panel(
                 .icon("system/assets/tools/pencil.svg")
                 .hotkey(Key::X)
                 .build_widget(ctx, "change duration"),
-            if canonical_signal.stages.len() > 1 {
+            if stages.len() > 1 {
                 ctx.style()
                     .btn_solid_destructive
                     .icon("system/assets/tools/trash.svg")
@@ -687,18 +763,19 @@ fn make_side_panel(
     );
 
     let mut stages_row = Vec::new();
-    for idx in 0..canonical_signal.stages.len() {
+    for idx in 0..stages.len() {
         let stage_btn = Widget::col(vec![
             format!(
                 "Stage {}: {}",
                 idx + 1,
-                match canonical_signal.stages[idx].stage_type {
+                match stages[idx].stage_type {
                     StageType::Fixed(d) => format!("{}", d),
                     StageType::Variable(min, _, _) => format!("{} (v)", min),
+                    StageType::Actuated(min, max, _) => format!("{}-{} (a)", min, max),
                 },
             )
             .text_widget(ctx),
-            draw_multiple_signals(ctx, app, members, idx, &translations),
+            draw_multiple_signals(ctx, app, members, plan, idx, &translations),
         ])
         .padding(10);
         // TODO Add a proper hover state to these buttons. Complication is that they're
@@ -719,7 +796,7 @@ fn make_side_panel(
         // TODO Say "normally" to account for variable stages?
         format!(
             "One full cycle lasts {}",
-            canonical_signal.simple_cycle_duration()
+            canonical_signal.plan_cycle_duration(plan)
         )
         .text_widget(ctx)
         .centered_vert(),
@@ -777,27 +854,32 @@ impl BundleEdits {
     }
 
     // If the intersections haven't been edited together before, the number of stages and the
-    // durations might not match up. Just initially force them to align somehow.
+    // durations might not match up. Just initially force them to align somehow. Only the plans
+    // that every member has can be edited together.
     fn synchronize(app: &App, members: &BTreeSet<IntersectionID>) -> BundleEdits {
         let map = &app.primary.map;
-        // Pick one of the members with the most stages as canonical.
-        let canonical = map.get_traffic_signal(
-            *members
-                .iter()
-                .max_by_key(|i| map.get_traffic_signal(**i).stages.len())
-                .unwrap(),
-        );
+        let mut signals: Vec<ControlTrafficSignal> = members
+            .iter()
+            .map(|i| map.get_traffic_signal(*i).clone())
+            .collect();
+        for plan in 0..num_plans(app, members) {
+            // Pick one of the members with the most stages as canonical.
+            let canonical = map.get_traffic_signal(
+                *members
+                    .iter()
+                    .max_by_key(|i| map.get_traffic_signal(**i).plan_stages(plan).len())
+                    .unwrap(),
+            );
 
-        let mut signals = Vec::new();
-        for i in members {
-            let mut signal = map.get_traffic_signal(*i).clone();
-            for (idx, canonical_stage) in canonical.stages.iter().enumerate() {
-                if signal.stages.len() == idx {
-                    signal.stages.push(Stage::new());
+            for signal in &mut signals {
+                let stages = signal.plan_stages_mut(plan);
+                for (idx, canonical_stage) in canonical.plan_stages(plan).iter().enumerate() {
+                    if stages.len() == idx {
+                        stages.push(Stage::new());
+                    }
+                    stages[idx].stage_type = canonical_stage.stage_type.clone();
                 }
-                signal.stages[idx].stage_type = canonical_stage.stage_type.clone();
             }
-            signals.push(signal);
         }
 
         BundleEdits { signals }
@@ -806,38 +888,47 @@ impl BundleEdits {
 
 // If None, nothing missing.
 fn check_for_missing_turns(app: &App, members: &BTreeSet<IntersectionID>) -> Option<BundleEdits> {
-    let mut all_missing = BTreeSet::new();
-    for i in members {
-        all_missing.extend(app.primary.map.get_traffic_signal(*i).missing_turns());
-    }
-    if all_missing.is_empty() {
-        return None;
-    }
-
     let mut bundle = BundleEdits::get_current(app, members);
-    // Stick all the missing turns in a new stage at the beginning.
-    for signal in &mut bundle.signals {
-        let mut stage = Stage::new();
-        // TODO Could do this more efficiently
-        for m in &all_missing {
-            if m.parent != signal.id {
-                continue;
-            }
-            if m.crosswalk {
-                stage.protected_movements.insert(*m);
-            } else {
-                stage.yield_movements.insert(*m);
+    let mut any_missing = false;
+    for plan in 0..num_plans(app, members) {
+        let mut all_missing = BTreeSet::new();
+        for i in members {
+            all_missing.extend(app.primary.map.get_traffic_signal(*i).missing_turns(plan));
+        }
+        if all_missing.is_empty() {
+            continue;
+        }
+        any_missing = true;
+
+        // Stick all the missing turns in a new stage at the beginning of the plan.
+        for signal in &mut bundle.signals {
+            let mut stage = Stage::new();
+            // TODO Could do this more efficiently
+            for m in &all_missing {
+                if m.parent != signal.id {
+                    continue;
+                }
+                if m.crosswalk {
+                    stage.protected_movements.insert(*m);
+                } else {
+                    stage.yield_movements.insert(*m);
+                }
             }
+            signal.plan_stages_mut(plan).insert(0, stage);
         }
-        signal.stages.insert(0, stage);
     }
-    Some(bundle)
+    if any_missing {
+        Some(bundle)
+    } else {
+        None
+    }
 }
 
 fn draw_multiple_signals(
     ctx: &mut EventCtx,
     app: &App,
     members: &BTreeSet<IntersectionID>,
+    plan: usize,
     idx: usize,
     translations: &Vec<(f64, f64)>,
 ) -> Widget {
@@ -850,7 +941,7 @@ fn draw_multiple_signals(
         );
         traffic_signal::draw_signal_stage(
             ctx.prerender,
-            &app.primary.map.get_traffic_signal(*i).stages[idx],
+            &app.primary.map.get_traffic_signal(*i).plan_stages(plan)[idx],
             idx,
             *i,
             None,
@@ -886,6 +977,15 @@ fn draw_multiple_signals(
     MultiButton::new(ctx, batch, hitboxes).named(format!("stage {}", idx + 1))
 }
 
+// The plans that every member has. Usually they all have just one.
+fn num_plans(app: &App, members: &BTreeSet<IntersectionID>) -> usize {
+    members
+        .iter()
+        .map(|i| app.primary.map.get_traffic_signal(*i).num_plans())
+        .min()
+        .unwrap()
+}
+
 // TODO Move to geom?
 fn squish_polygons_together(mut polygons: Vec<Polygon>) -> Vec<(f64, f64)> {
     if polygons.len() == 1 {
@@ -1,7 +1,7 @@
 use std::collections::BTreeSet;
 
 use abstutil::Timer;
-use geom::Duration;
+use geom::Time;
 use map_gui::tools::ChooseSomething;
 use map_model::IntersectionID;
 use widgetry::{
@@ -75,6 +75,7 @@ pub fn make_previewer(
     ctx: &mut EventCtx,
     app: &App,
     members: BTreeSet<IntersectionID>,
+    plan: usize,
     stage: usize,
 ) -> Box<dyn State<App>> {
     let random = "random agents around these intersections".to_string();
@@ -91,13 +92,13 @@ pub fn make_previewer(
             if x == "random agents around these intersections" {
                 for (idx, i) in members.iter().enumerate() {
                     if idx == 0 {
-                        // Start at the current stage
+                        // Start at the current stage of the plan
                         let signal = app.primary.map.get_traffic_signal(*i);
                         // TODO Use the offset correctly
                         // TODO If there are variable stages, this could land anywhere
-                        let mut step = Duration::ZERO;
+                        let mut step = signal.plan_start_time(plan) - Time::START_OF_DAY;
                         for idx in 0..stage {
-                            step += signal.stages[idx].stage_type.simple_duration();
+                            step += signal.plan_stages(plan)[idx].stage_type.simple_duration();
                         }
                         app.primary.sim.timed_step(
                             &app.primary.map,
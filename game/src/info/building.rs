@@ -56,6 +56,15 @@ fn info_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BuildingI
     } else {
         kv.push(("Parking", "None".to_string()));
     }
+    if let Some(status) = app.primary.sim.get_charging_station(b.id) {
+        kv.push((
+            "EV charging",
+            format!(
+                "{} / {} chargers in use, {} vehicles waiting",
+                status.charging, status.chargers, status.waiting
+            ),
+        ));
+    }
 
     rows.extend(make_table(ctx, kv));
 
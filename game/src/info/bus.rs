@@ -71,13 +71,20 @@ fn stop_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BusStopID
             alightings.inc(*r);
         }
     }
+    let mut denied: Counter<BusRouteID> = Counter::new();
+    if let Some(list) = app.primary.sim.get_analytics().denied_boardings.get(&id) {
+        for (_, r) in list {
+            denied.inc(*r);
+        }
+    }
     let mut txt = Text::new();
     txt.add_line("Total");
     txt.append(
         Line(format!(
-            ": {} boardings, {} alightings",
+            ": {} boardings, {} alightings, {} left behind",
             prettyprint_usize(boardings.sum()),
-            prettyprint_usize(alightings.sum())
+            prettyprint_usize(alightings.sum()),
+            prettyprint_usize(denied.sum())
         ))
         .secondary(),
     );
@@ -85,9 +92,10 @@ fn stop_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: BusStopID
         txt.add_line(format!("Route {}", r.short_name));
         txt.append(
             Line(format!(
-                ": {} boardings, {} alightings",
+                ": {} boardings, {} alightings, {} left behind",
                 prettyprint_usize(boardings.get(r.id)),
-                prettyprint_usize(alightings.get(r.id))
+                prettyprint_usize(alightings.get(r.id)),
+                prettyprint_usize(denied.get(r.id))
             ))
             .secondary(),
         );
@@ -131,8 +139,10 @@ fn bus_status_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: Car
 
     rows.push(
         Line(format!(
-            "Currently has {} passengers",
+            "Currently has {} passengers (comfortable capacity {}, crush load {})",
             app.primary.sim.num_transit_passengers(id),
+            route.capacity.comfortable,
+            route.capacity.crush_load,
         ))
         .into_widget(ctx),
     );
@@ -4,11 +4,11 @@ use abstutil::prettyprint_usize;
 use geom::{ArrowCap, Distance, Duration, PolyLine, Polygon, Time};
 use map_gui::options::TrafficSignalStyle;
 use map_gui::render::traffic_signal::draw_signal_stage;
-use map_model::{IntersectionID, IntersectionType, StageType};
+use map_model::{CrossingType, IntersectionID, IntersectionType, StageType};
 use sim::AgentType;
 use widgetry::{
     Color, DrawWithTooltips, EventCtx, FanChart, GeomBatch, Line, PlotOptions, ScatterPlot, Series,
-    Text, Toggle, Widget,
+    Text, TextExt, Toggle, Widget,
 };
 
 use crate::app::App;
@@ -42,6 +42,19 @@ fn info_body(ctx: &EventCtx, app: &App, id: IntersectionID) -> Widget {
     }
     rows.push(txt.into_widget(ctx));
 
+    match i.crossing {
+        Some(CrossingType::Zebra) => {
+            rows.push(
+                "Mid-block zebra crossing. Vehicles yield to pedestrians waiting to cross."
+                    .text_widget(ctx),
+            );
+        }
+        Some(CrossingType::Signalized) => {
+            rows.push("Mid-block crossing with a pedestrian signal".text_widget(ctx));
+        }
+        None => {}
+    }
+
     if app.opts.dev {
         rows.push(
             ctx.style()
@@ -314,13 +327,23 @@ fn traffic_signal_body(ctx: &mut EventCtx, app: &App, id: IntersectionID) -> Wid
     let bbox = Polygon::rectangle(zoom * bounds.width(), zoom * bounds.height());
 
     let signal = app.primary.map.get_traffic_signal(id);
+    // Describe the timing plan in effect right now
+    let plan = signal.plan_idx_at(app.primary.sim.time());
+    let stages = signal.plan_stages(plan);
     {
         let mut txt = Text::new();
-        txt.add_line(Line(format!("{} stages", signal.stages.len())).small_heading());
-        txt.add_line(format!("Signal offset: {}", signal.offset));
+        txt.add_line(Line(format!("{} stages", stages.len())).small_heading());
+        if !signal.later_plans.is_empty() {
+            txt.add_line(format!(
+                "Using timing plan {} of {}",
+                plan + 1,
+                signal.later_plans.len() + 1
+            ));
+        }
+        txt.add_line(format!("Signal offset: {}", signal.plan_offset(plan)));
         {
             let mut total = Duration::ZERO;
-            for s in &signal.stages {
+            for s in stages {
                 total += s.stage_type.simple_duration();
             }
             // TODO Say "normally" or something?
@@ -329,7 +352,7 @@ fn traffic_signal_body(ctx: &mut EventCtx, app: &App, id: IntersectionID) -> Wid
         rows.push(txt.into_widget(ctx));
     }
 
-    for (idx, stage) in signal.stages.iter().enumerate() {
+    for (idx, stage) in stages.iter().enumerate() {
         rows.push(
             match stage.stage_type {
                 StageType::Fixed(d) => Line(format!("Stage {}: {}", idx + 1, d)),
@@ -340,6 +363,13 @@ fn traffic_signal_body(ctx: &mut EventCtx, app: &App, id: IntersectionID) -> Wid
                     delay,
                     additional
                 )),
+                StageType::Actuated(min, max, passage) => Line(format!(
+                    "Stage {}: {} to {}, passage {} (actuated)",
+                    idx + 1,
+                    min,
+                    max,
+                    passage
+                )),
             }
             .into_widget(ctx),
         );
@@ -5,7 +5,10 @@ use map_model::{LaneID, PathConstraints};
 use widgetry::{EventCtx, Line, LinePlot, PlotOptions, Series, Text, TextExt, Widget};
 
 use crate::app::App;
-use crate::info::{header_btns, make_table, make_tabs, throughput, DataOptions, Details, Tab};
+use crate::info::{
+    describe_parking_policy, header_btns, make_table, make_tabs, throughput, DataOptions, Details,
+    Tab,
+};
 
 pub fn info(ctx: &EventCtx, app: &App, details: &mut Details, id: LaneID) -> Widget {
     Widget::custom_col(vec![
@@ -57,6 +60,10 @@ fn info_body(ctx: &EventCtx, app: &App, id: LaneID) -> Widget {
                 l.number_parking_spots(app.primary.map.get_config())
             ),
         ));
+        kv.push((
+            "Parking policy",
+            describe_parking_policy(app, &r.parking_policy),
+        ));
     } else {
         kv.push(("Speed limit", r.speed_limit.to_string(&app.opts.units)));
     }
@@ -140,7 +147,7 @@ fn debug_body(ctx: &EventCtx, app: &App, id: LaneID) -> Widget {
         ));
     }
 
-    if let Some(types) = l.get_turn_restrictions(r) {
+    if let Some(types) = l.get_turn_restrictions(map) {
         kv.push((
             "Turn restrictions".to_string(),
             format!("{:?}", types.into_iter().collect::<Vec<_>>()),
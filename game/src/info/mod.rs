@@ -5,7 +5,9 @@ pub use trip::OpenTrip;
 use geom::{Circle, Distance, Polygon, Time};
 use map_gui::tools::open_browser;
 use map_gui::ID;
-use map_model::{AreaID, BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, ParkingLotID};
+use map_model::{
+    AreaID, BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, ParkingLotID, ParkingPolicy,
+};
 use sim::{
     AgentID, AgentType, Analytics, CarID, ParkingSpot, PedestrianID, PersonID, PersonState, TripID,
     VehicleType,
@@ -682,6 +684,25 @@ fn make_table<I: Into<String>>(ctx: &EventCtx, rows: Vec<(I, String)>) -> Vec<Wi
         .collect()
 }
 
+fn describe_parking_policy(app: &App, policy: &ParkingPolicy) -> String {
+    let mut parts = Vec::new();
+    if policy.hourly_price == 0.0 {
+        parts.push("free".to_string());
+    } else {
+        parts.push(format!("${:.2} per hour", policy.hourly_price));
+    }
+    if let Some(limit) = policy.time_limit {
+        parts.push(format!("{} limit", limit.to_string(&app.opts.units)));
+    }
+    if policy.permit_only {
+        parts.push("residents only".to_string());
+    }
+    if policy.loading_zone {
+        parts.push("loading zone for deliveries".to_string());
+    }
+    parts.join(", ")
+}
+
 fn throughput<F: Fn(&Analytics) -> Vec<(AgentType, Vec<(Time, usize)>)>>(
     ctx: &EventCtx,
     app: &App,
@@ -5,7 +5,7 @@ use map_model::ParkingLotID;
 use widgetry::{EventCtx, Line, LinePlot, PlotOptions, Series, TextExt, Widget};
 
 use crate::app::App;
-use crate::info::{header_btns, make_tabs, Details, Tab};
+use crate::info::{describe_parking_policy, header_btns, make_tabs, Details, Tab};
 
 pub fn info(ctx: &mut EventCtx, app: &App, details: &mut Details, id: ParkingLotID) -> Widget {
     Widget::custom_col(vec![
@@ -27,6 +27,7 @@ fn info_body(ctx: &mut EventCtx, app: &App, id: ParkingLotID) -> Widget {
         )
         .text_widget(ctx),
     );
+    rows.push(describe_parking_policy(app, &pl.policy).text_widget(ctx));
 
     let mut series = vec![Series {
         label: format!("After \"{}\"", app.primary.map.get_edits().edits_name),
@@ -146,9 +146,11 @@ fn trips_body(
                     ctx.prerender,
                     match trip.mode {
                         TripMode::Walk => "system/assets/meters/pedestrian.svg",
-                        TripMode::Bike => "system/assets/meters/bike.svg",
+                        TripMode::Bike | TripMode::Micromobility => "system/assets/meters/bike.svg",
                         TripMode::Drive => "system/assets/meters/car.svg",
-                        TripMode::Transit => "system/assets/meters/bus.svg",
+                        TripMode::Transit | TripMode::ParkAndRide | TripMode::BikeAndRide => {
+                            "system/assets/meters/bus.svg"
+                        }
                     },
                 )
                 // we want the icon to be about the same height as the text
@@ -642,6 +644,7 @@ fn header(
                     AgentID::Car(c) => match c.vehicle_type {
                         VehicleType::Car => ("driving", Some("system/assets/meters/car.svg")),
                         VehicleType::Bike => ("biking", Some("system/assets/meters/bike.svg")),
+                        VehicleType::Van => ("delivering", Some("system/assets/meters/car.svg")),
                         VehicleType::Bus | VehicleType::Train => unreachable!(),
                     },
                     AgentID::BusPassenger(_, _) => {
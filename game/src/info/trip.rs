@@ -405,7 +405,7 @@ fn describe_problems(
     trip: &TripInfo,
     col_width: Percent,
 ) -> Widget {
-    if trip.mode == TripMode::Bike {
+    if trip.mode == TripMode::Bike || trip.mode == TripMode::Micromobility {
         let mut count_large_intersections = 0;
         let mut count_overtakes = 0;
         let empty = Vec::new();
@@ -723,7 +723,8 @@ fn make_trip_details(
             // Don't show the elevation plot for somebody walking to their car
             if ((trip.mode == TripMode::Walk || trip.mode == TripMode::Transit)
                 && p.phase_type == TripPhaseType::Walking)
-                || (trip.mode == TripMode::Bike && p.phase_type == TripPhaseType::Biking)
+                || ((trip.mode == TripMode::Bike || trip.mode == TripMode::Micromobility)
+                    && p.phase_type == TripPhaseType::Biking)
             {
                 elevation.push(make_elevation(
                     ctx,
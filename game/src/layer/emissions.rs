@@ -0,0 +1,142 @@
+use std::collections::BTreeMap;
+
+use abstutil::Counter;
+use geom::Time;
+use map_gui::tools::{ColorLegend, ColorNetwork};
+use map_gui::ID;
+use map_model::RoadID;
+use sim::{EmissionFactors, Pollutant};
+use widgetry::{Choice, Drawable, EventCtx, GfxCtx, Line, Outcome, Panel, Text, TextExt, Widget};
+
+use crate::app::App;
+use crate::layer::{header, Layer, LayerOutcome, PANEL_PLACEMENT};
+
+// Estimates emissions per road using the default emission factors.
+pub struct Emissions {
+    time: Time,
+    pollutant: Pollutant,
+    per_road: BTreeMap<RoadID, sim::Emissions>,
+    tooltip: Option<Text>,
+    unzoomed: Drawable,
+    zoomed: Drawable,
+    panel: Panel,
+}
+
+impl Layer for Emissions {
+    fn name(&self) -> Option<&'static str> {
+        Some("emissions")
+    }
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Option<LayerOutcome> {
+        let mut recalc_tooltip = false;
+        if app.primary.sim.time() != self.time {
+            *self = Emissions::new(ctx, app, self.pollutant);
+            recalc_tooltip = true;
+        }
+
+        // Show a tooltip with the amount, only when unzoomed
+        if ctx.canvas.cam_zoom < app.opts.min_zoom_for_detail {
+            if ctx.redo_mouseover() || recalc_tooltip {
+                self.tooltip = None;
+                if let Some(ID::Road(r)) = app.mouseover_unzoomed_roads_and_intersections(ctx) {
+                    if let Some(emissions) = self.per_road.get(&r) {
+                        self.tooltip = Some(Text::from(format!(
+                            "{:.1} {}",
+                            emissions.get(self.pollutant),
+                            self.pollutant.units()
+                        )));
+                    }
+                }
+            }
+        } else {
+            self.tooltip = None;
+        }
+
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "close" => {
+                    return Some(LayerOutcome::Close);
+                }
+                _ => unreachable!(),
+            },
+            Outcome::Changed(_) => {
+                let pollutant = self.panel.dropdown_value("pollutant");
+                return Some(LayerOutcome::Replace(Box::new(Emissions::new(
+                    ctx, app, pollutant,
+                ))));
+            }
+            _ => {}
+        }
+        None
+    }
+    fn draw(&self, g: &mut GfxCtx, app: &App) {
+        self.panel.draw(g);
+        if g.canvas.cam_zoom < app.opts.min_zoom_for_detail {
+            g.redraw(&self.unzoomed);
+        } else {
+            g.redraw(&self.zoomed);
+        }
+        if let Some(ref txt) = self.tooltip {
+            g.draw_mouse_tooltip(txt.clone());
+        }
+    }
+    fn draw_minimap(&self, g: &mut GfxCtx) {
+        g.redraw(&self.unzoomed);
+    }
+}
+
+impl Emissions {
+    pub fn new(ctx: &mut EventCtx, app: &App, pollutant: Pollutant) -> Emissions {
+        let report = EmissionFactors::default().estimate(app.primary.sim.get_analytics());
+
+        // Some pollutants are measured in tiny amounts, so scale up before rounding
+        let mut counter = Counter::new();
+        for (r, emissions) in &report.per_road {
+            counter.add(*r, (emissions.get(pollutant) * 1000.0).round() as usize);
+        }
+
+        let panel = Panel::new(Widget::col(vec![
+            header(ctx, "Emissions"),
+            Text::from(
+                Line("This estimates tailpipe emissions from all vehicles since midnight")
+                    .secondary(),
+            )
+            .wrap_to_pct(ctx, 15)
+            .into_widget(ctx),
+            Widget::row(vec![
+                "Show:".text_widget(ctx),
+                Widget::dropdown(
+                    ctx,
+                    "pollutant",
+                    pollutant,
+                    Pollutant::all()
+                        .into_iter()
+                        .map(|p| Choice::new(p.describe(), p))
+                        .collect(),
+                ),
+            ]),
+            format!(
+                "Total: {:.1} {}",
+                report.total.get(pollutant),
+                pollutant.units()
+            )
+            .text_widget(ctx),
+            ColorLegend::gradient(ctx, &app.cs.good_to_bad_red, vec!["0", "highest"]),
+        ]))
+        .aligned_pair(PANEL_PLACEMENT)
+        .build(ctx);
+
+        let mut colorer = ColorNetwork::new(app);
+        colorer.ranked_roads(counter, &app.cs.good_to_bad_red);
+        let (unzoomed, zoomed) = colorer.build(ctx);
+
+        Emissions {
+            time: app.primary.sim.time(),
+            pollutant,
+            per_road: report.per_road,
+            tooltip: None,
+            unzoomed,
+            zoomed,
+            panel,
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use map_gui::tools::{grey_out_map, HeatmapOptions};
-use sim::AgentType;
+use sim::{AgentType, Pollutant};
 use widgetry::{
     DrawBaselayer, EventCtx, GfxCtx, HorizontalAlignment, Image, Key, Line, Outcome, Panel, State,
     TextExt, VerticalAlignment, Widget,
@@ -9,6 +9,7 @@ use crate::app::{App, Transition};
 use crate::sandbox::dashboards;
 
 mod elevation;
+mod emissions;
 pub mod favorites;
 pub mod map;
 mod pandemic;
@@ -106,6 +107,7 @@ impl PickLayer {
                     btn("throughput", Key::T),
                     btn("traffic jams", Key::J),
                     btn("cycling activity", Key::B),
+                    btn("emissions", Key::I),
                 ]),
                 Widget::col(vec![
                     "Map".text_widget(ctx),
@@ -175,6 +177,13 @@ impl State<App> for PickLayer {
                 "delay" => {
                     app.primary.layer = Some(Box::new(traffic::Delay::new(ctx, app)));
                 }
+                "emissions" => {
+                    app.primary.layer = Some(Box::new(emissions::Emissions::new(
+                        ctx,
+                        app,
+                        Pollutant::CarbonDioxide,
+                    )));
+                }
                 "steep streets" => {
                     app.primary.layer = Some(Box::new(elevation::SteepStreets::new(ctx, app)));
                 }
@@ -375,6 +375,7 @@ impl Efficiency {
                     start,
                     end,
                     constraints: PathConstraints::Pedestrian,
+                    routing_params: None,
                 })
                 .collect();
             timer.stop("gather requests");
@@ -255,6 +255,13 @@ impl EditScenarioModifiers {
                 .text("Add extra new trips")
                 .build_def(ctx),
         );
+        rows.push(Widget::row(vec![
+            Spinner::widget(ctx, "deliveries_pct", (10, 500), 100, 10),
+            ctx.style()
+                .btn_outline
+                .text("Add delivery vans (% of typical demand)")
+                .build_def(ctx),
+        ]));
         rows.push(Widget::row(vec![
             Spinner::widget(ctx, "repeat_days", (2, 14), 2, 1),
             ctx.style()
@@ -348,6 +355,16 @@ impl State<App> for EditScenarioModifiers {
                         }),
                     ));
                 }
+                "Add delivery vans (% of typical demand)" => {
+                    self.modifiers.push(ScenarioModifier::AddDeliveries(
+                        self.panel.spinner("deliveries_pct"),
+                    ));
+                    return Transition::Replace(EditScenarioModifiers::new(
+                        ctx,
+                        self.scenario_name.clone(),
+                        self.modifiers.clone(),
+                    ));
+                }
                 "Repeat schedule multiple days" => {
                     self.modifiers.push(ScenarioModifier::RepeatDays(
                         self.panel.spinner("repeat_days"),
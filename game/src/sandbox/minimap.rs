@@ -256,6 +256,11 @@ fn make_agent_toggles(ctx: &mut EventCtx, app: &App, is_enabled: [bool; 4]) -> V
                 prettyprint_usize(counts.sov_drivers)
             ))
             .secondary(),
+            Line(format!(
+                "Delivery vans: {}",
+                prettyprint_usize(counts.delivery_vans)
+            ))
+            .secondary(),
         ]);
         colored_checkbox(
             ctx,
@@ -263,7 +268,7 @@ fn make_agent_toggles(ctx: &mut EventCtx, app: &App, is_enabled: [bool; 4]) -> V
             is_car_enabled,
             app.cs.unzoomed_car,
             "system/assets/meters/car.svg",
-            &prettyprint_usize(counts.sov_drivers),
+            &prettyprint_usize(counts.sov_drivers + counts.delivery_vans),
             tooltip,
         )
     };
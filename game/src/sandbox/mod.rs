@@ -532,16 +532,17 @@ impl State<App> for SandboxLoader {
                     ctx.loading_screen("instantiate scenario", |_, mut timer| {
                         app.primary.scenario = Some(scenario.clone());
 
+                        let mut rng = app.primary.current_flags.sim_flags.make_rng();
                         if let GameplayMode::PlayScenario(_, _, ref modifiers) = self.mode {
                             for m in modifiers {
-                                scenario = m.apply(&app.primary.map, scenario);
+                                scenario = m.apply(&app.primary.map, scenario, &mut rng);
                             }
                         }
 
                         scenario.instantiate(
                             &mut app.primary.sim,
                             &app.primary.map,
-                            &mut app.primary.current_flags.sim_flags.make_rng(),
+                            &mut rng,
                             &mut timer,
                         );
                         app.primary
@@ -32,8 +32,8 @@ use map_model::{
     MovementID, PermanentMapEdits, RoadID, TurnID,
 };
 use sim::{
-    AgentID, AgentType, DelayCause, ExternalPerson, PersonID, Scenario, ScenarioModifier, Sim,
-    SimFlags, SimOptions, TripID, TripMode, VehicleType,
+    AgentID, AgentType, DelayCause, EmissionFactors, ExternalPerson, OptimizeSignalsOptions,
+    PersonID, Scenario, ScenarioModifier, Sim, SimFlags, SimOptions, TripID, TripMode, VehicleType,
 };
 
 lazy_static::lazy_static! {
@@ -207,6 +207,26 @@ fn handle_command(
 
             Ok(format!("{} has been updated", id))
         }
+        "/traffic-signals/optimize" => {
+            // Search using the currently loaded scenario and map edits, but don't change the
+            // current simulation. The caller can pass the result to /sim/load.
+            let opts: OptimizeSignalsOptions = abstutil::from_json(body)?;
+            let mut timer = Timer::new("optimize traffic signals");
+            let mut scenario: Scenario = abstio::read_object(load.scenario.clone(), &mut timer)?;
+            let mut rng = XorShiftRng::seed_from_u64(load.rng_seed);
+            for m in &load.modifiers {
+                scenario = m.apply(map, scenario, &mut rng);
+            }
+            let edits = sim::optimize_signals(
+                map,
+                &scenario,
+                &load.opts,
+                load.rng_seed,
+                &opts,
+                &mut timer,
+            )?;
+            Ok(abstutil::to_json(&edits.to_permanent(map)))
+        }
         "/traffic-signals/get-delays" => {
             let i = IntersectionID(get("id")?.parse::<usize>()?);
             let t1 = Time::parse(get("t1")?)?;
@@ -330,6 +350,17 @@ fn handle_command(
                 .map(|((r, a, hr), cnt)| (*r, *a, *hr, *cnt))
                 .collect(),
         })),
+        "/data/get-emissions" => {
+            // Optionally pass in a different table of emission factors
+            let factors = if body.is_empty() {
+                EmissionFactors::default()
+            } else {
+                abstutil::from_json(body)?
+            };
+            Ok(abstutil::to_json(&factors.estimate(sim.get_analytics())))
+        }
+        "/data/get-micromobility-stats" => Ok(abstutil::to_json(&sim.get_micromobility_stats())),
+        "/data/get-charging-stations" => Ok(abstutil::to_json(&sim.get_charging_stations())),
         "/data/get-blocked-by-graph" => Ok(abstutil::to_json(&BlockedByGraph {
             blocked_by: sim
                 .get_blocked_by_graph(map)
@@ -490,11 +521,11 @@ impl LoadSim {
             map.recalculate_pathfinding_after_edits(timer);
         }
 
+        let mut rng = XorShiftRng::seed_from_u64(self.rng_seed);
         for m in &self.modifiers {
-            scenario = m.apply(&map, scenario);
+            scenario = m.apply(&map, scenario, &mut rng);
         }
 
-        let mut rng = XorShiftRng::seed_from_u64(self.rng_seed);
         let mut sim = Sim::new(&map, self.opts.clone());
         scenario.instantiate(&mut sim, &map, &mut rng, timer);
 
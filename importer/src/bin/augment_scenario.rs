@@ -4,6 +4,8 @@
 //!                       4-12 hours later.
 //! `--add_lunch_trips`: Before a person's final trip back home, insert a round-trip to a nearby
 //!                      cafe or restaurant.
+//! `--choose_modes`: Choose the mode of every trip again, using a logit model based on travel times
+//!                   and costs on the current map.
 //!
 //! These tools aren't very smart about detecting if a scenario already has these extra trips added
 //! in; be careful about running this on the correct input. It modifies the given `--input` binary
@@ -19,13 +21,14 @@ use rand_xorshift::XorShiftRng;
 use abstutil::{prettyprint_usize, CmdArgs, Timer};
 use geom::{Distance, Duration, FindClosest};
 use map_model::{AmenityType, BuildingID, Map};
-use sim::{IndividTrip, Scenario, TripEndpoint, TripMode, TripPurpose};
+use sim::{IndividTrip, ModeChoiceParams, Scenario, TripEndpoint, TripMode, TripPurpose};
 
 fn main() {
     let mut args = CmdArgs::new();
     let input = args.required("--input");
     let should_add_return_trips = args.enabled("--add_return_trips");
     let should_add_lunch_trips = args.enabled("--add_lunch_trips");
+    let should_choose_modes = args.enabled("--choose_modes");
     let rng_seed: u64 = args
         .optional_parse("--rng_seed", |s| s.parse())
         .unwrap_or(42);
@@ -43,6 +46,9 @@ fn main() {
     if should_add_lunch_trips {
         add_lunch_trips(&mut scenario, &map, &mut rng, &mut timer);
     }
+    if should_choose_modes {
+        ModeChoiceParams::default().assign_modes(&mut scenario, &map, &mut rng, &mut timer);
+    }
 
     scenario.save();
 }
@@ -0,0 +1,562 @@
+//! Imports transit schedules from a GTFS feed (https://developers.google.com/transit/gtfs). Routes
+//! and stops still come from OSM; GTFS stops are matched to the nearest `BusStop`, then each GTFS
+//! trip is matched to the `BusRoute` serving those stops in order. Every matched trip becomes one
+//! scheduled vehicle, replacing the guessed spawn times.
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fs::File;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::Result;
+use serde::Deserialize;
+
+use abstutil::{must_run_cmd, prettyprint_usize, Timer};
+use geom::{Distance, Duration, FindClosest, LonLat, Time};
+use map_model::{BusRouteID, BusStopID, Map, PathRequest, Position, ScheduledTrip};
+
+use crate::configuration::ImporterConfiguration;
+
+/// GTFS stops farther than this from any `BusStop` aren't matched.
+const MAX_DIST_TO_STOP: Distance = Distance::const_meters(30.0);
+
+/// What happened while importing a GTFS feed.
+pub struct GtfsReport {
+    /// GTFS stops inside the map
+    pub stops_in_bounds: usize,
+    pub matched_stops: usize,
+    /// GTFS stops inside the map with no `BusStop` nearby, as (stop_id, stop_name)
+    pub unmatched_stops: Vec<(String, String)>,
+    pub matched_trips: usize,
+    /// Trips visiting at least two matched stops, but not following any route
+    pub unmatched_trips: usize,
+    /// These routes keep their old schedule
+    pub routes_without_trips: Vec<BusRouteID>,
+}
+
+impl GtfsReport {
+    pub fn print(&self, map: &Map) {
+        println!(
+            "Matched {} / {} GTFS stops inside the map",
+            prettyprint_usize(self.matched_stops),
+            prettyprint_usize(self.stops_in_bounds)
+        );
+        for (id, name) in &self.unmatched_stops {
+            println!("- No bus stop near GTFS stop {} ({})", id, name);
+        }
+        println!(
+            "Matched {} GTFS trips to routes. {} trips don't follow any route.",
+            prettyprint_usize(self.matched_trips),
+            prettyprint_usize(self.unmatched_trips)
+        );
+        for br in &self.routes_without_trips {
+            println!(
+                "- No GTFS trips for {}, keeping its old schedule",
+                map.get_br(*br).full_name
+            );
+        }
+    }
+}
+
+/// Reads a GTFS feed, either a .zip or a directory with the extracted files, and replaces the
+/// schedule of every route with matching trips. Only trips running on one typical weekday are
+/// used; see `weekday_services`.
+pub fn import(
+    map: &mut Map,
+    path: &str,
+    config: &ImporterConfiguration,
+    timer: &mut Timer,
+) -> Result<GtfsReport> {
+    if path.ends_with(".zip") {
+        // Removed when this goes out of scope
+        let dir = tempfile::tempdir()?;
+        must_run_cmd(
+            Command::new(&config.unzip)
+                .arg(path)
+                .arg("-d")
+                .arg(dir.path()),
+        );
+        import_dir(map, dir.path(), timer)
+    } else {
+        import_dir(map, Path::new(path), timer)
+    }
+}
+
+fn import_dir(map: &mut Map, dir: &Path, timer: &mut Timer) -> Result<GtfsReport> {
+    timer.start("match GTFS stops");
+    let mut closest: FindClosest<BusStopID> = FindClosest::new(map.get_bounds());
+    for bs in map.all_bus_stops().values() {
+        closest.add(
+            bs.id,
+            &vec![bs.sidewalk_pos.pt(map), bs.driving_pos.pt(map)],
+        );
+    }
+    let mut stops: BTreeMap<String, BusStopID> = BTreeMap::new();
+    let mut stops_in_bounds = 0;
+    let mut unmatched_stops = Vec::new();
+    for rec in csv::Reader::from_reader(File::open(dir.join("stops.txt"))?).deserialize() {
+        let rec: StopRecord = rec?;
+        let (lon, lat) = match (rec.stop_lon, rec.stop_lat) {
+            (Some(lon), Some(lat)) => (lon, lat),
+            // Stations and entrances don't always have a location
+            _ => continue,
+        };
+        let gps = LonLat::new(lon, lat);
+        if !map.get_gps_bounds().contains(gps) {
+            continue;
+        }
+        stops_in_bounds += 1;
+        match closest.closest_pt(gps.to_pt(map.get_gps_bounds()), MAX_DIST_TO_STOP) {
+            Some((bs, _)) => {
+                stops.insert(rec.stop_id, bs);
+            }
+            None => {
+                unmatched_stops.push((rec.stop_id, rec.stop_name.unwrap_or_default()));
+            }
+        }
+    }
+    timer.stop("match GTFS stops");
+
+    // Skip trips whose shapes never enter the map, so we don't hold onto all of their stop times
+    let mut shapes_in_bounds: Option<HashSet<String>> = None;
+    if dir.join("shapes.txt").exists() {
+        let mut shapes = HashSet::new();
+        for rec in csv::Reader::from_reader(File::open(dir.join("shapes.txt"))?).deserialize() {
+            let rec: ShapeRecord = rec?;
+            if map
+                .get_gps_bounds()
+                .contains(LonLat::new(rec.shape_pt_lon, rec.shape_pt_lat))
+            {
+                shapes.insert(rec.shape_id);
+            }
+        }
+        shapes_in_bounds = Some(shapes);
+    }
+
+    let services = weekday_services(dir)?;
+    let mut trips: BTreeMap<String, Option<String>> = BTreeMap::new();
+    for rec in csv::Reader::from_reader(File::open(dir.join("trips.txt"))?).deserialize() {
+        let rec: TripRecord = rec?;
+        if let Some(ref services) = services {
+            if !services.contains(&rec.service_id) {
+                continue;
+            }
+        }
+        if let (Some(shapes), Some(shape)) = (&shapes_in_bounds, &rec.shape_id) {
+            if !shapes.contains(shape) {
+                continue;
+            }
+        }
+        trips.insert(rec.trip_id, rec.shape_id);
+    }
+
+    // Only remember the matched stops of every trip, in order
+    timer.start("read GTFS stop times");
+    let mut stop_times: BTreeMap<String, Vec<(usize, BusStopID, Option<(Time, Time)>)>> =
+        BTreeMap::new();
+    for rec in csv::Reader::from_reader(File::open(dir.join("stop_times.txt"))?).deserialize() {
+        let rec: StopTimeRecord = rec?;
+        if !trips.contains_key(&rec.trip_id) {
+            continue;
+        }
+        if let Some(bs) = stops.get(&rec.stop_id) {
+            // Only timepoints are required to have times
+            let times = match (rec.arrival_time, rec.departure_time) {
+                (Some(arrival), Some(departure)) => {
+                    Some((Time::parse(&arrival)?, Time::parse(&departure)?))
+                }
+                _ => None,
+            };
+            stop_times
+                .entry(rec.trip_id)
+                .or_insert_with(Vec::new)
+                .push((rec.stop_sequence, *bs, times));
+        }
+    }
+    timer.stop("read GTFS stop times");
+
+    // How long it takes each route to reach its first stop
+    let mut lead_times: BTreeMap<BusRouteID, Duration> = BTreeMap::new();
+    for br in map.all_bus_routes() {
+        let lead_time = map
+            .pathfind(PathRequest {
+                start: Position::start(br.start),
+                end: map.get_bs(br.stops[0]).driving_pos,
+                constraints: br.route_type,
+                routing_params: None,
+            })
+            .map(|path| path.estimate_duration(map, br.route_type, None))
+            .unwrap_or(Duration::ZERO);
+        lead_times.insert(br.id, lead_time);
+    }
+
+    let mut trips_per_route: BTreeMap<BusRouteID, Vec<ScheduledTrip>> = BTreeMap::new();
+    let mut matched_trips = 0;
+    let mut unmatched_trips = 0;
+    timer.start_iter("match GTFS trips to routes", stop_times.len());
+    for (trip_id, mut raw_times) in stop_times {
+        timer.next();
+        raw_times.sort_by_key(|(seq, _, _)| *seq);
+        // Stops between timepoints are assumed to be served at the last time given
+        let mut last = None;
+        let times: Vec<(BusStopID, (Time, Time))> = raw_times
+            .into_iter()
+            .filter_map(|(_, bs, maybe_times)| {
+                if maybe_times.is_some() {
+                    last = maybe_times;
+                }
+                last.map(|t| (bs, t))
+            })
+            .collect();
+        if times.len() < 2 {
+            continue;
+        }
+        let stop_ids: Vec<BusStopID> = times.iter().map(|(bs, _)| *bs).collect();
+        if let Some((br, indices)) = match_route(
+            map.all_bus_routes()
+                .iter()
+                .map(|br| (br.id, br.stops.as_slice(), br.gtfs_trip_marker.as_deref())),
+            trips[&trip_id].as_deref(),
+            &stop_ids,
+        ) {
+            let mut stop_times: Vec<(Time, Time)> =
+                indices.into_iter().map(|idx| times[idx].1).collect();
+            let mut spawn_time = if stop_times[0].0 - Time::START_OF_DAY >= lead_times[&br] {
+                stop_times[0].0 - lead_times[&br]
+            } else {
+                Time::START_OF_DAY
+            };
+            // GTFS times past midnight belong to the service day before. Wrap them around.
+            let day = Duration::hours(24);
+            if spawn_time >= Time::START_OF_DAY + day {
+                spawn_time = spawn_time - day;
+                for (arrival, departure) in &mut stop_times {
+                    *arrival = *arrival - day;
+                    *departure = *departure - day;
+                }
+            }
+            trips_per_route
+                .entry(br)
+                .or_insert_with(Vec::new)
+                .push(ScheduledTrip {
+                    gtfs_trip_id: trip_id,
+                    spawn_time,
+                    stop_times,
+                });
+            matched_trips += 1;
+        } else {
+            unmatched_trips += 1;
+        }
+    }
+
+    let mut routes_without_trips = Vec::new();
+    for br in map.all_bus_routes() {
+        if !trips_per_route.contains_key(&br.id) {
+            routes_without_trips.push(br.id);
+        }
+    }
+    for (br, trips) in trips_per_route {
+        map.hack_set_transit_schedule(br, trips);
+    }
+
+    Ok(GtfsReport {
+        stops_in_bounds,
+        matched_stops: stops.len(),
+        unmatched_stops,
+        matched_trips,
+        unmatched_trips,
+        routes_without_trips,
+    })
+}
+
+/// Which route does a trip follow? If OSM tags a route with a GTFS trip marker, that's matched
+/// against the trip's shape. Otherwise, pick the route with the most stops that the trip visits in
+/// order. Routes are given as (ID, stops, trip marker). Returns the route and the index into the
+/// trip's stops for each of the route's stops.
+fn match_route<'a, T: 'a + PartialEq>(
+    routes: impl Iterator<Item = (BusRouteID, &'a [T], Option<&'a str>)>,
+    shape: Option<&str>,
+    trip_stops: &[T],
+) -> Option<(BusRouteID, Vec<usize>)> {
+    let mut best: Option<(BusRouteID, Vec<usize>)> = None;
+    for (id, route_stops, marker) in routes {
+        let indices = match subsequence(route_stops, trip_stops) {
+            Some(indices) => indices,
+            None => continue,
+        };
+        // The gtfs:trip_marker tag starts with the shape_id, but OSM mappers append a suffix like
+        // ":0" that isn't part of the feed.
+        if let (Some(marker), Some(shape)) = (marker, shape) {
+            if marker.split(':').next() == Some(shape) {
+                return Some((id, indices));
+            }
+        }
+        if best
+            .as_ref()
+            .map(|(_, x)| indices.len() > x.len())
+            .unwrap_or(true)
+        {
+            best = Some((id, indices));
+        }
+    }
+    best
+}
+
+/// If every route stop appears in the trip's stops in order, returns where each one appears.
+fn subsequence<T: PartialEq>(route_stops: &[T], trip_stops: &[T]) -> Option<Vec<usize>> {
+    let mut indices = Vec::new();
+    let mut next = 0;
+    for bs in route_stops {
+        let idx = next + trip_stops[next..].iter().position(|x| x == bs)?;
+        indices.push(idx);
+        next = idx + 1;
+    }
+    Some(indices)
+}
+
+/// Services running on one typical weekday. Feeds describe when services run with calendar.txt,
+/// calendar_dates.txt, or both. Pick the Wednesday with the most service, so holidays are skipped,
+/// and apply any exceptions on that date. None if the feed has neither file.
+fn weekday_services(dir: &Path) -> Result<Option<BTreeSet<String>>> {
+    let has_calendar = dir.join("calendar.txt").exists();
+    let has_calendar_dates = dir.join("calendar_dates.txt").exists();
+    if !has_calendar && !has_calendar_dates {
+        return Ok(None);
+    }
+
+    let mut calendar = Vec::new();
+    if has_calendar {
+        for rec in csv::Reader::from_reader(File::open(dir.join("calendar.txt"))?).deserialize() {
+            let rec: CalendarRecord = rec?;
+            calendar.push(rec);
+        }
+    }
+    let mut exceptions = Vec::new();
+    if has_calendar_dates {
+        for rec in
+            csv::Reader::from_reader(File::open(dir.join("calendar_dates.txt"))?).deserialize()
+        {
+            let rec: CalendarDateRecord = rec?;
+            exceptions.push(rec);
+        }
+    }
+
+    // The first Wednesday each regular service runs, and every Wednesday with extra service
+    let mut candidates = BTreeSet::new();
+    for rec in &calendar {
+        if rec.wednesday == 1 {
+            let start = days_since_epoch(rec.start_date);
+            let wednesday = start + (WEDNESDAY - start).rem_euclid(7);
+            if wednesday <= days_since_epoch(rec.end_date) {
+                candidates.insert(wednesday);
+            }
+        }
+    }
+    for rec in &exceptions {
+        let date = days_since_epoch(rec.date);
+        if rec.exception_type == 1 && date.rem_euclid(7) == WEDNESDAY {
+            candidates.insert(date);
+        }
+    }
+
+    let mut best = BTreeSet::new();
+    for date in candidates {
+        let services = services_on(date, &calendar, &exceptions);
+        if services.len() > best.len() {
+            best = services;
+        }
+    }
+    Ok(Some(best))
+}
+
+/// Days since 1970-01-01 (a Thursday) modulo 7 for Wednesdays
+const WEDNESDAY: i64 = 6;
+
+/// The services running on a Wednesday, given as days since the epoch.
+fn services_on(
+    date: i64,
+    calendar: &[CalendarRecord],
+    exceptions: &[CalendarDateRecord],
+) -> BTreeSet<String> {
+    let mut services: BTreeSet<String> = calendar
+        .iter()
+        .filter(|rec| {
+            rec.wednesday == 1
+                && days_since_epoch(rec.start_date) <= date
+                && date <= days_since_epoch(rec.end_date)
+        })
+        .map(|rec| rec.service_id.clone())
+        .collect();
+    for rec in exceptions {
+        if days_since_epoch(rec.date) == date {
+            if rec.exception_type == 1 {
+                services.insert(rec.service_id.clone());
+            } else if rec.exception_type == 2 {
+                services.remove(&rec.service_id);
+            }
+        }
+    }
+    services
+}
+
+/// Converts a GTFS date like 20210317 to days since 1970-01-01.
+fn days_since_epoch(yyyymmdd: u32) -> i64 {
+    let (year, month, day) = (
+        (yyyymmdd / 10000) as i64,
+        (yyyymmdd / 100 % 100) as i64,
+        (yyyymmdd % 100) as i64,
+    );
+    // From http://howardhinnant.github.io/date_algorithms.html#days_from_civil
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[derive(Deserialize)]
+struct StopRecord {
+    stop_id: String,
+    stop_name: Option<String>,
+    stop_lat: Option<f64>,
+    stop_lon: Option<f64>,
+}
+
+#[derive(Deserialize)]
+struct ShapeRecord {
+    shape_id: String,
+    shape_pt_lat: f64,
+    shape_pt_lon: f64,
+}
+
+#[derive(Deserialize)]
+struct TripRecord {
+    service_id: String,
+    trip_id: String,
+    shape_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StopTimeRecord {
+    trip_id: String,
+    arrival_time: Option<String>,
+    departure_time: Option<String>,
+    stop_id: String,
+    stop_sequence: usize,
+}
+
+#[derive(Deserialize)]
+struct CalendarRecord {
+    service_id: String,
+    wednesday: usize,
+    start_date: u32,
+    end_date: u32,
+}
+
+#[derive(Deserialize)]
+struct CalendarDateRecord {
+    service_id: String,
+    date: u32,
+    /// 1 if the service runs on this date, 2 if it doesn't
+    exception_type: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subsequence() {
+        assert_eq!(subsequence(&[1, 2, 3], &[1, 2, 3]), Some(vec![0, 1, 2]));
+        // The trip can visit extra stops
+        assert_eq!(subsequence(&[2, 4], &[1, 2, 3, 4, 5]), Some(vec![1, 3]));
+        // Out of order
+        assert_eq!(subsequence(&[4, 2], &[1, 2, 3, 4]), None);
+        // Missing a stop
+        assert_eq!(subsequence(&[1, 6], &[1, 2, 3]), None);
+        // A loop visits the same stop twice
+        assert_eq!(subsequence(&[1, 2, 1], &[1, 2, 3, 1]), Some(vec![0, 1, 3]));
+        assert_eq!(subsequence(&[1, 2, 1], &[1, 2, 3]), None);
+    }
+
+    #[test]
+    fn test_match_route() {
+        let long: Vec<usize> = vec![1, 2, 3, 4];
+        let short: Vec<usize> = vec![2, 3];
+        let other: Vec<usize> = vec![7, 8];
+        let routes = vec![
+            (BusRouteID(0), short.as_slice(), Some("100:0")),
+            (BusRouteID(1), long.as_slice(), None),
+            (BusRouteID(2), other.as_slice(), None),
+        ];
+        let trip = vec![1, 2, 3, 4, 5];
+
+        // Without a shape, the route covering the most stops wins
+        assert_eq!(
+            match_route(routes.clone().into_iter(), None, &trip),
+            Some((BusRouteID(1), vec![0, 1, 2, 3]))
+        );
+        // The trip marker matches the shape, ignoring the suffix
+        assert_eq!(
+            match_route(routes.clone().into_iter(), Some("100"), &trip),
+            Some((BusRouteID(0), vec![1, 2]))
+        );
+        // A different shape falls back to the most stops
+        assert_eq!(
+            match_route(routes.clone().into_iter(), Some("200"), &trip),
+            Some((BusRouteID(1), vec![0, 1, 2, 3]))
+        );
+        // No route is served in order
+        assert_eq!(match_route(routes.into_iter(), None, &[8, 7]), None);
+    }
+
+    #[test]
+    fn test_days_since_epoch() {
+        assert_eq!(days_since_epoch(19700101), 0);
+        assert_eq!(days_since_epoch(20000301), 11017);
+        // 2021-03-17 was a Wednesday
+        assert_eq!(days_since_epoch(20210317).rem_euclid(7), WEDNESDAY);
+    }
+
+    #[test]
+    fn test_services_from_calendar_dates() {
+        // A feed with only calendar_dates.txt
+        let exceptions = vec![
+            CalendarDateRecord {
+                service_id: "weekday".to_string(),
+                date: 20210317,
+                exception_type: 1,
+            },
+            CalendarDateRecord {
+                service_id: "weekend".to_string(),
+                date: 20210320,
+                exception_type: 1,
+            },
+        ];
+        let services = services_on(days_since_epoch(20210317), &[], &exceptions);
+        assert_eq!(services, vec!["weekday".to_string()].into_iter().collect());
+    }
+
+    #[test]
+    fn test_services_with_holiday() {
+        let calendar = vec![CalendarRecord {
+            service_id: "weekday".to_string(),
+            wednesday: 1,
+            start_date: 20210301,
+            end_date: 20210331,
+        }];
+        let exceptions = vec![CalendarDateRecord {
+            service_id: "weekday".to_string(),
+            date: 20210303,
+            exception_type: 2,
+        }];
+        assert!(services_on(days_since_epoch(20210303), &calendar, &exceptions).is_empty());
+        assert_eq!(
+            services_on(days_since_epoch(20210310), &calendar, &exceptions).len(),
+            1
+        );
+        // Outside the range
+        assert!(services_on(days_since_epoch(20210407), &calendar, &exceptions).is_empty());
+    }
+}
@@ -16,6 +16,7 @@ use configuration::{load_configuration, ImporterConfiguration};
 mod berlin;
 mod configuration;
 mod generic;
+mod gtfs;
 mod seattle;
 mod soundcast;
 mod uk;
@@ -64,6 +65,9 @@ async fn main() {
         scenario: args.enabled("--scenario"),
         // Produce a city overview from all of the individual maps in a city.
         city_overview: args.enabled("--city_overview"),
+        // Replace the transit schedules of each map with a GTFS feed, either a .zip or a directory.
+        // Applied whenever a map is produced.
+        gtfs: args.optional("--gtfs"),
 
         // Only process one map. If not specified, process all maps defined by clipping polygons in
         // importer/config/$city/.
@@ -99,6 +103,7 @@ async fn regenerate_everything(config: ImporterConfiguration) {
             raw_to_map: true,
             scenario: false,
             city_overview: false,
+            gtfs: None,
             only_map: None,
         };
         // Only some maps run extra tasks
@@ -126,6 +131,7 @@ struct Job {
     raw_to_map: bool,
     scenario: bool,
     city_overview: bool,
+    gtfs: Option<String>,
 
     only_map: Option<String>,
 }
@@ -213,10 +219,28 @@ impl Job {
                         "distribute residents from planning areas for {}",
                         name.describe()
                     ));
-                } else if name.city == CityName::seattle() {
-                    timer.start(format!("add GTFS schedules for {}", name.describe()));
-                    seattle::add_gtfs_schedules(&mut map);
-                    timer.stop(format!("add GTFS schedules for {}", name.describe()));
+                }
+
+                // Seattle always has a GTFS feed
+                let gtfs_path = self.gtfs.clone().or_else(|| {
+                    if name.city == CityName::seattle() {
+                        Some(name.city.input_path("google_transit/"))
+                    } else {
+                        None
+                    }
+                });
+                if let Some(ref path) = gtfs_path {
+                    timer.start(format!("import GTFS schedules for {}", name.describe()));
+                    match gtfs::import(&mut map, path, config, timer) {
+                        Ok(report) => {
+                            report.print(&map);
+                            map.save();
+                        }
+                        Err(err) => {
+                            error!("Couldn't import GTFS from {}: {}", path, err);
+                        }
+                    }
+                    timer.stop(format!("import GTFS schedules for {}", name.describe()));
                 }
 
                 Some(map)
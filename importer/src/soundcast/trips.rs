@@ -150,8 +150,10 @@ fn clip_trips(map: &Map, popdat: &PopDat, huge_map: &Map, timer: &mut Timer) ->
                 borders.for_mode(orig.mode),
                 match orig.mode {
                     TripMode::Walk | TripMode::Transit => PathConstraints::Pedestrian,
-                    TripMode::Drive => PathConstraints::Car,
-                    TripMode::Bike => PathConstraints::Bike,
+                    TripMode::Drive | TripMode::ParkAndRide => PathConstraints::Car,
+                    TripMode::Bike | TripMode::Micromobility | TripMode::BikeAndRide => {
+                        PathConstraints::Bike
+                    }
                 },
                 maybe_huge_map.as_ref(),
             )?;
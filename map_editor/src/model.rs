@@ -192,6 +192,7 @@ impl Model {
                 point,
                 intersection_type: IntersectionType::StopSign,
                 elevation: Distance::ZERO,
+                crossing: None,
             },
         );
         self.intersection_added(ctx, id);
@@ -179,7 +179,7 @@ impl Renderable for DrawIntersection {
                     let mut batch = GeomBatch::new();
                     traffic_signal::draw_signal_stage(
                         g.prerender,
-                        &signal.stages[idx],
+                        &signal.stages_at(app.sim_time())[idx],
                         idx,
                         self.id,
                         Some(remaining),
@@ -306,20 +306,23 @@ fn calculate_turn_markings(map: &Map, lane: &Lane) -> Vec<Polygon> {
     }
 
     // Does this lane connect to every other possible outbound lane of the same type, excluding
-    // U-turns to the same road? If so, then there's nothing unexpected to communicate.
+    // U-turns to the same road? If so, then there's nothing unexpected to communicate -- unless
+    // the lane has turn arrows painted on it in reality.
     let i = map.get_i(lane.dst_i);
-    if i.outgoing_lanes.iter().all(|l| {
-        let l = map.get_l(*l);
-        l.lane_type != lane.lane_type
-            || l.parent == lane.parent
-            || map
-                .maybe_get_t(TurnID {
-                    parent: i.id,
-                    src: lane.id,
-                    dst: l.id,
-                })
-                .is_some()
-    }) {
+    if lane.get_turn_restrictions(map).is_none()
+        && i.outgoing_lanes.iter().all(|l| {
+            let l = map.get_l(*l);
+            l.lane_type != lane.lane_type
+                || l.parent == lane.parent
+                || map
+                    .maybe_get_t(TurnID {
+                        parent: i.id,
+                        src: lane.id,
+                        dst: l.id,
+                    })
+                    .is_some()
+        })
+    {
         return Vec::new();
     }
 
@@ -16,16 +16,18 @@ pub struct DrawMovement {
 }
 
 impl DrawMovement {
-    // Also returns the stuff to draw each movement
+    // Also returns the stuff to draw each movement. The stage comes from one of the signal's
+    // timing plans.
     pub fn for_i(
         prerender: &Prerender,
         map: &Map,
         cs: &ColorScheme,
         i: IntersectionID,
+        plan: usize,
         idx: usize,
     ) -> Vec<(DrawMovement, GeomBatch)> {
         let signal = map.get_traffic_signal(i);
-        let stage = &signal.stages[idx];
+        let stage = &signal.plan_stages(plan)[idx];
 
         // TODO Sort by angle here if we want some consistency
         let mut offset_per_lane: HashMap<LaneID, usize> = HashMap::new();
@@ -293,8 +293,10 @@ impl<T: 'static> AppLike for SimpleApp<T> {
 
     fn current_stage_and_remaining_time(&self, id: IntersectionID) -> (usize, Duration) {
         let signal = self.map.get_traffic_signal(id);
-        let mut time_left = (self.time - Time::START_OF_DAY) % signal.simple_cycle_duration();
-        for (idx, stage) in signal.stages.iter().enumerate() {
+        let plan = signal.plan_idx_at(self.time);
+        let stages = signal.plan_stages(plan);
+        let mut time_left = (self.time - Time::START_OF_DAY) % signal.plan_cycle_duration(plan);
+        for (idx, stage) in stages.iter().enumerate() {
             if time_left < stage.stage_type.simple_duration() {
                 return (idx, time_left);
             }
@@ -97,6 +97,13 @@ pub fn upgrade(mut value: Value, map: &Map) -> Result<PermanentMapEdits> {
             .unwrap()
             .insert("version".to_string(), Value::Number(9.into()));
     }
+    if value["version"] == Value::Number(9.into()) {
+        fix_parking_policy(&mut value, map)?;
+        value
+            .as_object_mut()
+            .unwrap()
+            .insert("version".to_string(), Value::Number(10.into()));
+    }
 
     abstutil::from_json(&value.to_string().into_bytes())
 }
@@ -388,6 +395,31 @@ fn fix_lane_widths(value: &mut Value, map: &Map) -> Result<()> {
     Ok(())
 }
 
+// Version 10 added parking_policy to EditRoad.
+fn fix_parking_policy(value: &mut Value, map: &Map) -> Result<()> {
+    for orig in value.as_object_mut().unwrap()["commands"]
+        .as_array_mut()
+        .unwrap()
+    {
+        let cmd = orig.as_object_mut().unwrap();
+        if let Some(cmd) = cmd.get_mut("ChangeRoad") {
+            let road_id: OriginalRoad = serde_json::from_value(cmd["r"].clone()).unwrap();
+            let road = map.get_r(map.find_r_by_osm_id(road_id)?);
+            // Before this, parking policies weren't editable, so the original one works for both
+            // "old" and "new".
+            let policy = serde_json::to_value(road.parking_policy_from_osm()).unwrap();
+            let cmd = cmd.as_object_mut().unwrap();
+            for key in vec!["old", "new"] {
+                cmd[key]
+                    .as_object_mut()
+                    .unwrap()
+                    .insert("parking_policy".to_string(), policy.clone());
+            }
+        }
+    }
+    Ok(())
+}
+
 // These're old structs used in fix_old_lane_cmds.
 #[derive(Debug, Deserialize)]
 struct OriginalLane {
@@ -16,7 +16,7 @@ use crate::make::{match_points_to_lanes, snap_driveway, trim_path};
 use crate::{
     connectivity, AccessRestrictions, BuildingID, BusRouteID, ControlStopSign,
     ControlTrafficSignal, IntersectionID, IntersectionType, LaneID, LaneSpec, Map, MapConfig,
-    ParkingLotID, PathConstraints, Pathfinder, Road, RoadID, TurnID, Zone,
+    ParkingLotID, ParkingPolicy, PathConstraints, Pathfinder, Road, RoadID, TurnID, Zone,
 };
 
 mod compat;
@@ -39,6 +39,7 @@ pub struct MapEdits {
     pub changed_roads: BTreeSet<RoadID>,
     pub original_intersections: BTreeMap<IntersectionID, EditIntersection>,
     pub changed_routes: BTreeSet<BusRouteID>,
+    pub changed_parking_lots: BTreeSet<ParkingLotID>,
 
     /// Some edits are included in the game by default, in data/system/proposals, as "community
     /// proposals." They require a description and may have a link to a write-up.
@@ -60,6 +61,7 @@ pub struct EditRoad {
     pub lanes_ltr: Vec<LaneSpec>,
     pub speed_limit: Speed,
     pub access_restrictions: AccessRestrictions,
+    pub parking_policy: ParkingPolicy,
 }
 
 impl EditRoad {
@@ -68,6 +70,7 @@ impl EditRoad {
             lanes_ltr: get_lane_specs_ltr(&r.osm_tags, cfg),
             speed_limit: r.speed_limit_from_osm(),
             access_restrictions: r.access_restrictions_from_osm(),
+            parking_policy: r.parking_policy_from_osm(),
         }
     }
 
@@ -109,6 +112,9 @@ impl EditRoad {
         if self.access_restrictions != other.access_restrictions {
             changes.push(format!("access restrictions"));
         }
+        if self.parking_policy != other.parking_policy {
+            changes.push(format!("parking policy"));
+        }
         changes
     }
 }
@@ -130,6 +136,11 @@ pub enum EditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    ChangeParkingLot {
+        id: ParkingLotID,
+        old: ParkingPolicy,
+        new: ParkingPolicy,
+    },
 }
 
 pub struct EditEffects {
@@ -154,6 +165,7 @@ impl MapEdits {
             changed_roads: BTreeSet::new(),
             original_intersections: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            changed_parking_lots: BTreeSet::new(),
         }
     }
 
@@ -187,6 +199,7 @@ impl MapEdits {
         self.changed_roads.clear();
         self.original_intersections.clear();
         self.changed_routes.clear();
+        self.changed_parking_lots.clear();
 
         for cmd in &self.commands {
             match cmd {
@@ -201,6 +214,9 @@ impl MapEdits {
                 EditCmd::ChangeRouteSchedule { id, .. } => {
                     self.changed_routes.insert(*id);
                 }
+                EditCmd::ChangeParkingLot { id, .. } => {
+                    self.changed_parking_lots.insert(*id);
+                }
             }
         }
 
@@ -214,6 +230,10 @@ impl MapEdits {
             let r = map.get_br(*br);
             r.spawn_times != r.orig_spawn_times
         });
+        retain_btreeset(&mut self.changed_parking_lots, |pl| {
+            let pl = map.get_pl(*pl);
+            pl.policy != pl.orig_policy
+        });
     }
 
     /// Assumes update_derived has been called.
@@ -240,6 +260,14 @@ impl MapEdits {
                 old: r.orig_spawn_times.clone(),
             });
         }
+        for pl in &self.changed_parking_lots {
+            let pl = map.get_pl(*pl);
+            self.commands.push(EditCmd::ChangeParkingLot {
+                id: pl.id,
+                new: pl.policy.clone(),
+                old: pl.orig_policy.clone(),
+            });
+        }
     }
 
     /// Pick apart changed_roads and figure out if an entire road was edited, or just a few lanes.
@@ -253,6 +281,7 @@ impl MapEdits {
             // What exactly changed?
             if r.speed_limit != orig.speed_limit
                 || r.access_restrictions != orig.access_restrictions
+                || r.parking_policy != orig.parking_policy
             {
                 roads.insert(r.id);
             } else {
@@ -298,6 +327,7 @@ impl EditCmd {
             EditCmd::ChangeRouteSchedule { id, .. } => {
                 format!("reschedule route {}", map.get_br(*id).short_name)
             }
+            EditCmd::ChangeParkingLot { id, .. } => format!("parking lot #{}", id.0),
         };
         (summary, details)
     }
@@ -314,6 +344,7 @@ impl EditCmd {
                 let road = &mut map.roads[r.0];
                 road.speed_limit = new.speed_limit;
                 road.access_restrictions = new.access_restrictions.clone();
+                road.parking_policy = new.parking_policy.clone();
 
                 effects.changed_roads.insert(road.id);
                 for i in vec![road.src_i, road.dst_i] {
@@ -374,6 +405,9 @@ impl EditCmd {
             EditCmd::ChangeRouteSchedule { id, new, .. } => {
                 map.bus_routes[id.0].spawn_times = new.clone();
             }
+            EditCmd::ChangeParkingLot { id, new, .. } => {
+                map.parking_lots[id.0].policy = new.clone();
+            }
         }
     }
 
@@ -394,6 +428,11 @@ impl EditCmd {
                 old: new,
                 new: old,
             },
+            EditCmd::ChangeParkingLot { id, old, new } => EditCmd::ChangeParkingLot {
+                id,
+                old: new,
+                new: old,
+            },
         }
     }
 }
@@ -744,6 +783,7 @@ impl Map {
             lanes_ltr: r.lane_specs(self),
             speed_limit: r.speed_limit,
             access_restrictions: r.access_restrictions.clone(),
+            parking_policy: r.parking_policy.clone(),
         }
     }
 
@@ -917,4 +957,11 @@ impl Map {
         );
         self.traffic_signals.insert(signal.id, signal);
     }
+
+    /// Replaces a traffic signal outside of the usual editing flow, as long as it's valid.
+    pub fn try_edit_traffic_signal(&mut self, signal: ControlTrafficSignal) -> Result<()> {
+        signal.validate()?;
+        self.incremental_edit_traffic_signal(signal);
+        Ok(())
+    }
 }
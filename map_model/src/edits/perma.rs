@@ -9,7 +9,7 @@ use geom::Time;
 
 use crate::edits::{EditCmd, EditIntersection, EditRoad, MapEdits};
 use crate::raw::OriginalRoad;
-use crate::{osm, ControlStopSign, IntersectionID, Map};
+use crate::{osm, ControlStopSign, IntersectionID, Map, ParkingPolicy};
 
 /// MapEdits are converted to this before serializing. Referencing things like LaneID in a Map won't
 /// work if the basemap is rebuilt from new OSM data, so instead we use stabler OSM IDs that're less
@@ -61,6 +61,11 @@ pub enum PermanentEditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    ChangeParkingLot {
+        osm_id: osm::OsmID,
+        old: ParkingPolicy,
+        new: ParkingPolicy,
+    },
 }
 
 impl EditCmd {
@@ -83,6 +88,11 @@ impl EditCmd {
                     new: new.clone(),
                 }
             }
+            EditCmd::ChangeParkingLot { id, old, new } => PermanentEditCmd::ChangeParkingLot {
+                osm_id: map.get_pl(*id).osm_id,
+                old: old.clone(),
+                new: new.clone(),
+            },
         }
     }
 }
@@ -127,6 +137,15 @@ impl PermanentEditCmd {
                     .ok_or(anyhow!("can't find {}", osm_rel_id))?;
                 Ok(EditCmd::ChangeRouteSchedule { id, old, new })
             }
+            PermanentEditCmd::ChangeParkingLot { osm_id, old, new } => {
+                let id = map
+                    .all_parking_lots()
+                    .iter()
+                    .find(|pl| pl.osm_id == osm_id)
+                    .map(|pl| pl.id)
+                    .ok_or(anyhow!("can't find parking lot {}", osm_id))?;
+                Ok(EditCmd::ChangeParkingLot { id, old, new })
+            }
         }
     }
 }
@@ -138,7 +157,7 @@ impl MapEdits {
             map_name: map.get_name().clone(),
             edits_name: self.edits_name.clone(),
             // Increase this every time there's a schema change
-            version: 9,
+            version: 10,
             proposal_description: self.proposal_description.clone(),
             proposal_link: self.proposal_link.clone(),
             commands: self.commands.iter().map(|cmd| cmd.to_perma(map)).collect(),
@@ -165,6 +184,7 @@ impl PermanentMapEdits {
             changed_roads: BTreeSet::new(),
             original_intersections: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            changed_parking_lots: BTreeSet::new(),
         };
         edits.update_derived(map);
         Ok(edits)
@@ -187,6 +207,7 @@ impl PermanentMapEdits {
             changed_roads: BTreeSet::new(),
             original_intersections: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            changed_parking_lots: BTreeSet::new(),
         };
         edits.update_derived(map);
         edits
@@ -38,22 +38,29 @@ pub use crate::city::City;
 pub use crate::edits::{
     EditCmd, EditEffects, EditIntersection, EditRoad, MapEdits, PermanentMapEdits,
 };
+pub use crate::make::traffic_signals::GreenWave;
 pub use crate::make::RawToMapOptions;
 pub use crate::map::{DrivingSide, MapConfig};
 pub use crate::objects::area::{Area, AreaID, AreaType};
 pub use crate::objects::building::{
     Amenity, AmenityType, Building, BuildingID, BuildingType, NamePerLanguage, OffstreetParking,
 };
-pub use crate::objects::bus_stop::{BusRoute, BusRouteID, BusStop, BusStopID};
-pub use crate::objects::intersection::{Intersection, IntersectionID, IntersectionType};
+pub use crate::objects::bus_stop::{
+    BusRoute, BusRouteID, BusStop, BusStopID, ScheduledTrip, VehicleCapacity,
+};
+pub use crate::objects::intersection::{
+    CrossingType, Intersection, IntersectionID, IntersectionType,
+};
 pub use crate::objects::lane::{
-    Lane, LaneID, LaneSpec, LaneType, NORMAL_LANE_THICKNESS, PARKING_LOT_SPOT_LENGTH,
-    SIDEWALK_THICKNESS,
+    parse_turn_lanes, Lane, LaneID, LaneSpec, LaneType, NORMAL_LANE_THICKNESS,
+    PARKING_LOT_SPOT_LENGTH, SIDEWALK_THICKNESS,
 };
-pub use crate::objects::parking_lot::{ParkingLot, ParkingLotID};
+pub use crate::objects::parking_lot::{ParkingLot, ParkingLotID, ParkingPolicy};
 pub use crate::objects::road::{DirectedRoadID, Direction, Road, RoadID};
 pub use crate::objects::stop_signs::{ControlStopSign, RoadWithStopSign};
-pub use crate::objects::traffic_signals::{ControlTrafficSignal, Stage, StageType};
+pub use crate::objects::traffic_signals::{
+    ControlTrafficSignal, Stage, StageType, TimingPlan, TransitPriority,
+};
 pub use crate::objects::turn::{
     CompressedMovementID, Movement, MovementID, Turn, TurnID, TurnPriority, TurnType,
 };
@@ -61,7 +68,8 @@ pub use crate::objects::zone::{AccessRestrictions, Zone};
 pub use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn};
 use crate::pathfind::Pathfinder;
 pub use crate::pathfind::{
-    Path, PathConstraints, PathRequest, PathStep, PathStepV2, PathV2, RoutingParams,
+    CostFunction, Path, PathConstraints, PathRequest, PathStep, PathStepV2, PathV2, RoutingParams,
+    TimeDependentPathfinder, TravelTimeProfile,
 };
 pub use crate::traversable::{Position, Traversable, MAX_BIKE_SPEED, MAX_WALKING_SPEED};
 
@@ -14,8 +14,9 @@ use crate::pathfind::Pathfinder;
 use crate::raw::{OriginalRoad, RawMap};
 use crate::{
     connectivity, osm, AccessRestrictions, Area, AreaID, AreaType, ControlStopSign,
-    ControlTrafficSignal, Intersection, IntersectionID, IntersectionType, Lane, LaneID, Map,
-    MapEdits, Movement, PathConstraints, Position, Road, RoadID, RoutingParams, Turn, Zone,
+    ControlTrafficSignal, Direction, Intersection, IntersectionID, IntersectionType, Lane, LaneID,
+    Map, MapEdits, Movement, ParkingPolicy, PathConstraints, Position, Road, RoadID, RoutingParams,
+    Turn, Zone,
 };
 
 mod bridges;
@@ -111,6 +112,7 @@ impl Map {
                 // Might change later
                 intersection_type: i.intersection_type,
                 orig_id: i.id,
+                crossing: raw.intersections[&i.id].crossing,
                 incoming_lanes: Vec::new(),
                 outgoing_lanes: Vec::new(),
                 roads: i.roads.iter().map(|id| road_id_mapping[id]).collect(),
@@ -175,10 +177,17 @@ impl Map {
                     0
                 },
                 access_restrictions: AccessRestrictions::new(),
+                parking_policy: ParkingPolicy::free(),
+                turn_lanes_fwd: None,
+                turn_lanes_back: None,
                 percent_incline: raw_road.percent_incline,
             };
             road.speed_limit = road.speed_limit_from_osm();
             road.access_restrictions = road.access_restrictions_from_osm();
+            road.parking_policy = road.parking_policy_from_osm();
+            road.turn_lanes_fwd = road.turn_lanes_from_osm(Direction::Fwd, raw.config.driving_side);
+            road.turn_lanes_back =
+                road.turn_lanes_from_osm(Direction::Back, raw.config.driving_side);
 
             for lane in road.create_lanes(r.lane_specs_ltr, &mut map.lane_id_counter) {
                 map.intersections[lane.src_i.0].outgoing_lanes.push(lane.id);
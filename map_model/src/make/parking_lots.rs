@@ -8,8 +8,8 @@ use geom::{Angle, Distance, FindClosest, HashablePt2D, Line, PolyLine, Polygon,
 use crate::make::{match_points_to_lanes, trim_path};
 use crate::raw::RawParkingLot;
 use crate::{
-    osm, Map, ParkingLot, ParkingLotID, PathConstraints, Position, NORMAL_LANE_THICKNESS,
-    PARKING_LOT_SPOT_LENGTH,
+    osm, Map, ParkingLot, ParkingLotID, ParkingPolicy, PathConstraints, Position,
+    NORMAL_LANE_THICKNESS, PARKING_LOT_SPOT_LENGTH,
 };
 
 /// Take in parking lots from OSM and all parking aisle roads. Match parking lots to the nearest
@@ -48,6 +48,7 @@ pub fn make_all_parking_lots(
         match snap_driveway(lot_center, &orig.polygon, &sidewalk_pts, map) {
             Ok((driveway_line, driving_pos, sidewalk_line, sidewalk_pos)) => {
                 let id = ParkingLotID(results.len());
+                let policy = ParkingPolicy::from_osm(&orig.osm_tags, "");
                 results.push(ParkingLot {
                     id,
                     polygon: orig.polygon.clone(),
@@ -60,6 +61,9 @@ pub fn make_all_parking_lots(
                     driving_pos,
                     sidewalk_line,
                     sidewalk_pos,
+
+                    policy: policy.clone(),
+                    orig_policy: policy,
                 });
             }
             Err(err) => {
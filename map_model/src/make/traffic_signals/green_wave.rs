@@ -0,0 +1,303 @@
+//! Coordinates the offsets of traffic signals along a corridor, so that platoons of vehicles
+//! traveling at the speed limit hit consecutive green lights in both directions.
+
+use anyhow::Result;
+
+use geom::Duration;
+
+use crate::{ControlTrafficSignal, EditCmd, EditIntersection, IntersectionID, Map, RoadID};
+
+// The resolution of the search over offsets and departure times. Offsets are exported as whole
+// seconds, so this shouldn't be any finer.
+const STEP: Duration = Duration::const_seconds(1.0);
+// How many times to sweep over the signals, improving each offset
+const PASSES: usize = 3;
+
+/// Offsets for the signals along a corridor that form a green wave, and how well they work. Only
+/// the base timing plan of each signal is considered.
+#[derive(Clone, Debug)]
+pub struct GreenWave {
+    /// The signals along the corridor, in order
+    pub intersections: Vec<IntersectionID>,
+    /// The new offset for each signal, in the same order. The first signal keeps its offset.
+    pub offsets: Vec<Duration>,
+    /// The cycle length shared by all of the signals
+    pub cycle: Duration,
+    /// The free-flow travel time from the first signal to the last
+    pub travel_time: Duration,
+    /// The bandwidth forwards (from the first signal to the last) and backwards, using the
+    /// original offsets. The bandwidth is how much of each cycle a platoon can start at one end of
+    /// the corridor and make it through every green light.
+    pub bandwidth_before: (Duration, Duration),
+    /// The bandwidth forwards and backwards using the new offsets
+    pub bandwidth_after: (Duration, Duration),
+}
+
+impl GreenWave {
+    /// `intersections` must be traffic signals along a route, in order. Consecutive signals are
+    /// connected by the shortest path between them. All of the signals need the same cycle
+    /// length.
+    pub fn new(map: &Map, intersections: Vec<IntersectionID>) -> Result<GreenWave> {
+        if intersections.len() < 2 {
+            bail!("A green wave needs at least two signals");
+        }
+        let mut signals: Vec<&ControlTrafficSignal> = Vec::new();
+        for i in &intersections {
+            if let Some(ts) = map.maybe_get_traffic_signal(*i) {
+                signals.push(ts);
+            } else {
+                bail!("{} isn't a traffic signal", i);
+            }
+        }
+        let cycle = signals[0].simple_cycle_duration();
+        for ts in &signals {
+            if ts.simple_cycle_duration() != cycle {
+                bail!(
+                    "All signals along a green wave need the same cycle length, but {} has {} and \
+                     {} has {}",
+                    signals[0].id,
+                    cycle,
+                    ts.id,
+                    ts.simple_cycle_duration()
+                );
+            }
+        }
+
+        // Find the roads between each pair of signals
+        let mut paths: Vec<Vec<RoadID>> = Vec::new();
+        for pair in intersections.windows(2) {
+            match map.simple_path_btwn(pair[0], pair[1]) {
+                Some(path) if !path.is_empty() => {
+                    paths.push(path);
+                }
+                _ => bail!("No path from {} to {}", pair[0], pair[1]),
+            }
+        }
+
+        let mut travel_times = vec![Duration::ZERO];
+        for path in &paths {
+            let mut dt = *travel_times.last().unwrap();
+            for r in path {
+                let r = map.get_r(*r);
+                dt += r.center_pts.length() / r.speed_limit;
+            }
+            travel_times.push(dt);
+        }
+
+        let mut fwd_green = Vec::new();
+        let mut back_green = Vec::new();
+        for (idx, ts) in signals.iter().enumerate() {
+            let road_before = if idx == 0 {
+                None
+            } else {
+                paths[idx - 1].last().cloned()
+            };
+            let road_after = paths.get(idx).map(|path| path[0]);
+            fwd_green.push(green_window(ts, road_before, road_after)?);
+            back_green.push(green_window(ts, road_after, road_before)?);
+        }
+
+        let corridor = Corridor {
+            cycle,
+            fwd_green,
+            back_green,
+            travel_times,
+        };
+        let orig_offsets: Vec<Duration> = signals.iter().map(|ts| ts.offset).collect();
+        let offsets = corridor.best_offsets(orig_offsets[0]);
+
+        Ok(GreenWave {
+            intersections,
+            cycle,
+            travel_time: *corridor.travel_times.last().unwrap(),
+            bandwidth_before: corridor.bandwidth(&orig_offsets),
+            bandwidth_after: corridor.bandwidth(&offsets),
+            offsets,
+        })
+    }
+
+    /// The edits needed to apply the new offsets.
+    pub fn edit_cmds(&self, map: &Map) -> Vec<EditCmd> {
+        let mut cmds = Vec::new();
+        for (i, offset) in self.intersections.iter().zip(self.offsets.iter()) {
+            let mut ts = map.get_traffic_signal(*i).clone();
+            if ts.offset == *offset {
+                continue;
+            }
+            ts.offset = *offset;
+            cmds.push(EditCmd::ChangeIntersection {
+                i: *i,
+                old: map.get_i_edit(*i),
+                new: EditIntersection::TrafficSignal(ts.export(map)),
+            });
+        }
+        cmds
+    }
+
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!(
+                "{} signals with a {} cycle, {} apart at the speed limit",
+                self.intersections.len(),
+                self.cycle,
+                self.travel_time
+            ),
+            format!(
+                "Bandwidth before: {} forwards, {} backwards",
+                self.bandwidth_before.0, self.bandwidth_before.1
+            ),
+            format!(
+                "Bandwidth after: {} forwards, {} backwards",
+                self.bandwidth_after.0, self.bandwidth_after.1
+            ),
+        ];
+        for (i, offset) in self.intersections.iter().zip(self.offsets.iter()) {
+            lines.push(format!("{}: offset {}", i, offset));
+        }
+        lines
+    }
+}
+
+struct Corridor {
+    cycle: Duration,
+    // For each signal, when the corridor's movements have a protected green in each direction.
+    // This is the (start, duration) within the cycle, ignoring the offset.
+    fwd_green: Vec<(Duration, Duration)>,
+    back_green: Vec<(Duration, Duration)>,
+    // From the first signal to each signal
+    travel_times: Vec<Duration>,
+}
+
+impl Corridor {
+    /// How much of the cycle can a platoon depart during and make it through every green, forwards
+    /// and backwards?
+    fn bandwidth(&self, offsets: &[Duration]) -> (Duration, Duration) {
+        let total = *self.travel_times.last().unwrap();
+        let mut fwd = 0;
+        let mut back = 0;
+        for step in 0..(self.cycle / STEP) as usize {
+            let depart = STEP * (step as f64);
+            if (0..offsets.len()).all(|k| {
+                self.is_green(
+                    self.fwd_green[k],
+                    depart + self.travel_times[k] + offsets[k],
+                )
+            }) {
+                fwd += 1;
+            }
+            if (0..offsets.len()).all(|k| {
+                self.is_green(
+                    self.back_green[k],
+                    depart + (total - self.travel_times[k]) + offsets[k],
+                )
+            }) {
+                back += 1;
+            }
+        }
+        (STEP * (fwd as f64), STEP * (back as f64))
+    }
+
+    fn is_green(&self, (start, duration): (Duration, Duration), time: Duration) -> bool {
+        self.wrap(time - start) < duration
+    }
+
+    fn wrap(&self, time: Duration) -> Duration {
+        let time = time % self.cycle;
+        if time < Duration::ZERO {
+            time + self.cycle
+        } else {
+            time
+        }
+    }
+
+    /// Start from one-way progression in the forwards direction, then repeatedly adjust each
+    /// offset (besides the first) to maximize the total bandwidth in both directions.
+    fn best_offsets(&self, first_offset: Duration) -> Vec<Duration> {
+        let mut offsets: Vec<Duration> = (0..self.travel_times.len())
+            .map(|k| {
+                let ideal =
+                    first_offset + self.fwd_green[k].0 - self.fwd_green[0].0 - self.travel_times[k];
+                Duration::seconds(self.wrap(ideal).inner_seconds().round()) % self.cycle
+            })
+            .collect();
+        offsets[0] = first_offset;
+
+        let score = |offsets: &[Duration]| {
+            let (fwd, back) = self.bandwidth(offsets);
+            fwd + back
+        };
+        let mut best = score(&offsets);
+        for _ in 0..PASSES {
+            let mut improved = false;
+            for k in 1..offsets.len() {
+                for step in 0..(self.cycle / STEP) as usize {
+                    let orig = offsets[k];
+                    offsets[k] = STEP * (step as f64);
+                    let candidate = score(&offsets);
+                    if candidate > best {
+                        best = candidate;
+                        improved = true;
+                    } else {
+                        offsets[k] = orig;
+                    }
+                }
+            }
+            if !improved {
+                break;
+            }
+        }
+        offsets
+    }
+}
+
+/// When does a signal give a protected green to vehicles coming from one road and going to
+/// another? Either road may be None at the ends of the corridor, matching any movement. Returns
+/// the start and duration of the longest run of such stages within the cycle.
+fn green_window(
+    ts: &ControlTrafficSignal,
+    from: Option<RoadID>,
+    to: Option<RoadID>,
+) -> Result<(Duration, Duration)> {
+    let matches: Vec<bool> = ts
+        .stages
+        .iter()
+        .map(|stage| {
+            stage.protected_movements.iter().any(|m| {
+                !m.crosswalk
+                    && from.map_or(true, |r| m.from.id == r)
+                    && to.map_or(true, |r| m.to.id == r)
+            })
+        })
+        .collect();
+    if !matches.contains(&true) {
+        bail!("{} never gives a protected green along the corridor", ts.id);
+    }
+    let durations: Vec<Duration> = ts
+        .stages
+        .iter()
+        .map(|stage| stage.stage_type.simple_duration())
+        .collect();
+    if !matches.contains(&false) {
+        return Ok((Duration::ZERO, ts.simple_cycle_duration()));
+    }
+
+    let n = matches.len();
+    let mut best = (Duration::ZERO, Duration::ZERO);
+    let mut start = Duration::ZERO;
+    for idx in 0..n {
+        // Only look at the beginning of each run of green stages, possibly wrapping around
+        if matches[idx] && !matches[(idx + n - 1) % n] {
+            let mut length = Duration::ZERO;
+            let mut j = idx;
+            while matches[j] {
+                length += durations[j];
+                j = (j + 1) % n;
+            }
+            if length > best.1 {
+                best = (start, length);
+            }
+        }
+        start += durations[idx];
+    }
+    Ok(best)
+}
@@ -7,12 +7,16 @@
 
 use std::collections::{BTreeSet, HashSet};
 
+use crate::objects::traffic_signals::DEFAULT_PASSAGE_TIME;
 use crate::{
-    ControlTrafficSignal, DrivingSide, IntersectionCluster, IntersectionID, Map, Movement,
-    MovementID, RoadID, Stage, StageType, TurnPriority, TurnType,
+    ControlTrafficSignal, CrossingType, DrivingSide, IntersectionCluster, IntersectionID, Map,
+    Movement, MovementID, RoadID, Stage, StageType, TurnPriority, TurnType,
 };
 use geom::Duration;
 
+pub use self::green_wave::GreenWave;
+
+mod green_wave;
 mod lagging_green;
 
 /// Applies a bunch of heuristics to a single intersection, returning the valid results in
@@ -56,6 +60,9 @@ pub fn get_possible_policies(
         }
     }
 
+    if let Some(ts) = pedestrian_push_button(map, id) {
+        results.push(("pedestrian push-button crossing".to_string(), ts));
+    }
     // As long as we're using silly heuristics for these by default, prefer shorter cycle
     // length.
     if let Some(ts) = four_way_two_stage(map, id) {
@@ -110,6 +117,8 @@ fn new(id: IntersectionID, map: &Map) -> ControlTrafficSignal {
         id,
         stages: Vec::new(),
         offset: Duration::ZERO,
+        later_plans: Vec::new(),
+        transit_priority: None,
         movements: Movement::for_i(id, map).unwrap(),
     }
 }
@@ -184,6 +193,39 @@ fn half_signal(map: &Map, i: IntersectionID) -> Option<ControlTrafficSignal> {
     Some(ts)
 }
 
+/// For signalized mid-block crossings. Vehicles keep the green until a pedestrian pushes the
+/// button; then, after vehicles have had the minimum green, pedestrians get a walk stage.
+fn pedestrian_push_button(map: &Map, i: IntersectionID) -> Option<ControlTrafficSignal> {
+    if map.get_i(i).crossing != Some(CrossingType::Signalized) {
+        return None;
+    }
+
+    let mut ts = new(i, map);
+    let mut vehicle_stage = Stage::new();
+    let mut ped_stage = Stage::new();
+    for (id, movement) in &ts.movements {
+        if id.crosswalk {
+            ped_stage.edit_movement(movement, TurnPriority::Protected);
+        } else {
+            vehicle_stage.edit_movement(movement, TurnPriority::Protected);
+        }
+    }
+    if ped_stage.protected_movements.is_empty() {
+        return None;
+    }
+    vehicle_stage.stage_type = StageType::Fixed(Duration::seconds(30.0));
+    // Skipped until somebody pushes the button. The minimum is raised later to give enough time
+    // to cross.
+    ped_stage.stage_type = StageType::Actuated(
+        Duration::seconds(10.0),
+        Duration::seconds(20.0),
+        DEFAULT_PASSAGE_TIME,
+    );
+
+    ts.stages = vec![vehicle_stage, ped_stage];
+    Some(ts)
+}
+
 fn three_way(map: &Map, i: IntersectionID) -> Option<ControlTrafficSignal> {
     let roads = map.get_i(i).get_sorted_incoming_roads(map);
     if roads.len() != 3 {
@@ -9,6 +9,7 @@ use crate::make::match_points_to_lanes;
 use crate::raw::{RawBusRoute, RawBusStop};
 use crate::{
     BusRoute, BusRouteID, BusStop, BusStopID, LaneID, LaneType, Map, PathConstraints, Position,
+    VehicleCapacity,
 };
 
 /// Construct the final model of bus/train stops and routes. This is quite broken currently, so not
@@ -149,6 +150,11 @@ fn make_route(
         end_border,
         spawn_times: default_spawn_times(),
         orig_spawn_times: default_spawn_times(),
+        scheduled_trips: Vec::new(),
+        capacity: r
+            .capacity
+            .map(VehicleCapacity::from_crush_load)
+            .unwrap_or_else(|| VehicleCapacity::default_for(route_type)),
     };
 
     let mut debug_route = format!("All parts of the route:");
@@ -141,10 +141,7 @@ fn ensure_unique(turns: Vec<Turn>) -> Vec<Turn> {
 }
 
 fn is_turn_allowed(turn: &Turn, map: &Map) -> bool {
-    if let Some(types) = map
-        .get_l(turn.id.src)
-        .get_turn_restrictions(map.get_parent(turn.id.src))
-    {
+    if let Some(types) = map.get_l(turn.id.src).get_turn_restrictions(map) {
         types.contains(&turn.turn_type)
     } else {
         true
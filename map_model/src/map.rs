@@ -13,10 +13,10 @@ use geom::{Bounds, Distance, GPSBounds, Polygon, Pt2D, Ring, Time};
 use crate::raw::{OriginalRoad, RawMap};
 use crate::{
     osm, Area, AreaID, AreaType, Building, BuildingID, BuildingType, BusRoute, BusRouteID, BusStop,
-    BusStopID, ControlStopSign, ControlTrafficSignal, DirectedRoadID, Intersection, IntersectionID,
-    Lane, LaneID, LaneType, Map, MapEdits, MovementID, OffstreetParking, ParkingLot, ParkingLotID,
-    Path, PathConstraints, PathRequest, Pathfinder, Position, Road, RoadID, RoutingParams, Turn,
-    TurnID, TurnType, Zone,
+    BusStopID, ControlStopSign, ControlTrafficSignal, CostFunction, DirectedRoadID, Intersection,
+    IntersectionID, Lane, LaneID, LaneType, Map, MapEdits, MovementID, OffstreetParking,
+    ParkingLot, ParkingLotID, Path, PathConstraints, PathRequest, Pathfinder, Position, Road,
+    RoadID, RoutingParams, ScheduledTrip, TravelTimeProfile, Turn, TurnID, TurnType, Zone,
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -604,6 +604,43 @@ impl Map {
         path.to_v1(self)
     }
 
+    /// Finds a path for a vehicle using custom costs. This is slow; see `CostFunction`.
+    pub fn pathfind_with_cost_function(
+        &self,
+        req: PathRequest,
+        cost_fn: &dyn CostFunction,
+    ) -> Result<Path> {
+        let params = self.routing_params_for(&req);
+        let path = crate::pathfind::dijkstra::pathfind_with_cost_function(
+            req.clone(),
+            params,
+            cost_fn,
+            self,
+        )
+        .ok_or_else(|| anyhow!("can't fulfill {}", req))?;
+        path.to_v1(self)
+    }
+
+    /// Finds a path for a car, using travel times that depend on the time of day. This is exact
+    /// but slow; see `TimeDependentPathfinder` to route many trips.
+    pub fn pathfind_with_travel_times(
+        &self,
+        req: PathRequest,
+        departure: Time,
+        profile: &TravelTimeProfile,
+    ) -> Result<Path> {
+        assert!(!self.pathfinder_dirty);
+        let path = crate::pathfind::dijkstra::pathfind_with_travel_times(
+            req.clone(),
+            departure,
+            profile,
+            &self.routing_params,
+            self,
+        )
+        .ok_or_else(|| anyhow!("can't fulfill {}", req))?;
+        path.to_v1(self)
+    }
+
     pub fn should_use_transit(
         &self,
         start: Position,
@@ -688,6 +725,16 @@ impl Map {
         self.bus_routes[br.0].spawn_times = times;
     }
 
+    /// Replaces a route's schedule with a published timetable. The spawn times come from the
+    /// trips.
+    /// Every trip spawns its own vehicle, even if several start at the same time.
+    pub fn hack_set_transit_schedule(&mut self, br: BusRouteID, mut trips: Vec<ScheduledTrip>) {
+        trips.sort_by_key(|trip| trip.spawn_time);
+        let times: Vec<Time> = trips.iter().map(|trip| trip.spawn_time).collect();
+        self.hack_override_orig_spawn_times(br, times);
+        self.bus_routes[br.0].scheduled_trips = trips;
+    }
+
     pub fn hack_add_area(&mut self, area_type: AreaType, polygon: Polygon, osm_tags: Tags) {
         self.areas.push(Area {
             id: AreaID(self.areas.len()),
@@ -703,6 +750,7 @@ impl Map {
         routing_params: RoutingParams,
         timer: &mut Timer,
     ) {
+        routing_params.check().unwrap();
         self.routing_params = routing_params;
         self.pathfinder_dirty = true;
         self.recalculate_pathfinding_after_edits(timer);
@@ -761,4 +809,11 @@ impl Map {
     pub fn routing_params(&self) -> &RoutingParams {
         &self.routing_params
     }
+
+    /// Returns the routing params that apply to one request -- its own, or else the map's.
+    pub fn routing_params_for<'a>(&'a self, req: &'a PathRequest) -> &'a RoutingParams {
+        req.routing_params
+            .as_deref()
+            .unwrap_or(&self.routing_params)
+    }
 }
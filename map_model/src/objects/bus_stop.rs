@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 use abstutil::{deserialize_usize, serialize_usize};
 use geom::Time;
 
+use crate::objects::building::sidewalk_to_bike;
 use crate::{osm, LaneID, Map, PathConstraints, PathRequest, Position};
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
@@ -50,6 +51,14 @@ pub struct BusStop {
     pub is_train_stop: bool,
 }
 
+impl BusStop {
+    /// Where a bike could be left next to this stop. Returns (biking position, sidewalk position),
+    /// or None if the stop's road has no lane that bikes can use.
+    pub fn biking_connection(&self, map: &Map) -> Option<(Position, Position)> {
+        sidewalk_to_bike(self.sidewalk_pos, map)
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 pub struct BusRoute {
     pub id: BusRouteID,
@@ -67,21 +76,84 @@ pub struct BusRoute {
     /// Explicitly store whatever the original was, since this can't be reconstructed without side
     /// input.
     pub orig_spawn_times: Vec<Time>,
+    /// When each vehicle is expected at every stop, if the schedule was imported from GTFS.
+    /// Otherwise empty. Matches `orig_spawn_times` one-to-one.
+    pub scheduled_trips: Vec<ScheduledTrip>,
+    /// How many people fit on each vehicle serving this route
+    pub capacity: VehicleCapacity,
+}
+
+/// One vehicle's run along a route, according to a published timetable.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ScheduledTrip {
+    /// The trip_id from GTFS
+    pub gtfs_trip_id: String,
+    /// When the vehicle should appear at the start of the route
+    pub spawn_time: Time,
+    /// The expected arrival and departure time at each of the route's stops. The difference is
+    /// how long the vehicle dwells there.
+    pub stop_times: Vec<(Time, Time)>,
+}
+
+/// How many passengers a transit vehicle can carry.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct VehicleCapacity {
+    /// The planned load, with seats and some standing room. Beyond this, the vehicle is crowded.
+    pub comfortable: usize,
+    /// Nobody else can squeeze on past this many passengers.
+    pub crush_load: usize,
+}
+
+impl VehicleCapacity {
+    /// Typical capacity of a standard 40ft bus or a two-car light rail train.
+    pub fn default_for(route_type: PathConstraints) -> VehicleCapacity {
+        if route_type == PathConstraints::Train {
+            VehicleCapacity {
+                comfortable: 300,
+                crush_load: 400,
+            }
+        } else {
+            VehicleCapacity {
+                comfortable: 60,
+                crush_load: 80,
+            }
+        }
+    }
+
+    /// When only the total capacity is known, such as from a `capacity` tag in OSM, assume the
+    /// vehicle gets crowded at three quarters of it.
+    pub fn from_crush_load(crush_load: usize) -> VehicleCapacity {
+        VehicleCapacity {
+            comfortable: crush_load * 3 / 4,
+            crush_load,
+        }
+    }
 }
 
 impl BusRoute {
+    /// The timetable for the vehicle starting at `spawn_times[idx]`. None if the route has no
+    /// imported schedule, or if its spawn times have been edited since.
+    pub fn scheduled_trip(&self, idx: usize) -> Option<&ScheduledTrip> {
+        if self.spawn_times != self.orig_spawn_times {
+            return None;
+        }
+        self.scheduled_trips.get(idx)
+    }
+
     pub fn all_steps(&self, map: &Map) -> Vec<PathRequest> {
         let mut steps = Vec::new();
         steps.push(PathRequest {
             start: Position::start(self.start),
             end: map.get_bs(self.stops[0]).driving_pos,
             constraints: self.route_type,
+            routing_params: None,
         });
         for pair in self.stops.windows(2) {
             steps.push(PathRequest {
                 start: map.get_bs(pair[0]).driving_pos,
                 end: map.get_bs(pair[1]).driving_pos,
                 constraints: self.route_type,
+                routing_params: None,
             });
         }
         if let Some(end) = self.end_border {
@@ -89,6 +161,7 @@ impl BusRoute {
                 start: map.get_bs(*self.stops.last().unwrap()).driving_pos,
                 end: Position::end(end, map),
                 constraints: self.route_type,
+                routing_params: None,
             });
         }
         steps
@@ -31,6 +31,16 @@ pub enum IntersectionType {
     Construction,
 }
 
+/// A pedestrian crossing in the middle of a block, away from any junction. These are imported
+/// from OSM as degenerate intersections between two pieces of the same road.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize, Deserialize)]
+pub enum CrossingType {
+    /// Marked, but with no signal. Vehicles must yield to pedestrians waiting to cross.
+    Zebra,
+    /// Pedestrians push a button and wait for a signal.
+    Signalized,
+}
+
 /// An intersection connects roads. Most have >2 roads and are controlled by stop signs or traffic
 /// signals. Roads that lead to the boundary of the map end at border intersections, with only that
 /// one road attached.
@@ -44,6 +54,8 @@ pub struct Intersection {
 
     pub intersection_type: IntersectionType,
     pub orig_id: osm::NodeID,
+    /// Only set for mid-block crossings
+    pub crossing: Option<CrossingType>,
 
     /// Note that a lane may belong to both incoming_lanes and outgoing_lanes.
     // TODO narrow down when and why. is it just sidewalks in weird cases?
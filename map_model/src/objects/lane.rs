@@ -1,13 +1,14 @@
 use std::collections::BTreeSet;
 use std::fmt;
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_usize, serialize_usize, wraparound_get, Tags};
 use geom::{Distance, Line, PolyLine, Polygon, Pt2D, Ring};
 
 use crate::{
-    osm, BusStopID, DirectedRoadID, Direction, IntersectionID, Map, MapConfig, Road, RoadID,
+    osm, BusStopID, DirectedRoadID, Direction, DrivingSide, IntersectionID, Map, MapConfig, RoadID,
     TurnType,
 };
 
@@ -259,68 +260,29 @@ impl Lane {
         }
     }
 
-    pub fn get_turn_restrictions(&self, road: &Road) -> Option<BTreeSet<TurnType>> {
+    /// The turns allowed from this lane by turn lane markings, if they're tagged in OSM.
+    pub fn get_turn_restrictions(&self, map: &Map) -> Option<BTreeSet<TurnType>> {
         if !self.is_driving() {
             return None;
         }
-
-        let all = if self.dir == Direction::Fwd && road.osm_tags.contains_key(osm::ENDPT_FWD) {
-            road.osm_tags
-                .get("turn:lanes:forward")
-                .or_else(|| road.osm_tags.get("turn:lanes"))?
-        } else if self.dir == Direction::Back && road.osm_tags.contains_key(osm::ENDPT_BACK) {
-            road.osm_tags.get("turn:lanes:backward")?
-        } else {
-            return None;
+        let road = map.get_r(self.parent);
+        let per_lane = match self.dir {
+            Direction::Fwd => road.turn_lanes_fwd.as_ref()?,
+            Direction::Back => road.turn_lanes_back.as_ref()?,
         };
-        let parts: Vec<&str> = all.split('|').collect();
-        // Verify the number of parts matches the road's lanes
         let lanes: Vec<LaneID> = road
             .children(self.dir)
             .into_iter()
             .filter(|(_, lt)| *lt == LaneType::Driving || *lt == LaneType::Bus)
             .map(|(id, _)| id)
             .collect();
-        if parts.len() != lanes.len() {
-            warn!("{}'s turn restrictions don't match the lanes", road.orig_id);
-            return None;
-        }
-        // TODO More warnings if this fails
-        let part = parts[lanes.iter().position(|l| *l == self.id)?];
-        // TODO Probably the target lane should get marked as LaneType::Bus
-        if part == "no" || part == "none" || part == "yes" || part == "psv" || part == "bus" {
+        // The markings might not match the lanes, if they were mapped inconsistently or the road
+        // has been edited
+        if per_lane.len() != lanes.len() {
             return None;
         }
-        // Empty means no restrictions
-        if part == "" {
-            return None;
-        }
-        Some(
-            part.split(';')
-                .flat_map(|s| match s {
-                    "left" | "left\\left" => vec![TurnType::Left],
-                    "right" => vec![TurnType::Right],
-                    // TODO What is blank supposed to mean? From few observed cases, same as through
-                    "through" | "" => vec![TurnType::Straight],
-                    // TODO Check this more carefully
-                    "slight_right" | "slight right" | "merge_to_right" | "sharp_right" => {
-                        vec![TurnType::Straight, TurnType::Right]
-                    }
-                    "slight_left" | "slight left" | "merge_to_left" | "sharp_left" => {
-                        vec![TurnType::Straight, TurnType::Left]
-                    }
-                    "reverse" => {
-                        // TODO We need TurnType::UTurn. Until then, u-turns usually show up as
-                        // left turns.
-                        vec![TurnType::Left]
-                    }
-                    s => {
-                        warn!("Unknown turn restriction {}", s);
-                        vec![]
-                    }
-                })
-                .collect(),
-        )
+        let idx = lanes.iter().position(|l| *l == self.id)?;
+        Some(per_lane[idx].clone())
     }
 
     /// Starting from this lane, follow the lane's left edge to the intersection, continuing to
@@ -465,3 +427,150 @@ impl LaneSpec {
         }
     }
 }
+
+/// Parses one direction of a `turn:lanes` tag (<https://wiki.openstreetmap.org/wiki/Key:turn>)
+/// into the turns allowed from each lane, ordered left-to-right from the driver's perspective.
+///
+/// Lanes without any marking can always go straight. They can also turn, as long as that doesn't
+/// cut across a marked lane -- for `left|none`, the right lane can't turn left.
+pub fn parse_turn_lanes(value: &str, driving_side: DrivingSide) -> Result<Vec<BTreeSet<TurnType>>> {
+    // U-turns are made from the lanes closest to the center of the road
+    let center_turn = if driving_side == DrivingSide::Right {
+        TurnType::Left
+    } else {
+        TurnType::Right
+    };
+
+    let mut marked: Vec<Option<BTreeSet<TurnType>>> = Vec::new();
+    for part in value.split('|') {
+        // TODO Probably lanes marked for buses should get marked as LaneType::Bus
+        if part == ""
+            || part == "none"
+            || part == "no"
+            || part == "yes"
+            || part == "psv"
+            || part == "bus"
+        {
+            marked.push(None);
+            continue;
+        }
+        let mut turns = BTreeSet::new();
+        for marking in part.split(';') {
+            match marking {
+                "left" | "left\\left" => {
+                    turns.insert(TurnType::Left);
+                }
+                "right" => {
+                    turns.insert(TurnType::Right);
+                }
+                "through" | "none" | "" => {
+                    turns.insert(TurnType::Straight);
+                }
+                // TODO Check this more carefully
+                "slight_right" | "slight right" | "merge_to_right" | "sharp_right" => {
+                    turns.insert(TurnType::Straight);
+                    turns.insert(TurnType::Right);
+                }
+                "slight_left" | "slight left" | "merge_to_left" | "sharp_left" => {
+                    turns.insert(TurnType::Straight);
+                    turns.insert(TurnType::Left);
+                }
+                "reverse" => {
+                    // When the road names differ, u-turns are classified as normal turns
+                    turns.insert(TurnType::UTurn);
+                    turns.insert(center_turn);
+                }
+                x => bail!("unknown turn marking {}", x),
+            }
+        }
+        marked.push(Some(turns));
+    }
+
+    let mut results = Vec::new();
+    for (idx, turns) in marked.iter().enumerate() {
+        if let Some(turns) = turns {
+            results.push(turns.clone());
+            continue;
+        }
+        let mut turns = BTreeSet::new();
+        turns.insert(TurnType::Straight);
+        if marked[..idx].iter().all(|x| x.is_none()) {
+            turns.insert(TurnType::Left);
+        }
+        if marked[idx + 1..].iter().all(|x| x.is_none()) {
+            turns.insert(TurnType::Right);
+        }
+        if turns.contains(&center_turn) {
+            turns.insert(TurnType::UTurn);
+        }
+        results.push(turns);
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each lane's turns are written in a fixed order: Left, Straight, Right, UTurn
+    fn turns_to_str(per_lane: Vec<BTreeSet<TurnType>>) -> String {
+        per_lane
+            .into_iter()
+            .map(|turns| {
+                vec![
+                    (TurnType::Left, "L"),
+                    (TurnType::Straight, "S"),
+                    (TurnType::Right, "R"),
+                    (TurnType::UTurn, "U"),
+                ]
+                .into_iter()
+                .filter(|(tt, _)| turns.contains(tt))
+                .map(|(_, x)| x)
+                .collect::<Vec<_>>()
+                .join("")
+            })
+            .collect::<Vec<_>>()
+            .join("|")
+    }
+
+    #[test]
+    fn test_parse_turn_lanes() {
+        let mut ok = true;
+        for (input, driving_side, expected) in vec![
+            // An unmarked lane can't turn across a marked one
+            ("left|none", DrivingSide::Right, Some("L|SR")),
+            ("none|right", DrivingSide::Right, Some("LSU|R")),
+            ("none|right", DrivingSide::Left, Some("LS|R")),
+            (
+                "left;through|through;right",
+                DrivingSide::Right,
+                Some("LS|SR"),
+            ),
+            ("slight_left|through", DrivingSide::Right, Some("LS|S")),
+            // U-turns are made towards the center of the road
+            ("reverse", DrivingSide::Right, Some("LU")),
+            ("reverse", DrivingSide::Left, Some("RU")),
+            ("reverse;left|through", DrivingSide::Right, Some("LU|S")),
+            // Empty parts are unmarked lanes
+            ("", DrivingSide::Right, Some("LSRU")),
+            ("|", DrivingSide::Right, Some("LSRU|LSRU")),
+            ("left||right", DrivingSide::Right, Some("L|S|R")),
+            ("none|none|right", DrivingSide::Right, Some("LSU|LSU|R")),
+            // Bad markings make the whole tag invalid
+            ("left|sideways", DrivingSide::Right, None),
+            ("left;up|through", DrivingSide::Right, None),
+        ] {
+            let actual = parse_turn_lanes(input, driving_side).ok().map(turns_to_str);
+            if actual.as_deref() != expected {
+                ok = false;
+                println!(
+                    "For turn:lanes={} driving on the {:?}:",
+                    input, driving_side
+                );
+                println!("    Got {:?}", actual);
+                println!("    Expected {:?}", expected);
+            }
+        }
+        assert!(ok);
+    }
+}
@@ -2,8 +2,8 @@ use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
-use abstutil::{deserialize_usize, serialize_usize};
-use geom::{Angle, Line, PolyLine, Polygon, Pt2D};
+use abstutil::{deserialize_usize, serialize_usize, Tags};
+use geom::{Angle, Duration, Line, PolyLine, Polygon, Pt2D};
 
 use crate::{osm, Position};
 
@@ -47,6 +47,10 @@ pub struct ParkingLot {
     /// Lot to sidewalk
     pub sidewalk_line: Line,
     pub sidewalk_pos: Position,
+
+    pub policy: ParkingPolicy,
+    /// From OSM, before any edits
+    pub orig_policy: ParkingPolicy,
 }
 
 impl ParkingLot {
@@ -54,3 +58,186 @@ impl ParkingLot {
         self.spots.len() + self.extra_spots
     }
 }
+
+/// Rules for parking along a road's parking lanes or inside a parking lot.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct ParkingPolicy {
+    /// In dollars. 0 is free.
+    pub hourly_price: f64,
+    /// Cars may not stay parked longer than this.
+    pub time_limit: Option<Duration>,
+    /// Only residents of buildings along the same road may park here.
+    pub permit_only: bool,
+    /// Reserved for delivery vans to stop briefly. Only used for onstreet parking.
+    pub loading_zone: bool,
+}
+
+impl ParkingPolicy {
+    /// Free, with no time limit, open to everybody
+    pub fn free() -> ParkingPolicy {
+        ParkingPolicy {
+            hourly_price: 0.0,
+            time_limit: None,
+            permit_only: false,
+            loading_zone: false,
+        }
+    }
+
+    pub fn is_free(&self) -> bool {
+        self == &ParkingPolicy::free()
+    }
+
+    /// The price of staying parked for some amount of time
+    pub fn price(&self, duration: Duration) -> f64 {
+        self.hourly_price * duration.inner_seconds() / 3600.0
+    }
+
+    /// If a car stayed parked past the time limit, by how much
+    pub fn overstay(&self, parked_for: Duration) -> Option<Duration> {
+        let limit = self.time_limit?;
+        if parked_for > limit {
+            Some(parked_for - limit)
+        } else {
+            None
+        }
+    }
+
+    /// Interprets `fee`, `charge`, `maxstay`, and `access` tags, all prefixed by `prefix` if it's
+    /// not empty. A `fee` without a price in `charge` is left free; it's up to the user to fill in
+    /// prices.
+    pub(crate) fn from_osm(tags: &Tags, prefix: &str) -> ParkingPolicy {
+        let key = |suffix: &str| {
+            if prefix.is_empty() {
+                suffix.to_string()
+            } else {
+                format!("{}:{}", prefix, suffix)
+            }
+        };
+
+        let mut policy = ParkingPolicy::free();
+        // Like "2 USD/hour" or "1.50/h"
+        if let Some(charge) = tags.get(&key("charge")) {
+            if charge.ends_with("/hour") || charge.ends_with("/h") {
+                if let Some(price) = charge
+                    .split(|c: char| c == ' ' || c == '/')
+                    .next()
+                    .and_then(|x| x.parse::<f64>().ok())
+                {
+                    policy.hourly_price = price;
+                }
+            }
+        }
+        // Like "2 hours" or "90 min"
+        if let Some(maxstay) = tags.get(&key("maxstay")) {
+            let mut parts = maxstay.split(' ');
+            if let (Some(Ok(value)), Some(unit)) =
+                (parts.next().map(|x| x.parse::<f64>()), parts.next())
+            {
+                if unit.starts_with('h') {
+                    policy.time_limit = Some(Duration::hours(1) * value);
+                } else if unit.starts_with("min") {
+                    policy.time_limit = Some(Duration::minutes(1) * value);
+                }
+            }
+        }
+        policy.permit_only = tags.is_any(&key("access"), vec!["permit", "residents"]);
+        policy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tags(kv: Vec<(&str, &str)>) -> Tags {
+        let mut tags = Tags::empty();
+        for (k, v) in kv {
+            tags.insert(k, v);
+        }
+        tags
+    }
+
+    #[test]
+    fn test_from_osm() {
+        let mut ok = true;
+        for (input, prefix, expected_price, expected_limit, expected_permit) in vec![
+            (vec![], "", 0.0, None, false),
+            (vec![("charge", "2 USD/hour")], "", 2.0, None, false),
+            (vec![("charge", "1.50/h")], "", 1.5, None, false),
+            // Only hourly rates are understood
+            (vec![("charge", "5 USD/day")], "", 0.0, None, false),
+            // A fee without a price stays free
+            (vec![("fee", "yes")], "", 0.0, None, false),
+            (
+                vec![("maxstay", "2 hours")],
+                "",
+                0.0,
+                Some(Duration::hours(2)),
+                false,
+            ),
+            (
+                vec![("maxstay", "90 min")],
+                "",
+                0.0,
+                Some(Duration::minutes(90)),
+                false,
+            ),
+            (vec![("maxstay", "unlimited")], "", 0.0, None, false),
+            (vec![("access", "permit")], "", 0.0, None, true),
+            (vec![("access", "residents")], "", 0.0, None, true),
+            (vec![("access", "yes")], "", 0.0, None, false),
+            (
+                vec![
+                    ("parking:lane:both:fee", "yes"),
+                    ("parking:lane:both:charge", "3 USD/hour"),
+                    ("parking:lane:both:maxstay", "1 hour"),
+                    ("parking:lane:both:access", "permit"),
+                ],
+                "parking:lane:both",
+                3.0,
+                Some(Duration::hours(1)),
+                true,
+            ),
+            // Tags for a different prefix are ignored
+            (
+                vec![("parking:lane:left:maxstay", "1 hour")],
+                "parking:lane:right",
+                0.0,
+                None,
+                false,
+            ),
+        ] {
+            let policy = ParkingPolicy::from_osm(&tags(input.clone()), prefix);
+            if policy.hourly_price != expected_price
+                || policy.time_limit != expected_limit
+                || policy.permit_only != expected_permit
+            {
+                println!("For input {:?} with prefix {:?}:", input, prefix);
+                println!(
+                    "  Expected price {}, limit {:?}, permit {}",
+                    expected_price, expected_limit, expected_permit
+                );
+                println!(
+                    "  Got price {}, limit {:?}, permit {}",
+                    policy.hourly_price, policy.time_limit, policy.permit_only
+                );
+                ok = false;
+            }
+        }
+        assert!(ok);
+    }
+
+    #[test]
+    fn test_overstay() {
+        let mut policy = ParkingPolicy::free();
+        assert_eq!(policy.overstay(Duration::hours(10)), None);
+
+        policy.time_limit = Some(Duration::hours(2));
+        assert_eq!(policy.overstay(Duration::hours(1)), None);
+        assert_eq!(policy.overstay(Duration::hours(2)), None);
+        assert_eq!(
+            policy.overstay(Duration::hours(3)),
+            Some(Duration::hours(1))
+        );
+    }
+}
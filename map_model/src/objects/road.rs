@@ -10,8 +10,8 @@ use geom::{Distance, PolyLine, Polygon, Speed};
 
 use crate::raw::{OriginalRoad, RestrictionType};
 use crate::{
-    osm, AccessRestrictions, BusStopID, DrivingSide, IntersectionID, Lane, LaneID, LaneSpec,
-    LaneType, Map, PathConstraints, Zone,
+    osm, parse_turn_lanes, AccessRestrictions, BusStopID, DrivingSide, IntersectionID, Lane,
+    LaneID, LaneSpec, LaneType, Map, ParkingPolicy, PathConstraints, TurnType, Zone,
 };
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -99,6 +99,16 @@ impl DirectedRoadID {
         }
     }
 
+    /// How steep this direction of the road is, as a fraction. Uphill is positive.
+    pub fn percent_incline(self, map: &Map) -> f64 {
+        let r = map.get_r(self.id);
+        if self.dir == Direction::Fwd {
+            r.percent_incline
+        } else {
+            -r.percent_incline
+        }
+    }
+
     /// Strict for bikes. If there are bike lanes, not allowed to use other lanes.
     pub fn lanes(self, constraints: PathConstraints, map: &Map) -> Vec<LaneID> {
         let r = map.get_r(self.id);
@@ -142,6 +152,13 @@ pub struct Road {
     pub orig_id: OriginalRoad,
     pub speed_limit: Speed,
     pub access_restrictions: AccessRestrictions,
+    /// Applies to all of the road's parking lanes
+    pub parking_policy: ParkingPolicy,
+    /// The turns allowed from each forwards driving or bus lane, left to right, if turn lane
+    /// markings are tagged
+    pub turn_lanes_fwd: Option<Vec<BTreeSet<TurnType>>>,
+    /// Likewise for the backwards lanes
+    pub turn_lanes_back: Option<Vec<BTreeSet<TurnType>>>,
     pub zorder: isize,
     /// [-1.0, 1.0] theoretically, but in practice, about [-0.25, 0.25]. 0 is flat,
     /// positive is uphill from src_i -> dst_i, negative is downhill.
@@ -465,6 +482,48 @@ impl Road {
         }
     }
 
+    pub(crate) fn parking_policy_from_osm(&self) -> ParkingPolicy {
+        // Parking lanes on both sides share one policy, so just use the first side tagged
+        for side in vec!["both", "right", "left"] {
+            let prefix = format!("parking:condition:{}", side);
+            let mut policy = ParkingPolicy::from_osm(&self.osm_tags, &prefix);
+            if self.osm_tags.is(&prefix, "residents") {
+                policy.permit_only = true;
+            }
+            if self
+                .osm_tags
+                .is_any(&prefix, vec!["loading", "loading_only"])
+            {
+                policy.loading_zone = true;
+            }
+            if !policy.is_free() {
+                return policy;
+            }
+        }
+        ParkingPolicy::free()
+    }
+
+    /// Markings only describe the intersection at the end of the original OSM way. Invalid
+    /// markings are ignored; the importer already recorded the problem in
+    /// `osm::INVALID_TURN_LANES`.
+    pub(crate) fn turn_lanes_from_osm(
+        &self,
+        dir: Direction,
+        driving_side: DrivingSide,
+    ) -> Option<Vec<BTreeSet<TurnType>>> {
+        let value = match dir {
+            Direction::Fwd if self.osm_tags.contains_key(osm::ENDPT_FWD) => self
+                .osm_tags
+                .get("turn:lanes:forward")
+                .or_else(|| self.osm_tags.get("turn:lanes"))?,
+            Direction::Back if self.osm_tags.contains_key(osm::ENDPT_BACK) => {
+                self.osm_tags.get("turn:lanes:backward")?
+            }
+            _ => return None,
+        };
+        parse_turn_lanes(value, driving_side).ok()
+    }
+
     pub fn get_zone<'a>(&self, map: &'a Map) -> Option<&'a Zone> {
         if !self.is_private() {
             return None;
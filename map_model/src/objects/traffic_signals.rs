@@ -5,7 +5,7 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, retain_btreeset, serialize_btreemap};
-use geom::{Distance, Duration, Speed};
+use geom::{Distance, Duration, Speed, Time};
 
 use crate::make::traffic_signals::get_possible_policies;
 use crate::raw::OriginalRoad;
@@ -14,6 +14,13 @@ use crate::{
     MovementID, RoadID, TurnID, TurnPriority, TurnType,
 };
 
+// Defaults for make_actuated
+const MIN_ACTUATED_GREEN: Duration = Duration::const_seconds(5.0);
+pub(crate) const DEFAULT_PASSAGE_TIME: Duration = Duration::const_seconds(3.0);
+
+// Timing plans repeat daily
+const DAY: Duration = Duration::const_seconds(24.0 * 3600.0);
+
 // The pace to use for crosswalk pace in m/s
 // https://en.wikipedia.org/wiki/Preferred_walking_speed
 const CROSSWALK_PACE: Speed = Speed::const_meters_per_second(1.4);
@@ -21,11 +28,19 @@ const CROSSWALK_PACE: Speed = Speed::const_meters_per_second(1.4);
 /// A traffic signal consists of a sequence of Stages that repeat in a cycle. Most Stages last for a
 /// fixed duration. During a single Stage, some movements are protected (can proceed with the
 /// highest priority), while others are permitted (have to yield before proceeding).
+///
+/// A signal may switch between different timing plans over the course of a day. `stages` and
+/// `offset` describe the plan starting at midnight.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
 pub struct ControlTrafficSignal {
     pub id: IntersectionID,
     pub stages: Vec<Stage>,
     pub offset: Duration,
+    /// Plans that take over from `stages` and `offset` later in the day, sorted by start time.
+    /// Usually empty.
+    pub later_plans: Vec<TimingPlan>,
+    /// If set, approaching buses and trains can extend or cut short stages.
+    pub transit_priority: Option<TransitPriority>,
 
     #[serde(
         serialize_with = "serialize_btreemap",
@@ -34,6 +49,35 @@ pub struct ControlTrafficSignal {
     pub movements: BTreeMap<MovementID, Movement>,
 }
 
+/// A different sequence of stages that a signal uses starting at some time of day.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TimingPlan {
+    /// The plan lasts until the next plan starts, or until midnight.
+    pub start_time: Time,
+    pub stages: Vec<Stage>,
+    pub offset: Duration,
+}
+
+/// Transit signal priority. Limits how much a signal may change its timing for buses and trains.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TransitPriority {
+    /// When a stage is about to end and a bus or train is approaching one of its protected
+    /// movements, the stage may be held green this much longer.
+    pub max_extension: Duration,
+    /// When a bus or train is waiting for a movement that isn't protected during the current
+    /// stage, the stage may end this much earlier.
+    pub max_truncation: Duration,
+}
+
+impl std::default::Default for TransitPriority {
+    fn default() -> TransitPriority {
+        TransitPriority {
+            max_extension: Duration::seconds(10.0),
+            max_truncation: Duration::seconds(10.0),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub struct Stage {
     pub protected_movements: BTreeSet<MovementID>,
@@ -50,6 +94,11 @@ pub enum StageType {
     /// Delay is the elapsed time with no demand that ends a cycle.
     /// Additional is the additional duration for an extended cycle.
     Variable(Duration, Duration, Duration),
+    /// Minimum, maximum, and passage time. Actuated control using loop detectors on the incoming
+    /// lanes. After the minimum, the stage extends by the passage time as long as vehicles keep
+    /// calling for one of its protected movements. It ends when nobody calls (gap-out) or when it
+    /// reaches the maximum (max-out). If nobody calls for the stage at all, it's skipped.
+    Actuated(Duration, Duration, Duration),
 }
 
 impl StageType {
@@ -58,6 +107,7 @@ impl StageType {
         match self {
             StageType::Fixed(d) => *d,
             StageType::Variable(duration, _, _) => *duration,
+            StageType::Actuated(min, _, _) => *min,
         }
     }
 }
@@ -90,9 +140,14 @@ impl ControlTrafficSignal {
         get_possible_policies(map, id, false)
     }
 
-    pub fn get_min_crossing_time(&self, idx: usize) -> Duration {
+    /// The shortest a stage of some plan can last, so pedestrians can finish crossing.
+    pub fn get_min_crossing_time(&self, plan_idx: usize, idx: usize) -> Duration {
+        self.min_crossing_time(&self.plan_stages(plan_idx)[idx])
+    }
+
+    fn min_crossing_time(&self, stage: &Stage) -> Duration {
         let mut max_distance = Distance::meters(0.0);
-        for movement in &self.stages[idx].protected_movements {
+        for movement in &stage.protected_movements {
             if movement.crosswalk {
                 max_distance =
                     max_distance.max(self.movements.get(&movement).unwrap().geom.length());
@@ -105,10 +160,27 @@ impl ControlTrafficSignal {
     }
 
     pub(crate) fn validate(&self) -> Result<()> {
+        self.validate_stages(&self.stages)?;
+        let mut last_start = Time::START_OF_DAY;
+        for plan in &self.later_plans {
+            if plan.start_time <= last_start || plan.start_time >= Time::START_OF_DAY + DAY {
+                bail!(
+                    "Traffic signal {} has a plan starting at {}, out of order",
+                    self.id,
+                    plan.start_time
+                );
+            }
+            last_start = plan.start_time;
+            self.validate_stages(&plan.stages)?;
+        }
+        Ok(())
+    }
+
+    fn validate_stages(&self, stages: &[Stage]) -> Result<()> {
         // Does the assignment cover the correct set of movements?
         let expected_movements: BTreeSet<MovementID> = self.movements.keys().cloned().collect();
         let mut actual_movements: BTreeSet<MovementID> = BTreeSet::new();
-        for stage in &self.stages {
+        for stage in stages {
             actual_movements.extend(stage.protected_movements.iter());
             actual_movements.extend(stage.yield_movements.iter());
         }
@@ -127,7 +199,7 @@ impl ControlTrafficSignal {
             );
         }
         let mut stage_index = 0;
-        for stage in &self.stages {
+        for stage in stages {
             // Do any of the priority movements in one stage conflict?
             for m1 in stage.protected_movements.iter().map(|m| &self.movements[m]) {
                 for m2 in stage.protected_movements.iter().map(|m| &self.movements[m]) {
@@ -147,7 +219,7 @@ impl ControlTrafficSignal {
                 assert!(m.turn_type != TurnType::Crosswalk);
             }
             // Is there enough time in each stage to walk across the crosswalk
-            let min_crossing_time = self.get_min_crossing_time(stage_index);
+            let min_crossing_time = self.min_crossing_time(stage);
             if stage.stage_type.simple_duration() < min_crossing_time {
                 bail!(
                     "Traffic signal does not allow enough time in stage to complete the \
@@ -158,6 +230,24 @@ impl ControlTrafficSignal {
                     stage.stage_type.simple_duration()
                 );
             }
+            if let StageType::Actuated(min, max, passage) = stage.stage_type {
+                if min > max {
+                    bail!(
+                        "Traffic signal {} stage {} has a minimum {} longer than its maximum {}",
+                        self.id,
+                        stage_index,
+                        min,
+                        max
+                    );
+                }
+                if passage == Duration::ZERO {
+                    bail!(
+                        "Traffic signal {} stage {} has no passage time",
+                        self.id,
+                        stage_index
+                    );
+                }
+            }
             stage_index += 1;
         }
         Ok(())
@@ -285,9 +375,10 @@ impl ControlTrafficSignal {
         }
     }
 
-    pub fn missing_turns(&self) -> BTreeSet<MovementID> {
+    /// Movements that no stage of one plan serves, using the same numbering as `plan_idx_at`.
+    pub fn missing_turns(&self, plan_idx: usize) -> BTreeSet<MovementID> {
         let mut missing: BTreeSet<MovementID> = self.movements.keys().cloned().collect();
-        for stage in &self.stages {
+        for stage in self.plan_stages(plan_idx) {
             for m in &stage.protected_movements {
                 missing.remove(m);
             }
@@ -313,6 +404,28 @@ impl ControlTrafficSignal {
         )
     }
 
+    /// Does any plan of this signal use actuated stages?
+    pub fn is_actuated(&self) -> bool {
+        (0..=self.later_plans.len()).any(|plan| {
+            self.plan_stages(plan)
+                .iter()
+                .any(|s| matches!(s.stage_type, StageType::Actuated(_, _, _)))
+        })
+    }
+
+    /// Switch every stage of the base plan to actuated control. The current duration becomes the
+    /// maximum green, so the actuated signal never runs a longer cycle than the fixed one did.
+    pub fn make_actuated(&mut self) {
+        for idx in 0..self.stages.len() {
+            let max = self.stages[idx].stage_type.simple_duration();
+            let min = self
+                .min_crossing_time(&self.stages[idx])
+                .max(MIN_ACTUATED_GREEN)
+                .min(max);
+            self.stages[idx].stage_type = StageType::Actuated(min, max, DEFAULT_PASSAGE_TIME);
+        }
+    }
+
     /// How long a full cycle of the signal lasts, assuming no actuated timings.
     pub fn simple_cycle_duration(&self) -> Duration {
         let mut total = Duration::ZERO;
@@ -321,6 +434,119 @@ impl ControlTrafficSignal {
         }
         total
     }
+
+    /// Which plan is in effect at some time. 0 means `stages` and `offset`; anything else is an
+    /// index into `later_plans`, plus one. Simulations lasting more than a day repeat the schedule.
+    pub fn plan_idx_at(&self, time: Time) -> usize {
+        let time_of_day = Time::START_OF_DAY + (time - Time::START_OF_DAY) % DAY;
+        let mut idx = 0;
+        for (i, plan) in self.later_plans.iter().enumerate() {
+            if plan.start_time <= time_of_day {
+                idx = i + 1;
+            }
+        }
+        idx
+    }
+
+    /// The stages of one plan, using the same numbering as `plan_idx_at`.
+    pub fn plan_stages(&self, plan_idx: usize) -> &Vec<Stage> {
+        if plan_idx == 0 {
+            &self.stages
+        } else {
+            &self.later_plans[plan_idx - 1].stages
+        }
+    }
+
+    /// The stages of one plan, using the same numbering as `plan_idx_at`.
+    pub fn plan_stages_mut(&mut self, plan_idx: usize) -> &mut Vec<Stage> {
+        if plan_idx == 0 {
+            &mut self.stages
+        } else {
+            &mut self.later_plans[plan_idx - 1].stages
+        }
+    }
+
+    /// How many plans this signal switches between over the day, including the one at midnight.
+    pub fn num_plans(&self) -> usize {
+        self.later_plans.len() + 1
+    }
+
+    /// When one plan starts, using the same numbering as `plan_idx_at`.
+    pub fn plan_start_time(&self, plan_idx: usize) -> Time {
+        if plan_idx == 0 {
+            Time::START_OF_DAY
+        } else {
+            self.later_plans[plan_idx - 1].start_time
+        }
+    }
+
+    /// How long a full cycle of one plan lasts, assuming no actuated timings.
+    pub fn plan_cycle_duration(&self, plan_idx: usize) -> Duration {
+        self.plan_stages(plan_idx)
+            .iter()
+            .map(|s| s.stage_type.simple_duration())
+            .sum()
+    }
+
+    /// The offset of one plan, using the same numbering as `plan_idx_at`.
+    pub fn plan_offset(&self, plan_idx: usize) -> Duration {
+        if plan_idx == 0 {
+            self.offset
+        } else {
+            self.later_plans[plan_idx - 1].offset
+        }
+    }
+
+    /// Changes the offset of one plan, using the same numbering as `plan_idx_at`.
+    pub fn set_plan_offset(&mut self, plan_idx: usize, offset: Duration) {
+        if plan_idx == 0 {
+            self.offset = offset;
+        } else {
+            self.later_plans[plan_idx - 1].offset = offset;
+        }
+    }
+
+    /// The stages in effect at some time.
+    pub fn stages_at(&self, time: Time) -> &Vec<Stage> {
+        self.plan_stages(self.plan_idx_at(time))
+    }
+
+    /// What fraction of the day some movement is protected by a stage of the plan in effect.
+    pub fn fraction_of_day_protected(&self, m: MovementID) -> f64 {
+        let mut protected = Duration::ZERO;
+        for idx in 0..self.num_plans() {
+            if self
+                .plan_stages(idx)
+                .iter()
+                .any(|stage| stage.protected_movements.contains(&m))
+            {
+                let end = if idx + 1 < self.num_plans() {
+                    self.plan_start_time(idx + 1)
+                } else {
+                    Time::START_OF_DAY + DAY
+                };
+                protected += end - self.plan_start_time(idx);
+            }
+        }
+        protected / DAY
+    }
+
+    /// When the plan in effect at `time` ends, or None if the signal only has one plan.
+    pub fn next_plan_change(&self, time: Time) -> Option<Time> {
+        if self.later_plans.is_empty() {
+            return None;
+        }
+        let since_midnight = (time - Time::START_OF_DAY) % DAY;
+        let midnight = time - since_midnight;
+        for plan in &self.later_plans {
+            let start = plan.start_time - Time::START_OF_DAY;
+            if start > since_midnight {
+                return Some(midnight + start);
+            }
+        }
+        // The first plan starts again the next day
+        Some(midnight + DAY)
+    }
 }
 
 impl Stage {
@@ -395,6 +621,9 @@ impl Stage {
                 StageType::Variable(_, delay, additional) => {
                     StageType::Variable(time, delay, additional)
                 }
+                StageType::Actuated(_, max, passage) => {
+                    StageType::Actuated(time, max.max(time), passage)
+                }
             };
         }
     }
@@ -425,100 +654,62 @@ impl Stage {
 
 impl ControlTrafficSignal {
     pub fn export(&self, map: &Map) -> traffic_signal_data::TrafficSignal {
+        let mut plans = vec![export_plan(
+            Time::START_OF_DAY,
+            &self.stages,
+            self.offset,
+            map,
+        )];
+        for plan in &self.later_plans {
+            plans.push(export_plan(plan.start_time, &plan.stages, plan.offset, map));
+        }
         traffic_signal_data::TrafficSignal {
             intersection_osm_node_id: map.get_i(self.id).orig_id.0,
-            plans: vec![traffic_signal_data::Plan {
-                start_time_seconds: 0,
-                stages: self
-                    .stages
-                    .iter()
-                    .map(|s| traffic_signal_data::Stage {
-                        protected_turns: s
-                            .protected_movements
-                            .iter()
-                            .map(|t| export_movement(t, map))
-                            .collect(),
-                        permitted_turns: s
-                            .yield_movements
-                            .iter()
-                            .map(|t| export_movement(t, map))
-                            .collect(),
-                        stage_type: match s.stage_type {
-                            StageType::Fixed(d) => {
-                                traffic_signal_data::StageType::Fixed(d.inner_seconds() as usize)
-                            }
-                            StageType::Variable(min, delay, additional) => {
-                                traffic_signal_data::StageType::Variable(
-                                    min.inner_seconds() as usize,
-                                    delay.inner_seconds() as usize,
-                                    additional.inner_seconds() as usize,
-                                )
-                            }
-                        },
-                    })
-                    .collect(),
-                offset_seconds: self.offset.inner_seconds() as usize,
-            }],
+            plans,
+            transit_priority: self.transit_priority.map(|tsp| {
+                traffic_signal_data::TransitPriority {
+                    max_extension_seconds: tsp.max_extension.inner_seconds() as usize,
+                    max_truncation_seconds: tsp.max_truncation.inner_seconds() as usize,
+                }
+            }),
         }
     }
 
     pub(crate) fn import(
-        mut raw: traffic_signal_data::TrafficSignal,
+        raw: traffic_signal_data::TrafficSignal,
         id: IntersectionID,
         map: &Map,
     ) -> Result<ControlTrafficSignal> {
-        // TODO Only import the first plan. Will import all of them later.
-        let plan = raw.plans.remove(0);
-        let mut stages = Vec::new();
-        for s in plan.stages {
-            let mut errors = Vec::new();
-            let mut protected_movements = BTreeSet::new();
-            for t in s.protected_turns {
-                match import_movement(t, map) {
-                    Ok(mvmnt) => {
-                        protected_movements.insert(mvmnt);
-                    }
-                    Err(err) => {
-                        errors.push(err.to_string());
-                    }
-                }
-            }
-            let mut permitted_movements = BTreeSet::new();
-            for t in s.permitted_turns {
-                match import_movement(t, map) {
-                    Ok(mvmnt) => {
-                        permitted_movements.insert(mvmnt);
-                    }
-                    Err(err) => {
-                        errors.push(err.to_string());
-                    }
-                }
-            }
-            if errors.is_empty() {
-                stages.push(Stage {
-                    protected_movements,
-                    yield_movements: permitted_movements,
-                    stage_type: match s.stage_type {
-                        traffic_signal_data::StageType::Fixed(d) => {
-                            StageType::Fixed(Duration::seconds(d as f64))
-                        }
-                        traffic_signal_data::StageType::Variable(min, delay, additional) => {
-                            StageType::Variable(
-                                Duration::seconds(min as f64),
-                                Duration::seconds(delay as f64),
-                                Duration::seconds(additional as f64),
-                            )
-                        }
-                    },
-                });
-            } else {
-                bail!("{}", errors.join("; "));
-            }
+        let mut plans = Vec::new();
+        for plan in raw.plans {
+            plans.push(TimingPlan {
+                start_time: Time::START_OF_DAY + Duration::seconds(plan.start_time_seconds as f64),
+                stages: import_stages(plan.stages, map)?,
+                offset: Duration::seconds(plan.offset_seconds as f64),
+            });
         }
+        if plans.is_empty() {
+            bail!("Traffic signal {} has no plans", id);
+        }
+        // The first plan has to cover the start of the day. validate() rejects later plans that
+        // are out of order.
+        if plans[0].start_time != Time::START_OF_DAY {
+            bail!(
+                "Traffic signal {}'s first plan starts at {}, not midnight",
+                id,
+                plans[0].start_time
+            );
+        }
+        let first = plans.remove(0);
         let ts = ControlTrafficSignal {
             id,
-            stages,
-            offset: Duration::seconds(plan.offset_seconds as f64),
+            stages: first.stages,
+            offset: first.offset,
+            later_plans: plans,
+            transit_priority: raw.transit_priority.map(|tsp| TransitPriority {
+                max_extension: Duration::seconds(tsp.max_extension_seconds as f64),
+                max_truncation: Duration::seconds(tsp.max_truncation_seconds as f64),
+            }),
             movements: Movement::for_i(id, map).unwrap(),
         };
         ts.validate()?;
@@ -526,6 +717,109 @@ impl ControlTrafficSignal {
     }
 }
 
+fn export_plan(
+    start_time: Time,
+    stages: &[Stage],
+    offset: Duration,
+    map: &Map,
+) -> traffic_signal_data::Plan {
+    traffic_signal_data::Plan {
+        start_time_seconds: (start_time - Time::START_OF_DAY).inner_seconds() as usize,
+        stages: stages
+            .iter()
+            .map(|s| traffic_signal_data::Stage {
+                protected_turns: s
+                    .protected_movements
+                    .iter()
+                    .map(|t| export_movement(t, map))
+                    .collect(),
+                permitted_turns: s
+                    .yield_movements
+                    .iter()
+                    .map(|t| export_movement(t, map))
+                    .collect(),
+                stage_type: match s.stage_type {
+                    StageType::Fixed(d) => {
+                        traffic_signal_data::StageType::Fixed(d.inner_seconds() as usize)
+                    }
+                    StageType::Variable(min, delay, additional) => {
+                        traffic_signal_data::StageType::Variable(
+                            min.inner_seconds() as usize,
+                            delay.inner_seconds() as usize,
+                            additional.inner_seconds() as usize,
+                        )
+                    }
+                    StageType::Actuated(min, max, passage) => {
+                        traffic_signal_data::StageType::Actuated(
+                            min.inner_seconds() as usize,
+                            max.inner_seconds() as usize,
+                            passage.inner_seconds() as usize,
+                        )
+                    }
+                },
+            })
+            .collect(),
+        offset_seconds: offset.inner_seconds() as usize,
+    }
+}
+
+fn import_stages(raw_stages: Vec<traffic_signal_data::Stage>, map: &Map) -> Result<Vec<Stage>> {
+    let mut stages = Vec::new();
+    for s in raw_stages {
+        let mut errors = Vec::new();
+        let mut protected_movements = BTreeSet::new();
+        for t in s.protected_turns {
+            match import_movement(t, map) {
+                Ok(mvmnt) => {
+                    protected_movements.insert(mvmnt);
+                }
+                Err(err) => {
+                    errors.push(err.to_string());
+                }
+            }
+        }
+        let mut permitted_movements = BTreeSet::new();
+        for t in s.permitted_turns {
+            match import_movement(t, map) {
+                Ok(mvmnt) => {
+                    permitted_movements.insert(mvmnt);
+                }
+                Err(err) => {
+                    errors.push(err.to_string());
+                }
+            }
+        }
+        if errors.is_empty() {
+            stages.push(Stage {
+                protected_movements,
+                yield_movements: permitted_movements,
+                stage_type: match s.stage_type {
+                    traffic_signal_data::StageType::Fixed(d) => {
+                        StageType::Fixed(Duration::seconds(d as f64))
+                    }
+                    traffic_signal_data::StageType::Variable(min, delay, additional) => {
+                        StageType::Variable(
+                            Duration::seconds(min as f64),
+                            Duration::seconds(delay as f64),
+                            Duration::seconds(additional as f64),
+                        )
+                    }
+                    traffic_signal_data::StageType::Actuated(min, max, passage) => {
+                        StageType::Actuated(
+                            Duration::seconds(min as f64),
+                            Duration::seconds(max as f64),
+                            Duration::seconds(passage as f64),
+                        )
+                    }
+                },
+            });
+        } else {
+            bail!("{}", errors.join("; "));
+        }
+    }
+    Ok(stages)
+}
+
 fn export_movement(id: &MovementID, map: &Map) -> traffic_signal_data::Turn {
     let from = map.get_r(id.from.id).orig_id;
     let to = map.get_r(id.to.id).orig_id;
@@ -570,3 +864,54 @@ fn find_r(id: traffic_signal_data::DirectedRoad, map: &Map) -> Result<DirectedRo
         },
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fraction_of_day_protected() {
+        let left_turn = MovementID {
+            from: DirectedRoadID {
+                id: RoadID(0),
+                dir: Direction::Back,
+            },
+            to: DirectedRoadID {
+                id: RoadID(1),
+                dir: Direction::Fwd,
+            },
+            parent: IntersectionID(0),
+            crosswalk: false,
+        };
+        let mut protected_stage = Stage::new();
+        protected_stage.protected_movements.insert(left_turn);
+        let mut ts = ControlTrafficSignal {
+            id: IntersectionID(0),
+            stages: vec![Stage::new()],
+            offset: Duration::ZERO,
+            later_plans: Vec::new(),
+            transit_priority: None,
+            movements: BTreeMap::new(),
+        };
+        assert_eq!(ts.fraction_of_day_protected(left_turn), 0.0);
+
+        // Only protected during a peak plan from 6am to noon
+        ts.later_plans.push(TimingPlan {
+            start_time: Time::START_OF_DAY + Duration::hours(6),
+            stages: vec![protected_stage.clone()],
+            offset: Duration::ZERO,
+        });
+        ts.later_plans.push(TimingPlan {
+            start_time: Time::START_OF_DAY + Duration::hours(12),
+            stages: vec![Stage::new()],
+            offset: Duration::ZERO,
+        });
+        assert_eq!(ts.fraction_of_day_protected(left_turn), 0.25);
+
+        // The reverse: protected except during that peak plan
+        ts.stages = vec![protected_stage.clone()];
+        ts.later_plans[0].stages = vec![Stage::new()];
+        ts.later_plans[1].stages = vec![protected_stage];
+        assert_eq!(ts.fraction_of_day_protected(left_turn), 0.75);
+    }
+}
@@ -30,6 +30,8 @@ pub const ENDPT_BACK: &str = "abst:endpt_back";
 // Any roads might have these.
 pub const INFERRED_PARKING: &str = "abst:parking_inferred";
 pub const INFERRED_SIDEWALKS: &str = "abst:sidewalks_inferred";
+// turn:lanes tags that can't be understood are kept as-is, but ignored. This describes why.
+pub const INVALID_TURN_LANES: &str = "abst:turn_lanes_invalid";
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug)]
 pub enum RoadRank {
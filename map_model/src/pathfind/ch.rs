@@ -1,6 +1,8 @@
 //! Uses https://github.com/easbar/fast_paths. Slower creation during map importing, but very fast
 //! queries.
 
+use std::sync::{Arc, RwLock};
+
 use serde::{Deserialize, Serialize};
 
 use abstutil::Timer;
@@ -9,7 +11,13 @@ use geom::Duration;
 use crate::pathfind::dijkstra;
 use crate::pathfind::vehicles::VehiclePathfinder;
 use crate::pathfind::walking::SidewalkPathfinder;
-use crate::{BusRouteID, BusStopID, Map, PathConstraints, PathRequest, PathV2, Position};
+use crate::{
+    BusRouteID, BusStopID, Map, PathConstraints, PathRequest, PathV2, Position, RoutingParams,
+};
+
+/// Customizing a contraction hierarchy takes a while, so remember this many for requests with
+/// their own routing params.
+const MAX_CUSTOMIZED_GRAPHS: usize = 10;
 
 #[derive(Serialize, Deserialize)]
 pub struct ContractionHierarchyPathfinder {
@@ -18,6 +26,13 @@ pub struct ContractionHierarchyPathfinder {
     bus_graph: VehiclePathfinder,
     walking_graph: SidewalkPathfinder,
     walking_with_transit_graph: SidewalkPathfinder,
+
+    // Built lazily for requests that override the map's routing params
+    #[serde(skip_serializing, skip_deserializing)]
+    customized: RwLock<Vec<(PathConstraints, RoutingParams, Arc<VehiclePathfinder>)>>,
+    // Keyed by fitness and uphill penalty, the only params affecting pedestrians
+    #[serde(skip_serializing, skip_deserializing)]
+    customized_walking: RwLock<Vec<((f64, f64), Arc<SidewalkPathfinder>)>>,
 }
 
 impl ContractionHierarchyPathfinder {
@@ -50,6 +65,8 @@ impl ContractionHierarchyPathfinder {
             bus_graph,
             walking_graph,
             walking_with_transit_graph,
+            customized: RwLock::new(Vec::new()),
+            customized_walking: RwLock::new(Vec::new()),
         }
     }
 
@@ -65,6 +82,84 @@ impl ContractionHierarchyPathfinder {
         }
     }
 
+    /// Like `pathfind`, but using routing params that differ from the map's. The first request
+    /// with some params customizes the hierarchy for them, reusing the node ordering; later
+    /// requests with the same params are fast.
+    pub fn pathfind_with_params(
+        &self,
+        req: PathRequest,
+        params: &RoutingParams,
+        map: &Map,
+    ) -> Option<PathV2> {
+        let graph = match req.constraints {
+            PathConstraints::Pedestrian => {
+                return self.pathfind_walking_with_params(req, params, map);
+            }
+            PathConstraints::Car => &self.car_graph,
+            PathConstraints::Bike => &self.bike_graph,
+            PathConstraints::Bus => &self.bus_graph,
+            PathConstraints::Train => {
+                return dijkstra::pathfind(req, params, map);
+            }
+        };
+
+        let cached = self
+            .customized
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(constraints, p, _)| *constraints == req.constraints && p == params)
+            .map(|(_, _, graph)| graph.clone());
+        let customized = match cached {
+            Some(graph) => graph,
+            None => {
+                info!(
+                    "Customizing pathfinding for {:?} with new params",
+                    req.constraints
+                );
+                let customized = Arc::new(graph.customize(map, params));
+                let mut cache = self.customized.write().unwrap();
+                if cache.len() == MAX_CUSTOMIZED_GRAPHS {
+                    cache.remove(0);
+                }
+                cache.push((req.constraints, params.clone(), customized.clone()));
+                customized
+            }
+        };
+        customized.pathfind(req, map)
+    }
+
+    fn pathfind_walking_with_params(
+        &self,
+        req: PathRequest,
+        params: &RoutingParams,
+        map: &Map,
+    ) -> Option<PathV2> {
+        let key = (params.fitness, params.uphill_penalty);
+        let cached = self
+            .customized_walking
+            .read()
+            .unwrap()
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, graph)| graph.clone());
+        let customized = match cached {
+            Some(graph) => graph,
+            None => {
+                info!("Customizing pathfinding for pedestrians with new params");
+                let customized =
+                    Arc::new(self.walking_graph.customize(map, params, &self.bus_graph));
+                let mut cache = self.customized_walking.write().unwrap();
+                if cache.len() == MAX_CUSTOMIZED_GRAPHS {
+                    cache.remove(0);
+                }
+                cache.push((key, customized.clone()));
+                customized
+            }
+        };
+        customized.pathfind(req, map)
+    }
+
     pub fn should_use_transit(
         &self,
         map: &Map,
@@ -75,7 +170,14 @@ impl ContractionHierarchyPathfinder {
             .should_use_transit(map, start, end)
     }
 
+    pub(crate) fn car_graph(&self) -> &VehiclePathfinder {
+        &self.car_graph
+    }
+
     pub fn apply_edits(&mut self, map: &Map, timer: &mut Timer) {
+        self.customized.write().unwrap().clear();
+        self.customized_walking.write().unwrap().clear();
+
         timer.start("apply edits to car pathfinding");
         self.car_graph.apply_edits(map);
         timer.stop("apply edits to car pathfinding");
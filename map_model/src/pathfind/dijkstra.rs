@@ -1,28 +1,57 @@
 //! Pathfinding without needing to build a separate contraction hierarchy.
 
-use std::collections::BTreeSet;
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap};
 
 use anyhow::Result;
 use petgraph::graphmap::DiGraphMap;
 
-use geom::Duration;
+use geom::{Duration, Time};
 
-use crate::pathfind::walking::{one_step_walking_path, walking_path_to_steps, WalkingNode};
-use crate::pathfind::{vehicle_cost, zone_cost};
+use crate::pathfind::walking::{
+    one_step_walking_path, walking_lane_cost, walking_path_to_steps, WalkingNode,
+};
+use crate::pathfind::{vehicle_cost, vehicle_cost_with_road_time, zone_cost, CostFunction};
 use crate::{
     DirectedRoadID, Map, MovementID, PathConstraints, PathRequest, PathV2, RoadID, RoutingParams,
-    Traversable,
+    TravelTimeProfile, Traversable,
 };
 
 // TODO These should maybe keep the DiGraphMaps as state. It's cheap to recalculate it for edits.
 
 pub fn pathfind(req: PathRequest, params: &RoutingParams, map: &Map) -> Option<PathV2> {
     if req.constraints == PathConstraints::Pedestrian {
-        pathfind_walking(req, map)
+        pathfind_walking(req, params, map)
     } else {
         let graph = build_graph_for_vehicles(map, req.constraints);
-        calc_path(graph, req, params, map)
+        let constraints = req.constraints;
+        calc_path(graph, req, map, |mvmnt| {
+            vehicle_cost(mvmnt.from, mvmnt, constraints, params, map)
+                + zone_cost(mvmnt, constraints, map)
+        })
+    }
+}
+
+/// Finds a path for a vehicle, letting a `CostFunction` adjust the cost of every movement.
+/// Pedestrians are routed normally.
+pub fn pathfind_with_cost_function(
+    req: PathRequest,
+    params: &RoutingParams,
+    cost_fn: &dyn CostFunction,
+    map: &Map,
+) -> Option<PathV2> {
+    if req.constraints == PathConstraints::Pedestrian {
+        return pathfind_walking(req, params, map);
     }
+    let graph = build_graph_for_vehicles(map, req.constraints);
+    let constraints = req.constraints;
+    calc_path(graph, req, map, |mvmnt| {
+        let base = vehicle_cost(mvmnt.from, mvmnt, constraints, params, map)
+            + zone_cost(mvmnt, constraints, map);
+        cost_fn
+            .vehicle_cost(mvmnt.from, mvmnt, constraints, base, map)
+            .max(Duration::ZERO)
+    })
 }
 
 pub fn build_graph_for_vehicles(
@@ -54,46 +83,112 @@ pub fn pathfind_avoiding_roads(
         }
     }
 
-    calc_path(graph, req.clone(), map.routing_params(), map)
-        .ok_or_else(|| anyhow!("No path for {} avoiding {} roads", req, avoid.len()))
+    let constraints = req.constraints;
+    let params = map.routing_params();
+    calc_path(graph, req.clone(), map, |mvmnt| {
+        vehicle_cost(mvmnt.from, mvmnt, constraints, params, map)
+            + zone_cost(mvmnt, constraints, map)
+    })
+    .ok_or_else(|| anyhow!("No path for {} avoiding {} roads", req, avoid.len()))
 }
 
-fn calc_path(
+fn calc_path<F: Fn(MovementID) -> Duration>(
     graph: DiGraphMap<DirectedRoadID, MovementID>,
     req: PathRequest,
-    params: &RoutingParams,
     map: &Map,
+    cost: F,
 ) -> Option<PathV2> {
     let end = map.get_l(req.end.lane()).get_directed_parent();
     let (cost, steps) = petgraph::algo::astar(
         &graph,
         map.get_l(req.start.lane()).get_directed_parent(),
         |dr| dr == end,
-        |(_, _, mvmnt)| {
-            vehicle_cost(mvmnt.from, *mvmnt, req.constraints, params, map)
-                + zone_cost(*mvmnt, req.constraints, map)
-        },
+        |(_, _, mvmnt)| cost(*mvmnt),
         |_| Duration::ZERO,
     )?;
     // TODO No uber-turns yet
     Some(PathV2::from_roads(steps, req, cost, Vec::new(), map))
 }
 
-pub fn build_graph_for_pedestrians(map: &Map) -> DiGraphMap<WalkingNode, Duration> {
+/// Finds a path where the time to cross each road depends on when it's reached. This only affects
+/// cars. Assumes that nobody can reach the end of a road sooner by entering it later.
+pub fn pathfind_with_travel_times(
+    req: PathRequest,
+    departure: Time,
+    profile: &TravelTimeProfile,
+    params: &RoutingParams,
+    map: &Map,
+) -> Option<PathV2> {
+    if req.constraints == PathConstraints::Pedestrian {
+        return pathfind_walking(req, params, map);
+    }
+    let graph = build_graph_for_vehicles(map, req.constraints);
+    pathfind_with_travel_times_on_graph(&graph, req, departure, profile, params, map)
+}
+
+/// Like `pathfind_with_travel_times`, but reuses a graph from `build_graph_for_vehicles`.
+pub fn pathfind_with_travel_times_on_graph(
+    graph: &DiGraphMap<DirectedRoadID, MovementID>,
+    req: PathRequest,
+    departure: Time,
+    profile: &TravelTimeProfile,
+    params: &RoutingParams,
+    map: &Map,
+) -> Option<PathV2> {
+    let start = map.get_l(req.start.lane()).get_directed_parent();
+    let end = map.get_l(req.end.lane()).get_directed_parent();
+
+    // The cost to reach each road, and the road before it on the best path
+    let mut best: BTreeMap<DirectedRoadID, Duration> = BTreeMap::new();
+    let mut prev: BTreeMap<DirectedRoadID, DirectedRoadID> = BTreeMap::new();
+    let mut queue = BinaryHeap::new();
+    best.insert(start, Duration::ZERO);
+    queue.push(Reverse((Duration::ZERO, start)));
+
+    while let Some(Reverse((cost, dr))) = queue.pop() {
+        if dr == end {
+            let mut roads = vec![end];
+            let mut current = end;
+            while current != start {
+                current = prev[&current];
+                roads.push(current);
+            }
+            roads.reverse();
+            return Some(PathV2::from_roads(roads, req, cost, Vec::new(), map));
+        }
+        if cost > best[&dr] {
+            continue;
+        }
+
+        let road_time = profile
+            .get(dr, departure + cost)
+            .or_else(|| params.road_costs.get(&dr).cloned());
+        for (_, next, mvmnt) in graph.edges(dr) {
+            let next_cost = cost
+                + vehicle_cost_with_road_time(dr, *mvmnt, req.constraints, road_time, params, map)
+                + zone_cost(*mvmnt, req.constraints, map);
+            if best.get(&next).map(|c| next_cost < *c).unwrap_or(true) {
+                best.insert(next, next_cost);
+                prev.insert(next, dr);
+                queue.push(Reverse((next_cost, next)));
+            }
+        }
+    }
+    None
+}
+
+pub fn build_graph_for_pedestrians(
+    map: &Map,
+    params: &RoutingParams,
+) -> DiGraphMap<WalkingNode, Duration> {
     let max_speed = Some(crate::MAX_WALKING_SPEED);
     let mut graph: DiGraphMap<WalkingNode, Duration> = DiGraphMap::new();
     for l in map.all_lanes().values() {
         if l.is_walkable() {
-            let cost = l.length()
-                / Traversable::Lane(l.id).max_speed_along(
-                    max_speed,
-                    PathConstraints::Pedestrian,
-                    map,
-                );
             let n1 = WalkingNode::SidewalkEndpoint(l.get_directed_parent(), true);
             let n2 = WalkingNode::SidewalkEndpoint(l.get_directed_parent(), false);
-            graph.add_edge(n1, n2, cost);
-            graph.add_edge(n2, n1, cost);
+            graph.add_edge(n1, n2, walking_lane_cost(l.id, false, params, map));
+            graph.add_edge(n2, n1, walking_lane_cost(l.id, true, params, map));
 
             for turn in map.get_turns_for(l.id, PathConstraints::Pedestrian) {
                 graph.add_edge(
@@ -119,12 +214,12 @@ pub fn build_graph_for_pedestrians(map: &Map) -> DiGraphMap<WalkingNode, Duratio
     graph
 }
 
-fn pathfind_walking(req: PathRequest, map: &Map) -> Option<PathV2> {
+fn pathfind_walking(req: PathRequest, params: &RoutingParams, map: &Map) -> Option<PathV2> {
     if req.start.lane() == req.end.lane() {
         return Some(one_step_walking_path(req, map));
     }
 
-    let graph = build_graph_for_pedestrians(map);
+    let graph = build_graph_for_pedestrians(map, params);
 
     let closest_start = WalkingNode::closest(req.start, map);
     let closest_end = WalkingNode::closest(req.end, map);
@@ -1,23 +1,30 @@
 //! Everything related to pathfinding through a map for different types of agents.
 
+use std::collections::BTreeMap;
+use std::hash::{Hash, Hasher};
+
+use anyhow::Result;
 use enumset::EnumSetType;
 use serde::{Deserialize, Serialize};
 
+use abstutil::{deserialize_btreemap, serialize_btreemap};
 use geom::Duration;
 
 pub use self::ch::ContractionHierarchyPathfinder;
 pub use self::dijkstra::{build_graph_for_pedestrians, build_graph_for_vehicles};
 pub use self::pathfinder::Pathfinder;
+pub use self::time_dependent::{TimeDependentPathfinder, TravelTimeProfile};
 pub use self::v1::{Path, PathRequest, PathStep};
 pub use self::v2::{PathStepV2, PathV2};
-pub use self::vehicles::vehicle_cost;
+pub use self::vehicles::{vehicle_cost, vehicle_cost_with_road_time};
 pub use self::walking::WalkingNode;
-use crate::{osm, Lane, LaneID, LaneType, Map, MovementID};
+use crate::{osm, DirectedRoadID, Lane, LaneID, LaneType, Map, MovementID};
 
 mod ch;
 pub mod dijkstra;
 mod node_map;
 mod pathfinder;
+mod time_dependent;
 // TODO tmp
 pub mod uber_turns;
 mod v1;
@@ -121,10 +128,25 @@ pub fn zone_cost(mvmnt: MovementID, constraints: PathConstraints, map: &Map) ->
     }
 }
 
-/// Tuneable parameters for all types of routing.
-// These will maybe become part of the PathRequest later, but that's an extremely invasive and
-// space-expensive change right now.
-#[derive(PartialEq, Serialize, Deserialize)]
+/// Lets other crates route vehicles using their own costs, without building a new `Pathfinder`.
+/// Paths are found with Dijkstra's algorithm, so this is much slower than normal pathfinding.
+pub trait CostFunction {
+    /// The cost of crossing a directed road, then making a movement at the end of it. `base` is
+    /// the cost from the routing params, including penalties for entering restricted zones.
+    /// Negative costs are treated as zero.
+    fn vehicle_cost(
+        &self,
+        dr: DirectedRoadID,
+        mvmnt: MovementID,
+        constraints: PathConstraints,
+        base: Duration,
+        map: &Map,
+    ) -> Duration;
+}
+
+/// Tuneable parameters for all types of routing. The map has a default set, and each
+/// `PathRequest` may override them. None of the penalties may be NaN; see `check`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RoutingParams {
     // For all vehicles. This is added to the cost of a movement as an additional delay.
     pub unprotected_turn_penalty: Duration,
@@ -133,10 +155,60 @@ pub struct RoutingParams {
     pub bike_lane_penalty: f64,
     pub bus_lane_penalty: f64,
     pub driving_lane_penalty: f64,
+    // For bike routing. Multiplied by the base cost on arterials and highways, to stay away from
+    // busy roads. 1.0 means no preference.
+    pub arterial_penalty: f64,
+    // For all vehicles and pedestrians. For every percent of uphill incline, the base cost is
+    // increased by this fraction. 0.0 means hills don't matter, beyond how they affect speed.
+    pub uphill_penalty: f64,
+    // For bikes and pedestrians. How well the traveler handles hills, affecting their speed uphill
+    // -- see `Traversable::max_speed_along_road_with_fitness`. 1.0 is average. The simulation also
+    // uses this to move cyclists and pedestrians.
+    pub fitness: f64,
+    // For all vehicles. This is added to the cost of every left turn (right turn when driving on
+    // the left) that no traffic signal stage protects. If a signal only protects the turn during
+    // some of its timing plans, this is scaled by the fraction of the day it's unprotected.
+    pub unprotected_left_turn_penalty: Duration,
+    // For cars. If a road is present, this is the time to cross it (including waiting to turn at
+    // the end), replacing the estimate from the speed limit. Usually learned from experienced
+    // travel times; see sim's dynamic traffic assignment.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub road_costs: BTreeMap<DirectedRoadID, Duration>,
+}
+
+// Penalties are compared and hashed bit-for-bit, so equality stays reflexive even if a NaN sneaks
+// in.
+impl PartialEq for RoutingParams {
+    fn eq(&self, other: &RoutingParams) -> bool {
+        self.float_bits() == other.float_bits()
+            && self.road_costs.len() == other.road_costs.len()
+            && self
+                .road_costs
+                .iter()
+                .zip(other.road_costs.iter())
+                .all(|((dr1, d1), (dr2, d2))| {
+                    dr1 == dr2 && d1.inner_seconds().to_bits() == d2.inner_seconds().to_bits()
+                })
+    }
+}
+
+impl Eq for RoutingParams {}
+
+impl Hash for RoutingParams {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.float_bits().hash(state);
+        for (dr, d) in &self.road_costs {
+            dr.hash(state);
+            d.inner_seconds().to_bits().hash(state);
+        }
+    }
 }
 
 impl RoutingParams {
-    pub const fn default() -> RoutingParams {
+    pub fn default() -> RoutingParams {
         RoutingParams {
             // This is a total guess -- it really depends on the traffic patterns of the particular
             // road at the time we're routing.
@@ -144,6 +216,97 @@ impl RoutingParams {
             bike_lane_penalty: 1.0,
             bus_lane_penalty: 1.1,
             driving_lane_penalty: 1.5,
+            arterial_penalty: 1.0,
+            uphill_penalty: 0.0,
+            fitness: 1.0,
+            unprotected_left_turn_penalty: Duration::ZERO,
+            road_costs: BTreeMap::new(),
+        }
+    }
+
+    /// Fails if any penalty isn't a finite number. Params coming from files or user input should
+    /// be checked before they're used.
+    pub fn check(&self) -> Result<()> {
+        let penalties = vec![
+            (
+                "unprotected_turn_penalty",
+                self.unprotected_turn_penalty.inner_seconds(),
+            ),
+            ("bike_lane_penalty", self.bike_lane_penalty),
+            ("bus_lane_penalty", self.bus_lane_penalty),
+            ("driving_lane_penalty", self.driving_lane_penalty),
+            ("arterial_penalty", self.arterial_penalty),
+            ("uphill_penalty", self.uphill_penalty),
+            ("fitness", self.fitness),
+            (
+                "unprotected_left_turn_penalty",
+                self.unprotected_left_turn_penalty.inner_seconds(),
+            ),
+        ];
+        for (name, x) in penalties {
+            if !x.is_finite() {
+                bail!("RoutingParams has a bad {}: {}", name, x);
+            }
+        }
+        for (dr, d) in &self.road_costs {
+            if !d.inner_seconds().is_finite() {
+                bail!("RoutingParams has a bad cost for {}: {}", dr, d);
+            }
         }
+        Ok(())
+    }
+
+    fn float_bits(&self) -> [u64; 8] {
+        [
+            self.unprotected_turn_penalty.inner_seconds().to_bits(),
+            self.bike_lane_penalty.to_bits(),
+            self.bus_lane_penalty.to_bits(),
+            self.driving_lane_penalty.to_bits(),
+            self.arterial_penalty.to_bits(),
+            self.uphill_penalty.to_bits(),
+            self.fitness.to_bits(),
+            self.unprotected_left_turn_penalty.inner_seconds().to_bits(),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+
+    use super::*;
+
+    fn hash(params: &RoutingParams) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        params.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn equality_and_hashing() {
+        let mut params = RoutingParams::default();
+        assert_eq!(params, RoutingParams::default());
+        assert_eq!(hash(&params), hash(&RoutingParams::default()));
+
+        params.arterial_penalty = 2.0;
+        assert_ne!(params, RoutingParams::default());
+
+        // Equality is reflexive, even for NaN
+        params.fitness = std::f64::NAN;
+        assert_eq!(params, params.clone());
+        assert_eq!(hash(&params), hash(&params.clone()));
+    }
+
+    #[test]
+    fn reject_nan() {
+        assert!(RoutingParams::default().check().is_ok());
+
+        let mut params = RoutingParams::default();
+        params.uphill_penalty = std::f64::NAN;
+        assert!(params.check().is_err());
+
+        let mut params = RoutingParams::default();
+        params.bike_lane_penalty = std::f64::INFINITY;
+        assert!(params.check().is_err());
     }
 }
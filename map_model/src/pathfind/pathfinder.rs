@@ -7,7 +7,10 @@ use abstutil::Timer;
 
 use crate::pathfind::ch::ContractionHierarchyPathfinder;
 use crate::pathfind::dijkstra;
-use crate::{BusRouteID, BusStopID, Map, PathRequest, PathV2, Position, RoadID, RoutingParams};
+use crate::{
+    BusRouteID, BusStopID, Map, PathConstraints, PathRequest, PathV2, Position, RoadID,
+    RoutingParams,
+};
 
 /// Most of the time, prefer using the faster contraction hierarchies. But sometimes, callers can
 /// explicitly opt into a slower (but preparation-free) pathfinder that just uses Dijkstra's
@@ -19,8 +22,24 @@ pub enum Pathfinder {
 }
 
 impl Pathfinder {
-    /// Finds a path from a start to an end for a certain type of agent.
+    /// Finds a path from a start to an end for a certain type of agent. If the request has its own
+    /// routing params, they're used instead of the map's.
     pub fn pathfind(&self, req: PathRequest, map: &Map) -> Option<PathV2> {
+        if let Some(params) = req.routing_params.clone() {
+            let map_params = map.routing_params();
+            let differs = if req.constraints == PathConstraints::Pedestrian {
+                params.fitness != map_params.fitness
+                    || params.uphill_penalty != map_params.uphill_penalty
+            } else {
+                *params != *map_params
+            };
+            if differs {
+                return match self {
+                    Pathfinder::Dijkstra => dijkstra::pathfind(req, &params, map),
+                    Pathfinder::CH(ref p) => p.pathfind_with_params(req, &params, map),
+                };
+            }
+        }
         self.pathfind_with_params(req, map.routing_params(), map)
     }
 
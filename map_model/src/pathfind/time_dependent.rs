@@ -0,0 +1,176 @@
+//! Routing for cars that depends on the time of day, using travel times measured from a previous
+//! simulation.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use petgraph::graphmap::DiGraphMap;
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap, Timer};
+use geom::{Duration, Time};
+
+use crate::pathfind::vehicles::VehiclePathfinder;
+use crate::pathfind::{dijkstra, Pathfinder};
+use crate::{DirectedRoadID, Map, MovementID, Path, PathConstraints, PathRequest};
+
+/// How long it takes cars to cross each road during each hour of the day, including time spent
+/// waiting to turn at the end. Roads and hours without a measurement are assumed to be at
+/// free-flow.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TravelTimeProfile {
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    per_road: BTreeMap<DirectedRoadID, BTreeMap<usize, Duration>>,
+}
+
+impl TravelTimeProfile {
+    /// Hours count from midnight, between 0 and 23.
+    pub fn set(&mut self, dr: DirectedRoadID, hour: usize, travel_time: Duration) {
+        assert!(hour < 24);
+        self.per_road
+            .entry(dr)
+            .or_insert_with(BTreeMap::new)
+            .insert(hour, travel_time);
+    }
+
+    /// Simulations lasting more than one day wrap around, reusing the same hours.
+    pub fn get(&self, dr: DirectedRoadID, time: Time) -> Option<Duration> {
+        self.per_road
+            .get(&dr)?
+            .get(&(time.get_hours() % 24))
+            .cloned()
+    }
+
+    /// All hours with at least one measurement
+    pub fn hours(&self) -> BTreeSet<usize> {
+        self.per_road
+            .values()
+            .flat_map(|per_hour| per_hour.keys().cloned())
+            .collect()
+    }
+
+    /// The travel time of every road measured during one hour
+    pub fn road_costs_at(&self, hour: usize) -> BTreeMap<DirectedRoadID, Duration> {
+        self.per_road
+            .iter()
+            .filter_map(|(dr, per_hour)| per_hour.get(&hour).map(|dt| (*dr, *dt)))
+            .collect()
+    }
+}
+
+/// Routes cars using a `TravelTimeProfile`, so trips avoid roads known to be congested when they
+/// depart. For every hour with measurements, the map's contraction hierarchy for cars is
+/// customized with that hour's travel times. This reuses the node ordering, so it's much faster
+/// than importing, but the costs of the whole path are fixed at the departure hour. If the map
+/// only uses Dijkstra's algorithm, or a request has its own routing params that the hourly
+/// hierarchies don't match, then the exact time-dependent variant is used instead.
+///
+/// This has to be built again after the map is edited.
+pub struct TimeDependentPathfinder {
+    profile: TravelTimeProfile,
+    hourly: BTreeMap<usize, VehiclePathfinder>,
+    /// For the time-dependent variant of Dijkstra's algorithm
+    graph: DiGraphMap<DirectedRoadID, MovementID>,
+}
+
+impl TimeDependentPathfinder {
+    pub fn new(
+        map: &Map,
+        profile: TravelTimeProfile,
+        timer: &mut Timer,
+    ) -> TimeDependentPathfinder {
+        let mut hourly = BTreeMap::new();
+        if let Pathfinder::CH(ref ch) = map.pathfinder {
+            let hours = profile.hours();
+            timer.start_iter("customize car pathfinding per hour", hours.len());
+            for hour in hours {
+                timer.next();
+                let mut params = map.routing_params().clone();
+                params.road_costs.extend(profile.road_costs_at(hour));
+                hourly.insert(hour, ch.car_graph().customize(map, &params));
+            }
+        }
+        TimeDependentPathfinder {
+            profile,
+            hourly,
+            graph: dijkstra::build_graph_for_vehicles(map, PathConstraints::Car),
+        }
+    }
+
+    /// Only requests for cars use the travel times; everything else is routed normally.
+    pub fn pathfind(&self, req: PathRequest, departure: Time, map: &Map) -> Result<Path> {
+        if req.constraints != PathConstraints::Car {
+            return map.pathfind(req);
+        }
+        let params = map.routing_params_for(&req);
+        let path = match map.pathfinder {
+            Pathfinder::CH(_) if params == map.routing_params() => {
+                match self.hourly.get(&(departure.get_hours() % 24)) {
+                    Some(graph) => graph.pathfind(req.clone(), map),
+                    None => {
+                        return map.pathfind(req);
+                    }
+                }
+            }
+            _ => dijkstra::pathfind_with_travel_times_on_graph(
+                &self.graph,
+                req.clone(),
+                departure,
+                &self.profile,
+                params,
+                map,
+            ),
+        };
+        path.ok_or_else(|| anyhow!("can't fulfill {}", req))?
+            .to_v1(map)
+    }
+
+    pub fn get_profile(&self) -> &TravelTimeProfile {
+        &self.profile
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Direction, RoadID};
+
+    #[test]
+    fn hours_wrap_around() {
+        let dr = DirectedRoadID {
+            id: RoadID(0),
+            dir: Direction::Fwd,
+        };
+        let mut profile = TravelTimeProfile::default();
+        profile.set(dr, 1, Duration::minutes(5));
+        profile.set(dr, 23, Duration::minutes(3));
+
+        let at = |hours: usize, minutes: usize| {
+            profile.get(
+                dr,
+                Time::START_OF_DAY + Duration::hours(hours) + Duration::minutes(minutes),
+            )
+        };
+        assert_eq!(at(1, 30), Some(Duration::minutes(5)));
+        assert_eq!(at(23, 59), Some(Duration::minutes(3)));
+        // The next day reuses the same hours
+        assert_eq!(at(25, 0), Some(Duration::minutes(5)));
+        assert_eq!(at(47, 10), Some(Duration::minutes(3)));
+        // Hours without measurements are left to the caller
+        assert_eq!(at(0, 0), None);
+        assert_eq!(at(24, 0), None);
+        assert_eq!(at(2, 0), None);
+        // Other roads
+        let back = DirectedRoadID {
+            id: RoadID(0),
+            dir: Direction::Back,
+        };
+        assert_eq!(
+            profile.get(back, Time::START_OF_DAY + Duration::hours(1)),
+            None
+        );
+    }
+}
@@ -6,7 +6,10 @@ use serde::{Deserialize, Serialize};
 
 use geom::{Distance, Duration, PolyLine, Speed, EPSILON_DIST};
 
-use crate::{BuildingID, LaneID, Map, PathConstraints, Position, Traversable, TurnID, UberTurn};
+use crate::{
+    BuildingID, LaneID, Map, PathConstraints, Position, RoutingParams, Traversable, TurnID,
+    UberTurn,
+};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum PathStep {
@@ -30,6 +33,36 @@ impl PathStep {
         self.as_traversable().as_lane()
     }
 
+    /// How fast somebody could go along this step. Unlike `Traversable::max_speed_along`, this
+    /// knows that walking against the direction of a sidewalk turns uphill into downhill.
+    pub fn max_speed_along(
+        &self,
+        max_speed_on_flat_ground: Option<Speed>,
+        fitness: f64,
+        constraints: PathConstraints,
+        map: &Map,
+    ) -> Speed {
+        match self {
+            PathStep::ContraflowLane(l) => {
+                let mut dr = map.get_l(*l).get_directed_parent();
+                dr.dir = dr.dir.opposite();
+                Traversable::max_speed_along_road_with_fitness(
+                    dr,
+                    max_speed_on_flat_ground,
+                    fitness,
+                    constraints,
+                    map,
+                )
+            }
+            _ => self.as_traversable().max_speed_along_with_fitness(
+                max_speed_on_flat_ground,
+                fitness,
+                constraints,
+                map,
+            ),
+        }
+    }
+
     pub fn as_turn(&self) -> TurnID {
         self.as_traversable().as_turn()
     }
@@ -400,12 +433,11 @@ impl Path {
         constraints: PathConstraints,
         max_speed: Option<Speed>,
     ) -> Duration {
+        let fitness = map.routing_params_for(&self.orig_req).fitness;
         let mut total = Duration::ZERO;
         for step in &self.steps {
             let dist = self.dist_crossed_from_step(map, step);
-            let speed = step
-                .as_traversable()
-                .max_speed_along(max_speed, constraints, map);
+            let speed = step.max_speed_along(max_speed, fitness, constraints, map);
             total += dist / speed;
         }
         total
@@ -417,6 +449,9 @@ pub struct PathRequest {
     pub start: Position,
     pub end: Position,
     pub constraints: PathConstraints,
+    /// Overrides the map's routing params for this one request, to express the preferences of a
+    /// particular traveler. Pedestrians only care about fitness and the uphill penalty.
+    pub routing_params: Option<Box<RoutingParams>>,
 }
 
 impl fmt::Display for PathRequest {
@@ -461,6 +496,7 @@ impl PathRequest {
             start,
             end,
             constraints,
+            routing_params: None,
         })
     }
 }
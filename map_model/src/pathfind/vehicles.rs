@@ -14,7 +14,7 @@ use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
 use crate::pathfind::uber_turns::{IntersectionCluster, UberTurnV2};
 use crate::pathfind::zone_cost;
 use crate::{
-    DirectedRoadID, Direction, DrivingSide, LaneType, Map, MovementID, PathConstraints,
+    osm, DirectedRoadID, Direction, DrivingSide, LaneType, Map, MovementID, PathConstraints,
     PathRequest, PathV2, RoutingParams, Traversable, TurnType,
 };
 
@@ -67,7 +67,8 @@ impl VehiclePathfinder {
             }
         }
 
-        let input_graph = make_input_graph(map, &nodes, &uber_turns, constraints);
+        let input_graph =
+            make_input_graph(map, &nodes, &uber_turns, constraints, map.routing_params());
 
         // All VehiclePathfinders have the same nodes (roads), so if we're not the first being
         // built, seed from the node ordering.
@@ -133,10 +134,31 @@ impl VehiclePathfinder {
         // the node ordering.
         // TODO Make sure the result of this is deterministic and equivalent to computing from
         // scratch.
-        let input_graph = make_input_graph(map, &self.nodes, &self.uber_turns, self.constraints);
+        let input_graph = make_input_graph(
+            map,
+            &self.nodes,
+            &self.uber_turns,
+            self.constraints,
+            map.routing_params(),
+        );
         let node_ordering = self.graph.get_node_ordering();
         self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap();
     }
+
+    /// Creates a pathfinder for the same map using different routing params. Only the edge weights
+    /// change, so the node ordering is reused, which is much faster than contracting from scratch.
+    pub fn customize(&self, map: &Map, params: &RoutingParams) -> VehiclePathfinder {
+        let input_graph =
+            make_input_graph(map, &self.nodes, &self.uber_turns, self.constraints, params);
+        let node_ordering = self.graph.get_node_ordering();
+        VehiclePathfinder {
+            graph: fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap(),
+            nodes: self.nodes.clone(),
+            uber_turns: self.uber_turns.clone(),
+            constraints: self.constraints,
+            path_calc: ThreadLocal::new(),
+        }
+    }
 }
 
 fn make_input_graph(
@@ -144,6 +166,7 @@ fn make_input_graph(
     nodes: &NodeMap<Node>,
     uber_turns: &Vec<UberTurnV2>,
     constraints: PathConstraints,
+    params: &RoutingParams,
 ) -> InputGraph {
     let mut input_graph = InputGraph::new();
 
@@ -178,13 +201,8 @@ fn make_input_graph(
                             from,
                             nodes.get(Node::Road(mvmnt.to)),
                             round(
-                                vehicle_cost(
-                                    mvmnt.from,
-                                    mvmnt,
-                                    constraints,
-                                    map.routing_params(),
-                                    map,
-                                ) + zone_cost(mvmnt, constraints, map),
+                                vehicle_cost(mvmnt.from, mvmnt, constraints, params, map)
+                                    + zone_cost(mvmnt, constraints, map),
                             ),
                         );
                     }
@@ -194,13 +212,8 @@ fn make_input_graph(
 
                         let mut sum_cost = Duration::ZERO;
                         for mvmnt in &ut.path {
-                            sum_cost += vehicle_cost(
-                                mvmnt.from,
-                                *mvmnt,
-                                constraints,
-                                map.routing_params(),
-                                map,
-                            ) + zone_cost(*mvmnt, constraints, map);
+                            sum_cost += vehicle_cost(mvmnt.from, *mvmnt, constraints, params, map)
+                                + zone_cost(*mvmnt, constraints, map);
                         }
                         input_graph.add_edge(
                             from,
@@ -233,6 +246,26 @@ pub fn vehicle_cost(
     constraints: PathConstraints,
     params: &RoutingParams,
     map: &Map,
+) -> Duration {
+    vehicle_cost_with_road_time(
+        dr,
+        mvmnt,
+        constraints,
+        params.road_costs.get(&dr).cloned(),
+        params,
+        map,
+    )
+}
+
+/// Like `vehicle_cost`, but for cars, the time to cross the road can be specified, instead of
+/// estimating it from the speed limit.
+pub fn vehicle_cost_with_road_time(
+    dr: DirectedRoadID,
+    mvmnt: MovementID,
+    constraints: PathConstraints,
+    road_time: Option<Duration>,
+    params: &RoutingParams,
+    map: &Map,
 ) -> Duration {
     // TODO Creating the consolidated polyline sometimes fails. It's rare, so just workaround
     // temporarily by pretending the turn is 1m long.
@@ -246,12 +279,19 @@ pub fn vehicle_cost(
         PathConstraints::Pedestrian => unreachable!(),
     };
     let t1 = map.get_r(dr.id).center_pts.length()
-        / Traversable::max_speed_along_road(dr, max_speed, constraints, map);
+        / Traversable::max_speed_along_road_with_fitness(
+            dr,
+            max_speed,
+            params.fitness,
+            constraints,
+            map,
+        );
     let t2 =
         mvmnt_length / Traversable::max_speed_along_movement(mvmnt, max_speed, constraints, map);
 
     let base = match constraints {
-        PathConstraints::Car | PathConstraints::Train => t1 + t2,
+        PathConstraints::Car => road_time.unwrap_or(t1) + t2,
+        PathConstraints::Train => t1 + t2,
         PathConstraints::Bike => {
             // TODO If we're on a driving lane, higher speed limit is worse.
             // TODO Bike lanes next to parking is dangerous.
@@ -266,7 +306,13 @@ pub fn vehicle_cost(
                 params.driving_lane_penalty
             };
 
-            lt_penalty * (t1 + t2)
+            let rank_penalty = if map.get_r(dr.id).get_rank() == osm::RoadRank::Local {
+                1.0
+            } else {
+                params.arterial_penalty
+            };
+
+            lt_penalty * rank_penalty * (t1 + t2)
         }
         PathConstraints::Bus => {
             // Like Car, but prefer bus lanes.
@@ -280,6 +326,13 @@ pub fn vehicle_cost(
         PathConstraints::Pedestrian => unreachable!(),
     };
 
+    let percent_incline = dr.percent_incline(map);
+    let mut base = if percent_incline > 0.0 {
+        (1.0 + params.uphill_penalty * percent_incline * 100.0) * base
+    } else {
+        base
+    };
+
     // Penalize unprotected turns at a stop sign from smaller to larger roads.
     let unprotected_turn_type = if map.get_config().driving_side == DrivingSide::Right {
         TurnType::Left
@@ -292,8 +345,20 @@ pub fn vehicle_cost(
         && rank_from < rank_to
         && map.get_i(mvmnt.parent).is_stop_sign()
     {
-        base + params.unprotected_turn_penalty
-    } else {
-        base
+        base += params.unprotected_turn_penalty;
+    }
+
+    // Routes don't know when they'll reach a signal, so if only some of its timing plans protect
+    // the turn, the penalty is scaled by how much of the day the turn is unprotected.
+    if mvmnt_turn_type == unprotected_turn_type
+        && params.unprotected_left_turn_penalty > Duration::ZERO
+    {
+        let unprotected = map
+            .maybe_get_traffic_signal(mvmnt.parent)
+            .map(|ts| 1.0 - ts.fraction_of_day_protected(mvmnt))
+            .unwrap_or(1.0);
+        base += params.unprotected_left_turn_penalty * unprotected;
     }
+
+    base
 }
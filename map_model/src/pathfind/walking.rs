@@ -15,8 +15,8 @@ use crate::pathfind::node_map::{deserialize_nodemap, NodeMap};
 use crate::pathfind::vehicles::VehiclePathfinder;
 use crate::pathfind::zone_cost;
 use crate::{
-    BusRoute, BusRouteID, BusStopID, DirectedRoadID, IntersectionID, Map, MovementID,
-    PathConstraints, PathRequest, PathStepV2, PathV2, Position, Traversable,
+    BusRoute, BusRouteID, BusStopID, DirectedRoadID, IntersectionID, LaneID, Map, MovementID,
+    PathConstraints, PathRequest, PathStepV2, PathV2, Position, RoutingParams, Traversable,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -89,7 +89,13 @@ impl SidewalkPathfinder {
             }
         }
 
-        let graph = fast_paths::prepare(&make_input_graph(map, &nodes, use_transit, bus_graph));
+        let graph = fast_paths::prepare(&make_input_graph(
+            map,
+            &nodes,
+            use_transit,
+            bus_graph,
+            map.routing_params(),
+        ));
         SidewalkPathfinder {
             graph,
             nodes,
@@ -99,11 +105,35 @@ impl SidewalkPathfinder {
     }
 
     pub fn apply_edits(&mut self, map: &Map, bus_graph: &VehiclePathfinder) {
-        let input_graph = make_input_graph(map, &self.nodes, self.use_transit, bus_graph);
+        let input_graph = make_input_graph(
+            map,
+            &self.nodes,
+            self.use_transit,
+            bus_graph,
+            map.routing_params(),
+        );
         let node_ordering = self.graph.get_node_ordering();
         self.graph = fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap();
     }
 
+    /// Creates a pathfinder for the same map for pedestrians with different routing params, like a
+    /// different fitness. The node ordering is reused.
+    pub fn customize(
+        &self,
+        map: &Map,
+        params: &RoutingParams,
+        bus_graph: &VehiclePathfinder,
+    ) -> SidewalkPathfinder {
+        let input_graph = make_input_graph(map, &self.nodes, self.use_transit, bus_graph, params);
+        let node_ordering = self.graph.get_node_ordering();
+        SidewalkPathfinder {
+            graph: fast_paths::prepare_with_order(&input_graph, &node_ordering).unwrap(),
+            nodes: self.nodes.clone(),
+            use_transit: self.use_transit,
+            path_calc: ThreadLocal::new(),
+        }
+    }
+
     pub fn pathfind(&self, req: PathRequest, map: &Map) -> Option<PathV2> {
         if req.start.lane() == req.end.lane() {
             return Some(one_step_walking_path(req, map));
@@ -215,34 +245,57 @@ impl SidewalkPathfinder {
     }
 }
 
+/// The cost of walking the full length of a sidewalk or shoulder. If `fwd` is false, the walk goes
+/// against the lane's direction, so uphill and downhill swap.
+pub(crate) fn walking_lane_cost(
+    l: LaneID,
+    fwd: bool,
+    params: &RoutingParams,
+    map: &Map,
+) -> Duration {
+    let lane = map.get_l(l);
+    let mut dr = lane.get_directed_parent();
+    if !fwd {
+        dr.dir = dr.dir.opposite();
+    }
+    let mut cost = lane.length()
+        / Traversable::max_speed_along_road_with_fitness(
+            dr,
+            Some(crate::MAX_WALKING_SPEED),
+            params.fitness,
+            PathConstraints::Pedestrian,
+            map,
+        );
+    let percent_incline = dr.percent_incline(map);
+    if percent_incline > 0.0 {
+        cost = (1.0 + params.uphill_penalty * percent_incline * 100.0) * cost;
+    }
+    // TODO Tune this penalty, along with many others.
+    if lane.is_shoulder() {
+        cost = 2.0 * cost;
+    }
+    cost
+}
+
 fn make_input_graph(
     map: &Map,
     nodes: &NodeMap<WalkingNode>,
     use_transit: bool,
     bus_graph: &VehiclePathfinder,
+    params: &RoutingParams,
 ) -> InputGraph {
     let max_speed = Some(crate::MAX_WALKING_SPEED);
     let mut input_graph = InputGraph::new();
 
     for l in map.all_lanes().values() {
         if l.is_walkable() {
-            let mut cost = l.length()
-                / Traversable::Lane(l.id).max_speed_along(
-                    max_speed,
-                    PathConstraints::Pedestrian,
-                    map,
-                );
-            // TODO Tune this penalty, along with many others.
-            if l.is_shoulder() {
-                cost = 2.0 * cost;
-            }
             let n1 = nodes.get(WalkingNode::SidewalkEndpoint(l.get_directed_parent(), true));
             let n2 = nodes.get(WalkingNode::SidewalkEndpoint(
                 l.get_directed_parent(),
                 false,
             ));
-            input_graph.add_edge(n1, n2, round(cost));
-            input_graph.add_edge(n2, n1, round(cost));
+            input_graph.add_edge(n1, n2, round(walking_lane_cost(l.id, false, params, map)));
+            input_graph.add_edge(n2, n1, round(walking_lane_cost(l.id, true, params, map)));
         }
     }
 
@@ -322,6 +375,7 @@ fn transit_input_graph(
                 start: stop1.driving_pos,
                 end: stop2.driving_pos,
                 constraints: route.route_type,
+                routing_params: None,
             };
             let maybe_driving_cost = match route.route_type {
                 PathConstraints::Bus => bus_graph.pathfind(req, map).map(|p| p.get_cost()),
@@ -351,6 +405,7 @@ fn transit_input_graph(
                 start: stop1.driving_pos,
                 end: Position::end(l, map),
                 constraints: route.route_type,
+                routing_params: None,
             };
             let maybe_driving_cost = match route.route_type {
                 PathConstraints::Bus => bus_graph.pathfind(req, map).map(|p| p.get_cost()),
@@ -15,7 +15,9 @@ use abstutil::{deserialize_btreemap, serialize_btreemap, Tags};
 use geom::{Distance, GPSBounds, PolyLine, Polygon, Pt2D};
 
 use crate::make::initial::lane_specs::get_lane_specs_ltr;
-use crate::{osm, Amenity, AreaType, Direction, DrivingSide, IntersectionType, MapConfig};
+use crate::{
+    osm, Amenity, AreaType, CrossingType, Direction, DrivingSide, IntersectionType, MapConfig,
+};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RawMap {
@@ -511,6 +513,8 @@ pub struct RawIntersection {
     pub point: Pt2D,
     pub intersection_type: IntersectionType,
     pub elevation: Distance,
+    /// Only set for mid-block pedestrian crossings
+    pub crossing: Option<CrossingType>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -572,6 +576,8 @@ pub struct RawBusRoute {
     pub gtfs_trip_marker: Option<String>,
     /// If not, light rail
     pub is_bus: bool,
+    /// How many passengers fit on each vehicle, if the route is tagged with it
+    pub capacity: Option<usize>,
     pub stops: Vec<RawBusStop>,
     pub border_start: Option<osm::NodeID>,
     pub border_end: Option<osm::NodeID>,
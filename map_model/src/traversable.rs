@@ -4,7 +4,7 @@ use serde::{Deserialize, Serialize};
 
 use geom::{Angle, Distance, PolyLine, Pt2D, Speed};
 
-use crate::{DirectedRoadID, Direction, LaneID, Map, MovementID, PathConstraints, TurnID};
+use crate::{DirectedRoadID, LaneID, Map, MovementID, PathConstraints, TurnID};
 
 /// Represents a specific point some distance along a lane.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
@@ -192,11 +192,24 @@ impl Traversable {
         max_speed_on_flat_ground: Option<Speed>,
         constraints: PathConstraints,
         map: &Map,
+    ) -> Speed {
+        self.max_speed_along_with_fitness(max_speed_on_flat_ground, 1.0, constraints, map)
+    }
+
+    /// Like `max_speed_along`, but for a cyclist or pedestrian who handles hills better or worse
+    /// than average. See `max_speed_along_road_with_fitness`.
+    pub fn max_speed_along_with_fitness(
+        &self,
+        max_speed_on_flat_ground: Option<Speed>,
+        fitness: f64,
+        constraints: PathConstraints,
+        map: &Map,
     ) -> Speed {
         match self {
-            Traversable::Lane(l) => Traversable::max_speed_along_road(
+            Traversable::Lane(l) => Traversable::max_speed_along_road_with_fitness(
                 map.get_l(*l).get_directed_parent(),
                 max_speed_on_flat_ground,
+                fitness,
                 constraints,
                 map,
             ),
@@ -216,13 +229,32 @@ impl Traversable {
         max_speed_on_flat_ground: Option<Speed>,
         constraints: PathConstraints,
         map: &Map,
+    ) -> Speed {
+        Traversable::max_speed_along_road_with_fitness(
+            dr,
+            max_speed_on_flat_ground,
+            1.0,
+            constraints,
+            map,
+        )
+    }
+
+    /// Like `max_speed_along_road`, but for a cyclist or pedestrian who handles hills better or
+    /// worse than average. A fitness of 1.0 is average. Uphill grades feel divided by the fitness,
+    /// so with a fitness of 2.0, climbing a 6% grade is like an average person climbing 3%.
+    /// Going downhill isn't affected.
+    pub fn max_speed_along_road_with_fitness(
+        dr: DirectedRoadID,
+        max_speed_on_flat_ground: Option<Speed>,
+        fitness: f64,
+        constraints: PathConstraints,
+        map: &Map,
     ) -> Speed {
         let road = map.get_r(dr.id);
-        let percent_incline = if dr.dir == Direction::Fwd {
-            road.percent_incline
-        } else {
-            -road.percent_incline
-        };
+        let mut percent_incline = dr.percent_incline(map);
+        if percent_incline > 0.0 && fitness > 0.0 {
+            percent_incline /= fitness;
+        }
 
         let base = if constraints == PathConstraints::Bike {
             // We assume every bike has a max_speed defined.
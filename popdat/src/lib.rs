@@ -27,7 +27,7 @@ use rand_xorshift::XorShiftRng;
 use abstutil::Timer;
 use geom::{Distance, Time};
 use map_model::{BuildingID, Map};
-use sim::Scenario;
+use sim::{ModeChoiceParams, Scenario};
 
 pub use self::distribute_people::distribute_population_to_homes;
 
@@ -88,8 +88,12 @@ pub enum Activity {
 /// Any arbitrarily chosen parameters needed should be put here, so they can be controlled from the
 /// UI or tuned for different cities.
 pub struct Config {
+    /// Trips between buildings shorter than this always walk, without considering other modes.
     pub walk_for_distances_shorter_than: Distance,
+    /// Walking and biking aren't considered for trips between buildings longer than this.
     pub walk_or_bike_for_distances_shorter_than: Distance,
+    /// How people choose to walk, bike, use transit, or drive for every other trip
+    pub mode_choice: ModeChoiceParams,
 }
 
 impl Config {
@@ -97,6 +101,7 @@ impl Config {
         Config {
             walk_for_distances_shorter_than: Distance::miles(0.5),
             walk_or_bike_for_distances_shorter_than: Distance::miles(3.0),
+            mode_choice: ModeChoiceParams::default(),
         }
     }
 }
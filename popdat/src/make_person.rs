@@ -1,7 +1,6 @@
 use std::collections::HashMap;
 
 use rand::seq::SliceRandom;
-use rand::Rng;
 use rand_xorshift::XorShiftRng;
 
 use abstutil::{Parallelism, Timer};
@@ -222,67 +221,38 @@ fn pick_mode(
     rng: &mut XorShiftRng,
     config: &Config,
 ) -> TripMode {
-    let (b1, b2) = match (from, to) {
-        (TripEndpoint::Bldg(b1), TripEndpoint::Bldg(b2)) => (b1, b2),
-        // TODO Always drive when going on or off-map?
-        _ => {
-            return TripMode::Drive;
-        }
-    };
-
-    // Decide mode based on walking distance
-    let distance = if let Some(path) =
-        PathRequest::between_buildings(map, b1, b2, PathConstraints::Pedestrian)
-            .and_then(|req| map.pathfind(req).ok())
-    {
-        path.total_length()
-    } else {
-        // If the buildings aren't connected, there was probably a bug importing the map. Just
-        // fallback to driving. If the trip can't be started in the simulation, it'll show up as
-        // cancelled with more details about the problem.
-        return TripMode::Drive;
-    };
-
     // TODO If either endpoint is in an access-restricted zone (like a living street), then
     // probably don't drive there. Actually, it depends on the specific tagging; access=no in the
     // US usually means a gated community.
 
-    // TODO Make this probabilistic
-    // for example probability of walking currently has massive differences
-    // at thresholds, it would be nicer to change this gradually
-    // TODO - do not select based on distance but select one that is fastest/best in the
-    // given situation excellent bus connection / plenty of parking /
-    // cycleways / suitable rail connection all strongly influence
-    // selected mode of transport, distance is not the sole influence
-    // in some cities there may case where driving is only possible method
-    // to get somewhere, even at a short distance
-
-    // Always walk for really short trips
-    if distance < config.walk_for_distances_shorter_than {
-        return TripMode::Walk;
-    }
-
-    // Sometimes bike or walk for moderate trips
-    if distance < config.walk_or_bike_for_distances_shorter_than {
-        // TODO We could move all of these params to Config, but I'm not sure if the overall flow
-        // of logic in this functon is what we want yet.
-        if rng.gen_bool(0.15) {
-            return TripMode::Bike;
-        }
-        if rng.gen_bool(0.05) {
-            return TripMode::Walk;
+    // Calculating skims for every mode is expensive, so first rule some out based on the walking
+    // distance.
+    let mut modes = vec![
+        TripMode::Walk,
+        TripMode::Bike,
+        TripMode::Transit,
+        TripMode::Drive,
+    ];
+    if let (TripEndpoint::Bldg(b1), TripEndpoint::Bldg(b2)) = (from, to) {
+        if let Some(path) = PathRequest::between_buildings(map, b1, b2, PathConstraints::Pedestrian)
+            .and_then(|req| map.pathfind(req).ok())
+        {
+            let distance = path.total_length();
+            // Always walk for really short trips
+            if distance < config.walk_for_distances_shorter_than {
+                return TripMode::Walk;
+            }
+            if distance >= config.walk_or_bike_for_distances_shorter_than {
+                modes.retain(|m| *m != TripMode::Walk && *m != TripMode::Bike);
+            }
         }
     }
 
-    // For longer trips, maybe bike for dedicated cyclists
-    if rng.gen_bool(0.005) {
-        return TripMode::Bike;
-    }
-    // Try transit if available, or fallback to walking
-    if rng.gen_bool(0.3) {
-        return TripMode::Transit;
-    }
-
-    // Most of the time, just drive
-    TripMode::Drive
+    // If no mode can make the trip, there was probably a bug importing the map. Just fallback to
+    // driving. If the trip can't be started in the simulation, it'll show up as cancelled with
+    // more details about the problem.
+    config
+        .mode_choice
+        .pick_mode_among(&modes, from, to, map, rng)
+        .unwrap_or(TripMode::Drive)
 }
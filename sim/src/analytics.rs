@@ -3,14 +3,17 @@ use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use serde::{Deserialize, Serialize};
 
 use abstutil::Counter;
-use geom::{Duration, Time};
+use geom::{Distance, Duration, Speed, Time};
 use map_model::{
-    BusRouteID, BusStopID, CompressedMovementID, IntersectionID, LaneID, Map, MovementID,
-    ParkingLotID, Path, PathRequest, RoadID, Traversable, TurnType,
+    BuildingID, BusRouteID, BusStopID, CompressedMovementID, DirectedRoadID, IntersectionID,
+    LaneID, Map, MovementID, ParkingLotID, Path, PathRequest, RoadID, TravelTimeProfile,
+    Traversable, TurnType, MAX_BIKE_SPEED,
 };
 
+use crate::emissions::idle_time;
 use crate::{
     AgentID, AgentType, AlertLocation, CarID, Event, ParkingSpot, TripID, TripMode, TripPhaseType,
+    VehicleActivity, VehicleType,
 };
 
 /// As a simulation runs, different pieces emit Events. The Analytics object listens to these,
@@ -34,11 +37,18 @@ pub struct Analytics {
     /// in time.
     pub demand: BTreeMap<MovementID, usize>,
 
+    /// When each bus or train was scheduled to start its route
+    pub bus_starts: Vec<(Time, CarID, BusRouteID)>,
     // TODO Reconsider this one
     pub bus_arrivals: Vec<(Time, CarID, BusRouteID, BusStopID)>,
+    pub bus_departures: Vec<(Time, CarID, BusRouteID, BusStopID)>,
     /// For each passenger boarding, how long did they wait at the stop?
     pub passengers_boarding: BTreeMap<BusStopID, Vec<(Time, BusRouteID, Duration)>>,
     pub passengers_alighting: BTreeMap<BusStopID, Vec<(Time, BusRouteID)>>,
+    /// Each time somebody couldn't board a full vehicle
+    pub denied_boardings: BTreeMap<BusStopID, Vec<(Time, BusRouteID)>>,
+    /// How many passengers were on board each vehicle as it left the stop
+    pub transit_loads: BTreeMap<BusStopID, Vec<(Time, BusRouteID, CarID, usize)>>,
 
     pub started_trips: BTreeMap<TripID, Time>,
     /// Finish time, ID, mode, trip duration if successful (or None if cancelled)
@@ -57,6 +67,40 @@ pub struct Analytics {
     /// Per parking lane or lot, when does a spot become filled (true) or free (false)
     pub parking_lane_changes: BTreeMap<LaneID, Vec<(Time, bool)>>,
     pub parking_lot_changes: BTreeMap<ParkingLotID, Vec<(Time, bool)>>,
+    /// For each car that looked for parking, when it started, and how long and how far it cruised
+    /// before finding a spot
+    pub parking_searches: Vec<(Time, Option<TripID>, Duration, Distance)>,
+    /// When a car left a spot after staying past its time limit, and by how much
+    pub parking_tickets: Vec<(Time, ParkingSpot, Duration)>,
+    /// Per driving lane, when a delivery van stopped there, and whether it had to double-park
+    pub delivery_stops: BTreeMap<LaneID, Vec<(Time, bool)>>,
+
+    /// Only recorded when a car-following model is used. For each vehicle currently driving, its
+    /// speed at different times since it started. Speed changes linearly between these points.
+    /// Vehicles are forgotten once they stop driving, so consumers that want whole trips should
+    /// watch for `Event::CarSpeedProfile` instead.
+    pub speed_profiles: BTreeMap<CarID, Vec<(Time, Speed)>>,
+
+    /// How far each type of vehicle has traveled and idled, used to estimate emissions. See
+    /// `EmissionFactors`.
+    pub road_vehicle_activity: BTreeMap<RoadID, BTreeMap<VehicleType, VehicleActivity>>,
+    pub intersection_vehicle_activity: BTreeMap<VehicleType, VehicleActivity>,
+    pub trip_vehicle_activity: BTreeMap<TripID, BTreeMap<VehicleType, VehicleActivity>>,
+
+    /// Per charging station, when an electric vehicle started charging and how long it waited
+    /// for a free charger
+    pub charging_waits: BTreeMap<BuildingID, Vec<(Time, Duration)>>,
+
+    /// Only for cars. When a car entered each road, and how long it took to reach the end and
+    /// start turning, including any time spent waiting at the intersection. Cars that park or
+    /// vanish partway along a road aren't counted.
+    pub road_travel_times: BTreeMap<DirectedRoadID, Vec<(Time, Duration)>>,
+    /// Which lane each car is currently crossing, and when it entered
+    cars_on_lanes: BTreeMap<CarID, (LaneID, Time)>,
+    /// Which lane or turn each vehicle is currently crossing, when it entered, and for which trip
+    vehicles_on_traversables: BTreeMap<CarID, (Traversable, Time, Option<TripID>)>,
+    /// When each car currently looking for parking started
+    cars_cruising: BTreeMap<CarID, Time>,
 
     pub(crate) alerts: Vec<(Time, AlertLocation, String)>,
 
@@ -81,9 +125,13 @@ impl Analytics {
             intersection_thruput: TimeSeriesCount::new(),
             traffic_signal_thruput: TimeSeriesCount::new(),
             demand: BTreeMap::new(),
+            bus_starts: Vec::new(),
             bus_arrivals: Vec::new(),
+            bus_departures: Vec::new(),
             passengers_boarding: BTreeMap::new(),
             passengers_alighting: BTreeMap::new(),
+            denied_boardings: BTreeMap::new(),
+            transit_loads: BTreeMap::new(),
             started_trips: BTreeMap::new(),
             finished_trips: Vec::new(),
             problems_per_trip: BTreeMap::new(),
@@ -91,6 +139,18 @@ impl Analytics {
             intersection_delays: BTreeMap::new(),
             parking_lane_changes: BTreeMap::new(),
             parking_lot_changes: BTreeMap::new(),
+            parking_searches: Vec::new(),
+            parking_tickets: Vec::new(),
+            delivery_stops: BTreeMap::new(),
+            speed_profiles: BTreeMap::new(),
+            road_vehicle_activity: BTreeMap::new(),
+            intersection_vehicle_activity: BTreeMap::new(),
+            trip_vehicle_activity: BTreeMap::new(),
+            charging_waits: BTreeMap::new(),
+            road_travel_times: BTreeMap::new(),
+            cars_on_lanes: BTreeMap::new(),
+            vehicles_on_traversables: BTreeMap::new(),
+            cars_cruising: BTreeMap::new(),
             alerts: Vec::new(),
             record_anything,
         }
@@ -155,9 +215,15 @@ impl Analytics {
         }
 
         // Bus arrivals
+        if let Event::BusStarted(bus, route) = ev {
+            self.bus_starts.push((time, bus, route));
+        }
         if let Event::BusArrivedAtStop(bus, route, stop) = ev {
             self.bus_arrivals.push((time, bus, route, stop));
         }
+        if let Event::BusDepartedFromStop(bus, route, stop) = ev {
+            self.bus_departures.push((time, bus, route, stop));
+        }
 
         // Passengers boarding/alighting
         if let Event::PassengerBoardsTransit(_, _, route, stop, waiting) = ev {
@@ -172,6 +238,18 @@ impl Analytics {
                 .or_insert_with(Vec::new)
                 .push((time, route));
         }
+        if let Event::PassengerDeniedBoarding(_, _, route, stop) = ev {
+            self.denied_boardings
+                .entry(stop)
+                .or_insert_with(Vec::new)
+                .push((time, route));
+        }
+        if let Event::TransitLoadDeparting(bus, route, stop, load) = ev {
+            self.transit_loads
+                .entry(stop)
+                .or_insert_with(Vec::new)
+                .push((time, route, bus, load));
+        }
 
         // Started trips
         if let Event::TripPhaseStarting(id, _, _, _) = ev {
@@ -193,6 +271,62 @@ impl Analytics {
             self.finished_trips.push((time, id, mode, None));
         }
 
+        // Vehicle activity. A lane or turn is counted once a vehicle leaves it.
+        if let Event::AgentEntersTraversable(AgentID::Car(car), trip, on, _) = ev {
+            if let Some((prev, entered, prev_trip)) =
+                self.vehicles_on_traversables.insert(car, (on, time, trip))
+            {
+                self.record_vehicle_activity(car, prev, time - entered, prev_trip, map);
+            }
+        }
+        // The vehicle stopped somewhere along its last lane, so that isn't counted
+        match ev {
+            Event::CarReachedParkingSpot(car, _)
+            | Event::BikeStoppedAtSidewalk(car, _)
+            | Event::PersonLeavesMap(_, Some(AgentID::Car(car)), _) => {
+                self.vehicles_on_traversables.remove(&car);
+                self.cars_on_lanes.remove(&car);
+            }
+            Event::TripCancelled(trip, _) => {
+                // The car might've been deleted by a live edit, or warped to the end of its trip
+                let cars: Vec<CarID> = self
+                    .vehicles_on_traversables
+                    .iter()
+                    .filter(|(_, (_, _, t))| *t == Some(trip))
+                    .map(|(car, _)| *car)
+                    .collect();
+                for car in cars {
+                    self.vehicles_on_traversables.remove(&car);
+                    self.cars_on_lanes.remove(&car);
+                }
+            }
+            _ => {}
+        }
+
+        // Experienced travel times
+        match ev {
+            Event::AgentEntersTraversable(AgentID::Car(car), _, on, _)
+                if car.vehicle_type == VehicleType::Car =>
+            {
+                match on {
+                    Traversable::Lane(l) => {
+                        self.cars_on_lanes.insert(car, (l, time));
+                    }
+                    Traversable::Turn(t) => {
+                        if let Some((l, entered)) = self.cars_on_lanes.remove(&car) {
+                            if l == t.src {
+                                self.road_travel_times
+                                    .entry(map.get_l(l).get_directed_parent())
+                                    .or_insert_with(Vec::new)
+                                    .push((entered, time - entered));
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+
         // Intersection delay
         if let Event::IntersectionDelayMeasured(trip_id, turn_id, agent, delay) = ev {
             let threshold = match agent {
@@ -235,6 +369,24 @@ impl Analytics {
                     .push((time, true));
             }
         }
+        if let Event::CarStartedCruising(car) = ev {
+            self.cars_cruising.insert(car, time);
+        }
+        if let Event::CarFinishedCruising(car, trip, dist) = ev {
+            if let Some(started) = self.cars_cruising.remove(&car) {
+                self.parking_searches
+                    .push((started, trip, time - started, dist));
+            }
+        }
+        if let Event::ParkingTicket(_, spot, overstayed) = ev {
+            self.parking_tickets.push((time, spot, overstayed));
+        }
+        if let Event::DeliveryStop(_, l, double_parked) = ev {
+            self.delivery_stops
+                .entry(l)
+                .or_insert_with(Vec::new)
+                .push((time, double_parked));
+        }
         if let Event::CarLeftParkingSpot(_, spot) = ev {
             if let ParkingSpot::Onstreet(l, _) = spot {
                 self.parking_lane_changes
@@ -287,10 +439,77 @@ impl Analytics {
                     .or_insert_with(Vec::new)
                     .push((time, problem));
             }
+            Event::CarSpeedProfile(car, points) => {
+                let profile = self.speed_profiles.entry(car).or_insert_with(Vec::new);
+                if let (Some((last_time, last_speed)), Some((first_time, _))) =
+                    (profile.last().cloned(), points.first())
+                {
+                    // The vehicle was stuck in a queue in between, and queues stop vehicles
+                    // immediately.
+                    if last_time < *first_time && last_speed > Speed::ZERO {
+                        profile.push((last_time, Speed::ZERO));
+                    }
+                }
+                profile.extend(points);
+            }
+            Event::VehicleDespawned(car) => {
+                self.speed_profiles.remove(&car);
+            }
+            Event::EVStartedCharging(_, b, waited) => {
+                self.charging_waits
+                    .entry(b)
+                    .or_insert_with(Vec::new)
+                    .push((time, waited));
+            }
             _ => {}
         }
     }
 
+    fn record_vehicle_activity(
+        &mut self,
+        car: CarID,
+        on: Traversable,
+        elapsed: Duration,
+        trip: Option<TripID>,
+        map: &Map,
+    ) {
+        let vehicle_type = car.vehicle_type;
+        let dist = on.get_polyline(map).length();
+        let max_speed = if vehicle_type == VehicleType::Bike {
+            Some(MAX_BIKE_SPEED)
+        } else {
+            None
+        };
+        let free_flow_speed = on.max_speed_along(max_speed, vehicle_type.to_constraints(), map);
+        let idling = idle_time(elapsed, dist, free_flow_speed);
+
+        let mut activities = vec![match on {
+            Traversable::Lane(l) => self
+                .road_vehicle_activity
+                .entry(map.get_l(l).parent)
+                .or_insert_with(BTreeMap::new)
+                .entry(vehicle_type)
+                .or_insert_with(VehicleActivity::default),
+            Traversable::Turn(_) => self
+                .intersection_vehicle_activity
+                .entry(vehicle_type)
+                .or_insert_with(VehicleActivity::default),
+        }];
+        if let Some(trip) = trip {
+            activities.push(
+                self.trip_vehicle_activity
+                    .entry(trip)
+                    .or_insert_with(BTreeMap::new)
+                    .entry(vehicle_type)
+                    .or_insert_with(VehicleActivity::default),
+            );
+        }
+        for activity in activities {
+            activity.distance += dist;
+            activity.idling += idling;
+        }
+    }
+
     pub fn record_demand(&mut self, path: &Path, map: &Map) {
         for step in path.get_steps() {
             if let Traversable::Turn(t) = step.as_traversable() {
@@ -370,6 +589,27 @@ impl Analytics {
         phases
     }
 
+    /// How long each phase of one trip took, in order. Trips combining modes, like park and ride,
+    /// report each leg separately. Unlike get_trip_phases, no paths are calculated. A phase still
+    /// in progress isn't included.
+    pub fn get_trip_phase_durations(&self, trip: TripID) -> Vec<(TripPhaseType, Duration)> {
+        let mut results = Vec::new();
+        let mut current: Option<(Time, TripPhaseType)> = None;
+        for (t, id, _, phase_type) in &self.trip_log {
+            if *id != trip {
+                continue;
+            }
+            if let Some((start, last)) = current.take() {
+                results.push((last, *t - start));
+            }
+            if *phase_type == TripPhaseType::Finished || *phase_type == TripPhaseType::Cancelled {
+                break;
+            }
+            current = Some((*t, *phase_type));
+        }
+        results
+    }
+
     pub fn get_all_trip_phases(&self) -> BTreeMap<TripID, Vec<TripPhase>> {
         let mut trips = BTreeMap::new();
         for (t, id, maybe_req, phase_type) in &self.trip_log {
@@ -465,6 +705,36 @@ impl Analytics {
         }
     }
 
+    /// How many vehicles are somewhere on the map, partway through a lane or turn that hasn't been
+    /// counted yet.
+    pub fn num_vehicles_in_progress(&self) -> usize {
+        self.vehicles_on_traversables
+            .keys()
+            .chain(self.cars_on_lanes.keys())
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+
+    /// The mean time cars took to cross each road, grouped by the hour they entered it. Use this
+    /// to route later simulations around congestion.
+    pub fn travel_time_profile(&self) -> TravelTimeProfile {
+        let mut profile = TravelTimeProfile::default();
+        for (dr, list) in &self.road_travel_times {
+            let mut per_hour: BTreeMap<usize, (Duration, usize)> = BTreeMap::new();
+            for (t, dt) in list {
+                let entry = per_hour
+                    .entry(t.get_hours() % 24)
+                    .or_insert((Duration::ZERO, 0));
+                entry.0 += *dt;
+                entry.1 += 1;
+            }
+            for (hour, (total, count)) in per_hour {
+                profile.set(*dr, hour, total / (count as f64));
+            }
+        }
+        profile
+    }
+
     fn parking_spot_availability(
         now: Time,
         changes: &Vec<(Time, bool)>,
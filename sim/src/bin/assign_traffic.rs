@@ -0,0 +1,57 @@
+//! Runs a scenario day after day, feeding experienced travel times back into routing, until drivers
+//! reach equilibrium. Prints the relative gap of each day, and saves the final results (including
+//! the learned routing params and the last day's Analytics).
+//!
+//! > cargo run --release --bin assign_traffic -- path/to/scenario.bin --max_iterations=20 \
+//!   --relative_gap=0.005 --output=assignment.bin
+
+use anyhow::Result;
+
+use abstutil::{CmdArgs, Timer};
+use map_model::Map;
+use sim::{Scenario, SimFlags, SimOptions, TrafficAssignmentOptions};
+
+fn main() -> Result<()> {
+    let mut args = CmdArgs::new();
+    let scenario_path = args.required_free();
+    let rng_seed = args
+        .optional_parse("--rng_seed", |s| s.parse())
+        .unwrap_or(SimFlags::RNG_SEED);
+    let mut opts = TrafficAssignmentOptions::default();
+    if let Some(n) = args.optional_parse("--max_iterations", |s| s.parse::<usize>()) {
+        opts.max_iterations = n;
+    }
+    if let Some(gap) = args.optional_parse("--relative_gap", |s| s.parse::<f64>()) {
+        opts.relative_gap = gap;
+    }
+    let output = args
+        .optional("--output")
+        .unwrap_or_else(|| "traffic_assignment.bin".to_string());
+    let sim_opts = SimOptions::from_args(&mut args, rng_seed);
+    args.done();
+
+    let mut timer = Timer::new("assign traffic");
+    let scenario: Scenario = abstio::read_object(scenario_path, &mut timer)?;
+    let mut map = Map::load_synchronously(scenario.map_name.path(), &mut timer);
+
+    let result = sim::assign_traffic(&mut map, &scenario, &sim_opts, rng_seed, &opts, &mut timer)?;
+    println!("day, relative gap, driving trips, chosen routes, best routes");
+    for iter in &result.iterations {
+        println!(
+            "{}, {:.4}, {}, {}, {}",
+            iter.iteration, iter.relative_gap, iter.trips, iter.chosen_routes, iter.best_routes
+        );
+    }
+    if result.converged {
+        println!("Converged after {} days", result.iterations.len());
+    } else {
+        println!(
+            "Didn't converge to a relative gap of {} after {} days",
+            opts.relative_gap,
+            result.iterations.len()
+        );
+    }
+    abstio::write_binary(output.clone(), &result);
+    println!("Wrote {}", output);
+    Ok(())
+}
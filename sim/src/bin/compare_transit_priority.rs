@@ -0,0 +1,70 @@
+//! Simulates a scenario with and without transit signal priority, and compares the delay of buses
+//! and trains at the signals against everybody else's.
+//!
+//! > cargo run --release --bin compare_transit_priority -- path/to/scenario.bin \
+//!   --signals=12,15,20 --max_extension=10 --max_truncation=10
+//!
+//! Without `--signals`, every traffic signal on the map is used.
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+
+use abstutil::{CmdArgs, Timer};
+use geom::Duration;
+use map_model::{IntersectionID, Map, TransitPriority};
+use sim::{Scenario, SignalDelay, SimFlags, SimOptions};
+
+fn main() -> Result<()> {
+    let mut args = CmdArgs::new();
+    let scenario_path = args.required_free();
+    let mut signals = BTreeSet::new();
+    if let Some(list) = args.optional("--signals") {
+        for i in list.split(',') {
+            signals.insert(IntersectionID(i.parse::<usize>()?));
+        }
+    }
+    let mut tsp = TransitPriority::default();
+    if let Some(dt) = args.optional_parse("--max_extension", |s| s.parse::<f64>()) {
+        tsp.max_extension = Duration::seconds(dt);
+    }
+    if let Some(dt) = args.optional_parse("--max_truncation", |s| s.parse::<f64>()) {
+        tsp.max_truncation = Duration::seconds(dt);
+    }
+    let rng_seed = args
+        .optional_parse("--rng_seed", |s| s.parse())
+        .unwrap_or(SimFlags::RNG_SEED);
+    let sim_opts = SimOptions::from_args(&mut args, rng_seed);
+    args.done();
+
+    let mut timer = Timer::new("compare transit signal priority");
+    let scenario: Scenario = abstio::read_object(scenario_path, &mut timer)?;
+    let mut map = Map::load_synchronously(scenario.map_name.path(), &mut timer);
+    if signals.is_empty() {
+        signals = map
+            .all_intersections()
+            .iter()
+            .filter(|i| i.is_traffic_signal())
+            .map(|i| i.id)
+            .collect();
+    }
+
+    let result = sim::compare_transit_priority(
+        &mut map, &scenario, &sim_opts, rng_seed, &signals, tsp, &mut timer,
+    )?;
+    println!("transit priority, transit delay, transit crossings, other delay, other crossings");
+    print_row("off", &result.without_priority);
+    print_row("on", &result.with_priority);
+    Ok(())
+}
+
+fn print_row(label: &str, delay: &SignalDelay) {
+    println!(
+        "{}, {}, {}, {}, {}",
+        label,
+        delay.transit_delay,
+        delay.transit_crossings,
+        delay.other_delay,
+        delay.other_crossings
+    );
+}
@@ -0,0 +1,63 @@
+//! Exports the transit routes, stops, and schedules of a map as a GTFS feed, so that a proposed
+//! service change can be loaded into other planning tools.
+//!
+//! > cargo run --release --bin export_gtfs -- path/to/map.bin --edits=new_schedule \
+//!   --timezone=America/Los_Angeles --output=gtfs/
+//!
+//! With `--scenario=path/to/scenario.bin`, a full day is simulated first, and stop times come from
+//! when buses actually arrived at each stop. Otherwise, they're estimated without traffic.
+
+use anyhow::Result;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use abstutil::{CmdArgs, Timer};
+use geom::Time;
+use map_model::{Map, MapEdits};
+use sim::{Scenario, Sim, SimFlags, SimOptions};
+
+fn main() -> Result<()> {
+    let mut args = CmdArgs::new();
+    let map_path = args.required_free();
+    let edits_name = args.optional("--edits");
+    let scenario_path = args.optional("--scenario");
+    let timezone = args
+        .optional("--timezone")
+        .unwrap_or_else(|| "UTC".to_string());
+    let output = args
+        .optional("--output")
+        .unwrap_or_else(|| "gtfs".to_string());
+    let rng_seed = args
+        .optional_parse("--rng_seed", |s| s.parse())
+        .unwrap_or(SimFlags::RNG_SEED);
+    let sim_opts = SimOptions::from_args(&mut args, rng_seed);
+    args.done();
+
+    let mut timer = Timer::new("export GTFS");
+    let mut map = Map::load_synchronously(map_path, &mut timer);
+    if let Some(name) = edits_name {
+        let edits = MapEdits::load(&map, abstio::path_edits(map.get_name(), &name), &mut timer)?;
+        map.must_apply_edits(edits);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+    }
+
+    let analytics = if let Some(path) = scenario_path {
+        let scenario: Scenario = abstio::read_object(path, &mut timer)?;
+        let mut rng = XorShiftRng::seed_from_u64(rng_seed);
+        let mut sim = Sim::new(&map, sim_opts);
+        scenario.instantiate(&mut sim, &map, &mut rng, &mut timer);
+        sim.timed_step(
+            &map,
+            sim.get_end_of_day() - Time::START_OF_DAY,
+            &mut None,
+            &mut timer,
+        );
+        Some(sim.get_analytics().clone())
+    } else {
+        None
+    };
+
+    sim::export_gtfs(&map, analytics.as_ref(), &timezone, &output)?;
+    println!("Wrote {}", output);
+    Ok(())
+}
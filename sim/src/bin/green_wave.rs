@@ -0,0 +1,71 @@
+//! Coordinates the offsets of traffic signals along a corridor into a green wave, reports the
+//! bandwidth achieved, and saves the offsets as map edits named "green wave".
+//!
+//! > cargo run --release --bin green_wave -- path/to/map.bin --intersections=12,15,20,31
+//!
+//! With `--evaluate=path/to/scenario.bin --start=07:00:00 --end=09:00:00`, this also simulates
+//! the scenario with the original and the new offsets, and compares the delay at the signals.
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+
+use abstutil::{CmdArgs, Timer};
+use geom::Time;
+use map_model::{GreenWave, IntersectionID, Map};
+use sim::{Scenario, SimFlags, SimOptions};
+
+fn main() -> Result<()> {
+    let mut args = CmdArgs::new();
+    let map_path = args.required_free();
+    let mut intersections = Vec::new();
+    for i in args.required("--intersections").split(',') {
+        intersections.push(IntersectionID(i.parse::<usize>()?));
+    }
+    let evaluate = args.optional("--evaluate");
+    let window = if evaluate.is_some() {
+        Some((
+            Time::parse(&args.required("--start"))?,
+            Time::parse(&args.required("--end"))?,
+        ))
+    } else {
+        None
+    };
+    let rng_seed = args
+        .optional_parse("--rng_seed", |s| s.parse())
+        .unwrap_or(SimFlags::RNG_SEED);
+    let sim_opts = SimOptions::from_args(&mut args, rng_seed);
+    args.done();
+
+    let mut timer = Timer::new("make a green wave");
+    let mut map = Map::load_synchronously(map_path, &mut timer);
+    let wave = GreenWave::new(&map, intersections)?;
+    for line in wave.describe() {
+        println!("{}", line);
+    }
+
+    let mut edits = map.get_edits().clone();
+    edits.edits_name = "green wave".to_string();
+    edits.commands.extend(wave.edit_cmds(&map));
+    let path = abstio::path_edits(map.get_name(), &edits.edits_name);
+    abstio::write_json(path.clone(), &edits.to_permanent(&map));
+    println!("Wrote {}", path);
+
+    if let (Some(scenario_path), Some(window)) = (evaluate, window) {
+        let scenario: Scenario = abstio::read_object(scenario_path, &mut timer)?;
+        let signals: BTreeSet<IntersectionID> = wave.intersections.iter().cloned().collect();
+        let before =
+            sim::measure_signal_delay(&map, &scenario, &sim_opts, rng_seed, window, &signals);
+
+        map.must_apply_edits(edits);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+        let after =
+            sim::measure_signal_delay(&map, &scenario, &sim_opts, rng_seed, window, &signals);
+
+        println!(
+            "Total delay at these signals from {} to {}: {} before, {} after",
+            window.0, window.1, before, after
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,48 @@
+//! Tunes traffic signal timing to minimize delay over a window of the day, by repeatedly running a
+//! scenario. The best timing found is saved as map edits named "optimized signals".
+//!
+//! > cargo run --release --bin optimize_signals -- path/to/scenario.bin --start=07:00:00 \
+//!   --end=09:00:00
+
+use anyhow::Result;
+
+use abstutil::{CmdArgs, Timer};
+use geom::{Duration, Time};
+use map_model::{IntersectionID, Map};
+use sim::{OptimizeSignalsOptions, Scenario, SimFlags, SimOptions};
+
+fn main() -> Result<()> {
+    let mut args = CmdArgs::new();
+    let scenario_path = args.required_free();
+    let rng_seed = args
+        .optional_parse("--rng_seed", |s| s.parse())
+        .unwrap_or(SimFlags::RNG_SEED);
+    let mut opts = OptimizeSignalsOptions::new(
+        Time::parse(&args.required("--start"))?,
+        Time::parse(&args.required("--end"))?,
+    );
+    if let Some(rounds) = args.optional_parse("--rounds", |s| s.parse::<usize>()) {
+        opts.rounds = rounds;
+    }
+    if let Some(step) = args.optional_parse("--step", |s| s.parse::<f64>()) {
+        opts.step = Duration::seconds(step);
+    }
+    // A comma-separated list of intersection IDs
+    if let Some(list) = args.optional("--signals") {
+        for i in list.split(',') {
+            opts.signals.push(IntersectionID(i.parse::<usize>()?));
+        }
+    }
+    let sim_opts = SimOptions::from_args(&mut args, rng_seed);
+    args.done();
+
+    let mut timer = Timer::new("optimize traffic signals");
+    let scenario: Scenario = abstio::read_object(scenario_path, &mut timer)?;
+    let mut map = Map::load_synchronously(scenario.map_name.path(), &mut timer);
+
+    let edits = sim::optimize_signals(&mut map, &scenario, &sim_opts, rng_seed, &opts, &mut timer)?;
+    let path = abstio::path_edits(map.get_name(), &edits.edits_name);
+    abstio::write_json(path.clone(), &edits.to_permanent(&map));
+    println!("Wrote {}", path);
+    Ok(())
+}
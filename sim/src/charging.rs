@@ -0,0 +1,274 @@
+//! Electric vehicles and charging stations. Each electric vehicle's battery drains as it drives.
+//! When it's low, it parks near a charging station instead of its destination, and charges there
+//! while its owner is away. A vehicle without enough charge for its next trip is stranded, and the
+//! trip is cancelled.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Duration, Time};
+use map_model::{BuildingID, Map};
+
+use crate::{
+    Battery, CarID, Command, Event, Scheduler, SimOptions, Vehicle, VehicleSpec, VehicleType,
+};
+
+/// Buildings with this OSM amenity are charging stations.
+const CHARGING_STATION_AMENITY: &str = "charging_station";
+/// If OSM doesn't say how many vehicles a station can charge at once
+const DEFAULT_CHARGERS_PER_STATION: usize = 2;
+/// How fast every charger works, in kilowatts
+const CHARGER_POWER_KW: f64 = 11.0;
+/// Electric vehicles below this fraction of their capacity will look for a charger.
+const LOW_CHARGE: f64 = 0.3;
+/// How far people are willing to walk from a charging station to their destination
+const MAX_WALK_FROM_CHARGER: Distance = Distance::const_meters(800.0);
+
+/// Tracks the state of charge of every electric vehicle, and manages the chargers at each
+/// charging station.
+///
+/// Electric vehicles low on charge park near a charging station close to their destination,
+/// instead of the destination itself. Once parked, they wait for a free charger at that station,
+/// then charge until they're full or until their owner comes back to drive somewhere else. Vehicles
+/// don't occupy a particular spot while waiting or charging; the station just has a limited number
+/// of chargers.
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct ChargingSimState {
+    stations: BTreeMap<BuildingID, Station>,
+    evs: BTreeMap<CarID, ElectricVehicle>,
+    events: Vec<Event>,
+
+    electric_vehicles_pct: usize,
+    car_specs_seen: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Station {
+    chargers: usize,
+    /// When each vehicle started charging
+    charging: BTreeMap<CarID, Time>,
+    /// When each vehicle arrived, in order
+    waiting: VecDeque<(CarID, Time)>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct ElectricVehicle {
+    battery: Battery,
+    /// In kilowatt-hours
+    charge: f64,
+    /// Where the vehicle is waiting or charging
+    at_station: Option<BuildingID>,
+}
+
+/// The current state of one charging station.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChargingStationStatus {
+    pub chargers: usize,
+    pub charging: usize,
+    /// How many vehicles are waiting for a free charger
+    pub waiting: usize,
+}
+
+impl ChargingSimState {
+    pub fn new(map: &Map, opts: &SimOptions) -> ChargingSimState {
+        let mut stations = BTreeMap::new();
+        for b in map.all_buildings() {
+            for amenity in &b.amenities {
+                if amenity.amenity_type == CHARGING_STATION_AMENITY {
+                    let chargers = amenity
+                        .osm_tags
+                        .get("capacity")
+                        .and_then(|x| x.parse::<usize>().ok())
+                        .filter(|x| *x > 0)
+                        .unwrap_or(DEFAULT_CHARGERS_PER_STATION);
+                    stations.insert(
+                        b.id,
+                        Station {
+                            chargers,
+                            charging: BTreeMap::new(),
+                            waiting: VecDeque::new(),
+                        },
+                    );
+                }
+            }
+        }
+        ChargingSimState {
+            stations,
+            evs: BTreeMap::new(),
+            events: Vec::new(),
+            electric_vehicles_pct: opts.electric_vehicles_pct,
+            car_specs_seen: 0,
+        }
+    }
+
+    /// Turns some fraction of cars into electric vehicles, depending on `SimOptions`. The choice
+    /// and initial charge are spread out deterministically, so they don't depend on the RNG.
+    pub fn maybe_electrify(&mut self, spec: &mut VehicleSpec) {
+        if spec.vehicle_type != VehicleType::Car || spec.battery.is_some() {
+            return;
+        }
+        let n = self.car_specs_seen;
+        self.car_specs_seen += 1;
+        if (n + 1) * self.electric_vehicles_pct / 100 == n * self.electric_vehicles_pct / 100 {
+            return;
+        }
+        let mut battery = Battery::typical();
+        // Between 20% and 100%
+        battery.initial_charge = battery.capacity * ((20 + (n * 37) % 81) as f64) / 100.0;
+        spec.battery = Some(battery);
+    }
+
+    /// Start tracking a vehicle, if it's electric.
+    pub fn register_vehicle(&mut self, vehicle: &Vehicle) {
+        if let Some(battery) = vehicle.battery {
+            self.evs.insert(
+                vehicle.id,
+                ElectricVehicle {
+                    battery,
+                    charge: battery.initial_charge,
+                    at_station: None,
+                },
+            );
+        }
+    }
+
+    /// If this vehicle is electric and low on charge, returns a charging station near the
+    /// destination to park at instead.
+    pub fn detour_to_charge(&self, car: CarID, to: BuildingID, map: &Map) -> Option<BuildingID> {
+        let ev = self.evs.get(&car)?;
+        if ev.charge >= LOW_CHARGE * ev.battery.capacity || self.stations.contains_key(&to) {
+            return None;
+        }
+        let pt = map.get_b(to).polygon.center();
+        self.stations
+            .keys()
+            .map(|b| (*b, map.get_b(*b).polygon.center().dist_to(pt)))
+            .filter(|(_, dist)| *dist <= MAX_WALK_FROM_CHARGER)
+            .min_by_key(|(_, dist)| *dist)
+            .map(|(b, _)| b)
+    }
+
+    /// A vehicle finished driving somewhere and parked near a building.
+    pub fn car_parked(
+        &mut self,
+        now: Time,
+        car: CarID,
+        dist: Distance,
+        near: BuildingID,
+        scheduler: &mut Scheduler,
+    ) {
+        self.car_drove(car, dist);
+        if !self.evs.contains_key(&car) {
+            return;
+        }
+        if let Some(station) = self.stations.get_mut(&near) {
+            self.evs.get_mut(&car).unwrap().at_station = Some(near);
+            station.waiting.push_back((car, now));
+            self.start_charging(now, near, scheduler);
+        }
+    }
+
+    /// A vehicle finished driving, but didn't park anywhere.
+    pub fn car_drove(&mut self, car: CarID, dist: Distance) {
+        if let Some(ev) = self.evs.get_mut(&car) {
+            // Trips only start with enough charge to reach the destination, but cruising for
+            // parking can drive farther. Then the vehicle arrives empty and is stranded there.
+            ev.charge =
+                (ev.charge - ev.battery.consumption * dist.inner_meters() / 1000.0).max(0.0);
+        }
+    }
+
+    /// Does this vehicle have enough charge to drive this far? Always true for vehicles that
+    /// aren't electric.
+    pub fn has_range(&self, car: CarID, dist: Distance) -> bool {
+        match self.evs.get(&car) {
+            Some(ev) => ev.battery.consumption * dist.inner_meters() / 1000.0 <= ev.charge,
+            None => true,
+        }
+    }
+
+    /// Someone is about to drive a vehicle, so stop charging or waiting.
+    pub fn car_leaving(&mut self, now: Time, car: CarID, scheduler: &mut Scheduler) {
+        let b = match self.evs.get_mut(&car).and_then(|ev| ev.at_station.take()) {
+            Some(b) => b,
+            None => {
+                return;
+            }
+        };
+        let started = self.stations.get_mut(&b).unwrap().charging.remove(&car);
+        if let Some(started) = started {
+            scheduler.cancel(Command::FinishCharging(car));
+            let ev = self.evs.get_mut(&car).unwrap();
+            ev.charge = (ev.charge + CHARGER_POWER_KW * (now - started).inner_seconds() / 3600.0)
+                .min(ev.battery.capacity);
+            self.events.push(Event::EVFinishedCharging(car, b));
+            self.start_charging(now, b, scheduler);
+        } else {
+            self.stations
+                .get_mut(&b)
+                .unwrap()
+                .waiting
+                .retain(|(c, _)| *c != car);
+        }
+    }
+
+    pub fn finish_charging(&mut self, now: Time, car: CarID, scheduler: &mut Scheduler) {
+        let ev = self.evs.get_mut(&car).unwrap();
+        ev.charge = ev.battery.capacity;
+        let b = ev.at_station.unwrap();
+        self.stations
+            .get_mut(&b)
+            .unwrap()
+            .charging
+            .remove(&car)
+            .unwrap();
+        self.events.push(Event::EVFinishedCharging(car, b));
+        self.start_charging(now, b, scheduler);
+    }
+
+    // Plug in waiting vehicles, if there are free chargers.
+    fn start_charging(&mut self, now: Time, b: BuildingID, scheduler: &mut Scheduler) {
+        let station = self.stations.get_mut(&b).unwrap();
+        while station.charging.len() < station.chargers {
+            let (car, arrived) = match station.waiting.pop_front() {
+                Some(pair) => pair,
+                None => {
+                    break;
+                }
+            };
+            let ev = &self.evs[&car];
+            let hours = (ev.battery.capacity - ev.charge) / CHARGER_POWER_KW;
+            station.charging.insert(car, now);
+            scheduler.push(
+                now + Duration::seconds(hours * 3600.0),
+                Command::FinishCharging(car),
+            );
+            self.events
+                .push(Event::EVStartedCharging(car, b, now - arrived));
+        }
+    }
+
+    /// The fraction of the battery that's charged, if the vehicle is electric.
+    pub fn state_of_charge(&self, car: CarID) -> Option<f64> {
+        let ev = self.evs.get(&car)?;
+        Some(ev.charge / ev.battery.capacity)
+    }
+
+    pub fn station_status(&self, b: BuildingID) -> Option<ChargingStationStatus> {
+        let station = self.stations.get(&b)?;
+        Some(ChargingStationStatus {
+            chargers: station.chargers,
+            charging: station.charging.len(),
+            waiting: station.waiting.len(),
+        })
+    }
+
+    pub fn all_stations(&self) -> Vec<BuildingID> {
+        self.stations.keys().cloned().collect()
+    }
+
+    pub fn collect_events(&mut self) -> Vec<Event> {
+        std::mem::replace(&mut self.events, Vec::new())
+    }
+}
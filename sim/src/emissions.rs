@@ -0,0 +1,263 @@
+//! Estimates vehicle emissions and energy use. As the simulation runs, Analytics records how far
+//! each type of vehicle travels and how long it idles, per road and per trip, as vehicles finish
+//! crossing each lane and turn. An `EmissionFactors`
+//! table turns that activity into emissions, so different factors can be plugged in without
+//! re-running anything.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Duration, Speed};
+use map_model::RoadID;
+
+use crate::{Analytics, TripID, VehicleType};
+
+/// How far a vehicle traveled and how long it idled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct VehicleActivity {
+    /// The lanes where a vehicle starts and stops partway along aren't counted.
+    pub distance: Distance,
+    /// Any time spent crossing a lane or turn beyond how long it takes at the speed limit. This
+    /// includes waiting in a queue and waiting to turn at intersections, which is attributed to
+    /// the road leading to the intersection.
+    pub idling: Duration,
+}
+
+/// How much of the time spent crossing something was spent stopped, assuming the vehicle otherwise
+/// moves at the free-flow speed.
+pub(crate) fn idle_time(elapsed: Duration, length: Distance, free_flow_speed: Speed) -> Duration {
+    (elapsed - length / free_flow_speed).max(Duration::ZERO)
+}
+
+/// Amounts of different pollutants and energy.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Emissions {
+    /// Carbon dioxide, in grams
+    pub co2: f64,
+    /// Nitrogen oxides, in grams
+    pub nox: f64,
+    /// Particulate matter, in grams
+    pub pm: f64,
+    /// Energy used, from fuel or electricity, in megajoules
+    pub energy: f64,
+}
+
+impl Emissions {
+    pub fn new(co2: f64, nox: f64, pm: f64, energy: f64) -> Emissions {
+        Emissions {
+            co2,
+            nox,
+            pm,
+            energy,
+        }
+    }
+
+    fn add_scaled(&mut self, other: &Emissions, scale: f64) {
+        self.co2 += other.co2 * scale;
+        self.nox += other.nox * scale;
+        self.pm += other.pm * scale;
+        self.energy += other.energy * scale;
+    }
+
+    pub fn get(&self, pollutant: Pollutant) -> f64 {
+        match pollutant {
+            Pollutant::CarbonDioxide => self.co2,
+            Pollutant::NitrogenOxides => self.nox,
+            Pollutant::ParticulateMatter => self.pm,
+            Pollutant::Energy => self.energy,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Pollutant {
+    CarbonDioxide,
+    NitrogenOxides,
+    ParticulateMatter,
+    Energy,
+}
+
+impl Pollutant {
+    pub fn all() -> Vec<Pollutant> {
+        vec![
+            Pollutant::CarbonDioxide,
+            Pollutant::NitrogenOxides,
+            Pollutant::ParticulateMatter,
+            Pollutant::Energy,
+        ]
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            Pollutant::CarbonDioxide => "CO2",
+            Pollutant::NitrogenOxides => "NOx",
+            Pollutant::ParticulateMatter => "particulate matter",
+            Pollutant::Energy => "energy",
+        }
+    }
+
+    pub fn units(self) -> &'static str {
+        match self {
+            Pollutant::Energy => "MJ",
+            _ => "g",
+        }
+    }
+}
+
+/// How much each type of vehicle emits. Vehicle types missing from the table don't emit anything.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EmissionFactors {
+    /// Emitted per kilometer traveled
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub per_km: BTreeMap<VehicleType, Emissions>,
+    /// Emitted per hour spent idling
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub per_idle_hour: BTreeMap<VehicleType, Emissions>,
+}
+
+impl std::default::Default for EmissionFactors {
+    /// Rough averages for a gasoline car, a diesel delivery van, a diesel bus, and an electric
+    /// train. Only tailpipe emissions are counted, so the train just uses energy.
+    fn default() -> EmissionFactors {
+        let mut per_km = BTreeMap::new();
+        per_km.insert(VehicleType::Car, Emissions::new(180.0, 0.3, 0.02, 2.5));
+        per_km.insert(VehicleType::Van, Emissions::new(280.0, 1.0, 0.04, 3.8));
+        per_km.insert(VehicleType::Bus, Emissions::new(1300.0, 5.0, 0.1, 17.0));
+        per_km.insert(VehicleType::Train, Emissions::new(0.0, 0.0, 0.0, 20.0));
+
+        let mut per_idle_hour = BTreeMap::new();
+        per_idle_hour.insert(VehicleType::Car, Emissions::new(1400.0, 3.0, 0.1, 20.0));
+        per_idle_hour.insert(VehicleType::Van, Emissions::new(2200.0, 10.0, 0.3, 30.0));
+        per_idle_hour.insert(VehicleType::Bus, Emissions::new(5000.0, 40.0, 1.0, 70.0));
+        per_idle_hour.insert(VehicleType::Train, Emissions::new(0.0, 0.0, 0.0, 20.0));
+
+        EmissionFactors {
+            per_km,
+            per_idle_hour,
+        }
+    }
+}
+
+impl EmissionFactors {
+    fn apply(&self, vehicle_type: VehicleType, activity: &VehicleActivity, total: &mut Emissions) {
+        if let Some(factors) = self.per_km.get(&vehicle_type) {
+            total.add_scaled(factors, activity.distance.inner_meters() / 1000.0);
+        }
+        if let Some(factors) = self.per_idle_hour.get(&vehicle_type) {
+            total.add_scaled(factors, activity.idling.inner_seconds() / 3600.0);
+        }
+    }
+
+    /// Estimates emissions from everything recorded so far.
+    pub fn estimate(&self, analytics: &Analytics) -> EmissionsReport {
+        let mut report = EmissionsReport {
+            per_trip: BTreeMap::new(),
+            per_road: BTreeMap::new(),
+            total: Emissions::default(),
+        };
+        for (r, per_type) in &analytics.road_vehicle_activity {
+            let mut emissions = Emissions::default();
+            for (vehicle_type, activity) in per_type {
+                self.apply(*vehicle_type, activity, &mut emissions);
+                self.apply(*vehicle_type, activity, &mut report.total);
+            }
+            report.per_road.insert(*r, emissions);
+        }
+        // Intersections aren't part of any road, but still count towards the total
+        for (vehicle_type, activity) in &analytics.intersection_vehicle_activity {
+            self.apply(*vehicle_type, activity, &mut report.total);
+        }
+        for (trip, per_type) in &analytics.trip_vehicle_activity {
+            let mut emissions = Emissions::default();
+            for (vehicle_type, activity) in per_type {
+                self.apply(*vehicle_type, activity, &mut emissions);
+            }
+            report.per_trip.insert(*trip, emissions);
+        }
+        report
+    }
+}
+
+/// Estimated emissions since midnight.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EmissionsReport {
+    /// Only trips by vehicle are included. Public transit vehicles don't belong to any trip.
+    pub per_trip: BTreeMap<TripID, Emissions>,
+    pub per_road: BTreeMap<RoadID, Emissions>,
+    /// Everything emitted, including while crossing intersections and by public transit
+    pub total: Emissions,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn idling_beyond_free_flow() {
+        let speed = Speed::meters_per_second(10.0);
+        // Moving the whole time
+        assert_eq!(
+            idle_time(Duration::seconds(10.0), Distance::meters(100.0), speed),
+            Duration::ZERO
+        );
+        // Stuck in a queue for half a minute
+        assert_eq!(
+            idle_time(Duration::seconds(40.0), Distance::meters(100.0), speed),
+            Duration::seconds(30.0)
+        );
+        // Rounding can make somebody look faster than the speed limit
+        assert_eq!(
+            idle_time(Duration::seconds(9.0), Distance::meters(100.0), speed),
+            Duration::ZERO
+        );
+    }
+
+    #[test]
+    fn estimate_from_activity() {
+        let mut factors = EmissionFactors::default();
+        factors
+            .per_km
+            .insert(VehicleType::Car, Emissions::new(100.0, 1.0, 0.0, 2.0));
+        factors
+            .per_idle_hour
+            .insert(VehicleType::Car, Emissions::new(10.0, 0.0, 0.0, 0.0));
+
+        let activity = VehicleActivity {
+            distance: Distance::meters(500.0),
+            idling: Duration::seconds(360.0),
+        };
+        let mut analytics = Analytics::new(true);
+        analytics
+            .road_vehicle_activity
+            .entry(RoadID(0))
+            .or_insert_with(BTreeMap::new)
+            .insert(VehicleType::Car, activity);
+        analytics
+            .intersection_vehicle_activity
+            .insert(VehicleType::Car, activity);
+        // Bikes don't emit anything
+        analytics
+            .road_vehicle_activity
+            .entry(RoadID(1))
+            .or_insert_with(BTreeMap::new)
+            .insert(VehicleType::Bike, activity);
+
+        let report = factors.estimate(&analytics);
+        // 0.5km at 100g/km, plus 6 minutes at 10g/hour
+        assert_eq!(
+            report.per_road[&RoadID(0)],
+            Emissions::new(51.0, 0.5, 0.0, 1.0)
+        );
+        assert_eq!(report.per_road[&RoadID(1)], Emissions::default());
+        assert_eq!(report.total, Emissions::new(102.0, 1.0, 0.0, 2.0));
+        assert!(report.per_trip.is_empty());
+    }
+}
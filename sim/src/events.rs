@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-use geom::Duration;
+use geom::{Distance, Duration, Speed, Time};
 use map_model::{
     BuildingID, BusRouteID, BusStopID, IntersectionID, LaneID, Map, Path, PathRequest, Traversable,
     TurnID,
@@ -20,12 +20,29 @@ use crate::{AgentID, CarID, ParkingSpot, PedestrianID, PersonID, Problem, TripID
 pub enum Event {
     CarReachedParkingSpot(CarID, ParkingSpot),
     CarLeftParkingSpot(CarID, ParkingSpot),
+    /// A car couldn't park in the first free spot it picked, either because somebody else took it
+    /// first or because there wasn't one on the last lane of its path, so it started circling.
+    CarStartedCruising(CarID),
+    /// A car found a spot and is starting to park, after cruising this far.
+    CarFinishedCruising(CarID, Option<TripID>, Distance),
+    /// A car stayed parked past the spot's time limit by this much and got a ticket as it left.
+    ParkingTicket(CarID, ParkingSpot, Duration),
+    /// A delivery van stopped along this lane. True if there was no free loading zone, so it
+    /// double-parked.
+    DeliveryStop(CarID, LaneID, bool),
 
+    /// A bus or train is starting its route as scheduled, though it may have to wait for room to
+    /// appear.
+    BusStarted(CarID, BusRouteID),
     BusArrivedAtStop(CarID, BusRouteID, BusStopID),
     BusDepartedFromStop(CarID, BusRouteID, BusStopID),
     /// How long waiting at the stop?
     PassengerBoardsTransit(PersonID, CarID, BusRouteID, BusStopID, Duration),
     PassengerAlightsTransit(PersonID, CarID, BusRouteID, BusStopID),
+    /// The vehicle was full, so the passenger has to keep waiting
+    PassengerDeniedBoarding(PersonID, CarID, BusRouteID, BusStopID),
+    /// How many passengers are on board as the vehicle leaves a stop
+    TransitLoadDeparting(CarID, BusRouteID, BusStopID, usize),
 
     PersonEntersBuilding(PersonID, BuildingID),
     PersonLeavesBuilding(PersonID, BuildingID),
@@ -44,6 +61,17 @@ pub enum Event {
     AgentEntersTraversable(AgentID, Option<TripID>, Traversable, Option<usize>),
     /// TripID, TurnID (Where the delay was encountered), Time spent waiting at that turn
     IntersectionDelayMeasured(TripID, TurnID, AgentID, Duration),
+    /// Only emitted when a car-following model is used. A vehicle's speed at some moments while
+    /// crossing a lane or turn.
+    CarSpeedProfile(CarID, Vec<(Time, Speed)>),
+    /// Only emitted when a car-following model is used. A vehicle stopped driving, because it
+    /// parked, left the map, or its trip was cancelled.
+    VehicleDespawned(CarID),
+    /// An electric vehicle plugged in at a charging station, after waiting this long for a free
+    /// charger.
+    EVStartedCharging(CarID, BuildingID, Duration),
+    /// An electric vehicle unplugged, because it's full or because its owner is driving away.
+    EVFinishedCharging(CarID, BuildingID),
 
     TripFinished {
         trip: TripID,
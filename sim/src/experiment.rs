@@ -0,0 +1,54 @@
+//! Offline tools like the signal optimizer compare many runs of the same scenario, each with
+//! something about the map changed. These helpers run one of those simulations.
+
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+
+use abstutil::Timer;
+use geom::Time;
+use map_model::{ControlTrafficSignal, Map};
+
+use crate::{Analytics, Scenario, Sim, SimOptions};
+
+/// Simulates the scenario from midnight until `until`, or the end of the day if that's None.
+/// Every run with the same RNG seed faces the same demand.
+pub fn simulate_day(
+    map: &Map,
+    scenario: &Scenario,
+    sim_opts: &SimOptions,
+    rng_seed: u64,
+    until: Option<Time>,
+    timer: &mut Timer,
+) -> Analytics {
+    let mut rng = XorShiftRng::seed_from_u64(rng_seed);
+    let mut sim = Sim::new(map, sim_opts.clone());
+    scenario.instantiate(&mut sim, map, &mut rng, timer);
+    let until = until.unwrap_or_else(|| sim.get_end_of_day());
+    sim.timed_step(map, until - Time::START_OF_DAY, &mut None, timer);
+    sim.get_analytics().clone()
+}
+
+/// Like `simulate_day`, but with some traffic signals replaced first. The map's original signals
+/// are restored before returning.
+pub fn simulate_with_changes(
+    map: &mut Map,
+    signals: Vec<ControlTrafficSignal>,
+    scenario: &Scenario,
+    sim_opts: &SimOptions,
+    rng_seed: u64,
+    until: Option<Time>,
+    timer: &mut Timer,
+) -> Analytics {
+    let originals: Vec<ControlTrafficSignal> = signals
+        .iter()
+        .map(|ts| map.get_traffic_signal(ts.id).clone())
+        .collect();
+    for ts in signals {
+        map.incremental_edit_traffic_signal(ts);
+    }
+    let analytics = simulate_day(map, scenario, sim_opts, rng_seed, until, timer);
+    for ts in originals {
+        map.incremental_edit_traffic_signal(ts);
+    }
+    analytics
+}
@@ -0,0 +1,255 @@
+//! Exports the transit service of a map, including edits to route schedules, as a GTFS feed
+//! (https://developers.google.com/transit/gtfs). This lets a proposed service change be loaded
+//! into other planning tools.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Result;
+
+use geom::{Duration, Time};
+use map_model::{BusRoute, BusRouteID, BusStopID, Map, PathConstraints};
+
+use crate::transit::BASE_DWELL_TIME;
+use crate::{Analytics, CarID};
+
+/// Writes agency.txt, stops.txt, routes.txt, calendar.txt, trips.txt, and stop_times.txt into a
+/// directory. Every spawn time of a route becomes one trip.
+///
+/// If Analytics from a simulation are passed in, stop times come from when each bus actually
+/// arrived at and departed from each stop. Otherwise, they come from the schedule originally
+/// imported from GTFS, if the trip wasn't changed, or free-flow travel times. GTFS requires a
+/// timezone, like "America/Los_Angeles", but maps don't store one.
+pub fn export_gtfs(
+    map: &Map,
+    analytics: Option<&Analytics>,
+    timezone: &str,
+    dir: &str,
+) -> Result<()> {
+    let dir = Path::new(dir);
+    std::fs::create_dir_all(dir)?;
+
+    // BusStopIDs don't have a short, stable form, so just number them
+    let stop_ids: BTreeMap<BusStopID, usize> = map
+        .all_bus_stops()
+        .keys()
+        .enumerate()
+        .map(|(idx, id)| (*id, idx))
+        .collect();
+
+    let mut agency = String::from("agency_id,agency_name,agency_url,agency_timezone\n");
+    agency.push_str(&format!("1,A/B Street,https://abstreet.org,{}\n", timezone));
+
+    let mut stops = String::from("stop_id,stop_name,stop_lat,stop_lon\n");
+    for bs in map.all_bus_stops().values() {
+        let gps = bs.sidewalk_pos.pt(map).to_gps(map.get_gps_bounds());
+        stops.push_str(&format!(
+            "{},{},{},{}\n",
+            stop_ids[&bs.id],
+            csv_field(&bs.name),
+            gps.y(),
+            gps.x()
+        ));
+    }
+
+    let mut routes =
+        String::from("route_id,agency_id,route_short_name,route_long_name,route_type\n");
+    for br in map.all_bus_routes() {
+        routes.push_str(&format!(
+            "{},1,{},{},{}\n",
+            br.id.0,
+            csv_field(&br.short_name),
+            csv_field(&br.full_name),
+            // Trains cover light rail too
+            if br.route_type == PathConstraints::Train {
+                0
+            } else {
+                3
+            }
+        ));
+    }
+
+    // The simulation only models one typical day
+    let calendar = String::from(
+        "service_id,monday,tuesday,wednesday,thursday,friday,saturday,sunday,start_date,\
+         end_date\ndaily,1,1,1,1,1,1,1,20200101,20991231\n",
+    );
+
+    let observed = analytics
+        .map(|a| observed_runs(a, map))
+        .unwrap_or_else(BTreeMap::new);
+    let mut trips = String::from("route_id,service_id,trip_id\n");
+    let mut stop_times =
+        String::from("trip_id,arrival_time,departure_time,stop_id,stop_sequence\n");
+    for br in map.all_bus_routes() {
+        let estimates = free_flow_stop_times(br, map);
+        for (idx, spawn_time) in br.spawn_times.iter().enumerate() {
+            let scheduled = br.scheduled_trip(idx);
+            // Which of the buses starting at this time is this one?
+            let nth = br.spawn_times[..idx]
+                .iter()
+                .filter(|t| *t == spawn_time)
+                .count();
+            let trip_id = scheduled
+                .map(|trip| trip.gtfs_trip_id.clone())
+                .unwrap_or_else(|| format!("{}_{}", br.id.0, idx));
+            trips.push_str(&format!("{},daily,{}\n", br.id.0, csv_field(&trip_id)));
+
+            for (stop_idx, bs) in br.stops.iter().enumerate() {
+                let (arrival, departure) = observed
+                    .get(&(br.id, *spawn_time))
+                    .and_then(|runs| runs.get(nth))
+                    .and_then(|run| run[stop_idx])
+                    .or_else(|| scheduled.and_then(|trip| trip.stop_times.get(stop_idx).cloned()))
+                    .unwrap_or_else(|| {
+                        let (arrival, departure) = estimates[stop_idx];
+                        (*spawn_time + arrival, *spawn_time + departure)
+                    });
+                stop_times.push_str(&format!(
+                    "{},{},{},{},{}\n",
+                    csv_field(&trip_id),
+                    gtfs_time(arrival),
+                    gtfs_time(departure),
+                    stop_ids[bs],
+                    stop_idx + 1
+                ));
+            }
+        }
+    }
+
+    std::fs::write(dir.join("agency.txt"), agency)?;
+    std::fs::write(dir.join("stops.txt"), stops)?;
+    std::fs::write(dir.join("routes.txt"), routes)?;
+    std::fs::write(dir.join("calendar.txt"), calendar)?;
+    std::fs::write(dir.join("trips.txt"), trips)?;
+    std::fs::write(dir.join("stop_times.txt"), stop_times)?;
+    Ok(())
+}
+
+/// Every bus that ran a route, keyed by the route and when the bus was scheduled to start. Several
+/// buses may start at the same time; they're listed in the order they started. For each stop
+/// along the route, when the bus arrived and departed, if it got there.
+fn observed_runs(
+    analytics: &Analytics,
+    map: &Map,
+) -> BTreeMap<(BusRouteID, Time), Vec<Vec<Option<(Time, Time)>>>> {
+    let started: BTreeMap<CarID, Time> = analytics
+        .bus_starts
+        .iter()
+        .map(|(t, bus, _)| (*bus, *t))
+        .collect();
+    let mut arrivals: BTreeMap<(BusRouteID, CarID), Vec<(Time, BusStopID)>> = BTreeMap::new();
+    for (t, bus, route, stop) in &analytics.bus_arrivals {
+        arrivals
+            .entry((*route, *bus))
+            .or_insert_with(Vec::new)
+            .push((*t, *stop));
+    }
+    let mut departures: BTreeMap<(BusRouteID, CarID), Vec<(Time, BusStopID)>> = BTreeMap::new();
+    for (t, bus, route, stop) in &analytics.bus_departures {
+        departures
+            .entry((*route, *bus))
+            .or_insert_with(Vec::new)
+            .push((*t, *stop));
+    }
+
+    // CarIDs increase as buses start, so this visits simultaneous starts in order
+    let mut runs = BTreeMap::new();
+    for ((route, bus), list) in arrivals {
+        let spawn_time = match started.get(&bus) {
+            Some(t) => *t,
+            None => continue,
+        };
+        let stops = &map.get_br(route).stops;
+        let arrived = match_stops(&list, stops);
+        let departed = match_stops(
+            departures
+                .get(&(route, bus))
+                .map(|list| list.as_slice())
+                .unwrap_or(&[]),
+            stops,
+        );
+        let run = arrived
+            .into_iter()
+            .zip(departed)
+            .map(|(arrival, departure)| arrival.map(|t1| (t1, departure.unwrap_or(t1))))
+            .collect();
+        runs.entry((route, spawn_time))
+            .or_insert_with(Vec::new)
+            .push(run);
+    }
+    runs
+}
+
+/// Assigns events to a route's stops in order, since a route could visit the same stop twice.
+fn match_stops(events: &[(Time, BusStopID)], route_stops: &[BusStopID]) -> Vec<Option<Time>> {
+    let mut results = vec![None; route_stops.len()];
+    let mut next = 0;
+    for (t, stop) in events {
+        if let Some(offset) = route_stops[next..].iter().position(|x| x == stop) {
+            results[next + offset] = Some(*t);
+            next += offset + 1;
+        }
+    }
+    results
+}
+
+/// How long after spawning a bus should arrive at and depart from each stop, with no traffic.
+fn free_flow_stop_times(br: &BusRoute, map: &Map) -> Vec<(Duration, Duration)> {
+    let mut results = Vec::new();
+    let mut elapsed = Duration::ZERO;
+    // The first step leads to the first stop, then each step to the next stop. The last step to
+    // a border doesn't matter.
+    for req in br.all_steps(map).into_iter().take(br.stops.len()) {
+        if let Ok(path) = map.pathfind(req) {
+            elapsed += path.estimate_duration(map, br.route_type, None);
+        }
+        results.push((elapsed, elapsed + BASE_DWELL_TIME));
+        elapsed += BASE_DWELL_TIME;
+    }
+    results
+}
+
+/// GTFS times are HH:MM:SS, and can exceed 24 hours for service running past midnight.
+fn gtfs_time(t: Time) -> String {
+    let secs = t.inner_seconds().round() as usize;
+    format!(
+        "{:02}:{:02}:{:02}",
+        secs / 3600,
+        (secs % 3600) / 60,
+        secs % 60
+    )
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains(',') || value.contains('"') || value.contains('\n') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gtfs_time() {
+        let t = |secs: f64| gtfs_time(Time::START_OF_DAY + Duration::seconds(secs));
+        assert_eq!(t(0.0), "00:00:00");
+        assert_eq!(t(8.0 * 3600.0 + 5.0 * 60.0 + 9.4), "08:05:09");
+        assert_eq!(t(59.6), "00:01:00");
+        // Service running past midnight keeps counting hours
+        assert_eq!(t(24.0 * 3600.0), "24:00:00");
+        assert_eq!(t(25.0 * 3600.0 + 30.0 * 60.0), "25:30:00");
+    }
+
+    #[test]
+    fn test_csv_field() {
+        assert_eq!(csv_field("Route 7"), "Route 7");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("3rd Ave, Pine St"), "\"3rd Ave, Pine St\"");
+        assert_eq!(csv_field("the \"E\" line"), "\"the \"\"E\"\" line\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+}
@@ -32,39 +32,64 @@ pub use crate::render::{
 
 pub use self::analytics::{Analytics, Problem, TripPhase};
 pub(crate) use self::cap::CapSimState;
+pub(crate) use self::charging::ChargingSimState;
+pub use self::charging::ChargingStationStatus;
+pub use self::emissions::{
+    EmissionFactors, Emissions, EmissionsReport, Pollutant, VehicleActivity,
+};
 pub(crate) use self::events::Event;
 pub use self::events::{AlertLocation, TripPhaseType};
+pub use self::experiment::{simulate_day, simulate_with_changes};
+pub use self::gtfs::export_gtfs;
 pub use self::make::{
     fork_rng, BorderSpawnOverTime, ExternalPerson, ExternalTrip, ExternalTripEndpoint, IndividTrip,
-    MapBorders, PersonSpec, Scenario, ScenarioGenerator, ScenarioModifier, SimFlags, SpawnOverTime,
-    TripEndpoint, TripPurpose,
+    MapBorders, ModeChoiceParams, PersonSpec, Scenario, ScenarioGenerator, ScenarioModifier,
+    SimFlags, Skim, SpawnOverTime, TripEndpoint, TripPurpose,
 };
 pub(crate) use self::make::{StartTripArgs, TripSpec};
+pub use self::mechanics::CarFollowingModel;
 pub(crate) use self::mechanics::{
     DrivingSimState, IntersectionSimState, ParkingSim, ParkingSimState, WalkingSimState,
 };
+pub(crate) use self::micromobility::MicromobilitySimState;
+pub use self::micromobility::{DockStats, MicromobilityStats, SharedVehicleKind};
 pub(crate) use self::pandemic::PandemicModel;
 pub(crate) use self::recorder::TrafficRecorder;
 pub(crate) use self::router::{ActionAtEnd, Router};
 pub(crate) use self::scheduler::{Command, Scheduler};
+pub use self::signal_optimizer::{measure_signal_delay, optimize_signals, OptimizeSignalsOptions};
 pub use self::sim::{AgentProperties, AlertHandler, DelayCause, Sim, SimCallback, SimOptions};
+pub use self::traffic_assignment::{
+    assign_traffic, AssignmentIteration, TrafficAssignment, TrafficAssignmentOptions,
+};
 pub(crate) use self::transit::TransitSimState;
+pub use self::transit_priority::{
+    compare_transit_priority, SignalDelay, TransitPriorityComparison,
+};
 pub use self::trips::TripMode;
 pub use self::trips::{CommutersVehiclesCounts, Person, PersonState, TripInfo, TripResult};
 pub(crate) use self::trips::{TripLeg, TripManager};
 
 mod analytics;
 mod cap;
+mod charging;
+mod emissions;
 mod events;
+mod experiment;
+mod gtfs;
 mod make;
 mod mechanics;
+mod micromobility;
 mod pandemic;
 mod recorder;
 mod render;
 mod router;
 mod scheduler;
+mod signal_optimizer;
 mod sim;
+mod traffic_assignment;
 mod transit;
+mod transit_priority;
 mod trips;
 
 // http://pccsc.net/bicycle-parking-info/ says 68 inches, which is 1.73m
@@ -73,6 +98,8 @@ pub(crate) const MIN_CAR_LENGTH: Distance = Distance::const_meters(4.5);
 pub(crate) const MAX_CAR_LENGTH: Distance = Distance::const_meters(6.5);
 // Note this is more than MAX_CAR_LENGTH
 pub(crate) const BUS_LENGTH: Distance = Distance::const_meters(12.5);
+// Vans still have to fit in a normal onstreet parking spot
+pub(crate) const VAN_LENGTH: Distance = Distance::const_meters(6.5);
 pub(crate) const LIGHT_RAIL_LENGTH: Distance = Distance::const_meters(60.0);
 
 /// At all speeds (including at rest), cars must be at least this far apart, measured from front of
@@ -103,6 +130,7 @@ impl fmt::Display for CarID {
             VehicleType::Bus => write!(f, "Bus #{}", self.id),
             VehicleType::Train => write!(f, "Train #{}", self.id),
             VehicleType::Bike => write!(f, "Bike #{}", self.id),
+            VehicleType::Van => write!(f, "Van #{}", self.id),
         }
     }
 }
@@ -145,6 +173,7 @@ impl AgentID {
                 VehicleType::Bike => AgentType::Bike,
                 VehicleType::Bus => AgentType::Bus,
                 VehicleType::Train => AgentType::Train,
+                VehicleType::Van => AgentType::Car,
             },
             AgentID::Pedestrian(_) => AgentType::Pedestrian,
             AgentID::BusPassenger(_, _) => AgentType::TransitRider,
@@ -284,6 +313,8 @@ pub enum VehicleType {
     Bus,
     Train,
     Bike,
+    /// Makes deliveries to businesses
+    Van,
 }
 
 impl fmt::Display for VehicleType {
@@ -293,6 +324,7 @@ impl fmt::Display for VehicleType {
             VehicleType::Bus => write!(f, "bus"),
             VehicleType::Train => write!(f, "train"),
             VehicleType::Bike => write!(f, "bike"),
+            VehicleType::Van => write!(f, "delivery van"),
         }
     }
 }
@@ -304,6 +336,7 @@ impl VehicleType {
             VehicleType::Bus => PathConstraints::Bus,
             VehicleType::Train => PathConstraints::Train,
             VehicleType::Bike => PathConstraints::Bike,
+            VehicleType::Van => PathConstraints::Car,
         }
     }
 
@@ -313,6 +346,7 @@ impl VehicleType {
             VehicleType::Bus => true,
             VehicleType::Train => true,
             VehicleType::Bike => false,
+            VehicleType::Van => false,
         }
     }
 }
@@ -324,6 +358,8 @@ pub struct Vehicle {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    /// Only for electric vehicles
+    pub battery: Option<Battery>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -331,6 +367,30 @@ pub struct VehicleSpec {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    /// Only for electric vehicles
+    pub battery: Option<Battery>,
+}
+
+/// The battery of an electric vehicle.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Battery {
+    /// In kilowatt-hours
+    pub capacity: f64,
+    /// Kilowatt-hours used per kilometer driven
+    pub consumption: f64,
+    /// How much charge the vehicle starts the day with, in kilowatt-hours
+    pub initial_charge: f64,
+}
+
+impl Battery {
+    /// A mid-size electric car, fully charged
+    pub fn typical() -> Battery {
+        Battery {
+            capacity: 60.0,
+            consumption: 0.18,
+            initial_charge: 60.0,
+        }
+    }
 }
 
 impl VehicleSpec {
@@ -342,6 +402,7 @@ impl VehicleSpec {
             vehicle_type: self.vehicle_type,
             length: self.length,
             max_speed: self.max_speed,
+            battery: self.battery,
         }
     }
 }
@@ -366,12 +427,21 @@ pub struct ParkedCar {
 pub(crate) enum DrivingGoal {
     ParkNear(BuildingID),
     Border(IntersectionID, LaneID),
+    /// Only for cars. Park in this lot if there's room, otherwise as close to the stop as
+    /// possible. The building is where the trip started; only its private parking may be used
+    /// instead.
+    ParkInLot(ParkingLotID, BuildingID, BusStopID),
+    /// Only for bikes. Leave the bike next to this stop.
+    ParkNearStop(BusStopID),
+    /// Only for delivery vans. Stop near this building for a while, then leave the map through
+    /// this border.
+    Deliver(BuildingID, IntersectionID, LaneID),
 }
 
 impl DrivingGoal {
     pub fn goal_pos(&self, constraints: PathConstraints, map: &Map) -> Option<Position> {
         match self {
-            DrivingGoal::ParkNear(b) => match constraints {
+            DrivingGoal::ParkNear(b) | DrivingGoal::Deliver(b, _, _) => match constraints {
                 PathConstraints::Car => {
                     let driving_lane = map.find_driving_lane_near_building(*b);
                     let sidewalk_pos = map.get_b(*b).sidewalk_pos;
@@ -387,6 +457,14 @@ impl DrivingGoal {
                 }
             },
             DrivingGoal::Border(_, l) => Some(Position::end(*l, map)),
+            DrivingGoal::ParkInLot(pl, _, _) => match constraints {
+                PathConstraints::Car => Some(map.get_pl(*pl).driving_pos),
+                _ => unreachable!(),
+            },
+            DrivingGoal::ParkNearStop(bs) => match constraints {
+                PathConstraints::Bike => Some(map.get_bs(*bs).biking_connection(map)?.0),
+                _ => unreachable!(),
+            },
         }
     }
 
@@ -402,6 +480,13 @@ impl DrivingGoal {
             DrivingGoal::Border(i, last_lane) => {
                 Router::end_at_border(owner, path, map.get_l(*last_lane).length(), *i)
             }
+            DrivingGoal::ParkInLot(pl, b, bs) => Router::park_in_lot(owner, path, *pl, *b, *bs),
+            DrivingGoal::Deliver(b, i, exit) => Router::deliver(owner, path, *b, *i, *exit),
+            DrivingGoal::ParkNearStop(bs) => Router::bike_then_stop(
+                owner,
+                path,
+                SidewalkSpot::bike_rack_at_stop(*bs, map).unwrap(),
+            ),
         }
     }
 }
@@ -465,6 +550,14 @@ impl SidewalkSpot {
         })
     }
 
+    pub fn bike_rack_at_stop(stop: BusStopID, map: &Map) -> Option<SidewalkSpot> {
+        let (bike_pos, sidewalk_pos) = map.get_bs(stop).biking_connection(map)?;
+        Some(SidewalkSpot {
+            connection: SidewalkPOI::BikeRack(bike_pos),
+            sidewalk_pos,
+        })
+    }
+
     pub fn bus_stop(stop: BusStopID, map: &Map) -> SidewalkSpot {
         SidewalkSpot {
             sidewalk_pos: map.get_bs(stop).sidewalk_pos,
@@ -609,7 +702,8 @@ pub(crate) struct CreateCar {
     pub maybe_parked_car: Option<ParkedCar>,
     /// None for buses
     pub trip_and_person: Option<(TripID, PersonID)>,
-    pub maybe_route: Option<BusRouteID>,
+    /// For buses, the route and which of its spawn_times this vehicle is
+    pub maybe_route: Option<(BusRouteID, usize)>,
 }
 
 impl CreateCar {
@@ -0,0 +1,90 @@
+//! Generates freight demand: delivery vans visiting businesses over the course of a day.
+
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+
+use geom::{Duration, Time};
+use map_model::{AmenityType, Building, BuildingType, IntersectionID, Map, PathConstraints};
+
+use crate::{IndividTrip, PersonSpec, TripEndpoint, TripMode, TripPurpose};
+
+/// Creates one delivery van trip per delivery. Each van enters from a border, stops near one
+/// business, and leaves the same way. `pct` scales the typical daily demand.
+pub fn delivery_demand(map: &Map, pct: usize, rng: &mut XorShiftRng) -> Vec<PersonSpec> {
+    // Vans need to drive in and back out through the same border
+    let borders: Vec<IntersectionID> = map
+        .all_incoming_borders()
+        .into_iter()
+        .filter(|i| {
+            i.is_outgoing_border()
+                && i.some_outgoing_road(map)
+                    .map(|dr| !dr.lanes(PathConstraints::Car, map).is_empty())
+                    .unwrap_or(false)
+                && i.some_incoming_road(map)
+                    .map(|dr| !dr.lanes(PathConstraints::Car, map).is_empty())
+                    .unwrap_or(false)
+        })
+        .map(|i| i.id)
+        .collect();
+    if borders.is_empty() {
+        warn!("No borders for delivery vans to come and go through");
+        return Vec::new();
+    }
+
+    let mut people = Vec::new();
+    for b in map.all_buildings() {
+        let expected = daily_deliveries(b) * (pct as f64) / 100.0;
+        // Round randomly, so small businesses still get the occasional delivery
+        let mut num = expected.floor() as usize;
+        if rng.gen_bool(expected.fract()) {
+            num += 1;
+        }
+        for _ in 0..num {
+            // Deliveries happen during business hours, between 7am and 6pm
+            let depart = Time::START_OF_DAY
+                + Duration::hours(7)
+                + Duration::minutes(rng.gen_range(0..11 * 60));
+            let border = borders[rng.gen_range(0..borders.len())];
+            people.push(PersonSpec {
+                orig_id: None,
+                origin: TripEndpoint::Border(border),
+                trips: vec![IndividTrip::new(
+                    depart,
+                    TripPurpose::Delivery,
+                    TripEndpoint::Bldg(b.id),
+                    TripMode::Drive,
+                )],
+            });
+        }
+    }
+    people
+}
+
+/// Roughly how many deliveries a building receives on a typical weekday, based on the businesses
+/// inside.
+fn daily_deliveries(b: &Building) -> f64 {
+    let mut total = 0.0;
+    for amenity in &b.amenities {
+        total += match AmenityType::categorize(&amenity.amenity_type) {
+            Some(AmenityType::Supermarket) => 4.0,
+            Some(AmenityType::ConvenienceStore)
+            | Some(AmenityType::Food)
+            | Some(AmenityType::FastFood)
+            | Some(AmenityType::Cafe)
+            | Some(AmenityType::Bar) => 2.0,
+            Some(AmenityType::Shopping)
+            | Some(AmenityType::Medical)
+            | Some(AmenityType::PostOffice)
+            | Some(AmenityType::Hotel) => 1.0,
+            // Everything else still gets the occasional package
+            _ => 0.2,
+        };
+    }
+    // Offices and other businesses without any amenities mapped
+    if b.amenities.is_empty() {
+        if let BuildingType::Commercial(_) = b.bldg_type {
+            total += 0.5;
+        }
+    }
+    total
+}
@@ -12,7 +12,9 @@ use abstutil::Timer;
 use geom::{Duration, Time};
 use map_model::{IntersectionID, Map};
 
-use crate::{IndividTrip, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose};
+use crate::{
+    IndividTrip, ModeChoiceParams, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose,
+};
 
 // TODO This can be simplified dramatically.
 
@@ -38,6 +40,9 @@ pub struct SpawnOverTime {
     pub percent_driving: f64,
     pub percent_biking: f64,
     pub percent_use_transit: f64,
+    /// If present, ignore the percentages above and choose each agent's mode with this model.
+    #[serde(default)]
+    pub mode_choice: Option<ModeChoiceParams>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -104,6 +109,7 @@ impl ScenarioGenerator {
                 percent_driving: 0.5,
                 percent_biking: 0.5,
                 percent_use_transit: 0.5,
+                mode_choice: None,
             }],
             // If there are no sidewalks/driving lanes at a border, scenario instantiation will
             // just warn and skip them.
@@ -131,6 +137,7 @@ impl ScenarioGenerator {
                 percent_driving: 0.5,
                 percent_biking: 0.5,
                 percent_use_transit: 0.5,
+                mode_choice: None,
             });
         }
         s
@@ -152,26 +159,32 @@ impl SpawnOverTime {
         // Note that it's fine for agents to start/end at the same building. Later we might
         // want a better assignment of people per household, or workers per office building.
         let from_bldg = map.all_buildings().choose(rng).unwrap().id;
-        let mode = if rng.gen_bool(self.percent_driving) {
-            TripMode::Drive
+        let fixed_mode = if self.mode_choice.is_some() {
+            None
+        } else if rng.gen_bool(self.percent_driving) {
+            Some(TripMode::Drive)
         } else if rng.gen_bool(self.percent_biking) {
-            TripMode::Bike
+            Some(TripMode::Bike)
         } else if rng.gen_bool(self.percent_use_transit) {
-            TripMode::Transit
+            Some(TripMode::Transit)
         } else {
-            TripMode::Walk
+            Some(TripMode::Walk)
         };
+        let goal = self
+            .goal
+            .clone()
+            .unwrap_or_else(|| TripEndpoint::Bldg(map.all_buildings().choose(rng).unwrap().id));
+        let mode = fixed_mode.unwrap_or_else(|| {
+            self.mode_choice
+                .as_ref()
+                .unwrap()
+                .pick_mode(TripEndpoint::Bldg(from_bldg), goal, map, rng)
+                .unwrap_or(TripMode::Walk)
+        });
         scenario.people.push(PersonSpec {
             orig_id: None,
             origin: TripEndpoint::Bldg(from_bldg),
-            trips: vec![IndividTrip::new(
-                depart,
-                TripPurpose::Shopping,
-                self.goal.clone().unwrap_or_else(|| {
-                    TripEndpoint::Bldg(map.all_buildings().choose(rng).unwrap().id)
-                }),
-                mode,
-            )],
+            trips: vec![IndividTrip::new(depart, TripPurpose::Shopping, goal, mode)],
         });
     }
 }
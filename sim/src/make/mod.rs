@@ -7,15 +7,18 @@ use rand_xorshift::XorShiftRng;
 pub use self::external::{ExternalPerson, ExternalTrip, ExternalTripEndpoint, MapBorders};
 pub use self::generator::{BorderSpawnOverTime, ScenarioGenerator, SpawnOverTime};
 pub use self::load::SimFlags;
+pub use self::mode_choice::{ModeChoiceParams, Skim};
 pub use self::modifier::ScenarioModifier;
 pub use self::scenario::{IndividTrip, PersonSpec, Scenario, TripPurpose};
 pub use self::spawner::TripEndpoint;
 pub(crate) use self::spawner::{StartTripArgs, TripSpec};
 
 mod activity_model;
+mod delivery;
 mod external;
 mod generator;
 mod load;
+mod mode_choice;
 mod modifier;
 mod scenario;
 mod spawner;
@@ -0,0 +1,420 @@
+//! A multinomial logit model to choose how people travel. For each trip, skims (the travel time,
+//! distance, and monetary cost of each mode) are calculated with the regular pathfinders. Each
+//! mode's generalized cost combines those, using a value of time. Then a mode is sampled, with
+//! cheaper modes exponentially more likely.
+//!
+//! Since the skims come from the current map, changes like new bike lanes or bus routes shift the
+//! costs and thus who chooses which mode.
+
+use std::collections::BTreeMap;
+
+use rand::Rng;
+use rand_xorshift::XorShiftRng;
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap, Timer};
+use geom::{Distance, Duration, Speed};
+use map_model::{Map, PathConstraints, PathRequest, PathStep};
+
+use crate::{Scenario, TripEndpoint, TripMode};
+
+/// Configures the mode choice model. Monetary amounts are in dollars.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ModeChoiceParams {
+    /// How much people value their time, in dollars per hour
+    pub value_of_time: f64,
+    /// Fuel and wear for driving, per kilometer
+    pub driving_cost_per_km: f64,
+    /// Paid once when driving to a building
+    pub parking_cost: f64,
+    pub transit_fare: f64,
+    /// Time spent waiting for transit, on top of walking to and from stops and riding
+    pub transit_wait: Duration,
+    pub walking_speed: Speed,
+    pub biking_speed: Speed,
+    /// Biking in mixed traffic is stressful. This is charged per kilometer not on a bike lane.
+    pub biking_in_traffic_cost_per_km: f64,
+    /// Everything else that makes a mode more or less attractive, added to the generalized cost.
+    /// Modes missing from here are never chosen.
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub mode_constants: BTreeMap<TripMode, f64>,
+    /// How sensitive people are to differences in cost, per dollar. Higher values mean nearly
+    /// everybody picks the cheapest mode; lower values make choices more random.
+    pub sensitivity: f64,
+}
+
+impl std::default::Default for ModeChoiceParams {
+    /// Loosely based on typical values for a mid-size US city.
+    fn default() -> ModeChoiceParams {
+        let mut mode_constants = BTreeMap::new();
+        mode_constants.insert(TripMode::Walk, 0.0);
+        mode_constants.insert(TripMode::Bike, 4.0);
+        mode_constants.insert(TripMode::Transit, 3.0);
+        mode_constants.insert(TripMode::Drive, 0.0);
+
+        ModeChoiceParams {
+            value_of_time: 15.0,
+            driving_cost_per_km: 0.35,
+            parking_cost: 2.0,
+            transit_fare: 2.75,
+            transit_wait: Duration::minutes(5),
+            walking_speed: Speed::miles_per_hour(3.0),
+            biking_speed: Speed::miles_per_hour(10.0),
+            biking_in_traffic_cost_per_km: 1.0,
+            mode_constants,
+            sensitivity: 0.5,
+        }
+    }
+}
+
+/// What it takes to make one trip using one mode.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Skim {
+    pub mode: TripMode,
+    pub duration: Duration,
+    pub distance: Distance,
+    /// Fares, parking, fuel, and the stress of biking in traffic, in dollars
+    pub cost: f64,
+}
+
+impl ModeChoiceParams {
+    /// Calculates skims for every mode that can make this trip.
+    pub fn skims(&self, from: TripEndpoint, to: TripEndpoint, map: &Map) -> Vec<Skim> {
+        let modes: Vec<TripMode> = self.mode_constants.keys().cloned().collect();
+        self.skims_among(&modes, from, to, map)
+    }
+
+    /// Calculates skims for some modes. Each skim needs at least one pathfinding call, so callers
+    /// that can rule out some modes cheaply should do so first.
+    pub fn skims_among(
+        &self,
+        modes: &[TripMode],
+        from: TripEndpoint,
+        to: TripEndpoint,
+        map: &Map,
+    ) -> Vec<Skim> {
+        let mut skims = Vec::new();
+        for mode in modes {
+            if !self.mode_constants.contains_key(mode) {
+                continue;
+            }
+            let skim = match mode {
+                TripMode::Walk => self.walking_skim(from, to, map),
+                TripMode::Bike | TripMode::Micromobility => self.biking_skim(*mode, from, to, map),
+                TripMode::Transit => self.transit_skim(from, to, map),
+                TripMode::Drive => self.driving_skim(from, to, map),
+                // TODO Skims for trips combining modes
+                TripMode::ParkAndRide | TripMode::BikeAndRide => None,
+            };
+            if let Some(skim) = skim {
+                skims.push(skim);
+            }
+        }
+        skims
+    }
+
+    /// Combines time and money into one cost, in dollars.
+    pub fn generalized_cost(&self, skim: &Skim) -> f64 {
+        skim.duration.inner_seconds() / 3600.0 * self.value_of_time
+            + skim.cost
+            + self.mode_constants.get(&skim.mode).cloned().unwrap_or(0.0)
+    }
+
+    /// The probability of choosing each mode that can make this trip.
+    pub fn probabilities(
+        &self,
+        from: TripEndpoint,
+        to: TripEndpoint,
+        map: &Map,
+    ) -> Vec<(TripMode, f64)> {
+        self.probabilities_from_skims(&self.skims(from, to, map))
+    }
+
+    /// The logit probability of choosing each skimmed mode.
+    pub fn probabilities_from_skims(&self, skims: &[Skim]) -> Vec<(TripMode, f64)> {
+        let costs: Vec<(TripMode, f64)> = skims
+            .iter()
+            .map(|skim| (skim.mode, self.generalized_cost(skim)))
+            .collect();
+        // Subtract the cheapest cost before exponentiating, to avoid overflow
+        let cheapest = costs
+            .iter()
+            .map(|(_, cost)| *cost)
+            .fold(std::f64::INFINITY, f64::min);
+        let weights: Vec<(TripMode, f64)> = costs
+            .into_iter()
+            .map(|(mode, cost)| (mode, (-self.sensitivity * (cost - cheapest)).exp()))
+            .collect();
+        let total: f64 = weights.iter().map(|(_, w)| *w).sum();
+        weights
+            .into_iter()
+            .map(|(mode, w)| (mode, w / total))
+            .collect()
+    }
+
+    /// Samples a mode for one trip. Returns None if no mode can make the trip.
+    pub fn pick_mode(
+        &self,
+        from: TripEndpoint,
+        to: TripEndpoint,
+        map: &Map,
+        rng: &mut XorShiftRng,
+    ) -> Option<TripMode> {
+        sample(&self.probabilities(from, to, map), rng)
+    }
+
+    /// Like `pick_mode`, but only considers some modes.
+    pub fn pick_mode_among(
+        &self,
+        modes: &[TripMode],
+        from: TripEndpoint,
+        to: TripEndpoint,
+        map: &Map,
+        rng: &mut XorShiftRng,
+    ) -> Option<TripMode> {
+        let skims = self.skims_among(modes, from, to, map);
+        sample(&self.probabilities_from_skims(&skims), rng)
+    }
+
+    /// Chooses the mode of every trip in a scenario again. Trips that no mode can make keep their
+    /// current mode.
+    pub fn assign_modes(
+        &self,
+        scenario: &mut Scenario,
+        map: &Map,
+        rng: &mut XorShiftRng,
+        timer: &mut Timer,
+    ) {
+        timer.start_iter("choose modes", scenario.people.len());
+        for person in &mut scenario.people {
+            timer.next();
+            let mut from = person.origin;
+            for trip in &mut person.trips {
+                if let Some(mode) = self.pick_mode(from, trip.destination, map, rng) {
+                    trip.mode = mode;
+                }
+                from = trip.destination;
+            }
+        }
+    }
+
+    fn walking_skim(&self, from: TripEndpoint, to: TripEndpoint, map: &Map) -> Option<Skim> {
+        let (duration, distance) =
+            self.walking_time(TripEndpoint::path_req(from, to, TripMode::Walk, map)?, map)?;
+        Some(Skim {
+            mode: TripMode::Walk,
+            duration,
+            distance,
+            cost: 0.0,
+        })
+    }
+
+    fn biking_skim(
+        &self,
+        mode: TripMode,
+        from: TripEndpoint,
+        to: TripEndpoint,
+        map: &Map,
+    ) -> Option<Skim> {
+        if mode == TripMode::Micromobility {
+            // Shared vehicles can only be used between buildings
+            match (from, to) {
+                (TripEndpoint::Bldg(_), TripEndpoint::Bldg(_)) => {}
+                _ => {
+                    return None;
+                }
+            }
+        }
+        let req = TripEndpoint::path_req(from, to, mode, map)?;
+        let path = map.pathfind(req).ok()?;
+        let mut in_traffic = Distance::ZERO;
+        for step in path.get_steps() {
+            if let PathStep::Lane(l) = step {
+                let lane = map.get_l(*l);
+                if !lane.is_biking() {
+                    in_traffic += lane.length();
+                }
+            }
+        }
+        Some(Skim {
+            mode,
+            duration: path.estimate_duration(map, PathConstraints::Bike, Some(self.biking_speed)),
+            distance: path.total_length(),
+            cost: in_traffic.inner_meters() / 1000.0 * self.biking_in_traffic_cost_per_km,
+        })
+    }
+
+    fn driving_skim(&self, from: TripEndpoint, to: TripEndpoint, map: &Map) -> Option<Skim> {
+        let req = TripEndpoint::path_req(from, to, TripMode::Drive, map)?;
+        let path = map.pathfind(req).ok()?;
+        let distance = path.total_length();
+        let mut cost = distance.inner_meters() / 1000.0 * self.driving_cost_per_km;
+        if let TripEndpoint::Bldg(_) = to {
+            cost += self.parking_cost;
+        }
+        Some(Skim {
+            mode: TripMode::Drive,
+            duration: path.estimate_duration(map, PathConstraints::Car, None),
+            distance,
+            cost,
+        })
+    }
+
+    fn transit_skim(&self, from: TripEndpoint, to: TripEndpoint, map: &Map) -> Option<Skim> {
+        let walk = TripEndpoint::path_req(from, to, TripMode::Walk, map)?;
+        // Only consider transit trips that get off at a stop
+        let (stop1, maybe_stop2, route) = map.should_use_transit(walk.start, walk.end)?;
+        let stop1 = map.get_bs(stop1);
+        let stop2 = map.get_bs(maybe_stop2?);
+
+        let (walk1_time, walk1_dist) = self.walking_time(
+            PathRequest {
+                start: walk.start,
+                end: stop1.sidewalk_pos,
+                constraints: PathConstraints::Pedestrian,
+                routing_params: None,
+            },
+            map,
+        )?;
+        let (walk2_time, walk2_dist) = self.walking_time(
+            PathRequest {
+                start: stop2.sidewalk_pos,
+                end: walk.end,
+                constraints: PathConstraints::Pedestrian,
+                routing_params: None,
+            },
+            map,
+        )?;
+        let constraints = map.get_br(route).route_type;
+        let ride = map
+            .pathfind(PathRequest {
+                start: stop1.driving_pos,
+                end: stop2.driving_pos,
+                constraints,
+                routing_params: None,
+            })
+            .ok()?;
+
+        Some(Skim {
+            mode: TripMode::Transit,
+            duration: walk1_time
+                + self.transit_wait
+                + ride.estimate_duration(map, constraints, None)
+                + walk2_time,
+            distance: walk1_dist + ride.total_length() + walk2_dist,
+            cost: self.transit_fare,
+        })
+    }
+
+    fn walking_time(&self, req: PathRequest, map: &Map) -> Option<(Duration, Distance)> {
+        let path = map.pathfind(req).ok()?;
+        Some((
+            path.estimate_duration(map, PathConstraints::Pedestrian, Some(self.walking_speed)),
+            path.total_length(),
+        ))
+    }
+}
+
+fn sample(probabilities: &[(TripMode, f64)], rng: &mut XorShiftRng) -> Option<TripMode> {
+    let mut x = rng.gen::<f64>();
+    for (mode, prob) in probabilities {
+        if x < *prob {
+            return Some(*mode);
+        }
+        x -= prob;
+    }
+    // Rounding error
+    probabilities.last().map(|(mode, _)| *mode)
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    fn skim(mode: TripMode, minutes: usize, cost: f64) -> Skim {
+        Skim {
+            mode,
+            duration: Duration::minutes(minutes),
+            distance: Distance::ZERO,
+            cost,
+        }
+    }
+
+    fn params() -> ModeChoiceParams {
+        let mut params = ModeChoiceParams::default();
+        params.value_of_time = 60.0;
+        params.sensitivity = 1.0;
+        for constant in params.mode_constants.values_mut() {
+            *constant = 0.0;
+        }
+        params
+    }
+
+    #[test]
+    fn equal_costs_are_equally_likely() {
+        let probs = params().probabilities_from_skims(&[
+            skim(TripMode::Walk, 10, 0.0),
+            skim(TripMode::Drive, 5, 5.0),
+        ]);
+        assert_eq!(probs.len(), 2);
+        assert!((probs[0].1 - 0.5).abs() < 1e-9);
+        assert!((probs[1].1 - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn logit_probabilities() {
+        // At $60/hour, walking costs $20 and driving costs $10 + $2 = $12
+        let probs = params().probabilities_from_skims(&[
+            skim(TripMode::Walk, 20, 0.0),
+            skim(TripMode::Drive, 10, 2.0),
+        ]);
+        let expected_walk = (-20.0_f64).exp() / ((-20.0_f64).exp() + (-12.0_f64).exp());
+        assert!((probs[0].1 - expected_walk).abs() < 1e-9);
+        assert!((probs[0].1 + probs[1].1 - 1.0).abs() < 1e-9);
+
+        // Mode constants count like any other cost
+        let mut params = params();
+        params.mode_constants.insert(TripMode::Drive, 8.0);
+        let probs = params.probabilities_from_skims(&[
+            skim(TripMode::Walk, 20, 0.0),
+            skim(TripMode::Drive, 10, 2.0),
+        ]);
+        assert!((probs[0].1 - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sensitivity() {
+        let skims = vec![skim(TripMode::Walk, 20, 0.0), skim(TripMode::Bike, 10, 0.0)];
+        let mut params = params();
+        params.sensitivity = 0.0;
+        let probs = params.probabilities_from_skims(&skims);
+        assert!((probs[1].1 - 0.5).abs() < 1e-9);
+
+        // Very sensitive people nearly always pick the cheapest mode, without overflowing
+        params.sensitivity = 1000.0;
+        let probs = params.probabilities_from_skims(&skims);
+        assert!(probs[1].1 > 0.999);
+        assert!(probs.iter().all(|(_, p)| p.is_finite()));
+    }
+
+    #[test]
+    fn sampling() {
+        let mut rng = XorShiftRng::seed_from_u64(42);
+        assert_eq!(sample(&[], &mut rng), None);
+        assert_eq!(
+            sample(&[(TripMode::Walk, 0.0), (TripMode::Drive, 1.0)], &mut rng),
+            Some(TripMode::Drive)
+        );
+
+        let probs = vec![(TripMode::Walk, 0.25), (TripMode::Drive, 0.75)];
+        let walks = (0..10_000)
+            .filter(|_| sample(&probs, &mut rng) == Some(TripMode::Walk))
+            .count();
+        assert!(walks > 2_000 && walks < 3_000);
+    }
+}
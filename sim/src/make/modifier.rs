@@ -1,11 +1,13 @@
 use std::collections::BTreeSet;
 
+use rand_xorshift::XorShiftRng;
 use serde::{Deserialize, Serialize};
 
 use abstutil::Timer;
 use geom::{Duration, Time};
 use map_model::Map;
 
+use crate::make::delivery::delivery_demand;
 use crate::{Scenario, TripMode};
 
 /// Transforms an existing Scenario before instantiating it.
@@ -21,12 +23,14 @@ pub enum ScenarioModifier {
     },
     /// Scenario name
     AddExtraTrips(String),
+    /// Delivery vans visiting businesses, as a percent of typical demand
+    AddDeliveries(usize),
 }
 
 impl ScenarioModifier {
     /// If this modifies scenario_name, then that means prebaked results don't match up and
     /// shouldn't be used.
-    pub fn apply(&self, map: &Map, mut s: Scenario) -> Scenario {
+    pub fn apply(&self, map: &Map, mut s: Scenario, rng: &mut XorShiftRng) -> Scenario {
         match self {
             ScenarioModifier::RepeatDays(n) => repeat_days(s, *n),
             ScenarioModifier::ChangeMode {
@@ -84,6 +88,15 @@ impl ScenarioModifier {
                 }
                 s
             }
+            ScenarioModifier::AddDeliveries(pct) => {
+                for mut p in delivery_demand(map, *pct, rng) {
+                    for trip in &mut p.trips {
+                        trip.modified = true;
+                    }
+                    s.people.push(p);
+                }
+                s
+            }
         }
     }
 
@@ -105,6 +118,9 @@ impl ScenarioModifier {
                 to_mode.map(|m| m.verb())
             ),
             ScenarioModifier::AddExtraTrips(name) => format!("Add extra trips from {}", name),
+            ScenarioModifier::AddDeliveries(pct) => {
+                format!("Add delivery vans, at {}% of typical demand", pct)
+            }
         }
     }
 }
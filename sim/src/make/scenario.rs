@@ -10,12 +10,12 @@ use serde::{Deserialize, Serialize};
 use abstio::MapName;
 use abstutil::{prettyprint_usize, Counter, Timer};
 use geom::{Distance, Speed, Time};
-use map_model::{BuildingID, Map, OffstreetParking, RoadID};
+use map_model::{BuildingID, Map, OffstreetParking, RoadID, RoutingParams};
 
 use crate::make::fork_rng;
 use crate::{
     OrigPersonID, ParkingSpot, Sim, StartTripArgs, TripEndpoint, TripInfo, TripMode, Vehicle,
-    VehicleSpec, VehicleType, BIKE_LENGTH, MAX_CAR_LENGTH, MIN_CAR_LENGTH,
+    VehicleSpec, VehicleType, BIKE_LENGTH, MAX_CAR_LENGTH, MIN_CAR_LENGTH, VAN_LENGTH,
 };
 
 /// A Scenario describes all the input to a simulation. Usually a scenario covers one day.
@@ -48,6 +48,9 @@ pub struct IndividTrip {
     pub cancelled: bool,
     /// Did a ScenarioModifier affect this?
     pub modified: bool,
+    /// Overrides the map's routing params when this trip drives or bikes, to model different
+    /// kinds of travelers -- like a cyclist who avoids arterials and hills.
+    pub routing_params: Option<Box<RoutingParams>>,
 }
 
 impl IndividTrip {
@@ -64,6 +67,7 @@ impl IndividTrip {
             purpose,
             cancelled: false,
             modified: false,
+            routing_params: None,
         }
     }
 }
@@ -82,6 +86,8 @@ pub enum TripPurpose {
     Recreation,
     Medical,
     ParkAndRideTransfer,
+    /// Dropping off goods at a business, using a delivery van
+    Delivery,
 }
 
 impl fmt::Display for TripPurpose {
@@ -102,6 +108,7 @@ impl fmt::Display for TripPurpose {
                 TripPurpose::Recreation => "recreation",
                 TripPurpose::Medical => "medical",
                 TripPurpose::ParkAndRideTransfer => "park-and-ride transfer",
+                TripPurpose::Delivery => "delivery",
             }
         )
     }
@@ -173,6 +180,7 @@ impl Scenario {
                         purpose: trip.purpose,
                         modified: trip.modified,
                         capped: false,
+                        routing_params: trip.routing_params.clone(),
                         cancellation_reason: if trip.cancelled {
                             Some(format!("cancelled by ScenarioModifier"))
                         } else {
@@ -192,6 +200,9 @@ impl Scenario {
         parked_cars.shuffle(rng);
         seed_parked_cars(parked_cars, sim, map, rng, timer);
 
+        // Do this after creating people, so their vehicle IDs don't depend on the shared fleet
+        sim.seed_shared_vehicles(map);
+
         sim.spawn_trips(schedule_trips, map, timer);
         timer.stop(format!("Instantiating {}", self.scenario_name));
     }
@@ -218,6 +229,16 @@ impl Scenario {
             vehicle_type: VehicleType::Car,
             length,
             max_speed: None,
+            battery: None,
+        }
+    }
+
+    fn van() -> VehicleSpec {
+        VehicleSpec {
+            vehicle_type: VehicleType::Van,
+            length: VAN_LENGTH,
+            max_speed: None,
+            battery: None,
         }
     }
 
@@ -231,6 +252,7 @@ impl Scenario {
             vehicle_type: VehicleType::Bike,
             length: BIKE_LENGTH,
             max_speed,
+            battery: None,
         }
     }
 
@@ -313,6 +335,12 @@ fn seed_parked_cars(
     let mut open_spots_per_road: BTreeMap<RoadID, Vec<(ParkingSpot, Option<BuildingID>)>> =
         BTreeMap::new();
     for spot in sim.get_all_parking_spots().1 {
+        // Nobody starts the day parked in a loading zone
+        if let ParkingSpot::Onstreet(l, _) = spot {
+            if map.get_parent(l).parking_policy.loading_zone {
+                continue;
+            }
+        }
         let (r, restriction) = match spot {
             ParkingSpot::Onstreet(l, _) => (map.get_l(l).parent, None),
             ParkingSpot::Offstreet(b, _) => (
@@ -418,6 +446,14 @@ impl PersonSpec {
             }
         }
 
+        for t in &self.trips {
+            if let Some(ref params) = t.routing_params {
+                params.check().map_err(|err| {
+                    anyhow!("Person ({:?}) has a trip with {}", self.orig_id, err)
+                })?;
+            }
+        }
+
         let mut endpts = vec![self.origin.clone()];
         for t in &self.trips {
             endpts.push(t.destination.clone());
@@ -448,39 +484,50 @@ impl PersonSpec {
         let mut vehicle_foreach_trip = Vec::new();
 
         let mut bike_idx = None;
-        // For each indexed car, is it parked somewhere, or off-map?
-        let mut car_locations: Vec<(usize, Option<BuildingID>)> = Vec::new();
+        // For each indexed car, where is it between trips?
+        let mut car_locations: Vec<(usize, CarLocation)> = Vec::new();
 
         // TODO If the trip is cancelled, this should be affected...
         let mut from = self.origin.clone();
         for trip in &self.trips {
             let use_for_trip = match trip.mode {
-                TripMode::Walk | TripMode::Transit => None,
-                TripMode::Bike => {
+                TripMode::Walk | TripMode::Transit | TripMode::Micromobility => None,
+                TripMode::Bike | TripMode::BikeAndRide => {
                     if bike_idx.is_none() {
                         bike_idx = Some(vehicle_specs.len());
                         vehicle_specs.push(Scenario::rand_bike(rng));
                     }
                     bike_idx
                 }
-                TripMode::Drive => {
+                TripMode::Drive | TripMode::ParkAndRide => {
                     let need_parked_at = match from {
-                        TripEndpoint::Bldg(b) => Some(b),
-                        _ => None,
+                        TripEndpoint::Bldg(b) => CarLocation::Bldg(b),
+                        _ => CarLocation::OffMap,
                     };
 
-                    // Any available cars in the right spot?
+                    // Any available cars in the right spot? Starting from a building, the person
+                    // can also go fetch a car they left behind when parking and riding.
                     let idx = if let Some(idx) = car_locations
                         .iter()
                         .find(|(_, parked_at)| *parked_at == need_parked_at)
+                        .or_else(|| match need_parked_at {
+                            CarLocation::Bldg(_) => car_locations
+                                .iter()
+                                .find(|(_, parked_at)| *parked_at == CarLocation::ParkAndRide),
+                            _ => None,
+                        })
                         .map(|(idx, _)| *idx)
                     {
                         idx
                     } else {
                         // Need a new car, starting in the right spot
                         let idx = vehicle_specs.len();
-                        vehicle_specs.push(Scenario::rand_car(rng));
-                        if let Some(b) = need_parked_at {
+                        vehicle_specs.push(if let TripPurpose::Delivery = trip.purpose {
+                            Scenario::van()
+                        } else {
+                            Scenario::rand_car(rng)
+                        });
+                        if let CarLocation::Bldg(b) = need_parked_at {
                             cars_initially_parked_at.push((idx, b));
                         }
                         idx
@@ -488,14 +535,19 @@ impl PersonSpec {
 
                     // Where does this car wind up?
                     car_locations.retain(|(i, _)| idx != *i);
-                    match trip.destination {
-                        TripEndpoint::Bldg(b) => {
-                            car_locations.push((idx, Some(b)));
-                        }
-                        TripEndpoint::Border(_) | TripEndpoint::SuddenlyAppear(_) => {
-                            car_locations.push((idx, None));
-                        }
-                    }
+                    car_locations.push((
+                        idx,
+                        match (trip.mode, &trip.destination) {
+                            // If there's no useful transit when the trip runs, the person drives
+                            // the whole way instead. Either way, the car is found wherever it
+                            // actually winds up when it's next used.
+                            (TripMode::ParkAndRide, _) => CarLocation::ParkAndRide,
+                            (_, TripEndpoint::Bldg(b)) => CarLocation::Bldg(*b),
+                            (_, TripEndpoint::Border(_)) | (_, TripEndpoint::SuddenlyAppear(_)) => {
+                                CarLocation::OffMap
+                            }
+                        },
+                    ));
 
                     Some(idx)
                 }
@@ -522,3 +574,49 @@ impl PersonSpec {
         )
     }
 }
+
+/// Where somebody's car is between their trips.
+#[derive(Clone, Copy, PartialEq)]
+enum CarLocation {
+    Bldg(BuildingID),
+    OffMap,
+    /// Left near a transit stop after parking and riding
+    ParkAndRide,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fetch_car_after_park_and_ride() {
+        let home = TripEndpoint::Bldg(BuildingID(0));
+        let work = TripEndpoint::Bldg(BuildingID(1));
+        let shop = TripEndpoint::Bldg(BuildingID(2));
+        let person = PersonSpec {
+            orig_id: None,
+            origin: home,
+            trips: vec![
+                IndividTrip::new(
+                    Time::START_OF_DAY,
+                    TripPurpose::Work,
+                    work,
+                    TripMode::ParkAndRide,
+                ),
+                IndividTrip::new(Time::START_OF_DAY, TripPurpose::Home, home, TripMode::Drive),
+                IndividTrip::new(
+                    Time::START_OF_DAY,
+                    TripPurpose::Shopping,
+                    shop,
+                    TripMode::Drive,
+                ),
+            ],
+        };
+        let (vehicles, parked_at, foreach_trip) =
+            person.get_vehicles(&mut XorShiftRng::seed_from_u64(42));
+        // The car left at the lot is fetched on the way home, not duplicated
+        assert_eq!(vehicles.len(), 1);
+        assert_eq!(parked_at, vec![(0, BuildingID(0))]);
+        assert_eq!(foreach_trip, vec![Some(0), Some(0), Some(0)]);
+    }
+}
@@ -4,13 +4,18 @@
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-use geom::Pt2D;
+use geom::{Distance, Pt2D};
 use map_model::{
-    BuildingID, BusRouteID, BusStopID, IntersectionID, Map, PathConstraints, PathRequest, Position,
+    BuildingID, BusRouteID, BusStopID, IntersectionID, Map, ParkingLotID, PathConstraints,
+    PathRequest, Position,
 };
 
 use crate::{CarID, DrivingGoal, SidewalkSpot, TripLeg, TripMode, VehicleType, SPAWN_DIST};
 
+/// Checking whether transit is useful from a place requires pathfinding, so only try this many
+/// places to park and ride.
+const MAX_TRANSFERS_TO_TRY: usize = 5;
+
 /// We need to remember a few things from scenario instantiation that're used for starting the
 /// trip.
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -50,6 +55,8 @@ pub(crate) enum TripSpec {
         start: BuildingID,
         goal: DrivingGoal,
     },
+    /// Which shared vehicle to use is only decided when the trip starts.
+    UsingSharedVehicle { start: BuildingID, goal: BuildingID },
     UsingTransit {
         start: SidewalkSpot,
         goal: SidewalkSpot,
@@ -57,6 +64,29 @@ pub(crate) enum TripSpec {
         stop1: BusStopID,
         maybe_stop2: Option<BusStopID>,
     },
+    /// Drive to a parking lot, then walk to a stop and ride transit.
+    ParkAndRide {
+        /// This must be a currently parked vehicle owned by the person.
+        car: CarID,
+        start_bldg: BuildingID,
+        lot: ParkingLotID,
+        goal: SidewalkSpot,
+        route: BusRouteID,
+        stop1: BusStopID,
+        maybe_stop2: Option<BusStopID>,
+    },
+    /// Bike to a stop, leave the bike there, and ride transit.
+    BikeAndRide {
+        bike: CarID,
+        start: BuildingID,
+        /// Where the bike is left. Usually the same as stop1, but the transit route could start
+        /// from a stop nearby.
+        rack: BusStopID,
+        goal: SidewalkSpot,
+        route: BusRouteID,
+        stop1: BusStopID,
+        maybe_stop2: Option<BusStopID>,
+    },
 }
 
 impl TripSpec {
@@ -117,6 +147,9 @@ impl TripSpec {
                         legs.push(TripLeg::Walk(SidewalkSpot::building(*b, map)));
                     }
                     DrivingGoal::Border(_, _) => {}
+                    DrivingGoal::ParkInLot(_, _, _)
+                    | DrivingGoal::ParkNearStop(_)
+                    | DrivingGoal::Deliver(_, _, _) => unreachable!(),
                 }
             }
             TripSpec::JustWalking { start, goal, .. } => {
@@ -141,6 +174,9 @@ impl TripSpec {
                             goal,
                         })
                     }
+                    DrivingGoal::ParkInLot(_, _, _)
+                    | DrivingGoal::ParkNearStop(_)
+                    | DrivingGoal::Deliver(_, _, _) => unreachable!(),
                 };
 
                 if let Some(start_spot) = SidewalkSpot::bike_rack(*start, map) {
@@ -170,6 +206,9 @@ impl TripSpec {
                             legs.push(TripLeg::Walk(SidewalkSpot::building(*b, map)));
                         }
                         DrivingGoal::Border(_, _) => {}
+                        DrivingGoal::ParkInLot(_, _, _)
+                        | DrivingGoal::ParkNearStop(_)
+                        | DrivingGoal::Deliver(_, _, _) => unreachable!(),
                     }
                 } else if backup_plan.is_some() {
                     info!("Can't start biking from {}. Walking instead", start);
@@ -185,6 +224,10 @@ impl TripSpec {
                     .to_plan(map);
                 }
             }
+            TripSpec::UsingSharedVehicle { .. } => {
+                // The legs depend on which vehicle is available when the trip starts, so the
+                // TripManager fills them in then.
+            }
             TripSpec::UsingTransit {
                 route,
                 stop1,
@@ -206,6 +249,45 @@ impl TripSpec {
                     ];
                 }
             }
+            TripSpec::ParkAndRide {
+                car,
+                start_bldg,
+                lot,
+                goal,
+                route,
+                stop1,
+                maybe_stop2,
+            } => {
+                legs = vec![
+                    TripLeg::Walk(SidewalkSpot::deferred_parking_spot()),
+                    TripLeg::Drive(*car, DrivingGoal::ParkInLot(*lot, *start_bldg, *stop1)),
+                    TripLeg::Walk(SidewalkSpot::bus_stop(*stop1, map)),
+                    TripLeg::RideBus(*route, *maybe_stop2),
+                ];
+                if maybe_stop2.is_some() {
+                    legs.push(TripLeg::Walk(goal.clone()));
+                }
+            }
+            TripSpec::BikeAndRide {
+                bike,
+                start,
+                rack,
+                goal,
+                route,
+                stop1,
+                maybe_stop2,
+            } => {
+                // transfer_to_transit already checked that both racks exist
+                legs = vec![
+                    TripLeg::Walk(SidewalkSpot::bike_rack(*start, map).unwrap()),
+                    TripLeg::Drive(*bike, DrivingGoal::ParkNearStop(*rack)),
+                    TripLeg::Walk(SidewalkSpot::bus_stop(*stop1, map)),
+                    TripLeg::RideBus(*route, *maybe_stop2),
+                ];
+                if maybe_stop2.is_some() {
+                    legs.push(TripLeg::Walk(goal.clone()));
+                }
+            }
         };
 
         (self, legs)
@@ -228,7 +310,15 @@ impl TripSpec {
                 } else {
                     PathConstraints::Bike
                 };
-                let goal = to.driving_goal(constraints, map)?;
+                let mut goal = to.driving_goal(constraints, map)?;
+                // Delivery vans stop at the building and leave the same way they came
+                if let (DrivingGoal::ParkNear(b), TripEndpoint::Border(_)) = (goal.clone(), &from) {
+                    if use_vehicle.map(|c| c.vehicle_type) == Some(VehicleType::Van) {
+                        if let DrivingGoal::Border(i, exit) = from.driving_goal(constraints, map)? {
+                            goal = DrivingGoal::Deliver(b, i, exit);
+                        }
+                    }
+                }
                 match from {
                     TripEndpoint::Bldg(start_bldg) => {
                         if mode == TripMode::Drive {
@@ -270,10 +360,30 @@ impl TripSpec {
                     },
                 }
             }
+            TripMode::Micromobility => match (from, to) {
+                (TripEndpoint::Bldg(start), TripEndpoint::Bldg(goal)) => {
+                    TripSpec::UsingSharedVehicle { start, goal }
+                }
+                _ => bail!("shared vehicles can only be used to travel between buildings"),
+            },
             TripMode::Walk => TripSpec::JustWalking {
                 start: from.start_sidewalk_spot(map)?,
                 goal: to.end_sidewalk_spot(map)?,
             },
+            TripMode::ParkAndRide | TripMode::BikeAndRide => {
+                if let Some(spec) = TripSpec::transfer_to_transit(from, to, mode, use_vehicle, map)
+                {
+                    spec
+                } else {
+                    // Just drive or bike the whole way
+                    let mode = if mode == TripMode::ParkAndRide {
+                        TripMode::Drive
+                    } else {
+                        TripMode::Bike
+                    };
+                    TripSpec::maybe_new(from, to, mode, use_vehicle, retry_if_no_room, map)?
+                }
+            }
             TripMode::Transit => {
                 let start = from.start_sidewalk_spot(map)?;
                 let goal = to.end_sidewalk_spot(map)?;
@@ -295,6 +405,95 @@ impl TripSpec {
             }
         })
     }
+
+    /// Plans the first leg of a park and ride or bike and ride trip. Returns None if there's no
+    /// useful place to switch to transit.
+    fn transfer_to_transit(
+        from: TripEndpoint,
+        to: TripEndpoint,
+        mode: TripMode,
+        use_vehicle: Option<CarID>,
+        map: &Map,
+    ) -> Option<TripSpec> {
+        let start = match from {
+            TripEndpoint::Bldg(b) => b,
+            // TODO Vehicles appearing at a border could also switch to transit
+            _ => {
+                return None;
+            }
+        };
+        let goal = to.end_sidewalk_spot(map).ok()?;
+
+        if mode == TripMode::ParkAndRide {
+            let lots = map
+                .all_parking_lots()
+                .iter()
+                .map(|pl| (pl.id, pl.polygon.center(), pl.sidewalk_pos))
+                .collect();
+            nearby_transfer(start, &goal, lots, map).map(|(lot, (stop1, maybe_stop2, route))| {
+                TripSpec::ParkAndRide {
+                    car: use_vehicle.unwrap(),
+                    start_bldg: start,
+                    lot,
+                    goal,
+                    route,
+                    stop1,
+                    maybe_stop2,
+                }
+            })
+        } else {
+            let start_lane = SidewalkSpot::bike_rack(start, map)?.sidewalk_pos.lane();
+            let stops = map
+                .all_bus_stops()
+                .values()
+                .filter_map(|bs| {
+                    let rack = SidewalkSpot::bike_rack_at_stop(bs.id, map)?;
+                    // Biking to somewhere else along the same sidewalk is silly
+                    if rack.sidewalk_pos.lane() == start_lane {
+                        return None;
+                    }
+                    Some((bs.id, bs.sidewalk_pos.pt(map), bs.sidewalk_pos))
+                })
+                .collect();
+            nearby_transfer(start, &goal, stops, map).map(|(rack, (stop1, maybe_stop2, route))| {
+                TripSpec::BikeAndRide {
+                    bike: use_vehicle.unwrap(),
+                    start,
+                    rack,
+                    goal,
+                    route,
+                    stop1,
+                    maybe_stop2,
+                }
+            })
+        }
+    }
+}
+
+/// Finds a place to switch to transit on the way from a building to a goal, like a parking lot or
+/// a bus stop to leave a bike. Only candidates closer to the goal than the start are considered,
+/// preferring the smallest detour. Returns the candidate and how to ride transit from there.
+fn nearby_transfer<ID: Copy>(
+    start: BuildingID,
+    goal: &SidewalkSpot,
+    candidates: Vec<(ID, Pt2D, Position)>,
+    map: &Map,
+) -> Option<(ID, (BusStopID, Option<BusStopID>, BusRouteID))> {
+    let start_pt = map.get_b(start).polygon.center();
+    let goal_pt = goal.sidewalk_pos.pt(map);
+    let direct = start_pt.dist_to(goal_pt);
+    let mut candidates: Vec<(Distance, ID, Position)> = candidates
+        .into_iter()
+        .filter(|(_, pt, _)| pt.dist_to(goal_pt) < direct)
+        .map(|(id, pt, pos)| (start_pt.dist_to(pt) + pt.dist_to(goal_pt), id, pos))
+        .collect();
+    candidates.sort_by_key(|(detour, _, _)| *detour);
+    for (_, id, pos) in candidates.into_iter().take(MAX_TRANSFERS_TO_TRY) {
+        if let Some(transit) = map.should_use_transit(pos, goal.sidewalk_pos) {
+            return Some((id, transit));
+        }
+    }
+    None
 }
 
 /// Specifies where a trip begins or ends.
@@ -322,9 +521,12 @@ impl TripEndpoint {
             end: to.clone().pos(mode, false, map)?,
             constraints: match mode {
                 TripMode::Walk | TripMode::Transit => PathConstraints::Pedestrian,
-                TripMode::Drive => PathConstraints::Car,
-                TripMode::Bike => PathConstraints::Bike,
+                TripMode::Drive | TripMode::ParkAndRide => PathConstraints::Car,
+                TripMode::Bike | TripMode::Micromobility | TripMode::BikeAndRide => {
+                    PathConstraints::Bike
+                }
             },
+            routing_params: None,
         })
     }
 
@@ -375,7 +577,11 @@ impl TripEndpoint {
             })
             .ok()
             .map(|spot| spot.sidewalk_pos),
-            TripMode::Drive | TripMode::Bike => {
+            TripMode::Drive
+            | TripMode::Bike
+            | TripMode::Micromobility
+            | TripMode::ParkAndRide
+            | TripMode::BikeAndRide => {
                 if from {
                     match self {
                         // Fall through and use DrivingGoal also to start.
@@ -2,9 +2,10 @@ use std::collections::{BTreeSet, VecDeque};
 
 use serde::{Deserialize, Serialize};
 
-use geom::{Distance, Duration, PolyLine, Time, EPSILON_DIST};
+use geom::{Distance, Duration, PolyLine, Speed, Time, EPSILON_DIST};
 use map_model::{Direction, Map, Traversable};
 
+use crate::mechanics::{CarFollowingModel, Leader, SpeedProfile};
 use crate::{
     CarID, CarStatus, DistanceInterval, DrawCarInput, ParkingSpot, PersonID, Router, TimeInterval,
     TransitSimState, TripID, Vehicle, VehicleType,
@@ -29,11 +30,23 @@ pub(crate) struct Car {
     /// Since lane over-taking isn't implemented yet, a vehicle tends to be stuck behind a slow
     /// leader for a while. Avoid duplicate events.
     pub wants_to_overtake: BTreeSet<CarID>,
+
+    pub car_following: CarFollowingModel,
+    /// When the vehicle last finished crossing something, and how fast it was going then. Only
+    /// used by the car-following models.
+    pub last_speed: (Time, Speed),
 }
 
 impl Car {
-    /// Assumes the current head of the path is the thing to cross.
-    pub fn crossing_state(&self, start_dist: Distance, start_time: Time, map: &Map) -> CarState {
+    /// Assumes the current head of the path is the thing to cross. Only the car-following models
+    /// use the leader.
+    pub fn crossing_state(
+        &self,
+        start_dist: Distance,
+        start_time: Time,
+        leader: Option<Leader>,
+        map: &Map,
+    ) -> CarState {
         let dist_int = DistanceInterval::new_driving(
             start_dist,
             if self.router.last_step() {
@@ -42,22 +55,92 @@ impl Car {
                 self.router.head().get_polyline(map).length()
             },
         );
-        self.crossing_state_with_end_dist(dist_int, start_time, map)
+        self.crossing_state_with_end_dist(dist_int, start_time, leader, map)
     }
 
     pub fn crossing_state_with_end_dist(
         &self,
         dist_int: DistanceInterval,
         start_time: Time,
+        leader: Option<Leader>,
         map: &Map,
     ) -> CarState {
-        let speed = self.router.head().max_speed_along(
+        let speed = self.router.head().max_speed_along_with_fitness(
             self.vehicle.max_speed,
+            map.routing_params_for(self.router.get_path().get_req())
+                .fitness,
             self.vehicle.vehicle_type.to_constraints(),
             map,
         );
-        let dt = (dist_int.end - dist_int.start) / speed;
-        CarState::Crossing(TimeInterval::new(start_time, start_time + dt), dist_int)
+        if self.car_following == CarFollowingModel::EventDriven {
+            let dt = (dist_int.end - dist_int.start) / speed;
+            return CarState::Crossing(
+                TimeInterval::new(start_time, start_time + dt),
+                dist_int,
+                None,
+            );
+        }
+
+        // Vehicles come to a stop at the end of their path and at stop signs. Elsewhere, the
+        // queues decide if they have to stop.
+        let stop_at_end = self.router.last_step()
+            || match self.router.head() {
+                Traversable::Lane(l) => {
+                    let lane = map.get_l(l);
+                    map.maybe_get_stop_sign(lane.dst_i)
+                        .and_then(|ss| ss.roads.get(&lane.parent))
+                        .map(|r| r.must_stop)
+                        .unwrap_or(false)
+                }
+                Traversable::Turn(_) => false,
+            };
+        let profile = SpeedProfile::new(
+            self.car_following,
+            self.vehicle.vehicle_type,
+            self.current_speed(start_time),
+            speed,
+            dist_int.end - dist_int.start,
+            stop_at_end,
+            leader,
+        );
+        CarState::Crossing(
+            TimeInterval::new(start_time, start_time + profile.duration()),
+            dist_int,
+            Some(profile),
+        )
+    }
+
+    /// If the vehicle is following a speed profile, returns its speed at different times, from the
+    /// start of the crossing up to now.
+    pub fn speed_profile_until(&self, now: Time) -> Option<Vec<(Time, Speed)>> {
+        if let CarState::Crossing(ref time_int, _, Some(ref profile)) = self.state {
+            let mut points: Vec<(Time, Speed)> = profile
+                .points()
+                .iter()
+                .map(|(dt, _, speed)| (time_int.start + *dt, *speed))
+                .take_while(|(t, _)| *t < now)
+                .collect();
+            if now > time_int.start {
+                points.push((now, profile.speed_at(now - time_int.start)));
+            }
+            return Some(points);
+        }
+        None
+    }
+
+    /// How fast is the vehicle moving right now? Only meaningful for the car-following models;
+    /// vehicles are otherwise treated as stopped between crossings.
+    pub fn current_speed(&self, now: Time) -> Speed {
+        if let CarState::Crossing(ref time_int, _, Some(ref profile)) = self.state {
+            if now >= time_int.start && now < time_int.end {
+                return profile.speed_at(now - time_int.start);
+            }
+        }
+        if self.last_speed.0 == now {
+            self.last_speed.1
+        } else {
+            Speed::ZERO
+        }
     }
 
     pub fn get_draw_car(
@@ -225,7 +308,7 @@ impl Car {
             status: match self.state {
                 CarState::Queued { .. } => CarStatus::Moving,
                 CarState::WaitingToAdvance { .. } => CarStatus::Moving,
-                CarState::Crossing(_, _) => CarStatus::Moving,
+                CarState::Crossing(_, _, _) => CarStatus::Moving,
                 CarState::Unparking(_, _, _) => CarStatus::Moving,
                 CarState::Parking(_, _, _) => CarStatus::Moving,
                 // Changing color for idling buses is helpful
@@ -265,7 +348,9 @@ impl Car {
 /// state machine encoded here.
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub(crate) enum CarState {
-    Crossing(TimeInterval, DistanceInterval),
+    /// The speed profile is only used by the car-following models; otherwise the vehicle crosses
+    /// at a constant speed.
+    Crossing(TimeInterval, DistanceInterval, Option<SpeedProfile>),
     Queued {
         blocked_since: Time,
     },
@@ -275,13 +360,14 @@ pub(crate) enum CarState {
     /// Where's the front of the car while this is happening?
     Unparking(Distance, ParkingSpot, TimeInterval),
     Parking(Distance, ParkingSpot, TimeInterval),
+    /// A bus at a stop, or a delivery van double-parked in the lane
     IdlingAtStop(Distance, TimeInterval),
 }
 
 impl CarState {
     pub fn get_end_time(&self) -> Time {
         match self {
-            CarState::Crossing(ref time_int, _) => time_int.end,
+            CarState::Crossing(ref time_int, _, _) => time_int.end,
             CarState::Queued { .. } => unreachable!(),
             CarState::WaitingToAdvance { .. } => unreachable!(),
             CarState::Unparking(_, _, ref time_int) => time_int.end,
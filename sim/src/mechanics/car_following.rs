@@ -0,0 +1,449 @@
+//! Optional car-following models. By default, vehicles move at a constant speed while crossing a
+//! lane or turn, and the queues resolve everything else. With one of the microscopic models
+//! selected, each crossing instead follows a speed profile with realistic acceleration and
+//! deceleration, which matters for emissions and safety analysis.
+//!
+//! The profile is calculated when a vehicle starts crossing something, predicting where the
+//! vehicle in front will be from that leader's own profile. Whenever the leader changes what it's
+//! doing, the follower's profile is recalculated from wherever it is then. A vehicle that comes to
+//! a stop behind its leader finishes its crossing there, and the queue keeps it in place until the
+//! leader moves on.
+
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Duration, Speed};
+
+use crate::VehicleType;
+
+/// The time step used to integrate the models.
+const DT: f64 = 0.5;
+/// The desired time headway used by both models, in seconds.
+const REACTION_TIME: f64 = 1.0;
+/// When this close to stopping, just finish the crossing.
+const STOPPING_SLACK: f64 = 0.5;
+/// Don't crawl slower than this to finish a stop.
+const MIN_FINISHING_SPEED: f64 = 0.25;
+/// Avoid dividing by zero when a vehicle is right behind its leader.
+const EPSILON: f64 = 0.01;
+
+/// How vehicles speed up and slow down while crossing lanes and turns.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CarFollowingModel {
+    /// Vehicles instantly reach the maximum speed for whatever they're crossing. This is the
+    /// default and the fastest to simulate.
+    EventDriven,
+    /// The Intelligent Driver Model (Treiber, Hennecke, Helbing)
+    IntelligentDriver,
+    /// Krauss's safe-speed model, used by SUMO. Random dawdling is disabled to keep the
+    /// simulation deterministic.
+    Krauss,
+}
+
+/// The vehicle in front of one starting to cross something.
+#[derive(Clone, Debug)]
+pub(crate) struct Leader {
+    /// How far ahead of the follower the back of the leader is, less the following distance
+    pub gap: Distance,
+    pub speed: Speed,
+    /// If the leader is following a speed profile, how far into it they are. Otherwise, assume
+    /// they keep going at the same speed.
+    pub profile: Option<(SpeedProfile, Duration)>,
+}
+
+impl Leader {
+    /// How far ahead of the follower's start the leader will be some seconds from now, and how
+    /// fast they'll be going. After their profile ends, they keep the final speed.
+    fn predict(&self, t: f64) -> (f64, f64) {
+        let gap = self.gap.inner_meters().max(0.0);
+        match self.profile {
+            Some((ref profile, elapsed)) => {
+                let later = elapsed + Duration::seconds(t);
+                let moved = (profile.dist_at(later) - profile.dist_at(elapsed)).inner_meters();
+                let end_speed = profile.end_speed().inner_meters_per_second();
+                let after_end = (later - profile.duration()).inner_seconds().max(0.0);
+                (
+                    gap + moved + end_speed * after_end,
+                    profile.speed_at(later).inner_meters_per_second(),
+                )
+            }
+            None => {
+                let speed = self.speed.inner_meters_per_second();
+                (gap + speed * t, speed)
+            }
+        }
+    }
+}
+
+/// How a vehicle's speed changes while crossing one lane or turn. Between the breakpoints,
+/// acceleration is constant.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct SpeedProfile {
+    /// (Time since the start of the crossing, distance from the start, speed). The first point is
+    /// always at zero time and distance.
+    points: Vec<(Duration, Distance, Speed)>,
+}
+
+impl SpeedProfile {
+    /// Cross `dist`, starting at `start_speed` and never exceeding `max_speed`. If `stop_at_end`,
+    /// the vehicle comes to a stop at the end.
+    pub fn new(
+        model: CarFollowingModel,
+        vehicle_type: VehicleType,
+        start_speed: Speed,
+        max_speed: Speed,
+        dist: Distance,
+        stop_at_end: bool,
+        leader: Option<Leader>,
+    ) -> SpeedProfile {
+        let (accel, decel): (f64, f64) = match vehicle_type {
+            VehicleType::Car => (2.6, 4.5),
+            VehicleType::Van => (2.0, 4.0),
+            VehicleType::Bike => (1.2, 3.0),
+            VehicleType::Bus | VehicleType::Train => (1.2, 4.0),
+        };
+        let v_max = max_speed.inner_meters_per_second();
+        let total = dist.inner_meters();
+
+        let mut t = 0.0;
+        let mut x = 0.0;
+        let mut v = start_speed.inner_meters_per_second().min(v_max);
+        let mut points = vec![(t, x, v)];
+        while total - x > 0.0 {
+            let remaining = total - x;
+            let gap = if stop_at_end { Some(remaining) } else { None };
+            // The leader only matters until it's past the end.
+            let leader_gap = leader
+                .as_ref()
+                .map(|leader| {
+                    let (back, speed) = leader.predict(t);
+                    (back - x, speed, leader.predict(t + DT).0 - back)
+                })
+                .filter(|(gap, _, _)| *gap < remaining);
+
+            if stop_at_end && remaining < STOPPING_SLACK {
+                let dt = remaining / (v / 2.0).max(MIN_FINISHING_SPEED);
+                t += dt;
+                x = total;
+                v = 0.0;
+                points.push((t, x, v));
+                break;
+            }
+            if let Some((leader_gap, _, leader_moves)) = leader_gap {
+                if leader_moves < MIN_FINISHING_SPEED * DT && leader_gap < STOPPING_SLACK {
+                    // Right behind a leader that's (nearly) stopped and staying that way. The
+                    // queue keeps the vehicle here.
+                    x = total;
+                    v = 0.0;
+                    points.push((t, x, v));
+                    break;
+                }
+            }
+
+            let next_v = match model {
+                CarFollowingModel::EventDriven => v_max,
+                CarFollowingModel::IntelligentDriver => {
+                    let free = 1.0 - (v / v_max).powi(4);
+                    // The desired gap depends on how fast the vehicle is closing in
+                    let desired = |approach_speed: f64| {
+                        (v * REACTION_TIME + v * approach_speed / (2.0 * (accel * decel).sqrt()))
+                            .max(0.0)
+                    };
+                    let mut interaction: f64 = 0.0;
+                    // The end is a stopped obstacle
+                    if let Some(gap) = gap {
+                        interaction = (desired(v) / gap).powi(2);
+                    }
+                    if let Some((leader_gap, leader_speed, _)) = leader_gap {
+                        interaction = interaction
+                            .max((desired(v - leader_speed) / leader_gap.max(EPSILON)).powi(2));
+                    }
+                    let a = (accel * (free - interaction)).max(-2.0 * decel).min(accel);
+                    v + a * DT
+                }
+                CarFollowingModel::Krauss => {
+                    let mut next = (v + accel * DT).min(v_max);
+                    if let Some(gap) = gap {
+                        let bt = decel * REACTION_TIME;
+                        next = next.min(-bt + (bt * bt + 2.0 * decel * gap).sqrt());
+                    }
+                    if let Some((leader_gap, leader_speed, _)) = leader_gap {
+                        // The safe speed behind a moving leader
+                        next = next.min(
+                            leader_speed
+                                + (leader_gap - leader_speed * REACTION_TIME)
+                                    / ((v + leader_speed) / (2.0 * decel) + REACTION_TIME),
+                        );
+                    }
+                    next
+                }
+            }
+            .max(0.0)
+            .min(v_max);
+
+            let step_dist = (v + next_v) / 2.0 * DT;
+            if step_dist >= remaining {
+                // Finish partway through this step
+                let a = (next_v - v) / DT;
+                let dt = if a.abs() < 1e-9 {
+                    remaining / v
+                } else {
+                    (-v + (v * v + 2.0 * a * remaining).max(0.0).sqrt()) / a
+                };
+                t += dt;
+                v = (v + a * dt).max(0.0);
+                x = total;
+                points.push((t, x, v));
+                break;
+            }
+            if step_dist <= 0.0 && next_v <= 0.0 {
+                if let Some((_, _, leader_moves)) = leader_gap {
+                    v = 0.0;
+                    if leader_moves < MIN_FINISHING_SPEED * DT {
+                        // Stopped behind the leader
+                        x = total;
+                        points.push((t, x, v));
+                        break;
+                    }
+                    // Wait for the leader to pull away
+                    t += DT;
+                    points.push((t, x, v));
+                    continue;
+                }
+            }
+            if step_dist <= 0.0 && next_v <= 0.0 {
+                // Stuck at a standstill short of the end. Creep forwards to finish.
+                t += remaining / MIN_FINISHING_SPEED;
+                x = total;
+                v = 0.0;
+                points.push((t, x, v));
+                break;
+            }
+
+            t += DT;
+            x += step_dist;
+            v = next_v;
+            points.push((t, x, v));
+        }
+
+        SpeedProfile {
+            points: points
+                .into_iter()
+                .map(|(t, x, v)| {
+                    (
+                        Duration::seconds(t),
+                        Distance::meters(x),
+                        Speed::meters_per_second(v),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// How long the whole crossing takes.
+    pub fn duration(&self) -> Duration {
+        self.points.last().unwrap().0
+    }
+
+    pub fn end_speed(&self) -> Speed {
+        self.points.last().unwrap().2
+    }
+
+    /// How far along the crossing the vehicle is, some time after starting.
+    pub fn dist_at(&self, dt: Duration) -> Distance {
+        let (t, x, v, a, end_dist) = self.segment(dt);
+        Distance::meters(x.inner_meters() + v * t + 0.5 * a * t * t).min(end_dist)
+    }
+
+    /// The vehicle's speed some time after starting the crossing.
+    pub fn speed_at(&self, dt: Duration) -> Speed {
+        let (t, _, v, a, _) = self.segment(dt);
+        Speed::meters_per_second((v + a * t).max(0.0))
+    }
+
+    /// The breakpoints, relative to the start of the crossing.
+    pub fn points(&self) -> &Vec<(Duration, Distance, Speed)> {
+        &self.points
+    }
+
+    // Returns the time into the segment containing dt, the distance and speed at the start of
+    // that segment, the acceleration, and the distance at the end of the segment. Calculations are
+    // in raw units.
+    fn segment(&self, dt: Duration) -> (f64, Distance, f64, f64, Distance) {
+        for pair in self.points.windows(2) {
+            let (t1, x1, v1) = pair[0];
+            let (t2, x2, v2) = pair[1];
+            if dt < t2 {
+                let t = (dt - t1).inner_seconds().max(0.0);
+                let v1 = v1.inner_meters_per_second();
+                let seg_time = (t2 - t1).inner_seconds();
+                let a = if seg_time > 0.0 {
+                    (v2.inner_meters_per_second() - v1) / seg_time
+                } else {
+                    0.0
+                };
+                return (t, x1, v1, a, x2);
+            }
+        }
+        let (_, x, v) = *self.points.last().unwrap();
+        (0.0, x, v.inner_meters_per_second(), 0.0, x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MODELS: [CarFollowingModel; 2] = [
+        CarFollowingModel::IntelligentDriver,
+        CarFollowingModel::Krauss,
+    ];
+
+    fn check_profile(profile: &SpeedProfile, dist: Distance) {
+        for pair in profile.points().windows(2) {
+            // Time and distance never go backwards
+            assert!(pair[0].0 <= pair[1].0, "{:?}", profile);
+            assert!(pair[0].1 <= pair[1].1, "{:?}", profile);
+        }
+        assert!(profile.points().iter().all(|(_, x, _)| *x <= dist));
+        assert_eq!(profile.points().last().unwrap().1, dist);
+    }
+
+    #[test]
+    fn stop_within_the_lane() {
+        let dist = Distance::meters(100.0);
+        for model in MODELS.iter() {
+            for vehicle_type in [VehicleType::Car, VehicleType::Bike, VehicleType::Bus].iter() {
+                for start_speed in [0.0, 5.0, 15.0].iter() {
+                    let profile = SpeedProfile::new(
+                        *model,
+                        *vehicle_type,
+                        Speed::meters_per_second(*start_speed),
+                        Speed::meters_per_second(15.0),
+                        dist,
+                        true,
+                        None,
+                    );
+                    check_profile(&profile, dist);
+                    assert_eq!(profile.end_speed(), Speed::ZERO);
+                    assert!(profile.duration() > Duration::ZERO);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn free_flow() {
+        let dist = Distance::meters(100.0);
+        let max_speed = Speed::meters_per_second(10.0);
+        for model in MODELS.iter() {
+            // Already at full speed, nothing in the way
+            let profile = SpeedProfile::new(
+                *model,
+                VehicleType::Car,
+                max_speed,
+                max_speed,
+                dist,
+                false,
+                None,
+            );
+            check_profile(&profile, dist);
+            assert!((profile.duration().inner_seconds() - 10.0).abs() < 0.1);
+
+            // Starting from a standstill takes longer
+            let slow_start = SpeedProfile::new(
+                *model,
+                VehicleType::Car,
+                Speed::ZERO,
+                max_speed,
+                dist,
+                false,
+                None,
+            );
+            check_profile(&slow_start, dist);
+            assert!(slow_start.duration() > profile.duration());
+        }
+    }
+
+    #[test]
+    fn stop_behind_leader() {
+        let dist = Distance::meters(100.0);
+        let leader_gap = Distance::meters(30.0);
+        for model in MODELS.iter() {
+            let profile = SpeedProfile::new(
+                *model,
+                VehicleType::Car,
+                Speed::meters_per_second(10.0),
+                Speed::meters_per_second(15.0),
+                dist,
+                false,
+                Some(Leader {
+                    gap: leader_gap,
+                    speed: Speed::ZERO,
+                    profile: None,
+                }),
+            );
+            check_profile(&profile, dist);
+            assert_eq!(profile.end_speed(), Speed::ZERO);
+            // Until the crossing is over, the vehicle stays behind its leader
+            let before_end = profile.duration() - Duration::seconds(0.01);
+            assert!(profile.dist_at(before_end) <= leader_gap);
+        }
+    }
+
+    #[test]
+    fn follow_slower_leader() {
+        let dist = Distance::meters(100.0);
+        for model in MODELS.iter() {
+            let free = SpeedProfile::new(
+                *model,
+                VehicleType::Car,
+                Speed::meters_per_second(10.0),
+                Speed::meters_per_second(15.0),
+                dist,
+                false,
+                None,
+            );
+            let following = SpeedProfile::new(
+                *model,
+                VehicleType::Car,
+                Speed::meters_per_second(10.0),
+                Speed::meters_per_second(15.0),
+                dist,
+                false,
+                Some(Leader {
+                    gap: Distance::meters(10.0),
+                    speed: Speed::meters_per_second(5.0),
+                    profile: None,
+                }),
+            );
+            check_profile(&following, dist);
+            assert!(following.duration() > free.duration());
+
+            // Behind a leader starting from a standstill, the follower doesn't have to stop
+            let leader_profile = SpeedProfile::new(
+                *model,
+                VehicleType::Car,
+                Speed::ZERO,
+                Speed::meters_per_second(15.0),
+                Distance::meters(200.0),
+                false,
+                None,
+            );
+            let following = SpeedProfile::new(
+                *model,
+                VehicleType::Car,
+                Speed::ZERO,
+                Speed::meters_per_second(15.0),
+                dist,
+                false,
+                Some(Leader {
+                    gap: Distance::ZERO,
+                    speed: Speed::ZERO,
+                    profile: Some((leader_profile, Duration::ZERO)),
+                }),
+            );
+            check_profile(&following, dist);
+            assert!(following.end_speed() > Speed::ZERO);
+        }
+    }
+}
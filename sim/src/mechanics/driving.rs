@@ -3,11 +3,11 @@ use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_hashmap, serialize_hashmap, FixedMap, IndexableKey};
-use geom::{Distance, Duration, PolyLine, Time};
-use map_model::{IntersectionID, LaneID, Map, Path, Position, Traversable};
+use geom::{Distance, Duration, PolyLine, Speed, Time};
+use map_model::{IntersectionID, LaneID, Map, Path, Position, Traversable, TurnID};
 
 use crate::mechanics::car::{Car, CarState};
-use crate::mechanics::Queue;
+use crate::mechanics::{CarFollowingModel, Leader, Queue};
 use crate::sim::Ctx;
 use crate::{
     ActionAtEnd, AgentID, AgentProperties, CarID, Command, CreateCar, DelayCause, DistanceInterval,
@@ -16,7 +16,11 @@ use crate::{
     Vehicle, VehicleType, WalkingSimState, FOLLOWING_DISTANCE,
 };
 
-const TIME_TO_WAIT_AT_BUS_STOP: Duration = Duration::const_seconds(10.0);
+// How far back from the stop line the loop detectors for actuated signals reach. This is longer
+// than a real stop-bar detector, because signals only check them at the end of each passage time.
+const DETECTOR_LENGTH: Distance = Distance::const_meters(30.0);
+// Transit signal priority only notices buses and trains this close to the stop line.
+const TRANSIT_DETECTION_DISTANCE: Distance = Distance::const_meters(100.0);
 
 // TODO Do something else.
 pub const BLIND_RETRY_TO_CREEP_FORWARDS: Duration = Duration::const_seconds(0.1);
@@ -44,11 +48,14 @@ pub(crate) struct DrivingSimState {
 
     recalc_lanechanging: bool,
     handle_uber_turns: bool,
+    car_following: CarFollowingModel,
 
     time_to_unpark_onstreet: Duration,
     time_to_park_onstreet: Duration,
     time_to_unpark_offstreet: Duration,
     time_to_park_offstreet: Duration,
+    /// How long a delivery van stops, whether in a loading zone or double-parked
+    time_to_deliver: Duration,
 }
 
 // Mutations
@@ -60,12 +67,14 @@ impl DrivingSimState {
             events: Vec::new(),
             recalc_lanechanging: opts.recalc_lanechanging,
             handle_uber_turns: opts.handle_uber_turns,
+            car_following: opts.car_following,
             waiting_to_spawn: BTreeMap::new(),
 
             time_to_unpark_onstreet: Duration::seconds(10.0),
             time_to_park_onstreet: Duration::seconds(15.0),
             time_to_unpark_offstreet: Duration::seconds(5.0),
             time_to_park_offstreet: Duration::seconds(5.0),
+            time_to_deliver: Duration::minutes(5),
         };
         if opts.infinite_parking {
             sim.time_to_unpark_offstreet = Duration::seconds(0.1);
@@ -121,6 +130,8 @@ impl DrivingSimState {
                 total_blocked_time: Duration::ZERO,
                 trip_and_person: params.trip_and_person,
                 wants_to_overtake: BTreeSet::new(),
+                car_following: self.car_following,
+                last_speed: (now, Speed::ZERO),
             };
             if let Some(p) = params.maybe_parked_car {
                 let delay = match p.spot {
@@ -135,6 +146,7 @@ impl DrivingSimState {
                 // Have to do this early
                 if car.router.last_step() {
                     match car.router.maybe_handle_end(
+                        now,
                         start_dist,
                         &car.vehicle,
                         ctx.parking,
@@ -168,7 +180,17 @@ impl DrivingSimState {
                     }
                 }
 
-                car.state = car.crossing_state(start_dist, now, ctx.map);
+                let leader = if idx == 0 {
+                    None
+                } else {
+                    Some(self.queues[&Traversable::Lane(first_lane)].cars[idx - 1])
+                };
+                car.state = car.crossing_state(
+                    start_dist,
+                    now,
+                    self.leader_of(Traversable::Lane(first_lane), leader, start_dist, now),
+                    ctx.map,
+                );
             }
             ctx.scheduler
                 .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
@@ -221,6 +243,7 @@ impl DrivingSimState {
         transit: &mut TransitSimState,
         walking: &mut WalkingSimState,
     ) {
+        let old_queue = self.cars[&id].router.head();
         let mut need_distances = {
             let car = &self.cars[&id];
             match car.state {
@@ -260,6 +283,52 @@ impl DrivingSimState {
                 self.delete_car_internal(&mut car, dists, idx, now, ctx);
             }
         }
+
+        if self.car_following != CarFollowingModel::EventDriven && self.cars.contains_key(&id) {
+            self.update_follower_profile(old_queue, id, now, ctx);
+        }
+    }
+
+    /// With a car-following model, a follower plans its speed around what its leader is doing.
+    /// Whenever the leader changes state or leaves the queue, recalculate the follower's profile
+    /// from where it is now.
+    fn update_follower_profile(
+        &mut self,
+        on: Traversable,
+        leader: CarID,
+        now: Time,
+        ctx: &mut Ctx,
+    ) {
+        let (follower, front, new_leader) = {
+            let queue = &self.queues[&on];
+            let follower = match queue.cars.iter().position(|c| *c == leader) {
+                Some(idx) => queue.cars.get(idx + 1),
+                // The leader moved on
+                None => queue.cars.front(),
+            };
+            let follower = match follower {
+                Some(id) => *id,
+                None => return,
+            };
+            match self.cars[&follower].state {
+                // Don't recalculate something that just started
+                CarState::Crossing(ref time_int, _, Some(_)) if time_int.start < now => {}
+                _ => return,
+            }
+            let front = queue
+                .get_car_position(follower, now, &self.cars, &self.queues)
+                .unwrap();
+            (follower, front, queue.get_leader(follower))
+        };
+        let leader = self.leader_of(on, new_leader, front, now);
+
+        let car = self.cars.get_mut(&follower).unwrap();
+        if let Some(points) = car.speed_profile_until(now) {
+            self.events.push(Event::CarSpeedProfile(follower, points));
+        }
+        car.state = car.crossing_state(front, now, leader, ctx.map);
+        ctx.scheduler
+            .update(car.state.get_end_time(), Command::UpdateCar(follower));
     }
 
     // If this returns true, we need to immediately run update_car_with_distances. If we don't,
@@ -273,7 +342,12 @@ impl DrivingSimState {
         transit: &mut TransitSimState,
     ) -> bool {
         match car.state {
-            CarState::Crossing(_, _) => {
+            CarState::Crossing(_, _, _) => {
+                if let Some(points) = car.speed_profile_until(now) {
+                    car.last_speed = (now, points.last().map(|(_, s)| *s).unwrap_or(Speed::ZERO));
+                    self.events
+                        .push(Event::CarSpeedProfile(car.vehicle.id, points));
+                }
                 car.state = CarState::Queued { blocked_since: now };
                 if car.router.last_step() {
                     // Immediately run update_car_with_distances.
@@ -314,6 +388,7 @@ impl DrivingSimState {
                     // the next loop will pick that up. Just trigger the side effect of choosing an
                     // end_dist.
                     car.router.maybe_handle_end(
+                        now,
                         front,
                         &car.vehicle,
                         ctx.parking,
@@ -322,15 +397,28 @@ impl DrivingSimState {
                         &mut self.events,
                     );
                 }
-                car.state = car.crossing_state(front, now, ctx.map);
+                let leader = self.leader_of_car(car, front, now);
+                car.state = car.crossing_state(front, now, leader, ctx.map);
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
             }
             CarState::IdlingAtStop(dist, _) => {
-                car.router = transit.bus_departed_from_stop(car.vehicle.id, ctx.map);
-                self.events
-                    .push(Event::PathAmended(car.router.get_path().clone()));
-                car.state = car.crossing_state(dist, now, ctx.map);
+                // A double-parked delivery van already knows where it's headed next
+                if car.vehicle.vehicle_type.is_transit() {
+                    // Wait for anybody who showed up while the doors were open
+                    if let Some(until) = transit.still_boarding(car.vehicle.id, now) {
+                        car.state = CarState::IdlingAtStop(dist, TimeInterval::new(now, until));
+                        ctx.scheduler
+                            .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                        return false;
+                    }
+
+                    car.router = transit.bus_departed_from_stop(car.vehicle.id, ctx.map);
+                    self.events
+                        .push(Event::PathAmended(car.router.get_path().clone()));
+                }
+                let leader = self.leader_of_car(car, dist, now);
+                car.state = car.crossing_state(dist, now, leader, ctx.map);
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
 
@@ -342,6 +430,15 @@ impl DrivingSimState {
                     .position(|c| *c == car.vehicle.id)
                     .unwrap();
                 if idx != queue.cars.len() - 1 {
+                    // We just started moving, right in front of them
+                    let leader = match car.state {
+                        CarState::Crossing(_, _, Some(ref profile)) => Some(Leader {
+                            gap: Distance::ZERO,
+                            speed: Speed::ZERO,
+                            profile: Some((profile.clone(), Duration::ZERO)),
+                        }),
+                        _ => None,
+                    };
                     let mut follower = self.cars.get_mut(&queue.cars[idx + 1]).unwrap();
                     match follower.state {
                         CarState::Queued { blocked_since } => {
@@ -353,6 +450,7 @@ impl DrivingSimState {
                                     // Since the follower was Queued, this must be where they are.
                                     dist - car.vehicle.length - FOLLOWING_DISTANCE,
                                     now,
+                                    leader,
                                     ctx.map,
                                 );
                                 ctx.scheduler.update(
@@ -364,7 +462,7 @@ impl DrivingSimState {
                         CarState::WaitingToAdvance { .. } => unreachable!(),
                         // They weren't blocked. Note that there's no way the Crossing state could
                         // jump forwards here; the leader is still in front of them.
-                        CarState::Crossing(_, _)
+                        CarState::Crossing(_, _, _)
                         | CarState::Unparking(_, _, _)
                         | CarState::Parking(_, _, _)
                         | CarState::IdlingAtStop(_, _) => {}
@@ -415,6 +513,7 @@ impl DrivingSimState {
                 // way, until laggy_head is None.
 
                 let last_step = car.router.advance(
+                    now,
                     &car.vehicle,
                     ctx.parking,
                     ctx.map,
@@ -422,7 +521,13 @@ impl DrivingSimState {
                     &mut self.events,
                 );
                 car.total_blocked_time += now - blocked_since;
-                car.state = car.crossing_state(Distance::ZERO, now, ctx.map);
+                let leader = self.leader_of(
+                    goto,
+                    self.queues[&goto].cars.back().cloned(),
+                    Distance::ZERO,
+                    now,
+                );
+                car.state = car.crossing_state(Distance::ZERO, now, leader, ctx.map);
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                 self.events.push(Event::AgentEntersTraversable(
@@ -449,6 +554,7 @@ impl DrivingSimState {
                             car.vehicle.length + FOLLOWING_DISTANCE,
                         ),
                         now,
+                        None,
                         ctx.map,
                     )
                     .get_end_time(),
@@ -481,12 +587,13 @@ impl DrivingSimState {
         let our_dist = dists[idx].1;
 
         match car.state {
-            CarState::Crossing(_, _)
+            CarState::Crossing(_, _, _)
             | CarState::Unparking(_, _, _)
             | CarState::IdlingAtStop(_, _)
             | CarState::WaitingToAdvance { .. } => unreachable!(),
             CarState::Queued { blocked_since } => {
                 match car.router.maybe_handle_end(
+                    now,
                     our_dist,
                     &car.vehicle,
                     ctx.parking,
@@ -532,6 +639,13 @@ impl DrivingSimState {
                         };
                         car.state =
                             CarState::Parking(our_dist, spot, TimeInterval::new(now, now + delay));
+                        if let Some(dist) = car.router.cruising_distance(our_dist) {
+                            self.events.push(Event::CarFinishedCruising(
+                                car.vehicle.id,
+                                car.trip_and_person.map(|(t, _)| t),
+                                dist,
+                            ));
+                        }
                         // If we don't do this, then we might have another car creep up behind, see
                         // the spot free, and start parking too. This can happen with multiple
                         // lanes and certain vehicle lengths.
@@ -542,7 +656,8 @@ impl DrivingSimState {
                     }
                     Some(ActionAtEnd::GotoLaneEnd) => {
                         car.total_blocked_time += now - blocked_since;
-                        car.state = car.crossing_state(our_dist, now, ctx.map);
+                        let leader = self.leader_of_car(car, our_dist, now);
+                        car.state = car.crossing_state(our_dist, now, leader, ctx.map);
                         ctx.scheduler
                             .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                         true
@@ -561,10 +676,12 @@ impl DrivingSimState {
                     }
                     Some(ActionAtEnd::BusAtStop) => {
                         car.total_blocked_time += now - blocked_since;
-                        if transit.bus_arrived_at_stop(now, car.vehicle.id, trips, walking, ctx) {
+                        if let Some(dwell_time) =
+                            transit.bus_arrived_at_stop(now, car.vehicle.id, trips, walking, ctx)
+                        {
                             car.state = CarState::IdlingAtStop(
                                 our_dist,
-                                TimeInterval::new(now, now + TIME_TO_WAIT_AT_BUS_STOP),
+                                TimeInterval::new(now, now + dwell_time),
                             );
                             ctx.scheduler
                                 .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
@@ -574,6 +691,66 @@ impl DrivingSimState {
                             false
                         }
                     }
+                    Some(ActionAtEnd::StartDelivery(maybe_spot)) => {
+                        car.total_blocked_time += now - blocked_since;
+                        let lane = car.router.head().as_lane();
+                        self.events.push(Event::DeliveryStop(
+                            car.vehicle.id,
+                            lane,
+                            maybe_spot.is_none(),
+                        ));
+                        if let Some(spot) = maybe_spot {
+                            // Pull into the loading zone, just like parking
+                            car.state = CarState::Parking(
+                                our_dist,
+                                spot,
+                                TimeInterval::new(now, now + self.time_to_park_onstreet),
+                            );
+                            ctx.parking.reserve_spot(spot, car.vehicle.id);
+                            ctx.scheduler
+                                .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                            return true;
+                        }
+
+                        // Double-park. The van stays in the queue while it idles, blocking
+                        // everybody behind it.
+                        match car
+                            .router
+                            .leave_after_delivery(Position::new(lane, our_dist), ctx.map)
+                        {
+                            Ok(router) => {
+                                // Record the first half of the trip before switching routes
+                                trips.van_stopped_for_delivery(
+                                    car.vehicle.id,
+                                    car.total_blocked_time,
+                                    car.router.get_path().total_length(),
+                                );
+                                car.total_blocked_time = Duration::ZERO;
+                                car.router = router;
+                                self.events
+                                    .push(Event::PathAmended(car.router.get_path().clone()));
+                                car.state = CarState::IdlingAtStop(
+                                    our_dist,
+                                    TimeInterval::new(now, now + self.time_to_deliver),
+                                );
+                                ctx.scheduler.push(
+                                    car.state.get_end_time(),
+                                    Command::UpdateCar(car.vehicle.id),
+                                );
+                                true
+                            }
+                            Err(err) => {
+                                trips.cancel_trip(
+                                    now,
+                                    car.trip_and_person.unwrap().0,
+                                    err.to_string(),
+                                    None,
+                                    ctx,
+                                );
+                                false
+                            }
+                        }
+                    }
                     None => {
                         ctx.scheduler.push(
                             now + BLIND_RETRY_TO_REACH_END_DIST,
@@ -585,7 +762,7 @@ impl DrivingSimState {
                         /*
                         // If this car wasn't blocked at all, when would it reach its goal?
                         let ideal_end_time = match car.crossing_state(our_dist, now, map) {
-                            CarState::Crossing(time_int, _) => time_int.end,
+                            CarState::Crossing(time_int, _, _) => time_int.end,
                             _ => unreachable!(),
                         };
                         if ideal_end_time == now {
@@ -604,11 +781,40 @@ impl DrivingSimState {
                 }
             }
             CarState::Parking(_, spot, _) => {
-                ctx.parking.add_parked_car(ParkedCar {
+                let parked_car = ParkedCar {
                     vehicle: car.vehicle.clone(),
                     spot,
                     parked_since: now,
-                });
+                };
+                if car.router.is_delivering() {
+                    // The van waits in the loading zone, then heads out. The trip continues the
+                    // whole time.
+                    let (trip, person) = car.trip_and_person.unwrap();
+                    let start = ctx.parking.spot_to_driving_pos(spot, &car.vehicle, ctx.map);
+                    match car.router.leave_after_delivery(start, ctx.map) {
+                        Ok(router) => {
+                            trips.van_stopped_for_delivery(
+                                car.vehicle.id,
+                                car.total_blocked_time,
+                                car.router.get_path().total_length(),
+                            );
+                            ctx.parking.add_parked_car(parked_car.clone());
+                            ctx.scheduler.push(
+                                now + self.time_to_deliver,
+                                Command::SpawnCar(
+                                    CreateCar::for_parked_car(parked_car, router, trip, person),
+                                    true,
+                                ),
+                            );
+                        }
+                        Err(err) => {
+                            ctx.parking.unreserve_spot(car.vehicle.id);
+                            trips.cancel_trip(now, trip, err.to_string(), None, ctx);
+                        }
+                    }
+                    return false;
+                }
+                ctx.parking.add_parked_car(parked_car);
                 trips.car_reached_parking_spot(
                     now,
                     car.vehicle.id,
@@ -669,6 +875,9 @@ impl DrivingSimState {
         now: Time,
         ctx: &mut Ctx,
     ) {
+        if self.car_following != CarFollowingModel::EventDriven {
+            self.events.push(Event::VehicleDespawned(car.vehicle.id));
+        }
         {
             let queue = self.queues.get_mut(&car.router.head()).unwrap();
             assert_eq!(queue.cars.remove(idx).unwrap(), car.vehicle.id);
@@ -704,6 +913,16 @@ impl DrivingSimState {
                 }
             }
 
+            let leader = self.leader_of(
+                car.router.head(),
+                if idx == 0 {
+                    None
+                } else {
+                    Some(dists[idx - 1].0)
+                },
+                follower_dist,
+                now,
+            );
             let mut follower = self.cars.get_mut(&follower_id).unwrap();
             // TODO If the leader vanished at a border node, this still jumps a bit -- the lead
             // car's back is still sticking out. Need to still be bound by them, even though they
@@ -712,16 +931,22 @@ impl DrivingSimState {
                 CarState::Queued { blocked_since } => {
                     // Prevent them from jumping forwards.
                     follower.total_blocked_time += now - blocked_since;
-                    follower.state = follower.crossing_state(follower_dist, now, ctx.map);
+                    follower.state = follower.crossing_state(follower_dist, now, leader, ctx.map);
                     ctx.scheduler.update(
                         follower.state.get_end_time(),
                         Command::UpdateCar(follower_id),
                     );
                 }
-                CarState::Crossing(_, _) => {
+                CarState::Crossing(_, _, _) => {
                     // If the follower was still Crossing, they might not've been blocked by leader
                     // yet. In that case, recalculating their Crossing state is a no-op.
-                    follower.state = follower.crossing_state(follower_dist, now, ctx.map);
+                    // Car-following models will calculate a new speed profile from here, so keep
+                    // what happened so far.
+                    if let Some(points) = follower.speed_profile_until(now) {
+                        self.events
+                            .push(Event::CarSpeedProfile(follower_id, points));
+                    }
+                    follower.state = follower.crossing_state(follower_dist, now, leader, ctx.map);
                     ctx.scheduler.update(
                         follower.state.get_end_time(),
                         Command::UpdateCar(follower_id),
@@ -782,6 +1007,7 @@ impl DrivingSimState {
                         self.cars[&id].vehicle.length + FOLLOWING_DISTANCE,
                     ),
                     now,
+                    None,
                     ctx.map,
                 )
                 .get_end_time();
@@ -862,7 +1088,7 @@ impl DrivingSimState {
                         CarState::WaitingToAdvance { .. } => unreachable!(),
                         // They weren't blocked. Note that there's no way the Crossing state could
                         // jump forwards here; the leader vanished from the end of the traversable.
-                        CarState::Crossing(_, _)
+                        CarState::Crossing(_, _, _)
                         | CarState::Unparking(_, _, _)
                         | CarState::Parking(_, _, _)
                         | CarState::IdlingAtStop(_, _) => {}
@@ -1167,6 +1393,49 @@ impl DrivingSimState {
         }
     }
 
+    /// Which of an intersection's incoming lanes have a vehicle over their loop detector?
+    pub fn occupied_detectors(&self, now: Time, i: IntersectionID, map: &Map) -> BTreeSet<LaneID> {
+        let mut lanes = BTreeSet::new();
+        for l in &map.get_i(i).incoming_lanes {
+            // Sidewalks don't have queues
+            if let Some(queue) = self.queues.get(&Traversable::Lane(*l)) {
+                if queue.is_detector_occupied(now, &self.cars, &self.queues, DETECTOR_LENGTH) {
+                    lanes.insert(*l);
+                }
+            }
+        }
+        lanes
+    }
+
+    /// Which turns at an intersection do buses and trains close to the stop line plan to make?
+    /// Vehicles about to stop somewhere on the lane, like at a bus stop, aren't approaching yet.
+    pub fn approaching_transit(&self, now: Time, i: IntersectionID, map: &Map) -> BTreeSet<TurnID> {
+        let mut turns = BTreeSet::new();
+        for l in &map.get_i(i).incoming_lanes {
+            if let Some(queue) = self.queues.get(&Traversable::Lane(*l)) {
+                for (id, dist) in queue.get_car_positions(now, &self.cars, &self.queues) {
+                    if dist < queue.geom_len - TRANSIT_DETECTION_DISTANCE {
+                        // Cars are ordered from the stop line back
+                        break;
+                    }
+                    let car = &self.cars[&id];
+                    if car.vehicle.vehicle_type != VehicleType::Bus
+                        && car.vehicle.vehicle_type != VehicleType::Train
+                    {
+                        continue;
+                    }
+                    if car.router.last_step() {
+                        continue;
+                    }
+                    if let Traversable::Turn(t) = car.router.next() {
+                        turns.insert(t);
+                    }
+                }
+            }
+        }
+        turns
+    }
+
     pub fn debug_queue_lengths(&self, l: LaneID) -> Option<(Distance, Distance)> {
         let queue = self.queues.get(&Traversable::Lane(l))?;
         Some((queue.reserved_length, queue.geom_len))
@@ -1209,6 +1478,40 @@ impl DrivingSimState {
         graph
     }
 
+    /// For the car-following models, describe the vehicle in front of something starting to cross
+    /// `on` at `front`.
+    fn leader_of(
+        &self,
+        on: Traversable,
+        leader: Option<CarID>,
+        front: Distance,
+        now: Time,
+    ) -> Option<Leader> {
+        if self.car_following == CarFollowingModel::EventDriven {
+            return None;
+        }
+        let leader = &self.cars[&leader?];
+        let leader_front =
+            self.queues[&on].get_car_position(leader.vehicle.id, now, &self.cars, &self.queues)?;
+        let profile = match leader.state {
+            CarState::Crossing(ref time_int, _, Some(ref profile)) if now >= time_int.start => {
+                Some((profile.clone(), now - time_int.start))
+            }
+            _ => None,
+        };
+        Some(Leader {
+            gap: leader_front - leader.vehicle.length - FOLLOWING_DISTANCE - front,
+            speed: leader.current_speed(now),
+            profile,
+        })
+    }
+
+    /// Like leader_of, for a vehicle already in the queue it's about to cross.
+    fn leader_of_car(&self, car: &Car, front: Distance, now: Time) -> Option<Leader> {
+        let on = car.router.head();
+        self.leader_of(on, self.queues[&on].get_leader(car.vehicle.id), front, now)
+    }
+
     fn get_car_front(&self, now: Time, car: &Car) -> Distance {
         self.queues[&car.router.head()]
             .get_car_positions(now, &self.cars, &self.queues)
@@ -7,18 +7,22 @@ use abstutil::{
 };
 use geom::{Duration, Time};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, LaneID, Map, StageType,
-    Traversable, TurnID, TurnPriority, TurnType, UberTurn,
+    ControlStopSign, ControlTrafficSignal, CrossingType, Intersection, IntersectionID, LaneID, Map,
+    Stage, StageType, TransitPriority, Traversable, TurnID, TurnPriority, TurnType, UberTurn,
 };
 
 use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::Queue;
 use crate::{
     AgentID, AlertLocation, CarID, Command, DelayCause, Event, Scheduler, SimOptions, Speed,
+    VehicleType,
 };
 
 const WAIT_AT_STOP_SIGN: Duration = Duration::const_seconds(0.5);
 const WAIT_BEFORE_YIELD_AT_TRAFFIC_SIGNAL: Duration = Duration::const_seconds(0.2);
+// While a bus or train keeps approaching, transit signal priority holds the green in steps this
+// long.
+const TRANSIT_PRIORITY_EXTENSION_STEP: Duration = Duration::const_seconds(2.0);
 
 /// Manages conflicts at intersections. When an agent has reached the end of a lane, they call
 /// maybe_start_turn to make a Request. Based on the intersection type (stop sign, traffic signal,
@@ -74,12 +78,18 @@ struct State {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 struct SignalState {
+    // Which timing plan is in effect. 0 is the signal's base plan, then later_plans follow.
+    current_plan: usize,
     // The current stage of the signal, zero based
     current_stage: usize,
     // The time when the signal is checked for advancing
     stage_ends_at: Time,
     // The number of times a variable signal has been extended during the current stage.
     extensions_count: usize,
+    // Transit signal priority: how long the current stage has been held green for buses and
+    // trains, and whether it was cut short for one.
+    priority_extension: Duration,
+    priority_truncated: bool,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Debug)]
@@ -223,8 +233,8 @@ impl IntersectionSimState {
                 protected.push(req);
             }
         } else if let Some(ref signal) = map.maybe_get_traffic_signal(i) {
-            let current_stage = self.state[&i].signal.as_ref().unwrap().current_stage;
-            let stage = &signal.stages[current_stage];
+            let signal_state = self.state[&i].signal.as_ref().unwrap();
+            let stage = &signal.plan_stages(signal_state.current_plan)[signal_state.current_stage];
             let reserved = &self.state[&i].reserved;
             for (req, _, _) in all {
                 match stage.get_priority_of_turn(req.turn, signal) {
@@ -275,51 +285,91 @@ impl IntersectionSimState {
         }
     }
 
-    /// This is only triggered for traffic signals.
+    /// This is only triggered for traffic signals. `detectors` lists the incoming lanes with a
+    /// vehicle over their loop detector; it only matters for actuated signals. `transit` lists the
+    /// turns that approaching buses and trains want to make; it only matters for signals with
+    /// transit priority.
     pub fn update_intersection(
         &mut self,
         now: Time,
         id: IntersectionID,
         map: &Map,
         scheduler: &mut Scheduler,
+        detectors: &BTreeSet<LaneID>,
+        transit: &BTreeSet<TurnID>,
     ) {
-        // trivial function that advances the signal stage and returns duration
+        // advances the signal stage, skipping stages that don't need to run, and returns duration
         fn advance(
             signal_state: &mut SignalState,
             signal: &ControlTrafficSignal,
             allow_crosswalk_skip: bool,
+            has_call: &dyn Fn(&Stage) -> bool,
         ) -> Duration {
-            signal_state.current_stage = (signal_state.current_stage + 1) % signal.stages.len();
-            let stage = &signal.stages[signal_state.current_stage];
-            // only skip for variable all-walk crosswalk
-            if let StageType::Variable(_, _, _) = stage.stage_type {
-                if allow_crosswalk_skip && stage.max_crosswalk_time(&signal.movements).is_some() {
-                    // we can skip this stage, as its all walk and we're allowed to skip (no
-                    // pedestrian waiting).
-                    signal_state.current_stage =
-                        (signal_state.current_stage + 1) % signal.stages.len();
-                }
-            }
-            signal.stages[signal_state.current_stage]
+            let stages = signal.plan_stages(signal_state.current_plan);
+            signal_state.priority_extension = Duration::ZERO;
+            signal_state.priority_truncated = false;
+            let stage_types: Vec<StageType> = stages.iter().map(|s| s.stage_type.clone()).collect();
+            signal_state.current_stage = next_stage(
+                &stage_types,
+                signal_state.current_stage,
+                &|idx| {
+                    allow_crosswalk_skip
+                        && stages[idx].max_crosswalk_time(&signal.movements).is_some()
+                },
+                &|idx| has_call(&stages[idx]),
+            );
+            stages[signal_state.current_stage]
                 .stage_type
                 .simple_duration()
         }
         let state = self.state.get_mut(&id).unwrap();
+        let waiting = &state.waiting;
         let signal_state = state.signal.as_mut().unwrap();
         let signal = map.get_traffic_signal(id);
-        let ped_waiting = state.waiting.keys().any(|req| {
+        assert_eq!(now, signal_state.stage_ends_at);
+        // A different timing plan takes over now. Start it fresh, picking the stage from the new
+        // plan's offset.
+        if signal.plan_idx_at(now) != signal_state.current_plan {
+            *signal_state = SignalState::new(id, now, map, scheduler);
+            self.wakeup_waiting(now, id, scheduler, map);
+            return;
+        }
+        let ped_waiting = waiting.keys().any(|req| {
             if let AgentID::Pedestrian(_) = req.agent {
                 return true;
             }
             false
         });
+        let has_call = |stage: &Stage| stage_has_call(stage, signal, detectors, waiting, true);
         let duration: Duration;
         // Switch to a new stage?
-        assert_eq!(now, signal_state.stage_ends_at);
-        let old_stage = &signal.stages[signal_state.current_stage];
+        let old_stage = &signal.plan_stages(signal_state.current_plan)[signal_state.current_stage];
+
+        // Transit signal priority: hold the green for a bus or train about to use it.
+        if let Some(tsp) = signal.transit_priority {
+            if !signal_state.priority_truncated
+                && signal_state.priority_extension < tsp.max_extension
+                && transit
+                    .iter()
+                    .any(|t| old_stage.get_priority_of_turn(*t, signal) == TurnPriority::Protected)
+            {
+                let extension = std::cmp::min(
+                    TRANSIT_PRIORITY_EXTENSION_STEP,
+                    tsp.max_extension - signal_state.priority_extension,
+                );
+                signal_state.priority_extension += extension;
+                signal_state.stage_ends_at = now + extension;
+                if let Some(t) = signal.next_plan_change(now) {
+                    signal_state.stage_ends_at = signal_state.stage_ends_at.min(t);
+                }
+                scheduler.push(signal_state.stage_ends_at, Command::UpdateIntersection(id));
+                return;
+            }
+        }
+
         match old_stage.stage_type {
             StageType::Fixed(_) => {
-                duration = advance(signal_state, signal, !ped_waiting);
+                duration = advance(signal_state, signal, !ped_waiting, &has_call);
             }
             StageType::Variable(min, delay, additional) => {
                 // test if anyone is waiting in current stage, and if so, extend the signal cycle.
@@ -337,9 +387,9 @@ impl IntersectionSimState {
                             min, delay, additional, signal_state.extensions_count
                         ),
                     ));
-                    duration = advance(signal_state, signal, !ped_waiting);
+                    duration = advance(signal_state, signal, !ped_waiting, &has_call);
                     signal_state.extensions_count = 0;
-                } else if state.waiting.keys().all(|req| {
+                } else if waiting.keys().all(|req| {
                     if let AgentID::Pedestrian(_) = req.agent {
                         return true;
                     }
@@ -348,7 +398,7 @@ impl IntersectionSimState {
                     old_stage.get_priority_of_turn(req.turn, signal) != TurnPriority::Protected
                 }) {
                     signal_state.extensions_count = 0;
-                    duration = advance(signal_state, signal, !ped_waiting);
+                    duration = advance(signal_state, signal, !ped_waiting, &has_call);
                 } else {
                     signal_state.extensions_count += 1;
                     duration = delay;
@@ -361,9 +411,30 @@ impl IntersectionSimState {
                     ));
                 }
             }
+            StageType::Actuated(min, max, passage) => {
+                let passage = std::cmp::max(Duration::const_seconds(1.0), passage);
+                let green_so_far = min + passage * (signal_state.extensions_count as f64);
+                if green_so_far >= max {
+                    // Max-out
+                    signal_state.extensions_count = 0;
+                    duration = advance(signal_state, signal, !ped_waiting, &has_call);
+                } else if stage_has_call(old_stage, signal, detectors, waiting, false) {
+                    // Pedestrians have had their chance already, so they don't extend the green.
+                    signal_state.extensions_count += 1;
+                    duration = std::cmp::min(passage, max - green_so_far);
+                } else {
+                    // Gap-out
+                    signal_state.extensions_count = 0;
+                    duration = advance(signal_state, signal, !ped_waiting, &has_call);
+                }
+            }
         }
 
         signal_state.stage_ends_at = now + duration;
+        // Cut the stage short if the plan changes during it
+        if let Some(t) = signal.next_plan_change(now) {
+            signal_state.stage_ends_at = signal_state.stage_ends_at.min(t);
+        }
         scheduler.push(signal_state.stage_ends_at, Command::UpdateIntersection(id));
         self.wakeup_waiting(now, id, scheduler, map);
     }
@@ -581,7 +652,13 @@ impl IntersectionSimState {
                 state.signal.as_mut(),
             ) {
                 (Some(ts), Some(signal_state)) => {
-                    if signal_state.current_stage >= ts.stages.len() {
+                    if ts.plan_idx_at(now) != signal_state.current_plan {
+                        // The timing plans were edited. Restart with whichever one is in effect.
+                        scheduler.cancel(Command::UpdateIntersection(state.id));
+                        *signal_state = SignalState::new(state.id, now, map, scheduler);
+                    } else if signal_state.current_stage
+                        >= ts.plan_stages(signal_state.current_plan).len()
+                    {
                         // Just jump back to the first one. Shrug.
                         signal_state.current_stage = 0;
                         println!(
@@ -816,6 +893,21 @@ impl IntersectionSimState {
             return false;
         }
 
+        // At a zebra crossing, vehicles have to let pedestrians waiting at the curb go first.
+        // Nobody needs to be woken up; when the pedestrians finish crossing, everyone waiting here
+        // gets another chance.
+        if let AgentID::Car(_) = req.agent {
+            if map.get_i(req.turn.parent).crossing == Some(CrossingType::Zebra) {
+                let turn = map.get_t(req.turn);
+                if self.state[&req.turn.parent].waiting.keys().any(|other| {
+                    matches!(other.agent, AgentID::Pedestrian(_))
+                        && map.get_t(other.turn).conflicts_with(turn)
+                }) {
+                    return false;
+                }
+            }
+        }
+
         // Once upon a time, we'd make sure that this request doesn't conflict with another in
         // self.waiting:
         // 1) Higher-ranking turns get to go first.
@@ -848,7 +940,7 @@ impl IntersectionSimState {
 
         let state = &self.state[&req.turn.parent];
         let signal_state = state.signal.as_ref().unwrap();
-        let stage = &signal.stages[signal_state.current_stage];
+        let stage = &signal.plan_stages(signal_state.current_plan)[signal_state.current_stage];
         let full_stage_duration = stage.stage_type.simple_duration();
         let remaining_stage_time = signal_state.stage_ends_at - now;
         let (our_time, _) = state.waiting[req];
@@ -856,6 +948,15 @@ impl IntersectionSimState {
         // Can't go at all this stage.
         let our_priority = stage.get_priority_of_turn(req.turn, signal);
         if our_priority == TurnPriority::Banned {
+            if let (Some(tsp), Some(scheduler)) = (signal.transit_priority, scheduler) {
+                if let AgentID::Car(car) = req.agent {
+                    if car.vehicle_type == VehicleType::Bus
+                        || car.vehicle_type == VehicleType::Train
+                    {
+                        self.truncate_stage_for_transit(req.turn.parent, tsp, now, scheduler);
+                    }
+                }
+            }
             return false;
         }
 
@@ -897,6 +998,29 @@ impl IntersectionSimState {
         true
     }
 
+    // Transit signal priority: a bus or train is waiting for a movement the current stage doesn't
+    // allow, so end the stage early. Each stage is cut short at most once, and never after being
+    // held green for another bus.
+    fn truncate_stage_for_transit(
+        &mut self,
+        id: IntersectionID,
+        tsp: TransitPriority,
+        now: Time,
+        scheduler: &mut Scheduler,
+    ) {
+        let signal_state = self.state.get_mut(&id).unwrap().signal.as_mut().unwrap();
+        if signal_state.priority_truncated || signal_state.priority_extension > Duration::ZERO {
+            return;
+        }
+        let remaining = signal_state.stage_ends_at - now;
+        let new_end = now + std::cmp::max(Duration::ZERO, remaining - tsp.max_truncation);
+        if new_end < signal_state.stage_ends_at {
+            signal_state.stage_ends_at = new_end;
+            signal_state.priority_truncated = true;
+            scheduler.update(new_end, Command::UpdateIntersection(id));
+        }
+    }
+
     // If true, the request can go.
     fn handle_accepted_conflicts(
         &mut self,
@@ -1028,23 +1152,25 @@ impl IntersectionSimState {
 
 impl SignalState {
     fn new(id: IntersectionID, now: Time, map: &Map, scheduler: &mut Scheduler) -> SignalState {
+        let signal = map.get_traffic_signal(id);
         let mut state = SignalState {
+            current_plan: signal.plan_idx_at(now),
             current_stage: 0,
             stage_ends_at: now,
             extensions_count: 0,
+            priority_extension: Duration::ZERO,
+            priority_truncated: false,
         };
 
-        let signal = map.get_traffic_signal(id);
+        let stages = signal.plan_stages(state.current_plan);
         // What stage are we starting with?
-        let mut offset = (now - Time::START_OF_DAY) + signal.offset;
+        let mut offset = (now - Time::START_OF_DAY) + signal.plan_offset(state.current_plan);
         loop {
-            let dt = signal.stages[state.current_stage]
-                .stage_type
-                .simple_duration();
+            let dt = stages[state.current_stage].stage_type.simple_duration();
             if offset >= dt {
                 offset -= dt;
                 state.current_stage += 1;
-                if state.current_stage == signal.stages.len() {
+                if state.current_stage == stages.len() {
                     state.current_stage = 0;
                 }
             } else {
@@ -1052,17 +1178,67 @@ impl SignalState {
                 break;
             }
         }
+        if let Some(t) = signal.next_plan_change(now) {
+            state.stage_ends_at = state.stage_ends_at.min(t);
+        }
         scheduler.push(state.stage_ends_at, Command::UpdateIntersection(id));
         state
     }
 }
 
+/// Does anybody want to use one of this stage's protected movements? Vehicles over a loop detector
+/// and agents already waiting at the intersection both count.
+fn stage_has_call(
+    stage: &Stage,
+    signal: &ControlTrafficSignal,
+    detectors: &BTreeSet<LaneID>,
+    waiting: &BTreeMap<Request, (Time, bool)>,
+    include_peds: bool,
+) -> bool {
+    if waiting.keys().any(|req| {
+        (include_peds || !matches!(req.agent, AgentID::Pedestrian(_)))
+            && stage.get_priority_of_turn(req.turn, signal) == TurnPriority::Protected
+    }) {
+        return true;
+    }
+    stage.protected_movements.iter().any(|m| {
+        signal.movements[m]
+            .members
+            .iter()
+            .any(|t| detectors.contains(&t.src))
+    })
+}
+
+/// Picks the stage to run after `current`. A variable all-walk stage is skipped when
+/// `skip_crosswalk` allows it, and actuated stages are skipped when nobody calls for them. If no
+/// actuated stage has a call, this winds up back where it started, resting there.
+fn next_stage(
+    stage_types: &[StageType],
+    current: usize,
+    skip_crosswalk: &dyn Fn(usize) -> bool,
+    has_call: &dyn Fn(usize) -> bool,
+) -> usize {
+    let mut idx = (current + 1) % stage_types.len();
+    if let StageType::Variable(_, _, _) = stage_types[idx] {
+        if skip_crosswalk(idx) {
+            idx = (idx + 1) % stage_types.len();
+        }
+    }
+    while idx != current
+        && matches!(stage_types[idx], StageType::Actuated(_, _, _))
+        && !has_call(idx)
+    {
+        idx = (idx + 1) % stage_types.len();
+    }
+    idx
+}
+
 fn allow_block_the_box(i: &Intersection) -> bool {
     // Degenerate intersections are often just artifacts of how roads are split up in OSM. Allow
     // vehicles to get stuck in them, since the only possible thing they could block is pedestrians
     // from using the crosswalk. Those crosswalks usually don't exist in reality, so this behavior
-    // is more realistic.
-    if i.roads.len() == 2 {
+    // is more realistic. Mid-block crossings are the exception.
+    if i.roads.len() == 2 && i.crossing.is_none() {
         return true;
     }
 
@@ -1096,3 +1272,59 @@ fn allow_block_the_box(i: &Intersection) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed() -> StageType {
+        StageType::Fixed(Duration::seconds(30.0))
+    }
+
+    fn actuated() -> StageType {
+        StageType::Actuated(
+            Duration::seconds(5.0),
+            Duration::seconds(40.0),
+            Duration::seconds(3.0),
+        )
+    }
+
+    fn all_walk() -> StageType {
+        StageType::Variable(
+            Duration::seconds(10.0),
+            Duration::seconds(2.0),
+            Duration::seconds(10.0),
+        )
+    }
+
+    #[test]
+    fn fixed_stages_never_skip() {
+        let stages = vec![fixed(), fixed(), fixed()];
+        assert_eq!(next_stage(&stages, 0, &|_| true, &|_| false), 1);
+        assert_eq!(next_stage(&stages, 2, &|_| true, &|_| false), 0);
+    }
+
+    #[test]
+    fn all_walk_skipped_only_once() {
+        // Without pedestrians, the all-walk stage is skipped, but the following stage always runs
+        let stages = vec![fixed(), all_walk(), fixed()];
+        assert_eq!(next_stage(&stages, 0, &|_| true, &|_| false), 2);
+        assert_eq!(next_stage(&stages, 0, &|_| false, &|_| false), 1);
+        let stages = vec![all_walk(), all_walk()];
+        assert_eq!(next_stage(&stages, 0, &|_| true, &|_| false), 0);
+    }
+
+    #[test]
+    fn actuated_stages_skip_without_calls() {
+        let stages = vec![actuated(), actuated(), actuated(), fixed()];
+        assert_eq!(next_stage(&stages, 0, &|_| false, &|idx| idx == 2), 2);
+        // A fixed stage stops the search
+        assert_eq!(next_stage(&stages, 0, &|_| false, &|_| false), 3);
+        assert_eq!(next_stage(&stages, 3, &|_| false, &|idx| idx == 1), 1);
+
+        // With no calls anywhere, stay put
+        let stages = vec![actuated(), actuated(), actuated()];
+        assert_eq!(next_stage(&stages, 1, &|_| false, &|_| false), 1);
+        assert_eq!(next_stage(&stages, 1, &|_| false, &|idx| idx == 0), 0);
+    }
+}
@@ -1,10 +1,15 @@
+pub use self::car_following::CarFollowingModel;
+pub(crate) use self::car_following::{Leader, SpeedProfile};
 pub(crate) use self::driving::DrivingSimState;
 pub(crate) use self::intersection::IntersectionSimState;
-pub(crate) use self::parking::{ParkingSim, ParkingSimState};
+pub(crate) use self::parking::{
+    best_spot, free_loading_zone, parking_overstay, ParkingSim, ParkingSimState,
+};
 pub(crate) use self::queue::Queue;
 pub(crate) use self::walking::WalkingSimState;
 
 mod car;
+mod car_following;
 mod driving;
 mod intersection;
 mod parking;
@@ -9,13 +9,20 @@ use abstutil::{
     deserialize_btreemap, deserialize_multimap, retain_btreemap, serialize_btreemap,
     serialize_multimap, MultiMap,
 };
-use geom::{Distance, PolyLine, Pt2D};
+use geom::{Distance, Duration, PolyLine, Pt2D, Time};
 use map_model::{
-    BuildingID, Lane, LaneID, LaneType, Map, OffstreetParking, ParkingLotID, PathConstraints,
-    PathStep, Position, Traversable, TurnID,
+    BuildingID, BuildingType, Lane, LaneID, LaneType, Map, OffstreetParking, ParkingLotID,
+    ParkingPolicy, PathConstraints, PathStep, Position, RoadID, Traversable, TurnID,
+    MAX_WALKING_SPEED,
 };
 
-use crate::{CarID, CarStatus, DrawCarInput, Event, ParkedCar, ParkingSpot, PersonID, Vehicle};
+use crate::{
+    CarID, CarStatus, DrawCarInput, Event, ParkedCar, ParkingSpot, PersonID, Vehicle, VehicleType,
+};
+
+/// How many dollars an hour of a driver's time is worth. Drivers use this to decide between
+/// walking farther and paying more for parking.
+const VALUE_OF_TIME_PER_HOUR: f64 = 20.0;
 
 /// Manages the state of parked cars. There are two implementations:
 /// - NormalParkingSimState allows only one vehicle per ParkingSpot defined in the map
@@ -64,12 +71,17 @@ pub trait ParkingSim {
     /// them there, producing some nice, realistic churn if there's too much contention. But
     /// the implementation has some internal jitter between different vehicles, to discourage
     /// everybody near one spot from all competing for it.
+    /// If the driver knows how long they'll stay parked, they'll weigh price against walking
+    /// distance to `walk_to` and avoid spots with a time limit that's too short. `target` is only
+    /// used to filter private and permit-only spots, like in `get_all_free_spots`.
     /// Note the first PathStep is the turn after start, NOT PathStep::Lane(start).
     fn path_to_free_parking_spot(
         &self,
         start: LaneID,
         vehicle: &Vehicle,
         target: BuildingID,
+        walk_to: Position,
+        expected_stay: Option<Duration>,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)>;
     fn collect_events(&mut self) -> Vec<Event>;
@@ -433,6 +445,13 @@ impl ParkingSim for NormalParkingSimState {
         let mut candidates = Vec::new();
 
         for l in self.driving_to_parking_lanes.get(driving_pos.lane()) {
+            let road = map.get_parent(*l);
+            if road.parking_policy.permit_only && !has_permit(target, road.id, map) {
+                continue;
+            }
+            if road.parking_policy.loading_zone && vehicle.vehicle_type != VehicleType::Van {
+                continue;
+            }
             for spot in self.onstreet_lanes[l].spots() {
                 if self.is_free(spot)
                     && driving_pos.dist_along()
@@ -460,7 +479,13 @@ impl ParkingSim for NormalParkingSimState {
         }
 
         for pl in self.driving_to_lots.get(driving_pos.lane()) {
-            let lot_dist = map.get_pl(*pl).driving_pos.dist_along();
+            let lot = map.get_pl(*pl);
+            if lot.policy.permit_only
+                && !has_permit(target, map.get_parent(lot.driving_pos.lane()).id, map)
+            {
+                continue;
+            }
+            let lot_dist = lot.driving_pos.dist_along();
             if driving_pos.dist_along() < lot_dist {
                 for idx in 0..self.num_spots_per_lot[&pl] {
                     let spot = ParkingSpot::Lot(*pl, idx);
@@ -545,6 +570,8 @@ impl ParkingSim for NormalParkingSimState {
         start: LaneID,
         vehicle: &Vehicle,
         target: BuildingID,
+        walk_to: Position,
+        expected_stay: Option<Duration>,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
         let mut backrefs: HashMap<LaneID, TurnID> = HashMap::new();
@@ -565,13 +592,13 @@ impl ParkingSim for NormalParkingSimState {
             // If the current lane has a spot open, we wouldn't be asking. This can happen if a spot
             // opens up on the 'start' lane, but behind the car.
             if current != start {
-                // Pick the closest to the start of the lane, since that's closest to where we came
-                // from
-                if let Some((spot, pos)) = self
-                    .get_all_free_spots(Position::start(current), vehicle, target, map)
-                    .into_iter()
-                    .min_by_key(|(_, pos)| pos.dist_along())
-                {
+                if let Some((spot, pos)) = best_spot(
+                    self,
+                    self.get_all_free_spots(Position::start(current), vehicle, target, map),
+                    walk_to,
+                    expected_stay,
+                    map,
+                ) {
                     let mut steps = vec![PathStep::Lane(current)];
                     let mut current = current;
                     loop {
@@ -950,6 +977,8 @@ impl ParkingSim for InfiniteParkingSimState {
         start: LaneID,
         vehicle: &Vehicle,
         target: BuildingID,
+        _: Position,
+        _: Option<Duration>,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
         // TODO This impl is copied from NormalParkingSimState. Instead, we already know the
@@ -1031,3 +1060,147 @@ impl ParkingSim for InfiniteParkingSimState {
         cars
     }
 }
+
+/// The rules for parking at a spot. Private and public garages inside buildings are free and have
+/// no limits.
+fn spot_policy(spot: ParkingSpot, map: &Map) -> Option<&ParkingPolicy> {
+    match spot {
+        ParkingSpot::Onstreet(l, _) => Some(&map.get_parent(l).parking_policy),
+        ParkingSpot::Offstreet(_, _) => None,
+        ParkingSpot::Lot(pl, _) => Some(&map.get_pl(pl).policy),
+    }
+}
+
+/// Residents of a building may use permit-only parking along the building's road.
+fn has_permit(target: BuildingID, road: RoadID, map: &Map) -> bool {
+    let b = map.get_b(target);
+    let residential = match b.bldg_type {
+        BuildingType::Residential { .. } | BuildingType::ResidentialCommercial(_, _) => true,
+        BuildingType::Commercial(_) | BuildingType::Empty => false,
+    };
+    residential && map.get_parent(b.sidewalk()).id == road
+}
+
+/// Out of some free spots, picks the one a driver walking to `walk_to` would prefer. Each spot
+/// costs the time needed to walk from it there, plus the price of staying for
+/// `expected_stay`, converted to time. Spots with a time limit shorter than the stay are skipped.
+/// Without an expected stay, only walking matters.
+pub(crate) fn best_spot<P: ParkingSim>(
+    parking: &P,
+    candidates: Vec<(ParkingSpot, Position)>,
+    walk_to: Position,
+    expected_stay: Option<Duration>,
+    map: &Map,
+) -> Option<(ParkingSpot, Position)> {
+    let target_pt = walk_to.pt(map);
+    candidates
+        .into_iter()
+        .filter_map(|(spot, pos)| {
+            let walk = parking
+                .spot_to_sidewalk_pos(spot, map)
+                .pt(map)
+                .dist_to(target_pt)
+                / MAX_WALKING_SPEED;
+            let cost = spot_cost(walk, spot_policy(spot, map), expected_stay)?;
+            Some((cost, spot, pos))
+        })
+        // Break ties by the closest to the start of the lane, since that's where the driver came
+        // from
+        .min_by_key(|(cost, _, pos)| (*cost, pos.dist_along()))
+        .map(|(_, spot, pos)| (spot, pos))
+}
+
+/// How much a spot costs a driver, in time, or None if they can't stay there that long.
+fn spot_cost(
+    walk: Duration,
+    policy: Option<&ParkingPolicy>,
+    expected_stay: Option<Duration>,
+) -> Option<Duration> {
+    match (policy, expected_stay) {
+        (Some(policy), Some(stay)) => {
+            if policy.overstay(stay).is_some() {
+                return None;
+            }
+            Some(walk + Duration::hours(1) * (policy.price(stay) / VALUE_OF_TIME_PER_HOUR))
+        }
+        _ => Some(walk),
+    }
+}
+
+/// If a car stayed parked past the spot's time limit, by how much
+pub(crate) fn parking_overstay(p: &ParkedCar, now: Time, map: &Map) -> Option<Duration> {
+    spot_policy(p.spot, map)?.overstay(now - p.parked_since)
+}
+
+/// A delivery van only considers free loading zones ahead of it on its current lane.
+pub(crate) fn free_loading_zone<P: ParkingSim>(
+    parking: &P,
+    driving_pos: Position,
+    vehicle: &Vehicle,
+    target: BuildingID,
+    map: &Map,
+) -> Option<(ParkingSpot, Position)> {
+    let candidates = parking
+        .get_all_free_spots(driving_pos, vehicle, target, map)
+        .into_iter()
+        .filter(|(spot, _)| match spot {
+            ParkingSpot::Onstreet(l, _) => map.get_parent(*l).parking_policy.loading_zone,
+            ParkingSpot::Offstreet(_, _) | ParkingSpot::Lot(_, _) => false,
+        })
+        .collect();
+    best_spot(
+        parking,
+        candidates,
+        map.get_b(target).sidewalk_pos,
+        None,
+        map,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_spot_cost() {
+        let walk = Duration::minutes(5);
+        let mut policy = ParkingPolicy::free();
+        policy.hourly_price = 10.0;
+        policy.time_limit = Some(Duration::hours(2));
+
+        // Without a policy or an expected stay, only walking matters
+        assert_eq!(spot_cost(walk, None, Some(Duration::hours(1))), Some(walk));
+        assert_eq!(spot_cost(walk, Some(&policy), None), Some(walk));
+        assert_eq!(
+            spot_cost(walk, Some(&ParkingPolicy::free()), Some(Duration::hours(8))),
+            Some(walk)
+        );
+
+        // $10 for an hour is worth half an hour of the driver's time
+        assert_eq!(
+            spot_cost(walk, Some(&policy), Some(Duration::hours(1))),
+            Some(walk + Duration::minutes(30))
+        );
+        // Staying exactly as long as the limit is fine, but not any longer
+        assert_eq!(
+            spot_cost(walk, Some(&policy), Some(Duration::hours(2))),
+            Some(walk + Duration::hours(1))
+        );
+        assert_eq!(
+            spot_cost(walk, Some(&policy), Some(Duration::hours(3))),
+            None
+        );
+
+        // For a long enough stay, walking farther to a free spot wins
+        let stay = Some(Duration::hours(1));
+        assert!(
+            spot_cost(Duration::minutes(20), Some(&ParkingPolicy::free()), stay)
+                < spot_cost(walk, Some(&policy), stay)
+        );
+        let stay = Some(Duration::minutes(10));
+        assert!(
+            spot_cost(Duration::minutes(20), Some(&ParkingPolicy::free()), stay)
+                > spot_cost(walk, Some(&policy), stay)
+        );
+    }
+}
@@ -3,7 +3,7 @@ use std::collections::{BTreeSet, HashMap, VecDeque};
 use serde::{Deserialize, Serialize};
 
 use abstutil::FixedMap;
-use geom::{Distance, Time};
+use geom::{Distance, Duration, Time};
 use map_model::{Map, Traversable};
 
 use crate::mechanics::car::{Car, CarState};
@@ -12,6 +12,9 @@ use crate::{CarID, VehicleType, FOLLOWING_DISTANCE};
 /// A Queue of vehicles on a single lane or turn. No over-taking or lane-changing. This is where
 /// https://a-b-street.github.io/docs/trafficsim/discrete_event.html#exact-positions is
 /// implemented.
+///
+/// Vehicles stopped in the lane are temporary blockages; everybody behind a bus at a stop or a
+/// double-parked delivery van has to wait until it leaves.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub(crate) struct Queue {
     pub id: Traversable,
@@ -45,7 +48,21 @@ impl Queue {
         cars: &FixedMap<CarID, Car>,
         queues: &HashMap<Traversable, Queue>,
     ) -> Option<(CarID, Distance)> {
-        self.inner_get_last_car_position(now, cars, queues, &mut BTreeSet::new(), None)
+        self.inner_get_last_car_position(now, cars, queues, &mut BTreeSet::new(), None, None)
+    }
+
+    /// Where's the front of one vehicle in this queue? Unlike get_car_positions, the vehicles
+    /// behind it aren't looked up at all, so they may be missing from `cars`.
+    pub fn get_car_position(
+        &self,
+        id: CarID,
+        now: Time,
+        cars: &FixedMap<CarID, Car>,
+        queues: &HashMap<Traversable, Queue>,
+    ) -> Option<Distance> {
+        self.inner_get_last_car_position(now, cars, queues, &mut BTreeSet::new(), None, Some(id))
+            .filter(|(car, _)| *car == id)
+            .map(|(_, dist)| dist)
     }
 
     /// Farthest along (greatest distance) is first.
@@ -62,6 +79,7 @@ impl Queue {
             queues,
             &mut BTreeSet::new(),
             Some(&mut all_cars),
+            None,
         );
         all_cars
     }
@@ -73,6 +91,7 @@ impl Queue {
         queues: &HashMap<Traversable, Queue>,
         recursed_queues: &mut BTreeSet<Traversable>,
         mut intermediate_results: Option<&mut Vec<(CarID, Distance)>>,
+        stop_at: Option<CarID>,
     ) -> Option<(CarID, Distance)> {
         if self.cars.is_empty() {
             return None;
@@ -120,6 +139,7 @@ impl Queue {
                                     queues,
                                     recursed_queues,
                                     None,
+                                    None,
                                 )
                                 .unwrap();
                             assert_eq!(head, id);
@@ -174,10 +194,21 @@ impl Queue {
                     assert_eq!(bound, self.geom_len);
                     self.geom_len
                 }
-                CarState::Crossing(ref time_int, ref dist_int) => {
+                CarState::Crossing(ref time_int, ref dist_int, ref profile) => {
                     // TODO Why percent_clamp_end? We process car updates in any order, so we might
                     // calculate this before moving this car from Crossing to another state.
-                    dist_int.lerp(time_int.percent_clamp_end(now)).min(bound)
+                    if let Some(profile) = profile {
+                        let dt = if now > time_int.start {
+                            now - time_int.start
+                        } else {
+                            Duration::ZERO
+                        };
+                        (dist_int.start + profile.dist_at(dt))
+                            .min(dist_int.end)
+                            .min(bound)
+                    } else {
+                        dist_int.lerp(time_int.percent_clamp_end(now)).min(bound)
+                    }
                 }
                 CarState::Unparking(front, _, _) => front,
                 CarState::Parking(front, _, _) => front,
@@ -188,6 +219,9 @@ impl Queue {
                 intermediate_results.push((*id, front));
             }
             previous = Some((*id, front));
+            if stop_at == Some(*id) {
+                break;
+            }
         }
         // Enable to detect possible bugs, but save time otherwise
         if false {
@@ -255,6 +289,25 @@ impl Queue {
         false
     }
 
+    /// Is any vehicle over a virtual loop detector covering the last `detector_len` of this
+    /// queue? Actuated traffic signals use this to find out who's calling for green.
+    pub fn is_detector_occupied(
+        &self,
+        now: Time,
+        cars: &FixedMap<CarID, Car>,
+        queues: &HashMap<Traversable, Queue>,
+        detector_len: Distance,
+    ) -> bool {
+        // The back of a vehicle that's already left the lane may still be over the detector
+        if self.laggy_head.is_some() {
+            return true;
+        }
+        let detector_start = self.geom_len - detector_len;
+        self.get_car_positions(now, cars, queues)
+            .into_iter()
+            .any(|(_, front)| front >= detector_start)
+    }
+
     pub fn is_overflowing(&self) -> bool {
         self.reserved_length >= self.geom_len
     }
@@ -339,7 +392,7 @@ fn dump_cars(
         let car = &cars[id];
         println!("- {} @ {} (length {})", id, dist, car.vehicle.length);
         match car.state {
-            CarState::Crossing(ref time_int, ref dist_int) => {
+            CarState::Crossing(ref time_int, ref dist_int, _) => {
                 println!(
                     "  Going {} .. {} during {} .. {}",
                     dist_int.start, dist_int.end, time_int.start, time_int.end
@@ -6,23 +6,35 @@ use abstutil::{deserialize_multimap, serialize_multimap, FixedMap, IndexableKey,
 use geom::{Distance, Duration, Line, PolyLine, Speed, Time};
 use map_model::{
     BuildingID, BusRouteID, DrivingSide, Map, ParkingLotID, Path, PathConstraints, PathStep,
-    Traversable, SIDEWALK_THICKNESS,
+    Position, Traversable, SIDEWALK_THICKNESS,
 };
 
 use crate::sim::Ctx;
 use crate::{
     AgentID, AgentProperties, Command, CommutersVehiclesCounts, CreatePedestrian, DistanceInterval,
     DrawPedCrowdInput, DrawPedestrianInput, Event, IntersectionSimState, ParkedCar, ParkingSpot,
-    PedCrowdLocation, PedestrianID, PersonID, Scheduler, SidewalkPOI, SidewalkSpot, TimeInterval,
-    TransitSimState, TripID, TripManager, UnzoomedAgent,
+    PedCrowdLocation, PedestrianID, PersonID, Scheduler, SidewalkPOI, SidewalkSpot, SimOptions,
+    TimeInterval, TransitSimState, TripID, TripManager, UnzoomedAgent,
 };
 
 const TIME_TO_START_BIKING: Duration = Duration::const_seconds(30.0);
 const TIME_TO_FINISH_BIKING: Duration = Duration::const_seconds(45.0);
 
+/// People per square meter when a crowd can't move at all
+const JAM_DENSITY: f64 = 5.4;
+/// Even in a crush, people still shuffle along at this fraction of their normal speed.
+const MIN_CROWDED_SPEED_FACTOR: f64 = 0.1;
+/// People only notice a crowd this far ahead of or behind them.
+const CROWD_RADIUS: Distance = Distance::const_meters(5.0);
+
 /// Simulates pedestrians. Unlike vehicles, pedestrians can move bidirectionally on sidewalks and
-/// just "ghost" through each other. There's no queueing or slowdown when many people are
-/// overlapping. They're simply grouped together into a DrawPedCrowdInput for rendering.
+/// just "ghost" through each other. There's no queueing when many people are overlapping. They're
+/// simply grouped together into a DrawPedCrowdInput for rendering.
+///
+/// With `SimOptions::pedestrian_crowding`, people slow down when a sidewalk or crosswalk is dense
+/// near them. People walking both ways share the whole width. Speed is only decided when somebody
+/// starts along each step of their path, so a crowd forming or dispersing further along doesn't
+/// affect them until the next step.
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct WalkingSimState {
     peds: FixedMap<PedestrianID, Pedestrian>,
@@ -32,14 +44,16 @@ pub(crate) struct WalkingSimState {
     )]
     peds_per_traversable: MultiMap<Traversable, PedestrianID>,
     events: Vec<Event>,
+    pedestrian_crowding: bool,
 }
 
 impl WalkingSimState {
-    pub fn new() -> WalkingSimState {
+    pub fn new(opts: &SimOptions) -> WalkingSimState {
         WalkingSimState {
             peds: FixedMap::new(),
             peds_per_traversable: MultiMap::new(),
             events: Vec::new(),
+            pedestrian_crowding: opts.pedestrian_crowding,
         }
     }
 
@@ -92,7 +106,12 @@ impl WalkingSimState {
                 Line::must_new(driving_pos.pt(map), params.start.sidewalk_pos.pt(map)),
                 TimeInterval::new(now, now + TIME_TO_FINISH_BIKING),
             ),
-            _ => ped.crossing_state(params.start.sidewalk_pos.dist_along(), now, map),
+            _ => ped.crossing_state(
+                params.start.sidewalk_pos.dist_along(),
+                now,
+                self.crowds(),
+                map,
+            ),
         };
 
         scheduler.push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
@@ -127,7 +146,8 @@ impl WalkingSimState {
         trips: &mut TripManager,
         transit: &mut TransitSimState,
     ) {
-        let mut ped = self.peds.get_mut(&id).unwrap();
+        // Take the pedestrian out while updating them, so it's easy to look at everybody else
+        let mut ped = self.peds.remove(&id).unwrap();
         match ped.state {
             PedState::Crossing(ref dist_int, _) => {
                 if ped.path.is_last_step() {
@@ -154,7 +174,7 @@ impl WalkingSimState {
                                     ped.path.total_length(),
                                     ctx,
                                 );
-                                self.peds.remove(&id);
+                                return;
                             }
                         }
                         SidewalkPOI::Building(b) => {
@@ -182,7 +202,7 @@ impl WalkingSimState {
                             } else {
                                 self.peds_per_traversable
                                     .remove(ped.path.current_step().as_traversable(), ped.id);
-                                self.peds.remove(&id);
+                                return;
                             }
                         }
                         SidewalkPOI::Border(i) => {
@@ -196,7 +216,7 @@ impl WalkingSimState {
                                 ped.path.total_length(),
                                 ctx,
                             );
-                            self.peds.remove(&id);
+                            return;
                         }
                         SidewalkPOI::BikeRack(driving_pos) => {
                             let pt1 = ped.goal.sidewalk_pos.pt(ctx.map);
@@ -229,6 +249,11 @@ impl WalkingSimState {
                         now,
                         ctx.map,
                         ctx.intersections,
+                        if self.pedestrian_crowding {
+                            Some(&self.peds)
+                        } else {
+                            None
+                        },
                         &mut self.peds_per_traversable,
                         &mut self.events,
                         ctx.scheduler,
@@ -246,6 +271,11 @@ impl WalkingSimState {
                     now,
                     ctx.map,
                     ctx.intersections,
+                    if self.pedestrian_crowding {
+                        Some(&self.peds)
+                    } else {
+                        None
+                    },
                     &mut self.peds_per_traversable,
                     &mut self.events,
                     ctx.scheduler,
@@ -262,8 +292,12 @@ impl WalkingSimState {
                 }
             }
             PedState::LeavingBuilding(b, _) => {
-                ped.state =
-                    ped.crossing_state(ctx.map.get_b(b).sidewalk_pos.dist_along(), now, ctx.map);
+                ped.state = ped.crossing_state(
+                    ctx.map.get_b(b).sidewalk_pos.dist_along(),
+                    now,
+                    self.crowds(),
+                    ctx.map,
+                );
                 ctx.scheduler
                     .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
             }
@@ -278,11 +312,15 @@ impl WalkingSimState {
                     ped.path.total_length(),
                     ctx,
                 );
-                self.peds.remove(&id);
+                return;
             }
             PedState::LeavingParkingLot(pl, _) => {
-                ped.state =
-                    ped.crossing_state(ctx.map.get_pl(pl).sidewalk_pos.dist_along(), now, ctx.map);
+                ped.state = ped.crossing_state(
+                    ctx.map.get_pl(pl).sidewalk_pos.dist_along(),
+                    now,
+                    self.crowds(),
+                    ctx.map,
+                );
                 ctx.scheduler
                     .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
             }
@@ -300,7 +338,7 @@ impl WalkingSimState {
                     ped.path.total_length(),
                     ctx,
                 );
-                self.peds.remove(&id);
+                return;
             }
             PedState::StartingToBike(ref spot, _, _) => {
                 self.peds_per_traversable
@@ -313,15 +351,17 @@ impl WalkingSimState {
                     ped.path.total_length(),
                     ctx,
                 );
-                self.peds.remove(&id);
+                return;
             }
             PedState::FinishingBiking(ref spot, _, _) => {
-                ped.state = ped.crossing_state(spot.sidewalk_pos.dist_along(), now, ctx.map);
+                ped.state =
+                    ped.crossing_state(spot.sidewalk_pos.dist_along(), now, self.crowds(), ctx.map);
                 ctx.scheduler
                     .push(ped.state.get_end_time(), Command::UpdatePed(ped.id));
             }
             PedState::WaitingForBus(_, _) => unreachable!(),
         }
+        self.peds.insert(id, ped);
     }
 
     pub fn ped_boarded_bus(&mut self, now: Time, id: PedestrianID) {
@@ -545,6 +585,35 @@ impl WalkingSimState {
         std::mem::replace(&mut self.events, Vec::new())
     }
 
+    /// How fast people can currently move near some spot along a sidewalk, as a fraction of their
+    /// normal speed. Always 1.0 without the crowding model.
+    pub fn crowding_speed_factor(&self, pos: Position, now: Time, map: &Map) -> f64 {
+        match self.crowds() {
+            Some(crowds) => {
+                let on = Traversable::Lane(pos.lane());
+                let dist = pos.dist_along();
+                crowding_speed_factor(density_near(
+                    crowds.num_near(on, dist, now, map),
+                    on,
+                    dist,
+                    map,
+                ))
+            }
+            None => 1.0,
+        }
+    }
+
+    fn crowds(&self) -> Option<Crowds> {
+        if self.pedestrian_crowding {
+            Some(Crowds {
+                peds: &self.peds,
+                peds_per_traversable: &self.peds_per_traversable,
+            })
+        } else {
+            None
+        }
+    }
+
     pub fn find_trips_to_parking(&self, evicted_cars: Vec<ParkedCar>) -> Vec<(AgentID, TripID)> {
         let goals: BTreeSet<SidewalkPOI> = evicted_cars
             .into_iter()
@@ -616,7 +685,14 @@ struct Pedestrian {
 }
 
 impl Pedestrian {
-    fn crossing_state(&self, start_dist: Distance, start_time: Time, map: &Map) -> PedState {
+    /// With the crowding model, people move more slowly when it's dense around where they start.
+    fn crossing_state(
+        &self,
+        start_dist: Distance,
+        start_time: Time,
+        crowds: Option<Crowds>,
+        map: &Map,
+    ) -> PedState {
         let end_dist = if self.path.is_last_step() {
             self.goal.sidewalk_pos.dist_along()
         } else {
@@ -628,12 +704,20 @@ impl Pedestrian {
             }
         };
         let dist_int = DistanceInterval::new_walking(start_dist, end_dist);
-        let speed = self.path.current_step().as_traversable().max_speed_along(
+        let speed = self.path.current_step().max_speed_along(
             Some(self.speed),
+            map.routing_params_for(self.path.get_req()).fitness,
             PathConstraints::Pedestrian,
             map,
         );
-        let time_int = TimeInterval::new(start_time, start_time + dist_int.length() / speed);
+        let mut dt = dist_int.length() / speed;
+        if let Some(crowds) = crowds {
+            let on = self.path.current_step().as_traversable();
+            // Count ourselves too
+            let num_peds = crowds.num_near(on, start_dist, start_time, map) + 1;
+            dt = dt / crowding_speed_factor(density_near(num_peds, on, start_dist, map));
+        }
+        let time_int = TimeInterval::new(start_time, start_time + dt);
         PedState::Crossing(dist_int, time_int)
     }
 
@@ -774,6 +858,8 @@ impl Pedestrian {
         now: Time,
         map: &Map,
         intersections: &mut IntersectionSimState,
+        // Only with the crowding model
+        others: Option<&FixedMap<PedestrianID, Pedestrian>>,
         peds_per_traversable: &mut MultiMap<Traversable, PedestrianID>,
         events: &mut Vec<Event>,
         scheduler: &mut Scheduler,
@@ -803,7 +889,11 @@ impl Pedestrian {
             PathStep::ContraflowLane(l) => map.get_l(l).length(),
             PathStep::Turn(_) => Distance::ZERO,
         };
-        self.state = self.crossing_state(start_dist, now, map);
+        let crowds = others.map(|peds| Crowds {
+            peds,
+            peds_per_traversable: &*peds_per_traversable,
+        });
+        self.state = self.crossing_state(start_dist, now, crowds, map);
         peds_per_traversable.insert(self.path.current_step().as_traversable(), self.id);
         events.push(Event::AgentEntersTraversable(
             AgentID::Pedestrian(self.id),
@@ -853,6 +943,61 @@ impl PedState {
     }
 }
 
+/// Where everybody else is, to see how crowded it is near somebody
+#[derive(Clone, Copy)]
+struct Crowds<'a> {
+    peds: &'a FixedMap<PedestrianID, Pedestrian>,
+    peds_per_traversable: &'a MultiMap<Traversable, PedestrianID>,
+}
+
+impl<'a> Crowds<'a> {
+    /// How many people are within CROWD_RADIUS of some spot along a sidewalk or crosswalk
+    fn num_near(&self, on: Traversable, dist: Distance, now: Time, map: &Map) -> usize {
+        self.peds_per_traversable
+            .get(on)
+            .iter()
+            // Somebody being updated isn't in peds
+            .filter_map(|id| self.peds.get(id))
+            .filter(|ped| {
+                let their_dist = match ped.state {
+                    // They might not have been updated yet this instant
+                    PedState::Crossing(ref dist_int, ref time_int) if now >= time_int.end => {
+                        dist_int.end
+                    }
+                    _ => ped.get_dist_along(now, map),
+                };
+                (their_dist - dist).abs() <= CROWD_RADIUS
+            })
+            .count()
+    }
+}
+
+/// People per square meter within CROWD_RADIUS of some spot along a sidewalk or crosswalk
+fn density_near(num_peds: usize, on: Traversable, dist: Distance, map: &Map) -> f64 {
+    // Crosswalks are about as wide as the sidewalk they start from
+    let width = match on {
+        Traversable::Lane(l) => map.get_l(l).width,
+        Traversable::Turn(t) => map.get_l(t.src).width,
+    };
+    let length = on.get_polyline(map).length();
+    let area_length = ((dist + CROWD_RADIUS).min(length)
+        - (dist - CROWD_RADIUS).max(Distance::ZERO))
+    .max(Distance::meters(1.0));
+    (num_peds as f64) / (width.inner_meters() * area_length.inner_meters())
+}
+
+/// Uses Weidmann's fundamental diagram for pedestrian flow to relate density (people per square
+/// meter) to walking speed, as a fraction of the speed when nobody else is around.
+fn crowding_speed_factor(density: f64) -> f64 {
+    if density <= 0.0 {
+        return 1.0;
+    }
+    if density >= JAM_DENSITY {
+        return MIN_CROWDED_SPEED_FACTOR;
+    }
+    (1.0 - (-1.913 * (1.0 / density - 1.0 / JAM_DENSITY)).exp()).max(MIN_CROWDED_SPEED_FACTOR)
+}
+
 // The crowds returned here may have low/high values extending up to radius past the real geometry.
 fn find_crowds(
     input: Vec<(PedestrianID, Distance)>,
@@ -903,3 +1048,48 @@ impl IndexableKey for PedestrianID {
         self.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_crowding_free_flow() {
+        assert_eq!(crowding_speed_factor(0.0), 1.0);
+        // One person on a 2m wide, 10m long stretch of sidewalk barely notices
+        assert!(crowding_speed_factor(1.0 / 20.0) > 0.99);
+    }
+
+    #[test]
+    fn test_crowding_jam_density() {
+        assert_eq!(crowding_speed_factor(JAM_DENSITY), MIN_CROWDED_SPEED_FACTOR);
+        assert_eq!(
+            crowding_speed_factor(2.0 * JAM_DENSITY),
+            MIN_CROWDED_SPEED_FACTOR
+        );
+        // Just below jam density, people still can't go slower than the minimum
+        assert_eq!(
+            crowding_speed_factor(JAM_DENSITY - 0.01),
+            MIN_CROWDED_SPEED_FACTOR
+        );
+    }
+
+    #[test]
+    fn test_crowding_monotonic() {
+        let mut last = crowding_speed_factor(0.0);
+        for i in 1..=100 {
+            let factor = crowding_speed_factor(JAM_DENSITY * (i as f64) / 100.0);
+            assert!(
+                factor <= last,
+                "speed went up from {} to {} at {}% of jam density",
+                last,
+                factor,
+                i
+            );
+            assert!(factor >= MIN_CROWDED_SPEED_FACTOR && factor <= 1.0);
+            last = factor;
+        }
+        // Moderate crowds noticeably slow people down
+        assert!(crowding_speed_factor(1.0) < 0.9);
+    }
+}
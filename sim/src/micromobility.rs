@@ -0,0 +1,378 @@
+//! Shared micromobility: a fleet of docked bikes and dockless scooters that anybody can use. People
+//! walk to the nearest available vehicle, ride it, and leave it at their destination (scooters) or
+//! at the dock closest to their destination (bikes). Nobody moves the vehicles around otherwise, so
+//! over the day the fleet drifts away from where it started; the stats measure how much
+//! rebalancing would be needed.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Speed};
+use map_model::{BuildingID, Map};
+
+use crate::{CarID, SidewalkSpot, SimOptions, Vehicle, VehicleSpec, VehicleType, BIKE_LENGTH};
+
+/// Buildings with this OSM amenity are bike share docks.
+const DOCK_AMENITY: &str = "bicycle_rental";
+/// If OSM doesn't say how many bikes a dock holds
+const DEFAULT_DOCK_CAPACITY: usize = 10;
+/// How far people are willing to walk to pick up or drop off a vehicle
+const MAX_WALK: Distance = Distance::const_meters(500.0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum SharedVehicleKind {
+    /// Must be picked up from and returned to a dock
+    DockedBike,
+    /// Can be left anywhere
+    DocklessScooter,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub(crate) struct MicromobilitySimState {
+    vehicles: BTreeMap<CarID, SharedVehicle>,
+    /// The capacity of each dock
+    docks: BTreeMap<BuildingID, usize>,
+    dockless_scooters: usize,
+
+    /// How many vehicles were available at each building at the start of the day
+    initial_distribution: BTreeMap<BuildingID, usize>,
+    pickups: BTreeMap<BuildingID, usize>,
+    dropoffs: BTreeMap<BuildingID, usize>,
+    rides: usize,
+    unmet_demand: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct SharedVehicle {
+    vehicle: Vehicle,
+    kind: SharedVehicleKind,
+    state: SharedVehicleState,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+enum SharedVehicleState {
+    Available(BuildingID),
+    /// Claimed by somebody walking to it or riding it
+    InUse {
+        pickup: BuildingID,
+        dropoff: BuildingID,
+    },
+}
+
+/// A summary of the shared fleet, to help decide how to rebalance it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MicromobilityStats {
+    pub vehicles: usize,
+    pub in_use: usize,
+    /// Finished rides
+    pub rides: usize,
+    /// Trips that walked instead, because no vehicle or dock was available nearby
+    pub unmet_demand: usize,
+    /// How many vehicles would have to be moved to restore the fleet's starting distribution
+    pub rebalancing_moves: usize,
+    pub docks: BTreeMap<BuildingID, DockStats>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DockStats {
+    pub capacity: usize,
+    pub available: usize,
+    pub pickups: usize,
+    pub dropoffs: usize,
+}
+
+impl MicromobilitySimState {
+    pub fn new(map: &Map, opts: &SimOptions) -> MicromobilitySimState {
+        let mut docks = BTreeMap::new();
+        for b in map.all_buildings() {
+            if SidewalkSpot::bike_rack(b.id, map).is_none() {
+                continue;
+            }
+            for amenity in &b.amenities {
+                if amenity.amenity_type == DOCK_AMENITY {
+                    let capacity = amenity
+                        .osm_tags
+                        .get("capacity")
+                        .and_then(|x| x.parse::<usize>().ok())
+                        .filter(|x| *x > 0)
+                        .unwrap_or(DEFAULT_DOCK_CAPACITY);
+                    docks.insert(b.id, capacity);
+                }
+            }
+        }
+        MicromobilitySimState {
+            vehicles: BTreeMap::new(),
+            docks,
+            dockless_scooters: opts.dockless_scooters,
+
+            initial_distribution: BTreeMap::new(),
+            pickups: BTreeMap::new(),
+            dropoffs: BTreeMap::new(),
+            rides: 0,
+            unmet_demand: 0,
+        }
+    }
+
+    /// Place the fleet. Docks start half full, and scooters are spread evenly through the map.
+    /// Does nothing if the fleet already exists.
+    pub fn seed_fleet<F: FnMut() -> usize>(&mut self, map: &Map, mut new_car_id: F) {
+        if !self.vehicles.is_empty() {
+            return;
+        }
+
+        let mut locations = Vec::new();
+        for (b, capacity) in &self.docks {
+            for _ in 0..(capacity + 1) / 2 {
+                locations.push((*b, SharedVehicleKind::DockedBike));
+            }
+        }
+        if self.dockless_scooters > 0 {
+            let candidates: Vec<BuildingID> = map
+                .all_buildings()
+                .iter()
+                .filter(|b| SidewalkSpot::bike_rack(b.id, map).is_some())
+                .map(|b| b.id)
+                .collect();
+            if !candidates.is_empty() {
+                for i in 0..self.dockless_scooters {
+                    let b = candidates[i * candidates.len() / self.dockless_scooters];
+                    locations.push((b, SharedVehicleKind::DocklessScooter));
+                }
+            }
+        }
+
+        for (b, kind) in locations {
+            let id = CarID {
+                id: new_car_id(),
+                vehicle_type: VehicleType::Bike,
+            };
+            let max_speed = match kind {
+                SharedVehicleKind::DockedBike => Speed::miles_per_hour(10.0),
+                SharedVehicleKind::DocklessScooter => Speed::miles_per_hour(15.0),
+            };
+            let vehicle = VehicleSpec {
+                vehicle_type: VehicleType::Bike,
+                length: BIKE_LENGTH,
+                max_speed: Some(max_speed),
+                battery: None,
+            }
+            .make(id, None);
+            self.vehicles.insert(
+                id,
+                SharedVehicle {
+                    vehicle,
+                    kind,
+                    state: SharedVehicleState::Available(b),
+                },
+            );
+            *self.initial_distribution.entry(b).or_insert(0) += 1;
+        }
+    }
+
+    /// Claim the closest available vehicle to ride between two buildings. Returns the vehicle,
+    /// where to pick it up, and where to drop it off. If nothing is available nearby, or there's
+    /// no free dock near the destination, the person should just walk.
+    pub fn claim_vehicle(
+        &mut self,
+        from: BuildingID,
+        to: BuildingID,
+        map: &Map,
+    ) -> Option<(CarID, BuildingID, BuildingID)> {
+        let from_pt = map.get_b(from).polygon.center();
+        let mut candidates: Vec<(Distance, CarID, BuildingID, SharedVehicleKind)> = Vec::new();
+        for (id, shared) in &self.vehicles {
+            if let SharedVehicleState::Available(b) = shared.state {
+                let dist = map.get_b(b).polygon.center().dist_to(from_pt);
+                if dist <= MAX_WALK {
+                    candidates.push((dist, *id, b, shared.kind));
+                }
+            }
+        }
+        candidates.sort_by_key(|(dist, id, _, _)| (*dist, *id));
+
+        for (_, id, pickup, kind) in candidates {
+            let dropoff = match kind {
+                SharedVehicleKind::DockedBike => self.free_dock_near(to, map),
+                SharedVehicleKind::DocklessScooter => Some(to),
+            };
+            if let Some(dropoff) = dropoff {
+                let start_lane =
+                    SidewalkSpot::bike_rack(pickup, map).map(|s| s.sidewalk_pos.lane());
+                let end_lane = SidewalkSpot::bike_rack(dropoff, map).map(|s| s.sidewalk_pos.lane());
+                // If the vehicle is on the same sidewalk as the destination, riding it is silly.
+                if start_lane.is_none() || end_lane.is_none() || start_lane == end_lane {
+                    continue;
+                }
+                self.claim(id, pickup, dropoff);
+                return Some((id, pickup, dropoff));
+            }
+        }
+        self.unmet_demand += 1;
+        None
+    }
+
+    fn claim(&mut self, id: CarID, pickup: BuildingID, dropoff: BuildingID) {
+        self.vehicles.get_mut(&id).unwrap().state = SharedVehicleState::InUse { pickup, dropoff };
+        *self.pickups.entry(pickup).or_insert(0) += 1;
+    }
+
+    fn free_dock_near(&self, to: BuildingID, map: &Map) -> Option<BuildingID> {
+        let pt = map.get_b(to).polygon.center();
+        self.docks
+            .iter()
+            .filter(|(b, capacity)| self.vehicles_headed_to(**b) < **capacity)
+            .map(|(b, _)| (*b, map.get_b(*b).polygon.center().dist_to(pt)))
+            .filter(|(_, dist)| *dist <= MAX_WALK)
+            .min_by_key(|(_, dist)| *dist)
+            .map(|(b, _)| b)
+    }
+
+    // Vehicles available at a building or about to be dropped off there
+    fn vehicles_headed_to(&self, b: BuildingID) -> usize {
+        self.vehicles
+            .values()
+            .filter(|shared| match shared.state {
+                SharedVehicleState::Available(at) => at == b,
+                SharedVehicleState::InUse { dropoff, .. } => dropoff == b,
+            })
+            .count()
+    }
+
+    /// Only returns something for shared vehicles.
+    pub fn get_vehicle(&self, id: CarID) -> Option<Vehicle> {
+        self.vehicles.get(&id).map(|shared| shared.vehicle.clone())
+    }
+
+    /// The ride is over, so leave the vehicle where it was supposed to go.
+    pub fn vehicle_dropped_off(&mut self, id: CarID) {
+        if let Some(shared) = self.vehicles.get_mut(&id) {
+            if let SharedVehicleState::InUse { dropoff, .. } = shared.state {
+                shared.state = SharedVehicleState::Available(dropoff);
+                *self.dropoffs.entry(dropoff).or_insert(0) += 1;
+                self.rides += 1;
+            }
+        }
+    }
+
+    /// The trip using this vehicle was cancelled, either before or during the ride. Return the
+    /// vehicle to where it was picked up, as if it had never been claimed. Does nothing for
+    /// vehicles that aren't shared or aren't in use.
+    pub fn release_vehicle(&mut self, id: CarID) {
+        if let Some(shared) = self.vehicles.get_mut(&id) {
+            if let SharedVehicleState::InUse { pickup, .. } = shared.state {
+                shared.state = SharedVehicleState::Available(pickup);
+                if let Some(cnt) = self.pickups.get_mut(&pickup) {
+                    *cnt = cnt.saturating_sub(1);
+                }
+            }
+        }
+    }
+
+    pub fn get_stats(&self) -> MicromobilityStats {
+        let mut available: BTreeMap<BuildingID, usize> = BTreeMap::new();
+        let mut in_use = 0;
+        for shared in self.vehicles.values() {
+            match shared.state {
+                SharedVehicleState::Available(b) => {
+                    *available.entry(b).or_insert(0) += 1;
+                }
+                SharedVehicleState::InUse { .. } => {
+                    in_use += 1;
+                }
+            }
+        }
+
+        let rebalancing_moves = self
+            .initial_distribution
+            .iter()
+            .map(|(b, cnt)| cnt.saturating_sub(available.get(b).cloned().unwrap_or(0)))
+            .sum();
+        let docks = self
+            .docks
+            .iter()
+            .map(|(b, capacity)| {
+                (
+                    *b,
+                    DockStats {
+                        capacity: *capacity,
+                        available: available.get(b).cloned().unwrap_or(0),
+                        pickups: self.pickups.get(b).cloned().unwrap_or(0),
+                        dropoffs: self.dropoffs.get(b).cloned().unwrap_or(0),
+                    },
+                )
+            })
+            .collect();
+
+        MicromobilityStats {
+            vehicles: self.vehicles.len(),
+            in_use,
+            rides: self.rides,
+            unmet_demand: self.unmet_demand,
+            rebalancing_moves,
+            docks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cancelled_rental() {
+        let dock = BuildingID(1);
+        let destination = BuildingID(2);
+        let id = CarID {
+            id: 0,
+            vehicle_type: VehicleType::Bike,
+        };
+        let vehicle = VehicleSpec {
+            vehicle_type: VehicleType::Bike,
+            length: BIKE_LENGTH,
+            max_speed: None,
+            battery: None,
+        }
+        .make(id, None);
+        let mut state = MicromobilitySimState {
+            vehicles: vec![(
+                id,
+                SharedVehicle {
+                    vehicle,
+                    kind: SharedVehicleKind::DockedBike,
+                    state: SharedVehicleState::Available(dock),
+                },
+            )]
+            .into_iter()
+            .collect(),
+            docks: vec![(dock, 2), (destination, 2)].into_iter().collect(),
+            dockless_scooters: 0,
+
+            initial_distribution: vec![(dock, 1)].into_iter().collect(),
+            pickups: BTreeMap::new(),
+            dropoffs: BTreeMap::new(),
+            rides: 0,
+            unmet_demand: 0,
+        };
+
+        state.claim(id, dock, destination);
+        let stats = state.get_stats();
+        assert_eq!(stats.in_use, 1);
+        assert_eq!(stats.docks[&dock].available, 0);
+        assert_eq!(stats.docks[&dock].pickups, 1);
+
+        // The trip is cancelled, so the bike goes back to the dock
+        state.release_vehicle(id);
+        let stats = state.get_stats();
+        assert_eq!(stats.in_use, 0);
+        assert_eq!(stats.rides, 0);
+        assert_eq!(stats.rebalancing_moves, 0);
+        assert_eq!(stats.docks[&dock].available, 1);
+        assert_eq!(stats.docks[&dock].pickups, 0);
+        assert_eq!(stats.docks[&destination].dropoffs, 0);
+
+        // Releasing twice is harmless
+        state.release_vehicle(id);
+        assert_eq!(state.get_stats(), stats);
+    }
+}
@@ -3,15 +3,16 @@
 
 use std::collections::HashMap;
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-use geom::Distance;
+use geom::{Distance, Duration, Time};
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep,
-    Position, Traversable, Turn, TurnID,
+    BuildingID, BusStopID, IntersectionID, LaneID, Map, ParkingLotID, Path, PathConstraints,
+    PathRequest, PathStep, Position, Traversable, Turn, TurnID,
 };
 
-use crate::mechanics::Queue;
+use crate::mechanics::{best_spot, free_loading_zone, Queue};
 use crate::{
     AlertLocation, CarID, Event, ParkingSim, ParkingSimState, ParkingSpot, PersonID, SidewalkSpot,
     TripID, TripPhaseType, Vehicle, VehicleType,
@@ -33,6 +34,8 @@ pub(crate) enum ActionAtEnd {
     StopBiking(SidewalkSpot),
     BusAtStop,
     GiveUpOnParking,
+    /// Stop in this loading zone, or double-park in the driving lane if there's none
+    StartDelivery(Option<ParkingSpot>),
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -44,6 +47,14 @@ enum Goal {
         /// No parking available at all!
         stuck_end_dist: Option<Distance>,
         started_looking: bool,
+        /// For park and ride, take a spot in this lot if there's one free, then walk to this stop.
+        /// If the lot is full, park as close to the stop as possible.
+        park_and_ride: Option<(ParkingLotID, BusStopID)>,
+        /// When the driver expects to leave again, if known
+        leave_at: Option<Time>,
+        /// How far the driver has cruised since their first choice of spot fell through, up to the
+        /// start of the current step. Negative at first, if they started partway along a lane.
+        cruised: Option<Distance>,
     },
     EndAtBorder {
         end_dist: Distance,
@@ -55,6 +66,14 @@ enum Goal {
     FollowBusRoute {
         end_dist: Distance,
     },
+    /// Stop near a building for a while, then leave the map.
+    Deliver {
+        target: BuildingID,
+        /// Where to stop along the last driving lane, and the free loading zone there, if any
+        stop: Option<(Option<ParkingSpot>, Distance)>,
+        exit_border: IntersectionID,
+        exit_lane: LaneID,
+    },
 }
 
 impl Router {
@@ -78,6 +97,7 @@ impl Router {
                     start,
                     end: Position::end(lane.id, map),
                     constraints: PathConstraints::Bus,
+                    routing_params: None,
                 },
                 map,
             ),
@@ -97,11 +117,47 @@ impl Router {
                 spot: None,
                 stuck_end_dist: None,
                 started_looking: false,
+                park_and_ride: None,
+                leave_at: None,
+                cruised: None,
+            },
+            owner,
+        }
+    }
+
+    pub fn park_in_lot(
+        owner: CarID,
+        path: Path,
+        lot: ParkingLotID,
+        bldg: BuildingID,
+        stop: BusStopID,
+    ) -> Router {
+        Router {
+            path,
+            goal: Goal::ParkNearBuilding {
+                target: bldg,
+                spot: None,
+                stuck_end_dist: None,
+                started_looking: false,
+                park_and_ride: Some((lot, stop)),
+                leave_at: None,
+                cruised: None,
             },
             owner,
         }
     }
 
+    /// When parking, drivers weigh prices and time limits against how long they'll stay, from
+    /// whenever they start looking for a spot until this time.
+    pub fn set_leave_at(&mut self, time: Time) {
+        if let Goal::ParkNearBuilding {
+            ref mut leave_at, ..
+        } = self.goal
+        {
+            *leave_at = Some(time);
+        }
+    }
+
     pub fn bike_then_stop(owner: CarID, path: Path, goal: SidewalkSpot) -> Router {
         Router {
             goal: Goal::BikeThenStop { goal },
@@ -120,6 +176,55 @@ impl Router {
         }
     }
 
+    pub fn deliver(
+        owner: CarID,
+        path: Path,
+        bldg: BuildingID,
+        exit_border: IntersectionID,
+        exit_lane: LaneID,
+    ) -> Router {
+        Router {
+            path,
+            goal: Goal::Deliver {
+                target: bldg,
+                stop: None,
+                exit_border,
+                exit_lane,
+            },
+            owner,
+        }
+    }
+
+    /// After stopping to make a delivery at `start`, head for the border.
+    pub fn leave_after_delivery(&self, start: Position, map: &Map) -> Result<Router> {
+        match self.goal {
+            Goal::Deliver {
+                exit_border,
+                exit_lane,
+                ..
+            } => {
+                let end = Position::end(exit_lane, map);
+                let path = map.pathfind(PathRequest {
+                    start,
+                    end,
+                    constraints: PathConstraints::Car,
+                    routing_params: self.path.get_req().routing_params.clone(),
+                })?;
+                Ok(Router::end_at_border(
+                    self.owner,
+                    path,
+                    end.dist_along(),
+                    exit_border,
+                ))
+            }
+            _ => bail!("{} isn't making a delivery", self.owner),
+        }
+    }
+
+    pub fn is_delivering(&self) -> bool {
+        matches!(self.goal, Goal::Deliver { .. })
+    }
+
     pub fn head(&self) -> Traversable {
         self.path.current_step().as_traversable()
     }
@@ -148,6 +253,7 @@ impl Router {
             } => stuck_end_dist.unwrap_or_else(|| spot.unwrap().1),
             Goal::BikeThenStop { ref goal } => goal.sidewalk_pos.dist_along(),
             Goal::FollowBusRoute { end_dist } => end_dist,
+            Goal::Deliver { stop, .. } => stop.unwrap().1,
         }
     }
 
@@ -158,6 +264,7 @@ impl Router {
     /// Returns the step just finished
     pub fn advance(
         &mut self,
+        now: Time,
         vehicle: &Vehicle,
         parking: &ParkingSimState,
         map: &Map,
@@ -165,9 +272,17 @@ impl Router {
         events: &mut Vec<Event>,
     ) -> Traversable {
         let prev = self.path.shift(map).as_traversable();
+        if let Goal::ParkNearBuilding {
+            cruised: Some(ref mut dist),
+            ..
+        } = self.goal
+        {
+            *dist += prev.get_polyline(map).length();
+        }
         if self.last_step() {
             // Do this to trigger the side-effect of looking for parking.
             self.maybe_handle_end(
+                now,
                 Distance::ZERO,
                 vehicle,
                 parking,
@@ -195,6 +310,7 @@ impl Router {
     /// step.
     pub fn maybe_handle_end(
         &mut self,
+        now: Time,
         front: Distance,
         vehicle: &Vehicle,
         parking: &ParkingSimState,
@@ -216,6 +332,9 @@ impl Router {
                 ref mut stuck_end_dist,
                 target,
                 ref mut started_looking,
+                park_and_ride,
+                leave_at,
+                ref mut cruised,
             } => {
                 if let Some(d) = stuck_end_dist {
                     if *d == front {
@@ -231,6 +350,10 @@ impl Router {
                 };
                 if need_new_spot {
                     *started_looking = true;
+                    // Somebody else took the spot the driver was heading for
+                    let spot_taken = spot.is_some();
+                    let expected_stay =
+                        leave_at.map(|t| if t > now { t - now } else { Duration::ZERO });
                     let current_lane = self.path.current_step().as_lane();
                     let candidates = parking.get_all_free_spots(
                         Position::new(current_lane, front),
@@ -238,26 +361,30 @@ impl Router {
                         target,
                         map,
                     );
-                    let best =
-                        if let Some((driving_pos, _)) = map.get_b(target).driving_connection(map) {
-                            if driving_pos.lane() == current_lane {
-                                let target_dist = driving_pos.dist_along();
-                                // Closest to the building
-                                candidates
-                                    .into_iter()
-                                    .min_by_key(|(_, pos)| (pos.dist_along() - target_dist).abs())
-                            } else {
-                                // Closest to the road endpoint, I guess
-                                candidates
-                                    .into_iter()
-                                    .min_by_key(|(_, pos)| pos.dist_along())
+                    let in_lot = candidates
+                        .iter()
+                        .find(|(s, _)| match s {
+                            ParkingSpot::Lot(pl, _) => {
+                                park_and_ride.map(|(lot, _)| lot) == Some(*pl)
                             }
-                        } else {
-                            // Closest to the road endpoint, I guess
-                            candidates
-                                .into_iter()
-                                .min_by_key(|(_, pos)| pos.dist_along())
-                        };
+                            _ => false,
+                        })
+                        .cloned();
+                    // Where the driver walks after parking
+                    let walk_to = match park_and_ride {
+                        Some((_, stop)) => map.get_bs(stop).sidewalk_pos,
+                        None => map.get_b(target).sidewalk_pos,
+                    };
+                    let best = if in_lot.is_some() {
+                        in_lot
+                    } else {
+                        // Trade off walking and the price
+                        best_spot(parking, candidates, walk_to, expected_stay, map)
+                    };
+                    if (spot_taken || best.is_none()) && cruised.is_none() {
+                        *cruised = Some(Distance::ZERO - front);
+                        events.push(Event::CarStartedCruising(vehicle.id));
+                    }
                     if let Some((new_spot, new_pos)) = best {
                         if let Some((t, p)) = trip_and_person {
                             events.push(Event::TripPhaseStarting(
@@ -267,6 +394,7 @@ impl Router {
                                     start: Position::new(current_lane, front),
                                     end: new_pos,
                                     constraints: PathConstraints::Car,
+                                    routing_params: self.path.get_req().routing_params.clone(),
                                 }),
                                 TripPhaseType::Parking,
                             ));
@@ -275,8 +403,15 @@ impl Router {
                         assert!(new_pos.dist_along() >= front);
                         *spot = Some((new_spot, new_pos.dist_along()));
                     } else {
-                        if let Some((new_path_steps, new_spot, new_pos)) =
-                            parking.path_to_free_parking_spot(current_lane, vehicle, target, map)
+                        if let Some((new_path_steps, new_spot, new_pos)) = parking
+                            .path_to_free_parking_spot(
+                                current_lane,
+                                vehicle,
+                                target,
+                                walk_to,
+                                expected_stay,
+                                map,
+                            )
                         {
                             assert!(!new_path_steps.is_empty());
                             for step in new_path_steps {
@@ -293,6 +428,7 @@ impl Router {
                                         start: Position::new(current_lane, front),
                                         end: new_pos,
                                         constraints: PathConstraints::Car,
+                                        routing_params: self.path.get_req().routing_params.clone(),
                                     }),
                                     TripPhaseType::Parking,
                                 ));
@@ -334,6 +470,39 @@ impl Router {
                     None
                 }
             }
+            Goal::Deliver {
+                target,
+                ref mut stop,
+                ..
+            } => {
+                // Pick where to stop once, when reaching the last lane. Vans don't cruise around
+                // looking for a loading zone; they'll just double-park.
+                if stop.is_none() {
+                    let current_lane = self.path.current_step().as_lane();
+                    *stop = Some(delivery_stop(
+                        free_loading_zone(
+                            parking,
+                            Position::new(current_lane, front),
+                            vehicle,
+                            target,
+                            map,
+                        )
+                        .map(|(spot, pos)| (spot, pos.dist_along())),
+                        self.path.get_req().end.dist_along(),
+                        front,
+                        map.get_l(current_lane).length(),
+                    ));
+                }
+
+                let (spot, dist) = stop.unwrap();
+                if dist != front {
+                    return None;
+                }
+                // Somebody else might've taken the loading zone first
+                Some(ActionAtEnd::StartDelivery(
+                    spot.filter(|spot| parking.is_free(*spot)),
+                ))
+            }
         }
     }
 
@@ -477,6 +646,15 @@ impl Router {
         }
     }
 
+    /// How far the driver has traveled since they started looking for parking, once they've
+    /// reached their spot at `front`.
+    pub fn cruising_distance(&self, front: Distance) -> Option<Distance> {
+        match self.goal {
+            Goal::ParkNearBuilding { cruised, .. } => cruised.map(|dist| dist + front),
+            _ => None,
+        }
+    }
+
     pub fn get_parking_spot_goal(&self) -> Option<&ParkingSpot> {
         match self.goal {
             Goal::ParkNearBuilding { ref spot, .. } => spot.as_ref().map(|(s, _)| s),
@@ -484,3 +662,70 @@ impl Router {
         }
     }
 }
+
+/// Where a delivery van stops on its last lane: at a free loading zone ahead if there is one,
+/// otherwise double-parked as close to the end of its path as it can still reach.
+fn delivery_stop(
+    loading_zone: Option<(ParkingSpot, Distance)>,
+    end_dist: Distance,
+    front: Distance,
+    lane_len: Distance,
+) -> (Option<ParkingSpot>, Distance) {
+    match loading_zone {
+        Some((spot, dist)) => (Some(spot), dist),
+        None => (None, end_dist.max(front).min(lane_len)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_delivery_stop() {
+        let spot = ParkingSpot::Onstreet(LaneID(0), 3);
+        let lane_len = Distance::meters(100.0);
+
+        // A free loading zone wins, even if it's past the end of the path
+        assert_eq!(
+            delivery_stop(
+                Some((spot, Distance::meters(80.0))),
+                Distance::meters(50.0),
+                Distance::meters(10.0),
+                lane_len
+            ),
+            (Some(spot), Distance::meters(80.0))
+        );
+
+        // Otherwise double-park at the end of the path
+        assert_eq!(
+            delivery_stop(
+                None,
+                Distance::meters(50.0),
+                Distance::meters(10.0),
+                lane_len
+            ),
+            (None, Distance::meters(50.0))
+        );
+        // ...unless the van is already past it
+        assert_eq!(
+            delivery_stop(
+                None,
+                Distance::meters(50.0),
+                Distance::meters(60.0),
+                lane_len
+            ),
+            (None, Distance::meters(60.0))
+        );
+        // ...and never beyond the end of the lane
+        assert_eq!(
+            delivery_stop(
+                None,
+                Distance::meters(120.0),
+                Distance::meters(10.0),
+                lane_len
+            ),
+            (None, lane_len)
+        );
+    }
+}
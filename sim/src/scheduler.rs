@@ -25,8 +25,10 @@ pub(crate) enum Command {
     UpdateIntersection(IntersectionID),
     Callback(Duration),
     Pandemic(pandemic::Cmd),
-    /// The Time is redundant, just used to dedupe commands
-    StartBus(BusRouteID, Time),
+    /// Indexes into the route's spawn_times. Several vehicles may start at the same time.
+    StartBus(BusRouteID, usize),
+    /// An electric vehicle is fully charged
+    FinishCharging(CarID),
 }
 
 impl Command {
@@ -49,7 +51,8 @@ impl Command {
             Command::UpdateIntersection(id) => CommandType::Intersection(*id),
             Command::Callback(_) => CommandType::Callback,
             Command::Pandemic(ref p) => CommandType::Pandemic(p.clone()),
-            Command::StartBus(r, t) => CommandType::StartBus(*r, *t),
+            Command::StartBus(r, idx) => CommandType::StartBus(*r, *idx),
+            Command::FinishCharging(id) => CommandType::Charging(*id),
         }
     }
 
@@ -65,6 +68,7 @@ impl Command {
             Command::Callback(_) => SimpleCommandType::Callback,
             Command::Pandemic(_) => SimpleCommandType::Pandemic,
             Command::StartBus(_, _) => SimpleCommandType::StartBus,
+            Command::FinishCharging(_) => SimpleCommandType::Charging,
         }
     }
 }
@@ -80,7 +84,8 @@ enum CommandType {
     Intersection(IntersectionID),
     Callback,
     Pandemic(pandemic::Cmd),
-    StartBus(BusRouteID, Time),
+    StartBus(BusRouteID, usize),
+    Charging(CarID),
 }
 
 /// A more compressed form of CommandType, just used for keeping stats on event processing.
@@ -94,6 +99,7 @@ enum SimpleCommandType {
     Callback,
     Pandemic,
     StartBus,
+    Charging,
 }
 
 #[derive(Serialize, Deserialize, PartialEq, Eq, Clone)]
@@ -0,0 +1,222 @@
+//! An offline optimizer for traffic signal timing. It repeatedly runs a simulation, measuring the
+//! delay at the signals it's tuning, and greedily searches over stage durations and offsets. The
+//! result is a set of map edits that can be saved or applied like any other proposal.
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use abstutil::Timer;
+use geom::{Duration, Time};
+use map_model::{
+    ControlTrafficSignal, EditCmd, EditIntersection, IntersectionID, Map, MapEdits, StageType,
+};
+
+use crate::{simulate_day, simulate_with_changes, Analytics, Scenario, SimOptions};
+
+/// Settings for `optimize_signals`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OptimizeSignalsOptions {
+    /// Only delay measured during this window counts. Each simulation runs from midnight until the
+    /// end of the window.
+    pub window_start: Time,
+    pub window_end: Time,
+    /// The maximum number of passes over all the signals. The search also stops after a pass that
+    /// doesn't improve anything.
+    pub rounds: usize,
+    /// How much to lengthen or shorten a stage, or shift an offset, in each attempt.
+    pub step: Duration,
+    /// Which signals to tune. If empty, every traffic signal in the map is used.
+    pub signals: Vec<IntersectionID>,
+}
+
+impl OptimizeSignalsOptions {
+    pub fn new(window_start: Time, window_end: Time) -> OptimizeSignalsOptions {
+        OptimizeSignalsOptions {
+            window_start,
+            window_end,
+            rounds: 3,
+            step: Duration::seconds(5.0),
+            signals: Vec::new(),
+        }
+    }
+}
+
+/// Searches for traffic signal timing that minimizes the total delay measured at the tuned
+/// signals, returning edits on top of the map's current edits. Only the timing plan of each signal
+/// in effect when the window starts is tuned. Its fixed-time stages have their duration changed,
+/// and its offset is tuned too. Every simulation uses the same RNG seed, so all candidates face the
+/// same demand.
+///
+/// Candidates are tried out by temporarily changing the map's signals. The original signals are
+/// restored before returning.
+pub fn optimize_signals(
+    map: &mut Map,
+    scenario: &Scenario,
+    sim_opts: &SimOptions,
+    rng_seed: u64,
+    opts: &OptimizeSignalsOptions,
+    timer: &mut Timer,
+) -> Result<MapEdits> {
+    if opts.window_start >= opts.window_end {
+        bail!(
+            "The window {} to {} is empty",
+            opts.window_start,
+            opts.window_end
+        );
+    }
+    let signals: BTreeSet<IntersectionID> = if opts.signals.is_empty() {
+        map.all_intersections()
+            .iter()
+            .filter(|i| i.is_traffic_signal())
+            .map(|i| i.id)
+            .collect()
+    } else {
+        for i in &opts.signals {
+            if map.maybe_get_traffic_signal(*i).is_none() {
+                bail!("{} isn't a traffic signal", i);
+            }
+        }
+        opts.signals.iter().cloned().collect()
+    };
+    if signals.is_empty() {
+        bail!("{} has no traffic signals", map.get_name().describe());
+    }
+
+    let originals: Vec<(ControlTrafficSignal, EditIntersection)> = signals
+        .iter()
+        .map(|i| (map.get_traffic_signal(*i).clone(), map.get_i_edit(*i)))
+        .collect();
+    let measure = |map: &mut Map, candidate: Vec<ControlTrafficSignal>| {
+        let analytics = simulate_with_changes(
+            map,
+            candidate,
+            scenario,
+            sim_opts,
+            rng_seed,
+            Some(opts.window_end),
+            &mut Timer::throwaway(),
+        );
+        signal_delay(&analytics, (opts.window_start, opts.window_end), &signals)
+    };
+
+    let mut best = measure(map, Vec::new());
+    info!("Before optimizing, total delay is {}", best);
+    for round in 0..opts.rounds {
+        let mut improved = false;
+        timer.start_iter(
+            format!("optimize signals, round {}", round + 1),
+            signals.len(),
+        );
+        for i in &signals {
+            timer.next();
+            let current = map.get_traffic_signal(*i).clone();
+            let plan = current.plan_idx_at(opts.window_start);
+            let mut best_candidate = None;
+            for candidate in candidates(&current, plan, opts.step) {
+                let delay = measure(map, vec![candidate.clone()]);
+                if delay < best {
+                    best = delay;
+                    best_candidate = Some(candidate);
+                }
+            }
+            if let Some(candidate) = best_candidate {
+                info!("Changed {}, total delay is now {}", i, best);
+                map.incremental_edit_traffic_signal(candidate);
+                improved = true;
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+    info!("After optimizing, total delay is {}", best);
+
+    let mut edits = map.get_edits().clone();
+    edits.edits_name = "optimized signals".to_string();
+    for (orig, old) in originals {
+        let i = orig.id;
+        let ts = map.get_traffic_signal(i);
+        if ts != &orig {
+            edits.commands.push(EditCmd::ChangeIntersection {
+                i,
+                old,
+                new: EditIntersection::TrafficSignal(ts.export(map)),
+            });
+        }
+        map.incremental_edit_traffic_signal(orig);
+    }
+    Ok(edits)
+}
+
+/// Every signal that differs from this one by a single step in the duration of one stage of a
+/// plan, or in that plan's offset.
+fn candidates(ts: &ControlTrafficSignal, plan: usize, step: Duration) -> Vec<ControlTrafficSignal> {
+    let mut results = Vec::new();
+    for idx in 0..ts.plan_stages(plan).len() {
+        if let StageType::Fixed(dt) = ts.plan_stages(plan)[idx].stage_type {
+            for new_dt in vec![dt + step, dt - step] {
+                if new_dt <= Duration::ZERO || new_dt < ts.get_min_crossing_time(plan, idx) {
+                    continue;
+                }
+                let mut candidate = ts.clone();
+                candidate.plan_stages_mut(plan)[idx].stage_type = StageType::Fixed(new_dt);
+                results.push(candidate);
+            }
+        }
+    }
+
+    // Only shift the offset within one cycle; anything else is equivalent
+    let cycle = ts.plan_cycle_duration(plan);
+    let offset = ts.plan_offset(plan);
+    if cycle > step {
+        for new_offset in vec![(offset + step) % cycle, (offset + cycle - step) % cycle] {
+            let mut candidate = ts.clone();
+            candidate.set_plan_offset(plan, new_offset);
+            results.push(candidate);
+        }
+    }
+
+    results
+}
+
+/// Runs the scenario from midnight until the end of the window, then sums the delay measured at
+/// some traffic signals during the window.
+pub fn measure_signal_delay(
+    map: &Map,
+    scenario: &Scenario,
+    sim_opts: &SimOptions,
+    rng_seed: u64,
+    (window_start, window_end): (Time, Time),
+    signals: &BTreeSet<IntersectionID>,
+) -> Duration {
+    let analytics = simulate_day(
+        map,
+        scenario,
+        sim_opts,
+        rng_seed,
+        Some(window_end),
+        &mut Timer::throwaway(),
+    );
+    signal_delay(&analytics, (window_start, window_end), signals)
+}
+
+/// Sums the delay measured at some traffic signals during the window.
+fn signal_delay(
+    analytics: &Analytics,
+    (window_start, window_end): (Time, Time),
+    signals: &BTreeSet<IntersectionID>,
+) -> Duration {
+    let mut total = Duration::ZERO;
+    for i in signals {
+        if let Some(list) = analytics.intersection_delays.get(i) {
+            for (_, t, dt, _) in list {
+                if *t >= window_start && *t <= window_end {
+                    total += *dt;
+                }
+            }
+        }
+    }
+    total
+}
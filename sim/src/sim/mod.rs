@@ -2,6 +2,7 @@
 
 use std::collections::{BTreeSet, HashSet};
 use std::panic;
+use std::sync::Arc;
 
 use anyhow::Result;
 use instant::Instant;
@@ -14,17 +15,18 @@ use abstutil::{prettyprint_usize, serialized_size_bytes, CmdArgs, Timer};
 use geom::{Distance, Duration, Speed, Time};
 use map_model::{
     BuildingID, BusRoute, IntersectionID, LaneID, Map, ParkingLotID, Path, PathConstraints,
-    PathRequest, Position, Traversable,
+    PathRequest, Position, TimeDependentPathfinder, TravelTimeProfile, Traversable,
 };
 
 pub use self::queries::{AgentProperties, DelayCause};
+use crate::mechanics::parking_overstay;
 use crate::{
-    AgentID, AlertLocation, Analytics, CapSimState, CarID, Command, CreateCar, DrivingSimState,
-    Event, IntersectionSimState, OrigPersonID, PandemicModel, ParkedCar, ParkingSim,
-    ParkingSimState, ParkingSpot, Person, PersonID, Router, Scheduler, SidewalkPOI, SidewalkSpot,
-    StartTripArgs, TrafficRecorder, TransitSimState, TripEndpoint, TripID, TripInfo, TripManager,
-    TripPhaseType, Vehicle, VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH,
-    LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH,
+    AgentID, AlertLocation, Analytics, CapSimState, CarFollowingModel, CarID, ChargingSimState,
+    Command, CreateCar, DrivingSimState, Event, IntersectionSimState, MicromobilitySimState,
+    OrigPersonID, PandemicModel, ParkedCar, ParkingSim, ParkingSimState, ParkingSpot, Person,
+    PersonID, Router, Scheduler, SidewalkPOI, SidewalkSpot, StartTripArgs, TrafficRecorder,
+    TransitSimState, TripEndpoint, TripID, TripInfo, TripManager, TripPhaseType, Vehicle,
+    VehicleSpec, VehicleType, WalkingSimState, BUS_LENGTH, LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH,
 };
 
 mod queries;
@@ -41,6 +43,8 @@ pub struct Sim {
     intersections: IntersectionSimState,
     transit: TransitSimState,
     cap: CapSimState,
+    charging: ChargingSimState,
+    micromobility: MicromobilitySimState,
     trips: TripManager,
     #[serde(skip_serializing, skip_deserializing)]
     pandemic: Option<PandemicModel>,
@@ -63,14 +67,22 @@ pub struct Sim {
 
     #[serde(skip_serializing, skip_deserializing)]
     alerts: AlertHandler,
+
+    // Expensive to build and usually absent, so not preserved in savestates.
+    #[serde(skip_serializing, skip_deserializing)]
+    travel_times: Option<Arc<TimeDependentPathfinder>>,
 }
 
 pub(crate) struct Ctx<'a> {
     pub parking: &'a mut ParkingSimState,
     pub intersections: &'a mut IntersectionSimState,
     pub cap: &'a mut CapSimState,
+    pub charging: &'a mut ChargingSimState,
+    pub micromobility: &'a mut MicromobilitySimState,
     pub scheduler: &'a mut Scheduler,
     pub map: &'a Map,
+    /// If present, cars route using travel times that depend on when they depart.
+    pub travel_times: Option<&'a TimeDependentPathfinder>,
     /// If present, live map edits are being processed, and the agents specified are in the process
     /// of being deleted. Some regular work should maybe be skipped.
     pub handling_live_edits: Option<BTreeSet<AgentID>>,
@@ -119,6 +131,22 @@ pub struct SimOptions {
     /// Don't collect any analytics. Only useful for benchmarking and debugging gridlock more
     /// quickly.
     pub skip_analytics: bool,
+    /// How vehicles accelerate and decelerate while crossing lanes and turns. Anything besides
+    /// the default event-driven model is slower, but records speed profiles in Analytics.
+    pub car_following: CarFollowingModel,
+    /// The percentage of cars that are electric. They detour to charging stations when low on
+    /// charge.
+    pub electric_vehicles_pct: usize,
+    /// How many dockless shared scooters to spread around the map. Docked bike share comes from
+    /// the map instead.
+    pub dockless_scooters: usize,
+    /// Pedestrians slow down on crowded sidewalks and crosswalks, depending on how wide they are,
+    /// and boarding transit takes longer from a crowded stop. Only matters for big events or busy
+    /// stations.
+    pub pedestrian_crowding: bool,
+    /// Route cars using the travel times measured by a previous simulation, given the path to its
+    /// saved Analytics. Usually this is the prebaked results for the same scenario.
+    pub travel_time_profile: Option<String>,
 }
 
 impl std::default::Default for SimOptions {
@@ -159,6 +187,23 @@ impl SimOptions {
             delay_trips_instead_of_cancelling: args
                 .optional_parse("--delay_trips_instead_of_cancelling", Duration::parse),
             skip_analytics: args.enabled("--skip_analytics"),
+            car_following: args
+                .optional("--car_following")
+                .map(|x| match x.as_ref() {
+                    "event" => CarFollowingModel::EventDriven,
+                    "idm" => CarFollowingModel::IntelligentDriver,
+                    "krauss" => CarFollowingModel::Krauss,
+                    _ => panic!("Bad --car_following={}. Must be event|idm|krauss", x),
+                })
+                .unwrap_or(CarFollowingModel::EventDriven),
+            electric_vehicles_pct: args
+                .optional_parse("--electric_vehicles_pct", |s| s.parse::<usize>())
+                .unwrap_or(0),
+            dockless_scooters: args
+                .optional_parse("--dockless_scooters", |s| s.parse::<usize>())
+                .unwrap_or(0),
+            pedestrian_crowding: args.enabled("--pedestrian_crowding"),
+            travel_time_profile: args.optional("--travel_time_profile"),
         }
     }
 }
@@ -195,6 +240,11 @@ impl SimOptions {
             cancel_drivers_delay_threshold: None,
             delay_trips_instead_of_cancelling: None,
             skip_analytics: false,
+            car_following: CarFollowingModel::EventDriven,
+            electric_vehicles_pct: 0,
+            dockless_scooters: 0,
+            pedestrian_crowding: false,
+            travel_time_profile: None,
         }
     }
 }
@@ -203,13 +253,16 @@ impl SimOptions {
 impl Sim {
     pub fn new(map: &Map, opts: SimOptions) -> Sim {
         let mut scheduler = Scheduler::new();
-        Sim {
+        let travel_time_profile = opts.travel_time_profile.clone();
+        let mut sim = Sim {
             driving: DrivingSimState::new(map, &opts),
             parking: ParkingSimState::new(map, opts.infinite_parking),
-            walking: WalkingSimState::new(),
+            walking: WalkingSimState::new(&opts),
             intersections: IntersectionSimState::new(map, &mut scheduler, &opts),
             transit: TransitSimState::new(map),
             cap: CapSimState::new(map, &opts),
+            charging: ChargingSimState::new(map, &opts),
+            micromobility: MicromobilitySimState::new(map, &opts),
             trips: TripManager::new(),
             pandemic: if let Some(rng) = opts.enable_pandemic_model {
                 Some(PandemicModel::new(rng))
@@ -228,7 +281,22 @@ impl Sim {
 
             analytics: Analytics::new(!opts.skip_analytics),
             recorder: None,
+
+            travel_times: None,
+        };
+        if let Some(path) = travel_time_profile {
+            let mut timer = Timer::new("route cars using previous travel times");
+            let prev: Analytics = abstio::read_binary(path, &mut timer);
+            sim.use_travel_times(map, prev.travel_time_profile(), &mut timer);
         }
+        sim
+    }
+
+    /// From now on, cars will route using travel times measured from a previous simulation (see
+    /// `Analytics::travel_time_profile`), avoiding roads congested at the time they depart. Paths
+    /// that've already been calculated aren't affected.
+    pub fn use_travel_times(&mut self, map: &Map, profile: TravelTimeProfile, timer: &mut Timer) {
+        self.travel_times = Some(Arc::new(TimeDependentPathfinder::new(map, profile, timer)));
     }
 
     pub(crate) fn spawn_trips(
@@ -289,6 +357,7 @@ impl Sim {
             vehicle_type: VehicleType::Car,
             length: MIN_CAR_LENGTH,
             max_speed: None,
+            battery: None,
         };
         let driving_lane = map.find_driving_lane_near_building(b);
 
@@ -302,9 +371,14 @@ impl Sim {
         {
             spot.clone()
         } else {
-            let (_, spot, _) =
-                self.parking
-                    .path_to_free_parking_spot(driving_lane, &vehicle, b, map)?;
+            let (_, spot, _) = self.parking.path_to_free_parking_spot(
+                driving_lane,
+                &vehicle,
+                b,
+                map.get_b(b).sidewalk_pos,
+                None,
+                map,
+            )?;
             spot
         };
 
@@ -314,6 +388,7 @@ impl Sim {
             start,
             end,
             constraints: PathConstraints::Pedestrian,
+            routing_params: None,
         })
         .ok()
     }
@@ -323,10 +398,18 @@ impl Sim {
         orig_id: Option<OrigPersonID>,
         home: TripEndpoint,
         ped_speed: Speed,
-        vehicle_specs: Vec<VehicleSpec>,
+        mut vehicle_specs: Vec<VehicleSpec>,
     ) -> &Person {
-        self.trips
-            .new_person(orig_id, home, ped_speed, vehicle_specs)
+        for spec in &mut vehicle_specs {
+            self.charging.maybe_electrify(spec);
+        }
+        let person = self
+            .trips
+            .new_person(orig_id, home, ped_speed, vehicle_specs);
+        for vehicle in &person.vehicles {
+            self.charging.register_vehicle(vehicle);
+        }
+        person
     }
     pub(crate) fn seed_parked_car(&mut self, vehicle: Vehicle, spot: ParkingSpot) {
         self.parking.reserve_spot(spot, vehicle.id);
@@ -337,13 +420,18 @@ impl Sim {
         });
     }
 
+    pub(crate) fn seed_shared_vehicles(&mut self, map: &Map) {
+        let trips = &mut self.trips;
+        self.micromobility.seed_fleet(map, || trips.new_car_id());
+    }
+
     pub(crate) fn seed_bus_route(&mut self, route: &BusRoute) {
-        for t in &route.spawn_times {
-            self.scheduler.push(*t, Command::StartBus(route.id, *t));
+        for (idx, t) in route.spawn_times.iter().enumerate() {
+            self.scheduler.push(*t, Command::StartBus(route.id, idx));
         }
     }
 
-    fn start_bus(&mut self, route: &BusRoute, map: &Map) {
+    fn start_bus(&mut self, route: &BusRoute, spawn_idx: usize, map: &Map) -> CarID {
         // Spawn one bus for the first leg.
         let path = self.transit.create_empty_route(route, map);
 
@@ -358,6 +446,7 @@ impl Sim {
             vehicle_type,
             length,
             max_speed: None,
+            battery: None,
         }
         .make(
             CarID {
@@ -367,19 +456,21 @@ impl Sim {
             None,
         );
 
+        let id = vehicle.id;
         self.scheduler.push(
             self.time,
             Command::SpawnCar(
                 CreateCar {
-                    router: Router::follow_bus_route(vehicle.id, path),
+                    router: Router::follow_bus_route(id, path),
                     vehicle,
                     maybe_parked_car: None,
                     trip_and_person: None,
-                    maybe_route: Some(route.id),
+                    maybe_route: Some((route.id, spawn_idx)),
                 },
                 true,
             ),
         );
+        id
     }
 
     pub fn set_name(&mut self, name: String) {
@@ -444,8 +535,11 @@ impl Sim {
             parking: &mut self.parking,
             intersections: &mut self.intersections,
             cap: &mut self.cap,
+            charging: &mut self.charging,
+            micromobility: &mut self.micromobility,
             scheduler: &mut self.scheduler,
             map,
+            travel_times: self.travel_times.as_deref(),
             handling_live_edits: None,
         };
 
@@ -535,10 +629,10 @@ impl Sim {
                                 trip,
                                 person,
                                 Some(req),
-                                if id.vehicle_type == VehicleType::Car {
-                                    TripPhaseType::Driving
-                                } else {
+                                if id.vehicle_type == VehicleType::Bike {
                                     TripPhaseType::Biking
+                                } else {
+                                    TripPhaseType::Driving
                                 },
                             ));
                         }
@@ -550,10 +644,14 @@ impl Sim {
                                     b,
                                 ));
                             }
+                            if let Some(overstayed) = parking_overstay(&parked_car, self.time, map)
+                            {
+                                events.push(Event::ParkingTicket(id, parked_car.spot, overstayed));
+                            }
                             self.parking.remove_parked_car(parked_car);
                         }
-                        if let Some(route) = maybe_route {
-                            self.transit.bus_created(id, route);
+                        if let Some((route, spawn_idx)) = maybe_route {
+                            self.transit.bus_created(id, map.get_br(route), spawn_idx);
                         }
                         self.analytics
                             .record_demand(self.driving.get_path(id).unwrap(), map);
@@ -621,8 +719,26 @@ impl Sim {
                 );
             }
             Command::UpdateIntersection(i) => {
-                self.intersections
-                    .update_intersection(self.time, i, map, &mut self.scheduler);
+                // Only actuated signals look at the loop detectors
+                let signal = map.get_traffic_signal(i);
+                let detectors = if signal.is_actuated() {
+                    self.driving.occupied_detectors(self.time, i, map)
+                } else {
+                    BTreeSet::new()
+                };
+                let transit = if signal.transit_priority.is_some() {
+                    self.driving.approaching_transit(self.time, i, map)
+                } else {
+                    BTreeSet::new()
+                };
+                self.intersections.update_intersection(
+                    self.time,
+                    i,
+                    map,
+                    &mut self.scheduler,
+                    &detectors,
+                    &transit,
+                );
             }
             Command::Callback(frequency) => {
                 self.scheduler
@@ -637,8 +753,13 @@ impl Sim {
                     .unwrap()
                     .handle_cmd(self.time, cmd, &mut self.scheduler);
             }
-            Command::StartBus(r, _) => {
-                self.start_bus(map.get_br(r), map);
+            Command::StartBus(r, idx) => {
+                let bus = self.start_bus(map.get_br(r), idx, map);
+                events.push(Event::BusStarted(bus, r));
+            }
+            Command::FinishCharging(car) => {
+                self.charging
+                    .finish_charging(self.time, car, &mut self.scheduler);
             }
         }
 
@@ -655,6 +776,7 @@ impl Sim {
         events.extend(self.walking.collect_events());
         events.extend(self.intersections.collect_events());
         events.extend(self.parking.collect_events());
+        events.extend(self.charging.collect_events());
         for ev in events {
             if let Some(ref mut m) = self.pandemic {
                 m.handle_event(self.time, &ev, &mut self.scheduler);
@@ -850,6 +972,11 @@ impl Sim {
     /// (trips cancelled, parked cars displaced).
     pub fn handle_live_edits(&mut self, map: &Map) -> (usize, usize) {
         self.edits_name = map.get_edits().edits_name.clone();
+        // The customized pathfinders were prepared for the old map
+        if let Some(travel_times) = self.travel_times.take() {
+            let profile = travel_times.get_profile().clone();
+            self.use_travel_times(map, profile, &mut Timer::throwaway());
+        }
 
         let (affected, num_parked_cars) = self.find_trips_affected_by_live_edits(map);
         let num_trips_cancelled = affected.len();
@@ -862,8 +989,11 @@ impl Sim {
             parking: &mut self.parking,
             intersections: &mut self.intersections,
             cap: &mut self.cap,
+            charging: &mut self.charging,
+            micromobility: &mut self.micromobility,
             scheduler: &mut self.scheduler,
             map,
+            travel_times: self.travel_times.as_deref(),
             handling_live_edits: Some(affected_agents),
         };
         for (agent, trip) in affected {
@@ -976,8 +1106,11 @@ impl Sim {
                 parking: &mut self.parking,
                 intersections: &mut self.intersections,
                 cap: &mut self.cap,
+                charging: &mut self.charging,
+                micromobility: &mut self.micromobility,
                 scheduler: &mut self.scheduler,
                 map,
+                travel_times: self.travel_times.as_deref(),
                 handling_live_edits: None,
             };
             let vehicle = self.driving.delete_car(id, self.time, &mut ctx);
@@ -13,10 +13,10 @@ use map_model::{
 
 use crate::analytics::Window;
 use crate::{
-    AgentID, AgentType, Analytics, CarID, CommutersVehiclesCounts, DrawCarInput, DrawPedCrowdInput,
-    DrawPedestrianInput, OrigPersonID, PandemicModel, ParkedCar, ParkingSim, PedestrianID, Person,
-    PersonID, PersonState, Scenario, Sim, TripEndpoint, TripID, TripInfo, TripMode, TripResult,
-    UnzoomedAgent, VehicleType,
+    AgentID, AgentType, Analytics, CarID, ChargingStationStatus, CommutersVehiclesCounts,
+    DrawCarInput, DrawPedCrowdInput, DrawPedestrianInput, MicromobilityStats, OrigPersonID,
+    PandemicModel, ParkedCar, ParkingSim, PedestrianID, Person, PersonID, PersonState, Scenario,
+    Sim, TripEndpoint, TripID, TripInfo, TripMode, TripResult, UnzoomedAgent, VehicleType,
 };
 
 // TODO Many of these just delegate to an inner piece. This is unorganized and hard to maintain.
@@ -196,6 +196,7 @@ impl Sim {
             VehicleType::Bike,
             VehicleType::Bus,
             VehicleType::Train,
+            VehicleType::Van,
         ] {
             let id = CarID {
                 id: idx,
@@ -206,13 +207,15 @@ impl Sim {
             }
         }
 
-        let id = CarID {
-            id: idx,
-            vehicle_type: VehicleType::Car,
-        };
-        // Only cars can be parked.
-        if self.parking.lookup_parked_car(id).is_some() {
-            return Some(id);
+        // Only cars and delivery vans can be parked.
+        for vt in &[VehicleType::Car, VehicleType::Van] {
+            let id = CarID {
+                id: idx,
+                vehicle_type: *vt,
+            };
+            if self.parking.lookup_parked_car(id).is_some() {
+                return Some(id);
+            }
         }
 
         None
@@ -316,6 +319,28 @@ impl Sim {
         self.trips.bldg_to_people(b)
     }
 
+    /// Returns the current state of every charging station.
+    pub fn get_charging_stations(&self) -> BTreeMap<BuildingID, ChargingStationStatus> {
+        self.charging
+            .all_stations()
+            .into_iter()
+            .map(|b| (b, self.charging.station_status(b).unwrap()))
+            .collect()
+    }
+
+    pub fn get_charging_station(&self, b: BuildingID) -> Option<ChargingStationStatus> {
+        self.charging.station_status(b)
+    }
+
+    pub fn get_micromobility_stats(&self) -> MicromobilityStats {
+        self.micromobility.get_stats()
+    }
+
+    /// Only for electric vehicles, the fraction of the battery that's charged.
+    pub fn state_of_charge(&self, car: CarID) -> Option<f64> {
+        self.charging.state_of_charge(car)
+    }
+
     pub fn get_pandemic_model(&self) -> Option<&PandemicModel> {
         self.pandemic.as_ref()
     }
@@ -435,9 +460,9 @@ impl Sim {
                 let max_speed = match info.mode {
                     TripMode::Walk | TripMode::Transit => Some(person.ped_speed),
                     // TODO We should really search the vehicles and grab it from there
-                    TripMode::Drive => None,
+                    TripMode::Drive | TripMode::Micromobility | TripMode::ParkAndRide => None,
                     // Assume just one bike
-                    TripMode::Bike => {
+                    TripMode::Bike | TripMode::BikeAndRide => {
                         person
                             .vehicles
                             .iter()
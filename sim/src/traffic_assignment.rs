@@ -0,0 +1,239 @@
+//! Iterative dynamic traffic assignment. Drivers normally pick routes using free-flow travel times,
+//! and only react to congestion while driving. This runs the same day over and over instead; after
+//! each day, the travel times that cars experienced on each road are fed back into pathfinding, so
+//! drivers learn to avoid congested roads. Learned costs are averaged over all days so far (the
+//! method of successive averages), which keeps routes from oscillating between two alternatives.
+//!
+//! Unlike `ScenarioModifier::RepeatDays`, every day starts from a fresh simulation, so cars don't
+//! accumulate away from home.
+//!
+//! Convergence is measured by the relative gap: how much more time drivers spent on the routes they
+//! chose than on the best routes they could have taken, given the travel times they experienced.
+//! When that's small, nobody could do much better by switching routes -- a user equilibrium.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use abstutil::Timer;
+use geom::Duration;
+use map_model::{
+    DirectedRoadID, Map, Path, PathConstraints, PathRequest, PathStep, RoutingParams, Traversable,
+};
+
+use crate::{simulate_day, Analytics, Scenario, SimOptions, TripPhaseType};
+
+/// Settings for `assign_traffic`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TrafficAssignmentOptions {
+    /// Stop after simulating this many days, even if the relative gap is still too high.
+    pub max_iterations: usize,
+    /// Stop once the relative gap is at most this.
+    pub relative_gap: f64,
+}
+
+impl std::default::Default for TrafficAssignmentOptions {
+    fn default() -> TrafficAssignmentOptions {
+        TrafficAssignmentOptions {
+            max_iterations: 10,
+            relative_gap: 0.01,
+        }
+    }
+}
+
+/// How close one simulated day came to equilibrium.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AssignmentIteration {
+    /// Starting from 1
+    pub iteration: usize,
+    pub relative_gap: f64,
+    /// How many driving trips the gap was measured over
+    pub trips: usize,
+    /// The total time drivers would spend on the routes they chose, at experienced travel times
+    pub chosen_routes: Duration,
+    /// The total time drivers would spend on the best routes, at experienced travel times
+    pub best_routes: Duration,
+}
+
+/// The outcome of `assign_traffic`.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct TrafficAssignment {
+    /// One entry per simulated day
+    pub iterations: Vec<AssignmentIteration>,
+    pub converged: bool,
+    /// The routing used on the last day, including learned road costs. Override the map's routing
+    /// params with this to reproduce the last day.
+    pub routing_params: RoutingParams,
+    /// The results of the last day
+    pub analytics: Analytics,
+}
+
+/// Repeatedly simulates a full day of the scenario, feeding the travel times experienced by cars
+/// back into routing, until the relative gap converges or the maximum number of days is reached.
+/// Every day uses the same RNG seed, so the only thing changing is the routes.
+///
+/// The gap is measured over driving trips, assuming each driver followed the route planned when
+/// their trip started. Rerouting during the trip and searching for parking aren't captured.
+///
+/// The map's routing params are overridden while this runs, then restored.
+pub fn assign_traffic(
+    map: &mut Map,
+    scenario: &Scenario,
+    sim_opts: &SimOptions,
+    rng_seed: u64,
+    opts: &TrafficAssignmentOptions,
+    timer: &mut Timer,
+) -> Result<TrafficAssignment> {
+    if opts.max_iterations == 0 {
+        bail!("Need at least one iteration");
+    }
+
+    let original_params = map.routing_params().clone();
+    let mut learned = original_params.road_costs.clone();
+    let mut iterations = Vec::new();
+    let mut converged = false;
+    let mut analytics;
+    let routing_params;
+
+    loop {
+        let iteration = iterations.len() + 1;
+        timer.start(format!("traffic assignment, day {}", iteration));
+
+        analytics = simulate_day(map, scenario, sim_opts, rng_seed, None, timer);
+        let experienced = average_travel_times(&analytics);
+
+        // Compare the routes drivers chose with the best routes at experienced travel times
+        let requests = driving_requests(&analytics);
+        let chosen_params = map.routing_params().clone();
+        let chosen: Vec<Option<Path>> = requests
+            .iter()
+            .map(|req| map.pathfind(req.clone()).ok())
+            .collect();
+        let mut best_params = chosen_params.clone();
+        best_params.road_costs = experienced.clone();
+        map.hack_override_routing_params(best_params, timer);
+
+        let mut chosen_routes = Duration::ZERO;
+        let mut best_routes = Duration::ZERO;
+        let mut trips = 0;
+        for (req, chosen) in requests.into_iter().zip(chosen) {
+            if let (Some(chosen), Ok(best)) = (chosen, map.pathfind(req)) {
+                chosen_routes += path_cost(&chosen, &experienced, map);
+                best_routes += path_cost(&best, &experienced, map);
+                trips += 1;
+            }
+        }
+        let relative_gap = if chosen_routes == Duration::ZERO {
+            0.0
+        } else {
+            // The pathfinding cost isn't exactly travel time, so a route could look a bit worse
+            // than the one chosen
+            ((chosen_routes - best_routes) / chosen_routes).max(0.0)
+        };
+        info!(
+            "Day {}: relative gap is {:.4} over {} driving trips",
+            iteration, relative_gap, trips
+        );
+        iterations.push(AssignmentIteration {
+            iteration,
+            relative_gap,
+            trips,
+            chosen_routes,
+            best_routes,
+        });
+        timer.stop(format!("traffic assignment, day {}", iteration));
+
+        if relative_gap <= opts.relative_gap {
+            converged = true;
+        }
+        if converged || iteration == opts.max_iterations {
+            // Report the routing that produced these results
+            routing_params = chosen_params;
+            break;
+        }
+
+        // Average the experienced times into everything learned so far. Roads that nobody crossed
+        // today were at free-flow.
+        let mut roads: Vec<DirectedRoadID> = learned.keys().cloned().collect();
+        roads.extend(experienced.keys().cloned());
+        roads.sort();
+        roads.dedup();
+        let step = 1.0 / (iteration as f64);
+        for dr in roads {
+            let today = experienced
+                .get(&dr)
+                .cloned()
+                .unwrap_or_else(|| free_flow_time(dr, map));
+            let before = learned
+                .get(&dr)
+                .cloned()
+                .unwrap_or_else(|| free_flow_time(dr, map));
+            learned.insert(dr, before + step * (today - before));
+        }
+        let mut params = chosen_params;
+        params.road_costs = learned.clone();
+        map.hack_override_routing_params(params, timer);
+    }
+
+    map.hack_override_routing_params(original_params, timer);
+
+    Ok(TrafficAssignment {
+        iterations,
+        converged,
+        routing_params,
+        analytics,
+    })
+}
+
+/// The mean time cars took to cross each road, over the whole day.
+fn average_travel_times(analytics: &Analytics) -> BTreeMap<DirectedRoadID, Duration> {
+    analytics
+        .road_travel_times
+        .iter()
+        .filter(|(_, list)| !list.is_empty())
+        .map(|(dr, list)| {
+            let total: Duration = list.iter().map(|(_, dt)| *dt).sum();
+            (*dr, total / (list.len() as f64))
+        })
+        .collect()
+}
+
+fn driving_requests(analytics: &Analytics) -> Vec<PathRequest> {
+    analytics
+        .trip_log
+        .iter()
+        .filter_map(|(_, _, req, phase)| match (req, phase) {
+            (Some(req), TripPhaseType::Driving) if req.constraints == PathConstraints::Car => {
+                Some(req.clone())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+// Turns are always crossed at free-flow speed; time spent waiting for them is part of the road.
+fn path_cost(path: &Path, road_costs: &BTreeMap<DirectedRoadID, Duration>, map: &Map) -> Duration {
+    let mut total = Duration::ZERO;
+    for step in path.get_steps() {
+        match step {
+            PathStep::Lane(l) | PathStep::ContraflowLane(l) => {
+                let dr = map.get_l(*l).get_directed_parent();
+                total += road_costs
+                    .get(&dr)
+                    .cloned()
+                    .unwrap_or_else(|| free_flow_time(dr, map));
+            }
+            PathStep::Turn(t) => {
+                total += map.get_t(*t).geom.length()
+                    / Traversable::Turn(*t).max_speed_along(None, PathConstraints::Car, map);
+            }
+        }
+    }
+    total
+}
+
+fn free_flow_time(dr: DirectedRoadID, map: &Map) -> Duration {
+    map.get_r(dr.id).center_pts.length()
+        / Traversable::max_speed_along_road(dr, None, PathConstraints::Car, map)
+}
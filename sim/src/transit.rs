@@ -3,8 +3,10 @@ use std::collections::{BTreeMap, BTreeSet};
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::Time;
-use map_model::{BusRoute, BusRouteID, BusStopID, Map, Path, PathRequest, Position};
+use geom::{Duration, Time};
+use map_model::{
+    BusRoute, BusRouteID, BusStopID, Map, Path, PathRequest, Position, VehicleCapacity,
+};
 
 use crate::sim::Ctx;
 use crate::{
@@ -15,6 +17,13 @@ use crate::{
 // These index stops along a route, not stops along a single sidewalk.
 type StopIdx = usize;
 
+/// Opening and closing the doors, even if nobody gets on or off
+pub(crate) const BASE_DWELL_TIME: Duration = Duration::const_seconds(5.0);
+const BOARDING_TIME_PER_PASSENGER: Duration = Duration::const_seconds(2.5);
+/// Past the comfortable load, people have to squeeze past standing riders
+const CROWDED_BOARDING_TIME_PER_PASSENGER: Duration = Duration::const_seconds(4.0);
+const ALIGHTING_TIME_PER_PASSENGER: Duration = Duration::const_seconds(1.5);
+
 #[derive(Serialize, Deserialize, Clone)]
 struct Stop {
     id: BusStopID,
@@ -28,6 +37,7 @@ struct Route {
     start: Path,
     end_at_border: Option<Path>,
     active_vehicles: BTreeSet<CarID>,
+    capacity: VehicleCapacity,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -37,6 +47,11 @@ struct Bus {
     /// Where does each passenger want to deboard?
     passengers: Vec<(PersonID, Option<BusStopID>)>,
     state: BusState,
+    /// If the route follows a timetable, when to leave each stop. Early vehicles wait.
+    scheduled_departures: Option<Vec<Time>>,
+    /// When people who showed up after the dwell time at the current stop was decided will have
+    /// finished boarding. The vehicle doesn't leave before this.
+    late_boarding_until: Time,
 }
 
 #[derive(Serialize, Deserialize, Clone)]
@@ -106,6 +121,7 @@ impl TransitSimState {
                     start: stop1.driving_pos,
                     end: map.get_bs(bus_route.stops[idx + 1]).driving_pos,
                     constraints: bus_route.route_type,
+                    routing_params: None,
                 };
                 match map.pathfind(req) {
                     Ok(path) => {
@@ -127,6 +143,7 @@ impl TransitSimState {
                 start: Position::start(bus_route.start),
                 end: map.get_bs(bus_route.stops[0]).driving_pos,
                 constraints: bus_route.route_type,
+                routing_params: None,
             };
             let start = map.pathfind(start_req).expect("no route to first stop");
             let end_at_border = if let Some(l) = bus_route.end_border {
@@ -134,6 +151,7 @@ impl TransitSimState {
                     start: map.get_bs(*bus_route.stops.last().unwrap()).driving_pos,
                     end: Position::end(l, map),
                     constraints: bus_route.route_type,
+                    routing_params: None,
                 };
                 let path = map
                     .pathfind(req)
@@ -149,6 +167,7 @@ impl TransitSimState {
                     stops,
                     start,
                     end_at_border,
+                    capacity: bus_route.capacity,
                 },
             );
         }
@@ -156,22 +175,31 @@ impl TransitSimState {
         self.routes[&bus_route.id].start.clone()
     }
 
-    pub fn bus_created(&mut self, bus: CarID, r: BusRouteID) {
-        let route = self.routes.get_mut(&r).unwrap();
+    pub fn bus_created(&mut self, bus: CarID, bus_route: &BusRoute, spawn_idx: usize) {
+        let route = self.routes.get_mut(&bus_route.id).unwrap();
         route.active_vehicles.insert(bus);
         self.buses.insert(
             bus,
             Bus {
                 car: bus,
-                route: r,
+                route: bus_route.id,
                 passengers: Vec::new(),
                 state: BusState::DrivingToStop(0),
+                scheduled_departures: bus_route.scheduled_trip(spawn_idx).map(|trip| {
+                    trip.stop_times
+                        .iter()
+                        .map(|(_, departure)| *departure)
+                        .collect()
+                }),
+                late_boarding_until: Time::START_OF_DAY,
             },
         );
     }
 
-    /// If true, the bus is idling. If false, the bus actually arrived at a border and should now
-    /// vanish.
+    /// If the bus is idling at a stop, returns how long to wait there, depending on how many
+    /// passengers got on and off and how crowded the stop is. A bus running ahead of its timetable
+    /// also holds until the scheduled departure. If None, the bus actually arrived at a border and
+    /// should now vanish.
     pub fn bus_arrived_at_stop(
         &mut self,
         now: Time,
@@ -179,7 +207,7 @@ impl TransitSimState {
         trips: &mut TripManager,
         walking: &mut WalkingSimState,
         ctx: &mut Ctx,
-    ) -> bool {
+    ) -> Option<Duration> {
         let mut bus = self.buses.get_mut(&id).unwrap();
         match bus.state {
             BusState::DrivingToStop(stop_idx) => {
@@ -189,9 +217,11 @@ impl TransitSimState {
                     .push(Event::BusArrivedAtStop(id, bus.route, stop1));
 
                 // Deboard existing passengers.
+                let mut alighted = 0;
                 let mut still_riding = Vec::new();
                 for (person, maybe_stop2) in bus.passengers.drain(..) {
                     if Some(stop1) == maybe_stop2 {
+                        alighted += 1;
                         trips.person_left_bus(now, person, bus.car, ctx);
                         self.events.push(Event::PassengerAlightsTransit(
                             person, bus.car, bus.route, stop1,
@@ -202,12 +232,22 @@ impl TransitSimState {
                 }
                 bus.passengers = still_riding;
 
-                // Board new passengers.
+                // People squeeze through a crowd on the stop more slowly. Check before anybody
+                // boards.
+                let crowding =
+                    walking.crowding_speed_factor(ctx.map.get_bs(stop1).sidewalk_pos, now, ctx.map);
+
+                let waiting = self.peds_waiting.remove(&stop1).unwrap();
+                let (boards, boarding_time) = pick_boarders(
+                    self.routes[&bus.route].capacity,
+                    bus.passengers.len(),
+                    waiting.iter().map(|(_, route, _, _)| *route == bus.route),
+                );
                 let mut still_waiting = Vec::new();
-                for (ped, route, maybe_stop2, started_waiting) in
-                    self.peds_waiting.remove(&stop1).unwrap()
+                for ((ped, route, maybe_stop2, started_waiting), boards) in
+                    waiting.into_iter().zip(boards)
                 {
-                    if bus.route == route {
+                    if boards {
                         let (trip, person) = trips.ped_boarded_bus(
                             now,
                             ped,
@@ -238,16 +278,40 @@ impl TransitSimState {
                                         .end
                                 },
                                 constraints: bus.car.vehicle_type.to_constraints(),
+                                routing_params: None,
                             }),
                             TripPhaseType::RidingBus(route, stop1, bus.car),
                         ));
                         bus.passengers.push((person, maybe_stop2));
                     } else {
+                        if bus.route == route {
+                            // They'll wait for the next vehicle
+                            if let Some(person) = trips
+                                .agent_to_trip(AgentID::Pedestrian(ped))
+                                .and_then(|trip| trips.trip_to_person(trip))
+                            {
+                                self.events.push(Event::PassengerDeniedBoarding(
+                                    person, bus.car, route, stop1,
+                                ));
+                            }
+                        }
                         still_waiting.push((ped, route, maybe_stop2, started_waiting));
                     }
                 }
                 self.peds_waiting.insert(stop1, still_waiting);
-                true
+                let mut dwell_time = BASE_DWELL_TIME
+                    + boarding_time / crowding
+                    + (alighted as f64) * ALIGHTING_TIME_PER_PASSENGER;
+                if let Some(departure) = bus
+                    .scheduled_departures
+                    .as_ref()
+                    .map(|times| times[stop_idx])
+                {
+                    if departure > now + dwell_time {
+                        dwell_time = departure - now;
+                    }
+                }
+                Some(dwell_time)
             }
             BusState::DrivingOffMap => {
                 self.routes
@@ -266,12 +330,23 @@ impl TransitSimState {
                     }
                     trips.transit_rider_reached_border(now, person, id, ctx);
                 }
-                false
+                None
             }
             BusState::AtStop(_) | BusState::Done => unreachable!(),
         }
     }
 
+    /// If people are still boarding a vehicle that's ready to leave a stop, returns when they'll
+    /// be done.
+    pub fn still_boarding(&self, id: CarID, now: Time) -> Option<Time> {
+        let until = self.buses[&id].late_boarding_until;
+        if until > now {
+            Some(until)
+        } else {
+            None
+        }
+    }
+
     pub fn bus_departed_from_stop(&mut self, id: CarID, map: &Map) -> Router {
         let mut bus = self.buses.get_mut(&id).unwrap();
         let route = self.routes.get_mut(&bus.route).unwrap();
@@ -281,6 +356,12 @@ impl TransitSimState {
                 let stop = &route.stops[stop_idx];
                 self.events
                     .push(Event::BusDepartedFromStop(id, bus.route, stop.id));
+                self.events.push(Event::TransitLoadDeparting(
+                    id,
+                    bus.route,
+                    stop.id,
+                    bus.passengers.len(),
+                ));
                 if let Some(path) = stop.next_stop.clone() {
                     bus.state = BusState::DrivingToStop(stop_idx + 1);
                     Router::follow_bus_route(id, path)
@@ -305,7 +386,8 @@ impl TransitSimState {
         }
     }
 
-    /// Returns the bus if the pedestrian boarded immediately.
+    /// Returns the bus if the pedestrian boarded immediately. The vehicle waits for them to board
+    /// before leaving. If a vehicle is at the stop but full, they wait for the next one.
     pub fn ped_waiting_for_bus(
         &mut self,
         now: Time,
@@ -322,11 +404,21 @@ impl TransitSimState {
             for bus in &route.active_vehicles {
                 if let BusState::AtStop(idx) = self.buses[bus].state {
                     if route.stops[idx].id == stop1 {
-                        self.buses
-                            .get_mut(bus)
-                            .unwrap()
-                            .passengers
-                            .push((person, maybe_stop2));
+                        let vehicle = self.buses.get_mut(bus).unwrap();
+                        let (boards, boarding_time) = pick_boarders(
+                            route.capacity,
+                            vehicle.passengers.len(),
+                            std::iter::once(true),
+                        );
+                        if !boards[0] {
+                            self.events.push(Event::PassengerDeniedBoarding(
+                                person, *bus, route_id, stop1,
+                            ));
+                            continue;
+                        }
+                        vehicle.late_boarding_until =
+                            vehicle.late_boarding_until.max(now) + boarding_time;
+                        vehicle.passengers.push((person, maybe_stop2));
                         self.events.push(Event::TripPhaseStarting(
                             trip,
                             person,
@@ -338,6 +430,7 @@ impl TransitSimState {
                                     route.end_at_border.as_ref().unwrap().get_req().end
                                 },
                                 constraints: bus.vehicle_type.to_constraints(),
+                                routing_params: None,
                             }),
                             TripPhaseType::RidingBus(route_id, stop1, *bus),
                         ));
@@ -453,3 +546,72 @@ impl TransitSimState {
         results
     }
 }
+
+/// People waiting at a stop board a vehicle already carrying `load` passengers, in the order they
+/// arrived, until it reaches its crush load. `wants_to_board` says who's waiting for this route.
+/// Returns who boarded and how long it took them.
+fn pick_boarders(
+    capacity: VehicleCapacity,
+    mut load: usize,
+    wants_to_board: impl Iterator<Item = bool>,
+) -> (Vec<bool>, Duration) {
+    let mut boards = Vec::new();
+    let mut time = Duration::ZERO;
+    for wants in wants_to_board {
+        if wants && load < capacity.crush_load {
+            time += if load < capacity.comfortable {
+                BOARDING_TIME_PER_PASSENGER
+            } else {
+                CROWDED_BOARDING_TIME_PER_PASSENGER
+            };
+            load += 1;
+            boards.push(true);
+        } else {
+            boards.push(false);
+        }
+    }
+    (boards, time)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPACITY: VehicleCapacity = VehicleCapacity {
+        comfortable: 2,
+        crush_load: 3,
+    };
+
+    #[test]
+    fn boarding_stops_at_crush_load() {
+        let (boards, time) = pick_boarders(CAPACITY, 0, vec![true; 5].into_iter());
+        assert_eq!(boards, vec![true, true, true, false, false]);
+        assert_eq!(
+            time,
+            2.0 * BOARDING_TIME_PER_PASSENGER + CROWDED_BOARDING_TIME_PER_PASSENGER
+        );
+
+        // Already full
+        let (boards, time) = pick_boarders(CAPACITY, 3, vec![true; 2].into_iter());
+        assert_eq!(boards, vec![false, false]);
+        assert_eq!(time, Duration::ZERO);
+    }
+
+    #[test]
+    fn boarding_in_arrival_order() {
+        // People waiting for other routes don't take up room, and they don't jump the queue
+        let (boards, _) = pick_boarders(
+            CAPACITY,
+            1,
+            vec![false, true, false, true, true].into_iter(),
+        );
+        assert_eq!(boards, vec![false, true, false, true, false]);
+    }
+
+    #[test]
+    fn crowded_boarding_is_slower() {
+        let (_, comfortable) = pick_boarders(CAPACITY, 0, std::iter::once(true));
+        let (_, crowded) = pick_boarders(CAPACITY, 2, std::iter::once(true));
+        assert!(crowded > comfortable);
+    }
+}
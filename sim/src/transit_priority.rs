@@ -0,0 +1,108 @@
+//! Measures what transit signal priority does to delay at traffic signals, by simulating the same
+//! day with and without it. Priority should cut the delay of buses and trains, at some cost to
+//! everybody else.
+
+use std::collections::BTreeSet;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use abstutil::Timer;
+use geom::Duration;
+use map_model::{IntersectionID, Map, TransitPriority};
+
+use crate::{simulate_with_changes, AgentType, Analytics, Scenario, SimOptions};
+
+/// Delay at some traffic signals over one simulated day, split between transit vehicles and
+/// everybody else.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignalDelay {
+    /// Buses and trains
+    pub transit_delay: Duration,
+    pub transit_crossings: usize,
+    /// Cars, bikes, and pedestrians
+    pub other_delay: Duration,
+    pub other_crossings: usize,
+}
+
+impl SignalDelay {
+    pub fn from_analytics(
+        analytics: &Analytics,
+        signals: &BTreeSet<IntersectionID>,
+    ) -> SignalDelay {
+        let mut result = SignalDelay {
+            transit_delay: Duration::ZERO,
+            transit_crossings: 0,
+            other_delay: Duration::ZERO,
+            other_crossings: 0,
+        };
+        for i in signals {
+            if let Some(list) = analytics.intersection_delays.get(i) {
+                for (_, _, dt, agent_type) in list {
+                    if *agent_type == AgentType::Bus || *agent_type == AgentType::Train {
+                        result.transit_delay += *dt;
+                        result.transit_crossings += 1;
+                    } else {
+                        result.other_delay += *dt;
+                        result.other_crossings += 1;
+                    }
+                }
+            }
+        }
+        result
+    }
+}
+
+/// The outcome of `compare_transit_priority`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TransitPriorityComparison {
+    pub without_priority: SignalDelay,
+    pub with_priority: SignalDelay,
+}
+
+/// Simulates a full day of the scenario twice: once with transit priority turned off at some
+/// signals, and once with it turned on using the given limits. Both days use the same RNG seed.
+///
+/// The map's signals are changed while this runs, then restored.
+pub fn compare_transit_priority(
+    map: &mut Map,
+    scenario: &Scenario,
+    sim_opts: &SimOptions,
+    rng_seed: u64,
+    signals: &BTreeSet<IntersectionID>,
+    tsp: TransitPriority,
+    timer: &mut Timer,
+) -> Result<TransitPriorityComparison> {
+    if signals.is_empty() {
+        bail!("No traffic signals to compare");
+    }
+    for i in signals {
+        if map.maybe_get_traffic_signal(*i).is_none() {
+            bail!("{} isn't a traffic signal", i);
+        }
+    }
+
+    let mut results = Vec::new();
+    for priority in vec![None, Some(tsp)] {
+        timer.start("simulate a day");
+        let changed = signals
+            .iter()
+            .map(|i| {
+                let mut ts = map.get_traffic_signal(*i).clone();
+                ts.transit_priority = priority;
+                ts
+            })
+            .collect();
+        let analytics =
+            simulate_with_changes(map, changed, scenario, sim_opts, rng_seed, None, timer);
+        timer.stop("simulate a day");
+        results.push(SignalDelay::from_analytics(&analytics, signals));
+    }
+
+    let with_priority = results.pop().unwrap();
+    let without_priority = results.pop().unwrap();
+    Ok(TransitPriorityComparison {
+        without_priority,
+        with_priority,
+    })
+}
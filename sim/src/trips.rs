@@ -7,7 +7,7 @@ use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
 use geom::{Distance, Duration, Speed, Time};
 use map_model::{
     BuildingID, BusRouteID, BusStopID, IntersectionID, Map, Path, PathConstraints, PathRequest,
-    Position,
+    Position, RoutingParams,
 };
 
 use crate::cap::CapResult;
@@ -184,7 +184,7 @@ impl TripManager {
         match spec {
             TripSpec::VehicleAppearing {
                 start_pos,
-                goal,
+                mut goal,
                 retry_if_no_room,
                 use_vehicle,
             } => {
@@ -198,6 +198,13 @@ impl TripManager {
 
                 let vehicle = person.get_vehicle(use_vehicle);
                 assert!(ctx.parking.lookup_parked_car(vehicle.id).is_none());
+                // Electric vehicles coming from off the map might also be low on charge
+                if let DrivingGoal::ParkNear(b) = goal {
+                    if let Some(station) = ctx.charging.detour_to_charge(vehicle.id, b, ctx.map) {
+                        goal = DrivingGoal::ParkNear(station);
+                        self.trips[trip.0].legs[0] = TripLeg::Drive(vehicle.id, goal.clone());
+                    }
+                }
                 let constraints = if use_vehicle.vehicle_type == VehicleType::Bike {
                     PathConstraints::Bike
                 } else {
@@ -207,12 +214,23 @@ impl TripManager {
                     start: start_pos,
                     end: goal.goal_pos(constraints, ctx.map).unwrap(),
                     constraints,
+                    routing_params: self.trips[trip.0].info.routing_params.clone(),
                 };
                 let person = person.id;
 
                 match self.maybe_spawn_car(ctx, now, trip, req, vehicle.id) {
+                    Ok(path) if !ctx.charging.has_range(vehicle.id, path.total_length()) => {
+                        self.cancel_trip(
+                            now,
+                            trip,
+                            format!("{} doesn't have enough charge", vehicle.id),
+                            Some(vehicle),
+                            ctx,
+                        );
+                    }
                     Ok(path) => {
-                        let router = goal.make_router(vehicle.id, path, ctx.map);
+                        let mut router = goal.make_router(vehicle.id, path, ctx.map);
+                        router.set_leave_at(self.next_departure(trip));
                         ctx.scheduler.push(
                             now,
                             Command::SpawnCar(
@@ -234,18 +252,35 @@ impl TripManager {
             }
             TripSpec::UsingParkedCar {
                 car, start_bldg, ..
+            }
+            | TripSpec::ParkAndRide {
+                car, start_bldg, ..
             } => {
                 assert_eq!(person.state, PersonState::Inside(start_bldg));
                 person.state = PersonState::Trip(trip);
 
                 if let Some(parked_car) = ctx.parking.lookup_parked_car(car).cloned() {
                     let start = SidewalkSpot::building(start_bldg, ctx.map);
-                    let walking_goal =
+                    let mut walking_goal =
                         SidewalkSpot::parking_spot(parked_car.spot, ctx.map, ctx.parking);
+                    // A car left in a lot after parking and riding may be far away. Ride transit
+                    // back to it, if that helps.
+                    if let ParkingSpot::Lot(_, _) = parked_car.spot {
+                        if let Some((stop1, Some(stop2), route)) = ctx
+                            .map
+                            .should_use_transit(start.sidewalk_pos, walking_goal.sidewalk_pos)
+                        {
+                            let legs = &mut self.trips[trip.0].legs;
+                            legs.push_front(TripLeg::RideBus(route, Some(stop2)));
+                            walking_goal = SidewalkSpot::bus_stop(stop1, ctx.map);
+                            legs.push_front(TripLeg::Walk(walking_goal.clone()));
+                        }
+                    }
                     let req = PathRequest {
                         start: start.sidewalk_pos,
                         end: walking_goal.sidewalk_pos,
                         constraints: PathConstraints::Pedestrian,
+                        routing_params: self.trips[trip.0].info.routing_params.clone(),
                     };
                     match ctx.map.pathfind(req) {
                         Ok(path) => {
@@ -318,6 +353,7 @@ impl TripManager {
                     start: start.sidewalk_pos,
                     end: goal.sidewalk_pos,
                     constraints: PathConstraints::Pedestrian,
+                    routing_params: self.trips[trip.0].info.routing_params.clone(),
                 };
                 match ctx.map.pathfind(req) {
                     Ok(path) => {
@@ -339,7 +375,7 @@ impl TripManager {
                     }
                 }
             }
-            TripSpec::UsingBike { start, .. } => {
+            TripSpec::UsingBike { start, .. } | TripSpec::BikeAndRide { start, .. } => {
                 assert_eq!(person.state, PersonState::Inside(start));
                 person.state = PersonState::Trip(trip);
 
@@ -348,6 +384,7 @@ impl TripManager {
                         start: SidewalkSpot::building(start, ctx.map).sidewalk_pos,
                         end: walk_to.sidewalk_pos,
                         constraints: PathConstraints::Pedestrian,
+                        routing_params: self.trips[trip.0].info.routing_params.clone(),
                     };
                     match ctx.map.pathfind(req) {
                         Ok(path) => {
@@ -426,6 +463,7 @@ impl TripManager {
                     start: start.sidewalk_pos,
                     end: walk_to.sidewalk_pos,
                     constraints: PathConstraints::Pedestrian,
+                    routing_params: self.trips[trip.0].info.routing_params.clone(),
                 };
                 match ctx.map.pathfind(req) {
                     Ok(path) => {
@@ -447,6 +485,55 @@ impl TripManager {
                     }
                 }
             }
+            TripSpec::UsingSharedVehicle { start, goal } => {
+                assert_eq!(person.state, PersonState::Inside(start));
+                person.state = PersonState::Trip(trip);
+
+                // Which vehicle to use depends on what's available right now. If there's nothing
+                // nearby, just walk.
+                let maybe_vehicle = ctx.micromobility.claim_vehicle(start, goal, ctx.map);
+                let walk_to = if let Some((vehicle, pickup, dropoff)) = maybe_vehicle {
+                    let walk_to = SidewalkSpot::bike_rack(pickup, ctx.map).unwrap();
+                    self.trips[trip.0].legs.extend(vec![
+                        TripLeg::Walk(walk_to.clone()),
+                        TripLeg::Drive(vehicle, DrivingGoal::ParkNear(dropoff)),
+                        TripLeg::Walk(SidewalkSpot::building(goal, ctx.map)),
+                    ]);
+                    walk_to
+                } else {
+                    let walk_to = SidewalkSpot::building(goal, ctx.map);
+                    self.trips[trip.0]
+                        .legs
+                        .push_back(TripLeg::Walk(walk_to.clone()));
+                    walk_to
+                };
+
+                let req = PathRequest {
+                    start: SidewalkSpot::building(start, ctx.map).sidewalk_pos,
+                    end: walk_to.sidewalk_pos,
+                    constraints: PathConstraints::Pedestrian,
+                    routing_params: self.trips[trip.0].info.routing_params.clone(),
+                };
+                match ctx.map.pathfind(req) {
+                    Ok(path) => {
+                        ctx.scheduler.push(
+                            now,
+                            Command::SpawnPed(CreatePedestrian {
+                                id: person.ped,
+                                speed: person.ped_speed,
+                                start: SidewalkSpot::building(start, ctx.map),
+                                goal: walk_to,
+                                path,
+                                trip,
+                                person: person.id,
+                            }),
+                        );
+                    }
+                    Err(err) => {
+                        self.cancel_trip(now, trip, err.to_string(), None, ctx);
+                    }
+                }
+            }
         }
     }
 
@@ -467,6 +554,19 @@ impl TripManager {
         self.active_trip_mode.insert(agent, t);
     }
 
+    /// A delivery van pulled into a loading zone. The trip continues once it leaves, as a new
+    /// vehicle on the map, so record how it did so far.
+    pub fn van_stopped_for_delivery(
+        &mut self,
+        car: CarID,
+        blocked_time: Duration,
+        distance_crossed: Distance,
+    ) {
+        let trip = &mut self.trips[self.active_trip_mode[&AgentID::Car(car)].0];
+        trip.total_blocked_time += blocked_time;
+        trip.total_distance += distance_crossed;
+    }
+
     pub fn car_reached_parking_spot(
         &mut self,
         now: Time,
@@ -481,8 +581,15 @@ impl TripManager {
         trip.total_distance += distance_crossed;
 
         match trip.legs.pop_front() {
-            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_))) => {
+            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(b))) => {
+                assert_eq!(car, c);
+                ctx.charging
+                    .car_parked(now, car, distance_crossed, b, ctx.scheduler);
+            }
+            // The car stays in the lot while the person continues by transit
+            Some(TripLeg::Drive(c, DrivingGoal::ParkInLot(_, _, _))) => {
                 assert_eq!(car, c);
+                ctx.charging.car_drove(car, distance_crossed);
             }
             _ => unreachable!(),
         };
@@ -534,7 +641,7 @@ impl TripManager {
 
         trip.assert_walking_leg(SidewalkSpot::deferred_parking_spot());
         let parked_car = ctx.parking.get_car_at_spot(spot).unwrap().clone();
-        let drive_to = match trip.legs[0] {
+        let mut drive_to = match trip.legs[0] {
             TripLeg::Drive(c, ref to) => {
                 assert_eq!(c, parked_car.vehicle.id);
                 to.clone()
@@ -542,6 +649,20 @@ impl TripManager {
             _ => unreachable!(),
         };
 
+        // Electric vehicles stop charging now. If they're low, they'll park at a charging station
+        // near the destination instead, and the person walks the rest of the way.
+        ctx.charging
+            .car_leaving(now, parked_car.vehicle.id, ctx.scheduler);
+        if let DrivingGoal::ParkNear(b) = drive_to {
+            if let Some(station) = ctx
+                .charging
+                .detour_to_charge(parked_car.vehicle.id, b, ctx.map)
+            {
+                drive_to = DrivingGoal::ParkNear(station);
+                trip.legs[0] = TripLeg::Drive(parked_car.vehicle.id, drive_to.clone());
+            }
+        }
+
         let mut start =
             ctx.parking
                 .spot_to_driving_pos(parked_car.spot, &parked_car.vehicle, ctx.map);
@@ -562,13 +683,29 @@ impl TripManager {
             start,
             end,
             constraints: PathConstraints::Car,
+            routing_params: trip.info.routing_params.clone(),
         };
 
         let person = trip.person;
         let trip = trip.id;
         match self.maybe_spawn_car(ctx, now, trip, req, parked_car.vehicle.id) {
+            Ok(path)
+                if !ctx
+                    .charging
+                    .has_range(parked_car.vehicle.id, path.total_length()) =>
+            {
+                // The car is stranded where it's parked
+                self.cancel_trip(
+                    now,
+                    trip,
+                    format!("{} doesn't have enough charge", parked_car.vehicle.id),
+                    None,
+                    ctx,
+                );
+            }
             Ok(path) => {
-                let router = drive_to.make_router(parked_car.vehicle.id, path, ctx.map);
+                let mut router = drive_to.make_router(parked_car.vehicle.id, path, ctx.map);
+                router.set_leave_at(self.next_departure(trip));
                 ctx.scheduler.push(
                     now,
                     Command::SpawnCar(
@@ -629,6 +766,7 @@ impl TripManager {
             start: driving_pos,
             end,
             constraints: PathConstraints::Bike,
+            routing_params: trip.info.routing_params.clone(),
         };
         let maybe_router = if req.start.lane() == req.end.lane() {
             // TODO Convert to a walking trip! Ideally, do this earlier and convert the trip to
@@ -644,15 +782,15 @@ impl TripManager {
         };
         match maybe_router {
             Ok(router) => {
+                // Shared vehicles don't belong to the person
+                let vehicle = match ctx.micromobility.get_vehicle(bike) {
+                    Some(vehicle) => vehicle,
+                    None => self.people[trip.person.0].get_vehicle(bike),
+                };
                 ctx.scheduler.push(
                     now,
                     Command::SpawnCar(
-                        CreateCar::for_appearing(
-                            self.people[trip.person.0].get_vehicle(bike),
-                            router,
-                            trip.id,
-                            trip.person,
-                        ),
+                        CreateCar::for_appearing(vehicle, router, trip.id, trip.person),
                         true,
                     ),
                 );
@@ -682,8 +820,10 @@ impl TripManager {
         trip.total_distance += distance_crossed;
 
         match trip.legs.pop_front() {
-            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_))) => {
+            Some(TripLeg::Drive(c, DrivingGoal::ParkNear(_)))
+            | Some(TripLeg::Drive(c, DrivingGoal::ParkNearStop(_))) => {
                 assert_eq!(c, bike);
+                ctx.micromobility.vehicle_dropped_off(bike);
             }
             _ => unreachable!(),
         };
@@ -896,16 +1036,25 @@ impl TripManager {
         trip.total_blocked_time += blocked_time;
         trip.total_distance += distance_crossed;
 
-        match trip.legs.pop_front().unwrap() {
+        let delivery = match trip.legs.pop_front().unwrap() {
             TripLeg::Drive(c, DrivingGoal::Border(int, _)) => {
                 assert_eq!(car, c);
                 assert_eq!(i, int);
+                ctx.charging.car_drove(car, distance_crossed);
+                false
+            }
+            // Delivery vans end the trip by leaving the map, even though the trip's destination
+            // is the building they stopped at
+            TripLeg::Drive(c, DrivingGoal::Deliver(_, int, _)) => {
+                assert_eq!(car, c);
+                assert_eq!(i, int);
+                true
             }
             _ => unreachable!(),
         };
 
         self.people[trip.person.0].state = PersonState::OffMap;
-        if let TripEndpoint::Border(_) = trip.info.end {
+        if delivery || matches!(trip.info.end, TripEndpoint::Border(_)) {
             self.events.push(Event::PersonLeavesMap(
                 trip.person,
                 Some(AgentID::Car(car)),
@@ -954,15 +1103,35 @@ impl TripManager {
 
     fn spawn_ped(&mut self, now: Time, id: TripID, start: SidewalkSpot, ctx: &mut Ctx) {
         let trip = &self.trips[id.0];
-        let walk_to = match trip.legs[0] {
+        let mut walk_to = match trip.legs[0] {
             TripLeg::Walk(ref to) => to.clone(),
             _ => unreachable!(),
         };
+        // After riding transit back to a car, find where it's parked.
+        if walk_to == SidewalkSpot::deferred_parking_spot() {
+            let car = match trip.legs[1] {
+                TripLeg::Drive(c, _) => c,
+                _ => unreachable!(),
+            };
+            if let Some(parked_car) = ctx.parking.lookup_parked_car(car) {
+                walk_to = SidewalkSpot::parking_spot(parked_car.spot, ctx.map, ctx.parking);
+            } else {
+                self.cancel_trip(
+                    now,
+                    id,
+                    format!("should have {} parked somewhere, but it's unavailable", car),
+                    None,
+                    ctx,
+                );
+                return;
+            }
+        }
 
         let req = PathRequest {
             start: start.sidewalk_pos,
             end: walk_to.sidewalk_pos,
             constraints: PathConstraints::Pedestrian,
+            routing_params: trip.info.routing_params.clone(),
         };
         match ctx.map.pathfind(req) {
             Ok(path) => {
@@ -996,7 +1165,10 @@ impl TripManager {
         req: PathRequest,
         car: CarID,
     ) -> Result<Path> {
-        let path = ctx.map.pathfind(req)?;
+        let path = match ctx.travel_times {
+            Some(pathfinder) => pathfinder.pathfind(req, now, ctx.map)?,
+            None => ctx.map.pathfind(req)?,
+        };
         match ctx
             .cap
             .maybe_cap_path(path, now, car, ctx.intersections, ctx.map)
@@ -1013,6 +1185,19 @@ impl TripManager {
             CapResult::Delay(_) => todo!(),
         }
     }
+
+    /// When somebody driving on this trip expects to need their car again: when their next trip is
+    /// scheduled to start, or the end of the day.
+    fn next_departure(&self, trip: TripID) -> Time {
+        let person = &self.people[self.trips[trip.0].person.0];
+        person
+            .trips
+            .iter()
+            .skip_while(|t| **t != trip)
+            .nth(1)
+            .map(|next| self.trips[next.0].info.departure)
+            .unwrap_or(Time::START_OF_DAY + Duration::hours(24))
+    }
 }
 
 // Cancelling trips
@@ -1061,6 +1246,13 @@ impl TripManager {
             TripEndpoint::SuddenlyAppear(_) => unreachable!(),
         };
 
+        // Return any shared bike or scooter that was claimed but not dropped off yet
+        for leg in &trip.legs {
+            if let TripLeg::Drive(c, _) = leg {
+                ctx.micromobility.release_vehicle(*c);
+            }
+        }
+
         // Don't forget the car!
         if let Some(vehicle) = abandoned_vehicle {
             if vehicle.vehicle_type == VehicleType::Car {
@@ -1081,7 +1273,14 @@ impl TripManager {
                         .map(|(spot, _)| spot.clone())
                         .or_else(|| {
                             ctx.parking
-                                .path_to_free_parking_spot(driving_lane, &vehicle, b, ctx.map)
+                                .path_to_free_parking_spot(
+                                    driving_lane,
+                                    &vehicle,
+                                    b,
+                                    ctx.map.get_b(b).sidewalk_pos,
+                                    None,
+                                    ctx.map,
+                                )
                                 .map(|(_, spot, _)| spot)
                         })
                     {
@@ -1214,6 +1413,7 @@ impl TripManager {
             cyclists: 0,
 
             sov_drivers: 0,
+            delivery_vans: 0,
 
             buses,
             trains,
@@ -1227,6 +1427,9 @@ impl TripManager {
                     VehicleType::Car => {
                         cnt.sov_drivers += 1;
                     }
+                    VehicleType::Van => {
+                        cnt.delivery_vans += 1;
+                    }
                     VehicleType::Bike => {
                         cnt.cyclists += 1;
                     }
@@ -1239,7 +1442,7 @@ impl TripManager {
                     VehicleType::Train => {
                         cnt.train_riders += 1;
                     }
-                    VehicleType::Car | VehicleType::Bike => unreachable!(),
+                    VehicleType::Car | VehicleType::Bike | VehicleType::Van => unreachable!(),
                 },
                 // These're counted separately
                 AgentID::Pedestrian(_) => {}
@@ -1320,8 +1523,10 @@ impl TripManager {
                     // We can make some assumptions here.
                     let agent_type = match t.info.mode {
                         TripMode::Walk => AgentType::Pedestrian,
-                        TripMode::Bike => AgentType::Bike,
-                        TripMode::Drive => AgentType::Car,
+                        TripMode::Bike | TripMode::Micromobility | TripMode::BikeAndRide => {
+                            AgentType::Bike
+                        }
+                        TripMode::Drive | TripMode::ParkAndRide => AgentType::Car,
                         // TODO Not true for long. People will be able to spawn at borders already
                         // on a bus.
                         TripMode::Transit => AgentType::Pedestrian,
@@ -1387,6 +1592,8 @@ pub struct TripInfo {
     /// Was this trip affected by a congestion cap?
     pub capped: bool,
     pub cancellation_reason: Option<String>,
+    /// Used instead of the map's routing params for driving and biking
+    pub routing_params: Option<Box<RoutingParams>>,
 }
 
 impl Trip {
@@ -1417,6 +1624,12 @@ pub enum TripMode {
     Bike,
     Transit,
     Drive,
+    /// Ride a shared bike or scooter, instead of one the person owns
+    Micromobility,
+    /// Drive to a parking lot, then continue by transit
+    ParkAndRide,
+    /// Bike to a transit stop, leave the bike there, and continue by transit
+    BikeAndRide,
 }
 
 impl TripMode {
@@ -1426,6 +1639,9 @@ impl TripMode {
             TripMode::Bike,
             TripMode::Transit,
             TripMode::Drive,
+            TripMode::Micromobility,
+            TripMode::ParkAndRide,
+            TripMode::BikeAndRide,
         ]
     }
 
@@ -1435,6 +1651,9 @@ impl TripMode {
             TripMode::Bike => "bike",
             TripMode::Transit => "use transit",
             TripMode::Drive => "drive",
+            TripMode::Micromobility => "use bike share",
+            TripMode::ParkAndRide => "park and ride",
+            TripMode::BikeAndRide => "bike and ride",
         }
     }
 
@@ -1445,6 +1664,9 @@ impl TripMode {
             TripMode::Bike => "biking",
             TripMode::Transit => "using transit",
             TripMode::Drive => "driving",
+            TripMode::Micromobility => "using bike share",
+            TripMode::ParkAndRide => "parking and riding",
+            TripMode::BikeAndRide => "biking and riding",
         }
     }
 
@@ -1454,6 +1676,9 @@ impl TripMode {
             TripMode::Bike => "Bike",
             TripMode::Transit => "Bus",
             TripMode::Drive => "Car",
+            TripMode::Micromobility => "Shared bike",
+            TripMode::ParkAndRide => "Park and ride",
+            TripMode::BikeAndRide => "Bike and ride",
         }
     }
 
@@ -1464,6 +1689,10 @@ impl TripMode {
             // TODO WRONG
             TripMode::Transit => PathConstraints::Bus,
             TripMode::Drive => PathConstraints::Car,
+            TripMode::Micromobility => PathConstraints::Bike,
+            // The first leg
+            TripMode::ParkAndRide => PathConstraints::Car,
+            TripMode::BikeAndRide => PathConstraints::Bike,
         }
     }
 
@@ -1547,6 +1776,7 @@ pub struct CommutersVehiclesCounts {
     pub cyclists: usize,
 
     pub sov_drivers: usize,
+    pub delivery_vans: usize,
 
     pub buses: usize,
     pub trains: usize,
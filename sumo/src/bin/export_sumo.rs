@@ -0,0 +1,32 @@
+//! Exports an A/B Street map and scenario to SUMO. Writes <map>.net.xml, <map>.add.xml with the
+//! bus stops, <map>.tls.add.xml with the traffic signal schedules, and <map>_<scenario>.rou.xml in
+//! the current directory.
+
+use anyhow::Result;
+
+use abstutil::{CmdArgs, Timer};
+use map_model::Map;
+use sim::Scenario;
+
+fn main() -> Result<()> {
+    let mut args = CmdArgs::new();
+    let map_path = args.required_free();
+    let scenario_path = args.required_free();
+    args.done();
+    let mut timer = Timer::new("export to SUMO");
+
+    let map = Map::load_synchronously(map_path, &mut timer);
+    let scenario: Scenario = abstio::read_binary(scenario_path, &mut timer);
+
+    let name = map.get_name().map.clone();
+    sumo::write_network(&map, &format!("{}.net.xml", name), &mut timer)?;
+    sumo::write_bus_stops(&map, &format!("{}.add.xml", name))?;
+    sumo::write_signal_schedules(&map, &format!("{}.tls.add.xml", name))?;
+    sumo::write_routes(
+        &map,
+        &scenario,
+        &format!("{}_{}.rou.xml", name, scenario.scenario_name),
+        &mut timer,
+    )?;
+    Ok(())
+}
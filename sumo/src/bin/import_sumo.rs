@@ -0,0 +1,31 @@
+//! Converts a SUMO .net.xml into a RawMap, then builds a full A/B Street map from it, using the
+//! same pipeline as OSM imports. Unlike the `sumo` tool, this produces a playable map with
+//! sidewalks, crosswalks, and traffic signals timed from the SUMO programs.
+
+use anyhow::Result;
+
+use abstio::MapName;
+use abstutil::{CmdArgs, Timer};
+use map_model::{Map, RawToMapOptions};
+
+fn main() -> Result<()> {
+    let mut args = CmdArgs::new();
+    let input = args.required_free();
+    args.done();
+    let mut timer = Timer::new("import SUMO network");
+
+    let network = sumo::Network::load(&input, &mut timer)?;
+    // Double basename because "foo.net.xml" just becomes "foo.net"
+    let name = MapName::new(
+        "zz",
+        "sumo",
+        &abstutil::basename(abstutil::basename(&input)),
+    );
+    let (raw, signals) = sumo::to_raw_map(&network, name, &mut timer)?;
+    raw.save();
+
+    let mut map = Map::create_from_raw(raw, RawToMapOptions::default(), &mut timer);
+    sumo::apply_traffic_signals(&mut map, signals, &mut timer);
+    map.save();
+    Ok(())
+}
@@ -0,0 +1,898 @@
+//! Exports A/B Street maps and scenarios to SUMO's
+//! [network](https://sumo.dlr.de/docs/Networks/SUMO_Road_Networks.html) and
+//! [route](https://sumo.dlr.de/docs/Definition_of_Vehicles,_Vehicle_Types,_and_Routes.html)
+//! formats.
+//!
+//! Each direction of a road becomes one edge, containing every lane that supports some kind of
+//! movement. Parking lanes are dropped. Internal lanes aren't written, so SUMO treats the result
+//! like a network built with `--no-internal-links`. Bus stops go in a separate additional file.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use anyhow::Result;
+
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Bounds, Distance, Duration, Pt2D, Speed, Time};
+use map_model::{
+    osm, BusRoute, BusStopID, DirectedRoadID, Direction, DrivingSide, IntersectionID,
+    IntersectionType, LaneID, LaneType, Map, PathConstraints, PathRequest, PathStep, Position,
+    Road, StageType, TurnID, TurnPriority, TurnType, MAX_BIKE_SPEED, MAX_WALKING_SPEED,
+};
+use sim::{Scenario, TripEndpoint, TripMode};
+
+/// How long transit vehicles without a timetable wait at each stop
+const DWELL_TIME: Duration = Duration::const_seconds(5.0);
+/// How long SUMO shows yellow before a movement loses its green
+const YELLOW_DURATION: Duration = Duration::const_seconds(3.0);
+
+/// Writes a map as a SUMO .net.xml file.
+pub fn write_network(map: &Map, path: &str, timer: &mut Timer) -> Result<()> {
+    let bounds = map.get_bounds();
+    let gps = map.get_gps_bounds();
+    let lanes = sumo_lane_ids(map);
+
+    // Connections have to be numbered per traffic signal before the tlLogic can be written, so
+    // figure them out first.
+    let signal_links = signal_links(map, &lanes);
+    let mut connections = Vec::new();
+    for t in map.all_turns().values() {
+        if t.between_sidewalks() {
+            continue;
+        }
+        let (from, to) = match (lanes.get(&t.id.src), lanes.get(&t.id.dst)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => continue,
+        };
+        let dir = match t.turn_type {
+            TurnType::Straight => "s",
+            TurnType::Left => "l",
+            TurnType::Right => "r",
+            TurnType::UTurn => "t",
+            TurnType::Crosswalk | TurnType::SharedSidewalkCorner => unreachable!(),
+        };
+        let i = t.id.parent;
+        let (state, tl) = match map.get_i(i).intersection_type {
+            IntersectionType::TrafficSignal => {
+                let link_idx = signal_links[&i].iter().position(|x| *x == t.id).unwrap();
+                (
+                    "O",
+                    format!(r#" tl="{}" linkIndex="{}""#, node_id(i), link_idx),
+                )
+            }
+            IntersectionType::StopSign => {
+                let state = if is_all_way_stop(map, i) {
+                    "="
+                } else if map.get_stop_sign(i).get_priority(t.id, map) == TurnPriority::Yield {
+                    "s"
+                } else {
+                    "M"
+                };
+                (state, String::new())
+            }
+            IntersectionType::Border | IntersectionType::Construction => ("M", String::new()),
+        };
+        connections.push(format!(
+            r#"    <connection from="{}" to="{}" fromLane="{}" toLane="{}"{} dir="{}" state="{}"/>"#,
+            from.0, to.0, from.1, to.1, tl, dir, state
+        ));
+    }
+
+    let mut f = BufWriter::new(File::create(path)?);
+    writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        f,
+        r#"<net version="1.9"{}>"#,
+        if map.get_config().driving_side == DrivingSide::Left {
+            r#" lefthand="true""#
+        } else {
+            ""
+        }
+    )?;
+    writeln!(
+        f,
+        r#"    <location netOffset="0.00,0.00" convBoundary="{:.2},0.00,{:.2},{:.2}" origBoundary="{},{},{},{}" projParameter="!"/>"#,
+        bounds.min_x,
+        bounds.max_x,
+        bounds.max_y - bounds.min_y,
+        gps.min_lon,
+        gps.min_lat,
+        gps.max_lon,
+        gps.max_lat
+    )?;
+
+    // SUMO fills in missing edge attributes from the types, so every edge needs one
+    let mut types: BTreeMap<String, (usize, Speed)> = BTreeMap::new();
+    for r in map.all_roads() {
+        let (priority, speed) = types
+            .entry(edge_type(r))
+            .or_insert((r.get_detailed_rank(), r.speed_limit));
+        *priority = (*priority).max(r.get_detailed_rank());
+        *speed = (*speed).max(r.speed_limit);
+    }
+    for (id, (priority, speed)) in types {
+        writeln!(
+            f,
+            r#"    <type id="{}" priority="{}" speed="{:.2}"/>"#,
+            id,
+            priority,
+            speed.inner_meters_per_second()
+        )?;
+    }
+
+    timer.start_iter("write edges", map.all_roads().len());
+    for r in map.all_roads() {
+        timer.next();
+        for dir in vec![Direction::Fwd, Direction::Back] {
+            let dr = DirectedRoadID { id: r.id, dir };
+            let edge_lanes = sumo_lanes(dr, map);
+            if edge_lanes.is_empty() {
+                continue;
+            }
+            let (from, to) = if dir == Direction::Fwd {
+                (r.src_i, r.dst_i)
+            } else {
+                (r.dst_i, r.src_i)
+            };
+            writeln!(
+                f,
+                r#"    <edge id="{}" from="{}" to="{}" name="{}" priority="{}" type="{}">"#,
+                edge_id(dr),
+                node_id(from),
+                node_id(to),
+                escape(&r.get_name(None)),
+                r.get_detailed_rank(),
+                edge_type(r)
+            )?;
+            for (idx, l) in edge_lanes.into_iter().enumerate() {
+                let lane = map.get_l(l);
+                writeln!(
+                    f,
+                    r#"        <lane id="{}_{}" index="{}" {} speed="{:.2}" length="{:.2}" width="{:.2}" shape="{}"/>"#,
+                    edge_id(dr),
+                    idx,
+                    idx,
+                    permissions(lane.lane_type),
+                    r.speed_limit.inner_meters_per_second(),
+                    lane.length().inner_meters(),
+                    lane.width.inner_meters(),
+                    shape(lane.lane_center_pts.points(), bounds)
+                )?;
+            }
+            writeln!(f, "    </edge>")?;
+        }
+    }
+
+    // Every timing plan becomes its own program. Which one is active at what time of day is up to
+    // the WAUTs from write_signal_schedules.
+    for (i, links) in &signal_links {
+        let signal = map.get_traffic_signal(*i);
+        for plan in 0..signal.num_plans() {
+            let stages: Vec<(StageType, String)> = signal
+                .plan_stages(plan)
+                .iter()
+                .map(|stage| {
+                    let state = links
+                        .iter()
+                        .map(|t| match stage.get_priority_of_turn(*t, signal) {
+                            TurnPriority::Protected => 'G',
+                            TurnPriority::Yield => 'g',
+                            TurnPriority::Banned => 'r',
+                        })
+                        .collect();
+                    (stage.stage_type.clone(), state)
+                })
+                .collect();
+            let phases = signal_phases(&stages);
+            writeln!(
+                f,
+                r#"    <tlLogic id="{}" type="{}" programID="{}" offset="{:.0}">"#,
+                node_id(*i),
+                if phases.iter().any(|p| p.actuated.is_some()) {
+                    "actuated"
+                } else {
+                    "static"
+                },
+                plan,
+                signal.plan_offset(plan).inner_seconds()
+            )?;
+            for phase in phases {
+                if let Some((min, max)) = phase.actuated {
+                    writeln!(
+                        f,
+                        r#"        <phase duration="{:.0}" minDur="{:.0}" maxDur="{:.0}" state="{}"/>"#,
+                        phase.duration.inner_seconds(),
+                        min.inner_seconds(),
+                        max.inner_seconds(),
+                        phase.state
+                    )?;
+                } else {
+                    writeln!(
+                        f,
+                        r#"        <phase duration="{:.0}" state="{}"/>"#,
+                        phase.duration.inner_seconds(),
+                        phase.state
+                    )?;
+                }
+            }
+            writeln!(f, "    </tlLogic>")?;
+        }
+    }
+
+    for i in map.all_intersections() {
+        let junction_type = match i.intersection_type {
+            IntersectionType::TrafficSignal => "traffic_light",
+            IntersectionType::StopSign => {
+                if is_all_way_stop(map, i.id) {
+                    "allway_stop"
+                } else if map
+                    .get_stop_sign(i.id)
+                    .roads
+                    .values()
+                    .any(|ss| ss.must_stop)
+                {
+                    "priority_stop"
+                } else {
+                    "priority"
+                }
+            }
+            IntersectionType::Border | IntersectionType::Construction => "dead_end",
+        };
+        let inc_lanes: Vec<String> = i
+            .incoming_lanes
+            .iter()
+            .filter_map(|l| lanes.get(l))
+            .map(|(edge, idx)| format!("{}_{}", edge, idx))
+            .collect();
+        let center = flip(i.polygon.center(), bounds);
+        writeln!(
+            f,
+            r#"    <junction id="{}" type="{}" x="{:.2}" y="{:.2}" incLanes="{}" intLanes="" shape="{}"/>"#,
+            node_id(i.id),
+            junction_type,
+            center.x(),
+            center.y(),
+            inc_lanes.join(" "),
+            shape(i.polygon.points(), bounds)
+        )?;
+    }
+
+    for line in connections {
+        writeln!(f, "{}", line)?;
+    }
+    writeln!(f, "</net>")?;
+    Ok(())
+}
+
+/// Writes the trips from a scenario as a SUMO .rou.xml file. Trips that can't be expressed on the
+/// network produced by `write_network` are skipped.
+pub fn write_routes(map: &Map, scenario: &Scenario, path: &str, timer: &mut Timer) -> Result<()> {
+    let lanes = sumo_lane_ids(map);
+
+    let mut entries: Vec<(Time, String)> = Vec::new();
+    let mut skipped = 0;
+    timer.start_iter("convert trips", scenario.people.len());
+    for (person_idx, person) in scenario.people.iter().enumerate() {
+        timer.next();
+        let mut from = person.origin;
+        for (trip_idx, trip) in person.trips.iter().enumerate() {
+            if !trip.cancelled {
+                let id = format!("p{}_{}", person_idx, trip_idx);
+                match trip_xml(
+                    id,
+                    trip.depart,
+                    from,
+                    trip.destination,
+                    trip.mode,
+                    &lanes,
+                    map,
+                ) {
+                    Some(xml) => entries.push((trip.depart, xml)),
+                    None => {
+                        skipped += 1;
+                    }
+                }
+            }
+            from = trip.destination;
+        }
+    }
+
+    // Every transit vehicle follows the same route, stopping at the stops written by
+    // write_bus_stops.
+    let stops = sumo_bus_stop_ids(map);
+    let mut routes = Vec::new();
+    let mut skipped_routes = 0;
+    for br in map.all_bus_routes() {
+        let edges = match bus_route_edges(br, &lanes, map) {
+            Some(edges) => edges,
+            None => {
+                skipped_routes += 1;
+                continue;
+            }
+        };
+        routes.push(format!(
+            r#"    <route id="br{}" edges="{}"/>"#,
+            br.id.0,
+            edges.join(" ")
+        ));
+        for (idx, depart) in br.spawn_times.iter().enumerate() {
+            let mut xml = format!(
+                r#"    <vehicle id="br{}_{}" type="{}" route="br{}" depart="{:.2}" line="{}">"#,
+                br.id.0,
+                idx,
+                if br.route_type == PathConstraints::Train {
+                    "train"
+                } else {
+                    "bus"
+                },
+                br.id.0,
+                depart.inner_seconds(),
+                escape(&br.short_name)
+            );
+            let schedule = br.scheduled_trip(idx);
+            for (stop_idx, bs) in br.stops.iter().enumerate() {
+                // Hold to the timetable if there is one
+                let wait = match schedule {
+                    Some(trip) => format!(
+                        r#"until="{:.2}""#,
+                        trip.stop_times[stop_idx].1.inner_seconds()
+                    ),
+                    None => format!(r#"duration="{:.2}""#, DWELL_TIME.inner_seconds()),
+                };
+                xml.push_str(&format!(
+                    "\n        <stop busStop=\"{}\" {}/>",
+                    stops[bs], wait
+                ));
+            }
+            xml.push_str("\n    </vehicle>");
+            entries.push((*depart, xml));
+        }
+    }
+    if skipped_routes > 0 {
+        println!(
+            "Skipped {} transit routes that couldn't be expressed in SUMO",
+            prettyprint_usize(skipped_routes)
+        );
+    }
+
+    if skipped > 0 {
+        println!(
+            "Skipped {} trips that couldn't be expressed in SUMO",
+            prettyprint_usize(skipped)
+        );
+    }
+    // SUMO requires vehicles and people to be sorted by departure time
+    entries.sort_by_key(|(t, _)| *t);
+
+    let mut f = BufWriter::new(File::create(path)?);
+    writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(f, "<routes>")?;
+    // These match the typical vehicles produced by Scenario::instantiate
+    writeln!(
+        f,
+        r#"    <vType id="car" vClass="passenger" length="5.50" minGap="1.00"/>"#
+    )?;
+    writeln!(
+        f,
+        r#"    <vType id="bike" vClass="bicycle" length="1.80" minGap="1.00" maxSpeed="{:.2}"/>"#,
+        MAX_BIKE_SPEED.inner_meters_per_second()
+    )?;
+    writeln!(
+        f,
+        r#"    <vType id="pedestrian" vClass="pedestrian" maxSpeed="{:.2}"/>"#,
+        MAX_WALKING_SPEED.inner_meters_per_second()
+    )?;
+    writeln!(
+        f,
+        r#"    <vType id="bus" vClass="bus" length="12.50" minGap="1.00"/>"#
+    )?;
+    writeln!(
+        f,
+        r#"    <vType id="train" vClass="rail_urban" length="60.00" minGap="1.00"/>"#
+    )?;
+    for route in routes {
+        writeln!(f, "{}", route)?;
+    }
+    for (_, xml) in entries {
+        writeln!(f, "{}", xml)?;
+    }
+    writeln!(f, "</routes>")?;
+    Ok(())
+}
+
+/// Writes every bus stop and light rail platform as a SUMO additional file, so the transit vehicles
+/// and riders from `write_routes` have somewhere to meet. Load it with `--additional-files`.
+pub fn write_bus_stops(map: &Map, path: &str) -> Result<()> {
+    let lanes = sumo_lane_ids(map);
+    let stops = sumo_bus_stop_ids(map);
+
+    let mut f = BufWriter::new(File::create(path)?);
+    writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(f, "<additional>")?;
+    for bs in map.all_bus_stops().values() {
+        let (edge, idx) = match lanes.get(&bs.driving_pos.lane()) {
+            Some(x) => x,
+            None => continue,
+        };
+        let length = if bs.is_train_stop {
+            Distance::meters(60.0)
+        } else {
+            Distance::meters(12.5)
+        };
+        let end = bs.driving_pos.dist_along();
+        let lines: Vec<String> = map
+            .get_routes_serving_stop(bs.id)
+            .into_iter()
+            .map(|br| escape(&br.short_name))
+            .collect();
+        writeln!(
+            f,
+            r#"    <busStop id="{}" lane="{}_{}" startPos="{:.2}" endPos="{:.2}" name="{}" lines="{}">"#,
+            stops[&bs.id],
+            edge,
+            idx,
+            (end - length).max(Distance::ZERO).inner_meters(),
+            end.inner_meters(),
+            escape(&bs.name),
+            lines.join(" ")
+        )?;
+        // Light rail platforms and some bus stops are reached from a sidewalk on another edge
+        if let Some((sidewalk_edge, sidewalk_idx)) = lanes.get(&bs.sidewalk_pos.lane()) {
+            if sidewalk_edge != edge {
+                writeln!(
+                    f,
+                    r#"        <access lane="{}_{}" pos="{:.2}"/>"#,
+                    sidewalk_edge,
+                    sidewalk_idx,
+                    bs.sidewalk_pos.dist_along().inner_meters()
+                )?;
+            }
+        }
+        writeln!(f, "    </busStop>")?;
+    }
+    writeln!(f, "</additional>")?;
+    Ok(())
+}
+
+fn trip_xml(
+    id: String,
+    depart: Time,
+    from: TripEndpoint,
+    to: TripEndpoint,
+    mode: TripMode,
+    lanes: &BTreeMap<LaneID, (String, usize)>,
+    map: &Map,
+) -> Option<String> {
+    let req = TripEndpoint::path_req(from, to, mode, map)?;
+    let (from_edge, _) = lanes.get(&req.start.lane())?;
+    let (to_edge, _) = lanes.get(&req.end.lane())?;
+    let depart = depart.inner_seconds();
+    let depart_pos = req.start.dist_along().inner_meters();
+    let arrival_pos = req.end.dist_along().inner_meters();
+    Some(match mode {
+        TripMode::Drive
+        | TripMode::Bike
+        | TripMode::Micromobility
+        | TripMode::ParkAndRide
+        | TripMode::BikeAndRide => format!(
+            r#"    <trip id="{}" type="{}" depart="{:.2}" from="{}" to="{}" departPos="{:.2}" arrivalPos="{:.2}"/>"#,
+            id,
+            // Park and ride trips are exported as if they drove the whole way
+            if mode.to_constraints() == PathConstraints::Car {
+                "car"
+            } else {
+                "bike"
+            },
+            depart,
+            from_edge,
+            to_edge,
+            depart_pos,
+            arrival_pos
+        ),
+        TripMode::Walk | TripMode::Transit => format!(
+            "    <person id=\"{}\" type=\"pedestrian\" depart=\"{:.2}\" \
+             departPos=\"{:.2}\">\n        {}\n    </person>",
+            id,
+            depart,
+            depart_pos,
+            if mode == TripMode::Walk {
+                format!(
+                    r#"<walk from="{}" to="{}" arrivalPos="{:.2}"/>"#,
+                    from_edge, to_edge, arrival_pos
+                )
+            } else {
+                format!(
+                    r#"<personTrip from="{}" to="{}" arrivalPos="{:.2}" modes="public"/>"#,
+                    from_edge, to_edge, arrival_pos
+                )
+            }
+        ),
+    })
+}
+
+/// Writes a SUMO additional file with a
+/// [WAUT](https://sumo.dlr.de/docs/Simulation/Traffic_Lights.html#defining_program_switch_times_and_procedure)
+/// for every traffic signal that switches between timing plans over the day. Without it, SUMO runs
+/// whichever program of `write_network` it loaded last all day. Load it with
+/// `--additional-files`.
+pub fn write_signal_schedules(map: &Map, path: &str) -> Result<()> {
+    let lanes = sumo_lane_ids(map);
+
+    let mut f = BufWriter::new(File::create(path)?);
+    writeln!(f, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(f, "<additional>")?;
+    for i in signal_links(map, &lanes).keys() {
+        let signal = map.get_traffic_signal(*i);
+        if signal.num_plans() == 1 {
+            continue;
+        }
+        writeln!(
+            f,
+            r#"    <WAUT id="{}" refTime="0" startProg="0">"#,
+            node_id(*i)
+        )?;
+        for plan in 1..signal.num_plans() {
+            writeln!(
+                f,
+                r#"        <wautSwitch time="{:.0}" to="{}"/>"#,
+                (signal.plan_start_time(plan) - Time::START_OF_DAY).inner_seconds(),
+                plan
+            )?;
+        }
+        writeln!(f, "    </WAUT>")?;
+        writeln!(
+            f,
+            r#"    <wautJunction wautID="{}" junctionID="{}"/>"#,
+            node_id(*i),
+            node_id(*i)
+        )?;
+    }
+    writeln!(f, "</additional>")?;
+    Ok(())
+}
+
+/// The connections controlled by each exported traffic signal, in link index order.
+fn signal_links(
+    map: &Map,
+    lanes: &BTreeMap<LaneID, (String, usize)>,
+) -> BTreeMap<IntersectionID, Vec<TurnID>> {
+    let mut signal_links: BTreeMap<IntersectionID, Vec<TurnID>> = BTreeMap::new();
+    for t in map.all_turns().values() {
+        if t.between_sidewalks()
+            || !lanes.contains_key(&t.id.src)
+            || !lanes.contains_key(&t.id.dst)
+            || map.get_i(t.id.parent).intersection_type != IntersectionType::TrafficSignal
+        {
+            continue;
+        }
+        signal_links
+            .entry(t.id.parent)
+            .or_insert_with(Vec::new)
+            .push(t.id);
+    }
+    signal_links
+}
+
+/// One phase of a SUMO tlLogic.
+#[derive(Debug, PartialEq)]
+struct SignalPhase {
+    duration: Duration,
+    /// The minimum and maximum duration of an actuated phase
+    actuated: Option<(Duration, Duration)>,
+    state: String,
+}
+
+/// Translates the stages of a signal, each with its SUMO state string, into phases. Variable and
+/// actuated stages become actuated phases. A/B Street doesn't model yellow lights, but SUMO expects
+/// one whenever a movement loses its green, so the yellow is carved out of the end of the stage
+/// before. That keeps the cycle length the same.
+fn signal_phases(stages: &[(StageType, String)]) -> Vec<SignalPhase> {
+    let mut phases = Vec::new();
+    for (idx, (stage_type, state)) in stages.iter().enumerate() {
+        let next_state = &stages[(idx + 1) % stages.len()].1;
+        let yellow_state: String = state
+            .chars()
+            .zip(next_state.chars())
+            .map(|(now, next)| {
+                if (now == 'G' || now == 'g') && next == 'r' {
+                    'y'
+                } else {
+                    now
+                }
+            })
+            .collect();
+        let yellow = if yellow_state == *state {
+            Duration::ZERO
+        } else {
+            YELLOW_DURATION.min(stage_type.simple_duration())
+        };
+
+        phases.push(match stage_type {
+            StageType::Fixed(d) => SignalPhase {
+                duration: *d - yellow,
+                actuated: None,
+                state: state.clone(),
+            },
+            StageType::Variable(min, _, additional) => SignalPhase {
+                duration: *min - yellow,
+                actuated: Some((*min - yellow, *min + *additional - yellow)),
+                state: state.clone(),
+            },
+            StageType::Actuated(min, max, _) => SignalPhase {
+                duration: *min - yellow,
+                actuated: Some((*min - yellow, *max - yellow)),
+                state: state.clone(),
+            },
+        });
+        if yellow > Duration::ZERO {
+            phases.push(SignalPhase {
+                duration: yellow,
+                actuated: None,
+                state: yellow_state,
+            });
+        }
+    }
+    phases
+}
+
+/// The edges a transit vehicle drives through, from the start of its route, past every stop, to
+/// its end. None if some part of the route isn't on the exported network.
+fn bus_route_edges(
+    br: &BusRoute,
+    lanes: &BTreeMap<LaneID, (String, usize)>,
+    map: &Map,
+) -> Option<Vec<String>> {
+    let mut positions = vec![Position::start(br.start)];
+    positions.extend(br.stops.iter().map(|bs| map.get_bs(*bs).driving_pos));
+    if let Some(l) = br.end_border {
+        positions.push(Position::end(l, map));
+    }
+
+    let mut edges: Vec<String> = Vec::new();
+    for pair in positions.windows(2) {
+        let path = map
+            .pathfind(PathRequest {
+                start: pair[0],
+                end: pair[1],
+                constraints: br.route_type,
+                routing_params: None,
+            })
+            .ok()?;
+        for step in path.get_steps() {
+            if let PathStep::Lane(l) = step {
+                let (edge, _) = lanes.get(l)?;
+                if edges.last() != Some(edge) {
+                    edges.push(edge.clone());
+                }
+            }
+        }
+    }
+    Some(edges)
+}
+
+/// Names every exported bus stop.
+fn sumo_bus_stop_ids(map: &Map) -> BTreeMap<BusStopID, String> {
+    map.all_bus_stops()
+        .keys()
+        .enumerate()
+        .map(|(idx, bs)| (*bs, format!("bs{}", idx)))
+        .collect()
+}
+
+/// Maps every exported lane to its SUMO edge and lane index.
+fn sumo_lane_ids(map: &Map) -> BTreeMap<LaneID, (String, usize)> {
+    let mut lanes = BTreeMap::new();
+    for r in map.all_roads() {
+        for dir in vec![Direction::Fwd, Direction::Back] {
+            let dr = DirectedRoadID { id: r.id, dir };
+            for (idx, l) in sumo_lanes(dr, map).into_iter().enumerate() {
+                lanes.insert(l, (edge_id(dr), idx));
+            }
+        }
+    }
+    lanes
+}
+
+/// The lanes of one edge, in SUMO's order. Index 0 is the outermost lane -- the rightmost one when
+/// driving on the right.
+fn sumo_lanes(dr: DirectedRoadID, map: &Map) -> Vec<LaneID> {
+    let mut lanes: Vec<LaneID> = map
+        .get_r(dr.id)
+        .lanes_ltr()
+        .into_iter()
+        .filter(|(_, dir, lt)| *dir == dr.dir && lt.supports_any_movement())
+        .map(|(l, _, _)| l)
+        .collect();
+    // lanes_ltr is relative to the forward direction of the road
+    if dr.dir == Direction::Back {
+        lanes.reverse();
+    }
+    if map.get_config().driving_side == DrivingSide::Right {
+        lanes.reverse();
+    }
+    lanes
+}
+
+fn is_all_way_stop(map: &Map, i: IntersectionID) -> bool {
+    map.get_stop_sign(i).roads.values().all(|ss| ss.must_stop)
+}
+
+fn edge_id(dr: DirectedRoadID) -> String {
+    match dr.dir {
+        Direction::Fwd => format!("r{}", dr.id.0),
+        Direction::Back => format!("-r{}", dr.id.0),
+    }
+}
+
+fn node_id(i: IntersectionID) -> String {
+    format!("i{}", i.0)
+}
+
+fn edge_type(r: &Road) -> String {
+    if r.is_light_rail() {
+        "railway.light_rail".to_string()
+    } else {
+        format!(
+            "highway.{}",
+            r.osm_tags
+                .get(osm::HIGHWAY)
+                .map(|x| x.as_str())
+                .unwrap_or("road")
+        )
+    }
+}
+
+fn permissions(lt: LaneType) -> &'static str {
+    match lt {
+        LaneType::Sidewalk => r#"allow="pedestrian""#,
+        LaneType::Shoulder => r#"allow="pedestrian bicycle""#,
+        LaneType::Biking => r#"allow="bicycle""#,
+        LaneType::Bus => r#"allow="bus""#,
+        LaneType::LightRail => r#"allow="rail_urban""#,
+        _ => r#"disallow="pedestrian""#,
+    }
+}
+
+/// SUMO's Y axis points north.
+fn flip(pt: Pt2D, bounds: &Bounds) -> Pt2D {
+    Pt2D::new(pt.x(), bounds.max_y - pt.y())
+}
+
+fn shape(pts: &[Pt2D], bounds: &Bounds) -> String {
+    pts.iter()
+        .map(|pt| {
+            let pt = flip(*pt, bounds);
+            format!("{:.2},{:.2}", pt.x(), pt.y())
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn escape(x: &str) -> String {
+    x.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stages(list: Vec<(StageType, &str)>) -> Vec<(StageType, String)> {
+        list.into_iter()
+            .map(|(stage_type, state)| (stage_type, state.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn fixed_stages_get_yellow() {
+        let phases = signal_phases(&stages(vec![
+            (StageType::Fixed(Duration::seconds(30.0)), "GGrr"),
+            (StageType::Fixed(Duration::seconds(20.0)), "rrGg"),
+        ]));
+        assert_eq!(
+            phases,
+            vec![
+                SignalPhase {
+                    duration: Duration::seconds(27.0),
+                    actuated: None,
+                    state: "GGrr".to_string(),
+                },
+                SignalPhase {
+                    duration: Duration::seconds(3.0),
+                    actuated: None,
+                    state: "yyrr".to_string(),
+                },
+                SignalPhase {
+                    duration: Duration::seconds(17.0),
+                    actuated: None,
+                    state: "rrGg".to_string(),
+                },
+                SignalPhase {
+                    duration: Duration::seconds(3.0),
+                    actuated: None,
+                    state: "rryy".to_string(),
+                },
+            ]
+        );
+        // The cycle length doesn't change
+        let total: Duration = phases.iter().map(|p| p.duration).sum();
+        assert_eq!(total, Duration::seconds(50.0));
+    }
+
+    #[test]
+    fn movements_that_stay_green_skip_yellow() {
+        let phases = signal_phases(&stages(vec![
+            (StageType::Fixed(Duration::seconds(30.0)), "GGr"),
+            (StageType::Fixed(Duration::seconds(20.0)), "GrG"),
+        ]));
+        let states: Vec<&str> = phases.iter().map(|p| p.state.as_str()).collect();
+        assert_eq!(states, vec!["GGr", "Gyr", "GrG", "Gry"]);
+
+        // A single stage never changes
+        let phases = signal_phases(&stages(vec![(
+            StageType::Fixed(Duration::seconds(30.0)),
+            "GGg",
+        )]));
+        assert_eq!(phases.len(), 1);
+        assert_eq!(phases[0].duration, Duration::seconds(30.0));
+    }
+
+    #[test]
+    fn actuated_stages() {
+        let phases = signal_phases(&stages(vec![
+            (
+                StageType::Actuated(
+                    Duration::seconds(10.0),
+                    Duration::seconds(40.0),
+                    Duration::seconds(3.0),
+                ),
+                "Gr",
+            ),
+            (
+                StageType::Variable(
+                    Duration::seconds(15.0),
+                    Duration::seconds(5.0),
+                    Duration::seconds(10.0),
+                ),
+                "rG",
+            ),
+        ]));
+        assert_eq!(
+            phases[0].actuated,
+            Some((Duration::seconds(7.0), Duration::seconds(37.0)))
+        );
+        assert_eq!(phases[1].state, "yr");
+        assert_eq!(phases[1].actuated, None);
+        assert_eq!(
+            phases[2].actuated,
+            Some((Duration::seconds(12.0), Duration::seconds(22.0)))
+        );
+        assert_eq!(phases[3].state, "ry");
+    }
+
+    #[test]
+    fn skippable_stage_has_no_yellow() {
+        // An actuated stage with no minimum can be skipped entirely, so there's no time to show
+        // yellow in
+        let phases = signal_phases(&stages(vec![
+            (StageType::Fixed(Duration::seconds(30.0)), "rG"),
+            (
+                StageType::Actuated(
+                    Duration::ZERO,
+                    Duration::seconds(20.0),
+                    Duration::seconds(2.0),
+                ),
+                "Gr",
+            ),
+        ]));
+        let states: Vec<&str> = phases.iter().map(|p| p.state.as_str()).collect();
+        assert_eq!(states, vec!["rG", "ry", "Gr"]);
+        assert_eq!(
+            phases[2].actuated,
+            Some((Duration::ZERO, Duration::seconds(20.0)))
+        );
+    }
+}
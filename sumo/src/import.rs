@@ -0,0 +1,841 @@
+//! Converts a SUMO network into a RawMap, so the usual A/B Street importing pipeline can build a
+//! full map from it. SUMO's lanes are described with the OSM tags that `get_lane_specs_ltr`
+//! understands, so the lane configuration round-trips through the same code as OSM imports.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+
+use abstio::MapName;
+use abstutil::{retain_btreeset, Tags, Timer};
+use geom::{Distance, Duration, GPSBounds, LonLat, Pt2D};
+use map_model::raw::{OriginalRoad, RawIntersection, RawMap, RawRoad};
+use map_model::{
+    osm, DirectedRoadID, DrivingSide, IntersectionType, LaneType, Map, MovementID, Stage,
+    StageType, TurnPriority, TurnType,
+};
+
+use crate::{raw, Edge, EdgeID, Lane, Network, NodeID, Phase, VehicleClass};
+
+/// SUMO's default max-gap for actuated phases. A phase ends once no vehicle has crossed a detector
+/// for this long.
+const ACTUATED_PASSAGE_TIME_SECONDS: usize = 3;
+
+/// Converts a SUMO network into a RawMap. Traffic light programs can't be expressed in a RawMap,
+/// so they're returned separately. Apply them with `apply_traffic_signals` after building the
+/// Map.
+pub fn to_raw_map(
+    network: &Network,
+    name: MapName,
+    timer: &mut Timer,
+) -> Result<(RawMap, Vec<traffic_signal_data::TrafficSignal>)> {
+    let gps_bounds = network.location.orig_boundary.clone();
+    if gps_bounds.min_lon < -180.0
+        || gps_bounds.max_lon > 180.0
+        || gps_bounds.min_lat < -90.0
+        || gps_bounds.max_lat > 90.0
+    {
+        bail!(
+            "origBoundary {:?} isn't in degrees; run netconvert with a projection",
+            gps_bounds
+        );
+    }
+
+    let mut raw = RawMap::blank(name);
+    raw.boundary_polygon = gps_bounds.to_bounds().get_rectangle();
+    raw.gps_bounds = gps_bounds.clone();
+    raw.config.driving_side = if network.lefthand {
+        DrivingSide::Left
+    } else {
+        DrivingSide::Right
+    };
+    // SUMO explicitly models sidewalks
+    raw.config.inferred_sidewalks = false;
+
+    let mut node_ids: BTreeMap<NodeID, osm::NodeID> = BTreeMap::new();
+    for junction in network.junctions.values() {
+        let id = osm::NodeID(node_ids.len() as i64 + 1);
+        node_ids.insert(junction.id.clone(), id);
+        raw.intersections.insert(
+            id,
+            RawIntersection {
+                point: to_map_space(junction.pt, &network.location, &gps_bounds),
+                intersection_type: if junction.junction_type.starts_with("traffic_light") {
+                    IntersectionType::TrafficSignal
+                } else {
+                    IntersectionType::StopSign
+                },
+                elevation: Distance::ZERO,
+                crossing: None,
+            },
+        );
+    }
+
+    // SUMO has one edge in each direction, but a RawRoad covers both. Pair up edges going opposite
+    // ways between the same junctions.
+    let mut edges_between: BTreeMap<(NodeID, NodeID), Vec<&Edge>> = BTreeMap::new();
+    for edge in network.normal_edges.values() {
+        edges_between
+            .entry((edge.from.clone(), edge.to.clone()))
+            .or_insert_with(Vec::new)
+            .push(edge);
+    }
+    // The road and direction each edge became
+    let mut edge_ids: BTreeMap<EdgeID, (OriginalRoad, bool)> = BTreeMap::new();
+    timer.start_iter("convert edges", network.normal_edges.len());
+    for edge in network.normal_edges.values() {
+        timer.next();
+        if edge_ids.contains_key(&edge.id) || edge.from == edge.to {
+            continue;
+        }
+        let back = edges_between
+            .get(&(edge.to.clone(), edge.from.clone()))
+            .and_then(|list| list.iter().find(|e| !edge_ids.contains_key(&e.id)))
+            .cloned();
+
+        let id = OriginalRoad::new(
+            raw.roads.len() as i64 + 1,
+            (node_ids[&edge.from].0, node_ids[&edge.to].0),
+        );
+        let mut center_points: Vec<Pt2D> = edge
+            .center_line
+            .points()
+            .iter()
+            .map(|pt| to_map_space(*pt, &network.location, &gps_bounds))
+            .collect();
+        // RawRoads must begin and end exactly at their intersections
+        center_points[0] = raw.intersections[&id.i1].point;
+        *center_points.last_mut().unwrap() = raw.intersections[&id.i2].point;
+        center_points.dedup();
+        if center_points.len() < 2 {
+            continue;
+        }
+
+        edge_ids.insert(edge.id.clone(), (id, true));
+        if let Some(back) = back {
+            edge_ids.insert(back.id.clone(), (id, false));
+        }
+        raw.roads.insert(
+            id,
+            RawRoad {
+                center_points,
+                osm_tags: road_tags(edge, back, raw.config.driving_side),
+                turn_restrictions: Vec::new(),
+                complicated_turn_restrictions: Vec::new(),
+                percent_incline: 0.0,
+            },
+        );
+    }
+
+    // Junctions at the fringe of the network become borders, so traffic can enter and leave.
+    let mut degree: BTreeMap<osm::NodeID, usize> = BTreeMap::new();
+    for id in raw.roads.keys() {
+        *degree.entry(id.i1).or_insert(0) += 1;
+        *degree.entry(id.i2).or_insert(0) += 1;
+    }
+    for (id, i) in raw.intersections.iter_mut() {
+        if degree.get(id) == Some(&1) {
+            i.intersection_type = IntersectionType::Border;
+        }
+    }
+    let orphans: Vec<osm::NodeID> = raw
+        .intersections
+        .keys()
+        .filter(|id| !degree.contains_key(id))
+        .cloned()
+        .collect();
+    for id in orphans {
+        raw.delete_intersection(id);
+    }
+
+    let signals = traffic_signals(network, &node_ids, &edge_ids);
+    Ok((raw, signals))
+}
+
+/// Replaces the heuristic traffic signals in a map with the timing imported from SUMO. Any
+/// signals that no longer match the map are skipped with a warning.
+pub fn apply_traffic_signals(
+    map: &mut Map,
+    signals: Vec<traffic_signal_data::TrafficSignal>,
+    timer: &mut Timer,
+) {
+    timer.start_iter("apply SUMO traffic signals", signals.len());
+    for raw in signals {
+        timer.next();
+        let id = raw.intersection_osm_node_id;
+        if let Err(err) = apply_traffic_signal(map, raw) {
+            warn!("Not using SUMO timing for {}: {}", id, err);
+        }
+    }
+}
+
+fn apply_traffic_signal(map: &mut Map, raw: traffic_signal_data::TrafficSignal) -> Result<()> {
+    let i = map.find_i_by_osm_id(osm::NodeID(raw.intersection_osm_node_id))?;
+    let mut ts = match map.maybe_get_traffic_signal(i) {
+        Some(ts) => ts.clone(),
+        None => bail!("{} isn't a traffic signal", i),
+    };
+    let plan = &raw.plans[0];
+    ts.offset = Duration::seconds(plan.offset_seconds as f64);
+    ts.stages.clear();
+    for s in &plan.stages {
+        let mut stage = Stage::new();
+        stage.stage_type = match s.stage_type {
+            traffic_signal_data::StageType::Fixed(d) => {
+                StageType::Fixed(Duration::seconds(d as f64))
+            }
+            traffic_signal_data::StageType::Variable(min, delay, additional) => {
+                StageType::Variable(
+                    Duration::seconds(min as f64),
+                    Duration::seconds(delay as f64),
+                    Duration::seconds(additional as f64),
+                )
+            }
+            traffic_signal_data::StageType::Actuated(min, max, passage) => StageType::Actuated(
+                Duration::seconds(min as f64),
+                Duration::seconds(max as f64),
+                Duration::seconds(passage as f64),
+            ),
+        };
+        for (turns, pri) in vec![
+            (&s.protected_turns, TurnPriority::Protected),
+            (&s.permitted_turns, TurnPriority::Yield),
+        ] {
+            for t in turns {
+                let id = MovementID {
+                    from: find_r(&t.from, map)?,
+                    to: find_r(&t.to, map)?,
+                    parent: i,
+                    crosswalk: false,
+                };
+                // Lane connections in SUMO don't always match up with turns here
+                if let Some(movement) = ts.movements.get(&id) {
+                    stage.edit_movement(movement, pri);
+                }
+            }
+        }
+        // SUMO networks usually don't have pedestrian crossings, so add crosswalks the same way
+        // the heuristics do.
+        for movement in ts.movements.values() {
+            if movement.turn_type == TurnType::Crosswalk
+                && stage.could_be_protected(movement.id, &ts.movements)
+            {
+                stage.edit_movement(movement, TurnPriority::Protected);
+            }
+        }
+        ts.stages.push(stage);
+    }
+
+    // There may be movements that SUMO doesn't have, like U-turns. Let them yield whenever
+    // something else from the same road can go.
+    for m in ts.missing_turns(0) {
+        if m.crosswalk {
+            continue;
+        }
+        for stage in &mut ts.stages {
+            if stage
+                .protected_movements
+                .iter()
+                .chain(stage.yield_movements.iter())
+                .any(|x| x.from == m.from)
+            {
+                stage.edit_movement(&ts.movements[&m], TurnPriority::Yield);
+            }
+        }
+    }
+
+    map.try_edit_traffic_signal(ts)
+}
+
+fn traffic_signals(
+    network: &Network,
+    node_ids: &BTreeMap<NodeID, osm::NodeID>,
+    edge_ids: &BTreeMap<EdgeID, (OriginalRoad, bool)>,
+) -> Vec<traffic_signal_data::TrafficSignal> {
+    // One traffic light can control several junctions. For each, find the movement controlled by
+    // every link index.
+    let mut links: BTreeMap<&str, BTreeMap<osm::NodeID, Vec<(usize, traffic_signal_data::Turn)>>> =
+        BTreeMap::new();
+    for c in &network.connections {
+        let (tl, idx) = match (&c.tl, c.link_index) {
+            (Some(tl), Some(idx)) => (tl, idx),
+            _ => continue,
+        };
+        let (from, to) = match (edge_ids.get(&c.from), edge_ids.get(&c.to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => continue,
+        };
+        let i = node_ids[&network.normal_edges[&c.from].to];
+        links
+            .entry(tl.as_str())
+            .or_insert_with(BTreeMap::new)
+            .entry(i)
+            .or_insert_with(Vec::new)
+            .push((
+                idx,
+                traffic_signal_data::Turn {
+                    from: directed_road(*from),
+                    to: directed_road(*to),
+                    intersection_osm_node_id: i.0,
+                    is_crosswalk: false,
+                },
+            ));
+    }
+
+    let mut results = Vec::new();
+    for (tl, per_junction) in links {
+        let logic = if let Some(logic) = network.traffic_lights.get(tl) {
+            logic
+        } else {
+            continue;
+        };
+        for (i, links) in per_junction {
+            let stages = stages_from_phases(&logic.phases, &links);
+            if stages.is_empty() {
+                continue;
+            }
+            results.push(traffic_signal_data::TrafficSignal {
+                intersection_osm_node_id: i.0,
+                plans: vec![traffic_signal_data::Plan {
+                    start_time_seconds: 0,
+                    stages,
+                    offset_seconds: logic.offset.inner_seconds() as usize,
+                }],
+                transit_priority: None,
+            });
+        }
+    }
+    results
+}
+
+/// Groups the phases of a SUMO traffic light into stages, given the turn controlled by each link
+/// index. Phases of actuated programs become actuated stages.
+fn stages_from_phases(
+    phases: &[Phase],
+    links: &[(usize, traffic_signal_data::Turn)],
+) -> Vec<traffic_signal_data::Stage> {
+    let mut stages: Vec<traffic_signal_data::Stage> = Vec::new();
+    // Yellow and all-red phases before the first green belong to the last stage of the previous
+    // cycle
+    let mut leading_duration = 0;
+    for phase in phases {
+        let mut protected_turns = BTreeSet::new();
+        let mut permitted_turns = BTreeSet::new();
+        for (idx, turn) in links {
+            match phase.state.get(*idx) {
+                Some('G') => {
+                    protected_turns.insert(turn.clone());
+                }
+                Some('g') => {
+                    permitted_turns.insert(turn.clone());
+                }
+                _ => {}
+            }
+        }
+        // Different lanes of one movement might have different states
+        retain_btreeset(&mut permitted_turns, |t| !protected_turns.contains(t));
+
+        let duration = phase.duration.inner_seconds() as usize;
+        let (min_duration, max_duration) = phase
+            .actuated
+            .map(|(min, max)| (min.inner_seconds() as usize, max.inner_seconds() as usize))
+            .unwrap_or((duration, duration));
+        // Yellow and all-red phases are just part of the previous stage here, and so are repeats
+        // of the same stage.
+        let repeat = stages
+            .last()
+            .map(|last| {
+                last.protected_turns == protected_turns && last.permitted_turns == permitted_turns
+            })
+            .unwrap_or(false);
+        if repeat || (protected_turns.is_empty() && permitted_turns.is_empty()) {
+            match stages.last_mut().map(|s| &mut s.stage_type) {
+                Some(traffic_signal_data::StageType::Fixed(d)) => {
+                    *d += duration;
+                }
+                Some(traffic_signal_data::StageType::Actuated(min, max, _)) => {
+                    *min += min_duration;
+                    *max += max_duration;
+                }
+                Some(traffic_signal_data::StageType::Variable(_, _, _)) => unreachable!(),
+                None => {
+                    leading_duration += duration;
+                }
+            }
+            continue;
+        }
+        stages.push(traffic_signal_data::Stage {
+            protected_turns,
+            permitted_turns,
+            stage_type: if phase.actuated.is_some() {
+                traffic_signal_data::StageType::Actuated(
+                    min_duration,
+                    max_duration,
+                    ACTUATED_PASSAGE_TIME_SECONDS,
+                )
+            } else {
+                traffic_signal_data::StageType::Fixed(duration)
+            },
+        });
+    }
+    match stages.last_mut().map(|s| &mut s.stage_type) {
+        Some(traffic_signal_data::StageType::Fixed(d)) => {
+            *d += leading_duration;
+        }
+        Some(traffic_signal_data::StageType::Actuated(min, max, _)) => {
+            *min += leading_duration;
+            *max += leading_duration;
+        }
+        _ => {}
+    }
+    stages
+}
+
+/// Describes the lanes of one or two SUMO edges with OSM tags.
+fn road_tags(fwd: &Edge, back: Option<&Edge>, driving_side: DrivingSide) -> Tags {
+    let mut tags = Tags::empty();
+    tags.insert("id", fwd.id.0.clone());
+    if let Some(name) = &fwd.name {
+        tags.insert(osm::NAME, name);
+    }
+    if let Some(lane) = fwd.lanes.first() {
+        tags.insert(
+            osm::MAXSPEED,
+            format!("{}", (lane.speed.inner_meters_per_second() * 3.6).round()),
+        );
+    }
+    // Like "highway.residential", or a combination like "highway.primary|railway.tram"
+    let parts: Vec<&str> = fwd
+        .edge_type
+        .split('|')
+        .next()
+        .unwrap()
+        .splitn(2, '.')
+        .collect();
+    if parts.len() == 2 {
+        tags.insert(parts[0], parts[1]);
+    } else {
+        tags.insert(osm::HIGHWAY, "unclassified");
+    }
+
+    let fwd_lanes = lanes_inside_out(fwd);
+    let back_lanes = back.map(lanes_inside_out).unwrap_or_else(Vec::new);
+    if fwd_lanes
+        .iter()
+        .chain(back_lanes.iter())
+        .all(|lt| *lt == LaneType::LightRail)
+    {
+        tags.insert("railway", "light_rail");
+        return tags;
+    }
+    let (near_side, far_side) = match driving_side {
+        DrivingSide::Right => ("right", "left"),
+        DrivingSide::Left => ("left", "right"),
+    };
+
+    let is_vehicle = |lt: &&LaneType| **lt == LaneType::Driving || **lt == LaneType::Bus;
+    let num_fwd = fwd_lanes.iter().filter(is_vehicle).count();
+    let num_back = back_lanes.iter().filter(is_vehicle).count();
+    let has = |lt: LaneType| fwd_lanes.contains(&lt) || back_lanes.contains(&lt);
+    if num_fwd + num_back == 0 {
+        // No vehicles, so this is some kind of path
+        tags.insert(
+            osm::HIGHWAY,
+            if has(LaneType::Biking) {
+                "cycleway"
+            } else {
+                "footway"
+            },
+        );
+        if !has(LaneType::Sidewalk) && !has(LaneType::Shoulder) {
+            tags.insert("foot", "no");
+        }
+        if back.is_none() {
+            tags.insert("oneway", "yes");
+        }
+        return tags;
+    }
+
+    tags.insert("lanes", (num_fwd + num_back).to_string());
+    tags.insert("lanes:forward", num_fwd.to_string());
+    if back.is_some() {
+        tags.insert("lanes:backward", num_back.to_string());
+    } else {
+        tags.insert("oneway", "yes");
+    }
+    if fwd_lanes.contains(&LaneType::Bus) {
+        tags.insert("bus:lanes:forward", bus_lanes(&fwd_lanes));
+    }
+    if back_lanes.contains(&LaneType::Bus) {
+        tags.insert("bus:lanes:backward", bus_lanes(&back_lanes));
+    }
+
+    match (
+        fwd_lanes.contains(&LaneType::Biking),
+        back_lanes.contains(&LaneType::Biking),
+    ) {
+        (true, true) => {
+            tags.insert("cycleway:both", "lane");
+        }
+        (true, false) => {
+            tags.insert(format!("cycleway:{}", near_side), "lane");
+        }
+        (false, true) => {
+            tags.insert(format!("cycleway:{}", far_side), "lane");
+        }
+        (false, false) => {}
+    }
+
+    let fwd_sidewalk = fwd_lanes.last() == Some(&LaneType::Sidewalk);
+    // A one-way edge might have sidewalks on both sides
+    let back_sidewalk = if back.is_some() {
+        back_lanes.last() == Some(&LaneType::Sidewalk)
+    } else {
+        fwd_lanes.len() > 1 && fwd_lanes[0] == LaneType::Sidewalk
+    };
+    tags.insert(
+        osm::SIDEWALK,
+        match (fwd_sidewalk, back_sidewalk) {
+            (true, true) => "both",
+            (true, false) => near_side,
+            (false, true) => far_side,
+            (false, false) => "no",
+        },
+    );
+
+    tags
+}
+
+/// SUMO numbers lanes from the outside of the road, so reverse that.
+fn lanes_inside_out(edge: &Edge) -> Vec<LaneType> {
+    let mut lanes: Vec<&Lane> = edge.lanes.iter().collect();
+    lanes.sort_by_key(|l| l.index);
+    lanes.reverse();
+    lanes.into_iter().map(lane_type).collect()
+}
+
+fn lane_type(lane: &Lane) -> LaneType {
+    let allow = &lane.allow;
+    let is_other = |vc: &VehicleClass, classes: &[&str]| match vc {
+        VehicleClass::Other(x) => classes.contains(&x.as_str()),
+        _ => false,
+    };
+    if allow.is_empty() {
+        LaneType::Driving
+    } else if allow.iter().all(|vc| *vc == VehicleClass::Pedestrian) {
+        LaneType::Sidewalk
+    } else if allow.iter().all(|vc| *vc == VehicleClass::Bicycle) {
+        LaneType::Biking
+    } else if allow
+        .iter()
+        .all(|vc| *vc == VehicleClass::Pedestrian || *vc == VehicleClass::Bicycle)
+    {
+        LaneType::Shoulder
+    } else if allow
+        .iter()
+        .any(|vc| *vc == VehicleClass::RailUrban || is_other(vc, &["tram", "rail"]))
+    {
+        LaneType::LightRail
+    } else if allow.iter().any(|vc| is_other(vc, &["bus"]))
+        && !allow.iter().any(|vc| is_other(vc, &["passenger"]))
+    {
+        LaneType::Bus
+    } else {
+        LaneType::Driving
+    }
+}
+
+fn bus_lanes(lanes: &[LaneType]) -> String {
+    lanes
+        .iter()
+        .filter_map(|lt| match lt {
+            LaneType::Bus => Some("designated"),
+            LaneType::Driving => Some("yes"),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("|")
+}
+
+fn directed_road((id, fwd): (OriginalRoad, bool)) -> traffic_signal_data::DirectedRoad {
+    traffic_signal_data::DirectedRoad {
+        osm_way_id: id.osm_way_id.0,
+        osm_node1: id.i1.0,
+        osm_node2: id.i2.0,
+        is_forwards: fwd,
+    }
+}
+
+fn find_r(id: &traffic_signal_data::DirectedRoad, map: &Map) -> Result<DirectedRoadID> {
+    Ok(DirectedRoadID {
+        id: map.find_r_by_osm_id(OriginalRoad::new(
+            id.osm_way_id,
+            (id.osm_node1, id.osm_node2),
+        ))?,
+        dir: if id.is_forwards {
+            map_model::Direction::Fwd
+        } else {
+            map_model::Direction::Back
+        },
+    })
+}
+
+/// SUMO coordinates are usually a UTM projection of origBoundary. Over the small area of a
+/// network, treat that as linear.
+fn to_map_space(pt: Pt2D, location: &raw::Location, gps_bounds: &GPSBounds) -> Pt2D {
+    let conv = &location.converted_boundary;
+    let x = (pt.x() - conv.min_x) / (conv.max_x - conv.min_x);
+    // The Y axis was already flipped during normalization
+    let y = (conv.max_y - pt.y() - conv.min_y) / (conv.max_y - conv.min_y);
+    LonLat::new(
+        gps_bounds.min_lon + x * (gps_bounds.max_lon - gps_bounds.min_lon),
+        gps_bounds.min_lat + y * (gps_bounds.max_lat - gps_bounds.min_lat),
+    )
+    .to_pt(gps_bounds)
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::{PolyLine, Speed};
+
+    use super::*;
+    use crate::LaneID;
+
+    fn lane(index: usize, allow: Vec<VehicleClass>) -> Lane {
+        Lane {
+            id: LaneID(format!("e_{}", index)),
+            index,
+            speed: Speed::meters_per_second(13.89),
+            length: Distance::meters(100.0),
+            width: Distance::meters(3.0),
+            center_line: PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)]),
+            allow,
+        }
+    }
+
+    fn edge(edge_type: &str, lanes: Vec<Lane>) -> Edge {
+        Edge {
+            id: EdgeID("e".to_string()),
+            edge_type: edge_type.to_string(),
+            name: None,
+            from: NodeID("n1".to_string()),
+            to: NodeID("n2".to_string()),
+            priority: 1,
+            lanes,
+            center_line: PolyLine::must_new(vec![Pt2D::new(0.0, 0.0), Pt2D::new(100.0, 0.0)]),
+        }
+    }
+
+    fn other(vc: &str) -> VehicleClass {
+        VehicleClass::Other(vc.to_string())
+    }
+
+    #[test]
+    fn test_lane_type() {
+        for (allow, lt) in vec![
+            (vec![], LaneType::Driving),
+            (vec![VehicleClass::Pedestrian], LaneType::Sidewalk),
+            (vec![VehicleClass::Bicycle], LaneType::Biking),
+            (
+                vec![VehicleClass::Pedestrian, VehicleClass::Bicycle],
+                LaneType::Shoulder,
+            ),
+            (vec![other("tram")], LaneType::LightRail),
+            (vec![VehicleClass::RailUrban], LaneType::LightRail),
+            (vec![other("bus")], LaneType::Bus),
+            (vec![other("bus"), other("taxi")], LaneType::Bus),
+            (vec![other("bus"), other("passenger")], LaneType::Driving),
+        ] {
+            assert_eq!(lane_type(&lane(0, allow)), lt);
+        }
+    }
+
+    #[test]
+    fn test_road_tags() {
+        // A one-way street with a sidewalk on the right
+        let fwd = edge(
+            "highway.residential",
+            vec![lane(0, vec![VehicleClass::Pedestrian]), lane(1, vec![])],
+        );
+        let tags = road_tags(&fwd, None, DrivingSide::Right);
+        assert!(tags.is(osm::HIGHWAY, "residential"));
+        assert!(tags.is(osm::MAXSPEED, "50"));
+        assert!(tags.is("lanes", "1"));
+        assert!(tags.is("oneway", "yes"));
+        assert!(tags.is(osm::SIDEWALK, "right"));
+
+        // A two-way street with a bus lane and bike lane in one direction
+        let fwd = edge(
+            "highway.primary|railway.tram",
+            vec![
+                lane(0, vec![VehicleClass::Pedestrian]),
+                lane(1, vec![VehicleClass::Bicycle]),
+                lane(2, vec![other("bus")]),
+                lane(3, vec![]),
+            ],
+        );
+        let back = edge(
+            "highway.primary",
+            vec![lane(0, vec![VehicleClass::Pedestrian]), lane(1, vec![])],
+        );
+        let tags = road_tags(&fwd, Some(&back), DrivingSide::Right);
+        assert!(tags.is(osm::HIGHWAY, "primary"));
+        assert!(tags.is("lanes", "3"));
+        assert!(tags.is("lanes:forward", "2"));
+        assert!(tags.is("lanes:backward", "1"));
+        assert!(tags.is("bus:lanes:forward", "yes|designated"));
+        assert!(tags.is("cycleway:right", "lane"));
+        assert!(tags.is(osm::SIDEWALK, "both"));
+        assert!(!tags.contains_key("oneway"));
+
+        // Only pedestrians
+        let path = edge(
+            "highway.footway",
+            vec![lane(0, vec![VehicleClass::Pedestrian])],
+        );
+        let tags = road_tags(&path, None, DrivingSide::Left);
+        assert!(tags.is(osm::HIGHWAY, "footway"));
+        assert!(!tags.contains_key("foot"));
+
+        // Unknown types
+        let tags = road_tags(&edge("", vec![lane(0, vec![])]), None, DrivingSide::Right);
+        assert!(tags.is(osm::HIGHWAY, "unclassified"));
+    }
+
+    fn turn(way: i64) -> traffic_signal_data::Turn {
+        let road = |id| traffic_signal_data::DirectedRoad {
+            osm_way_id: id,
+            osm_node1: 1,
+            osm_node2: 2,
+            is_forwards: true,
+        };
+        traffic_signal_data::Turn {
+            from: road(way),
+            to: road(way + 100),
+            intersection_osm_node_id: 2,
+            is_crosswalk: false,
+        }
+    }
+
+    fn phase(duration: f64, state: &str) -> Phase {
+        Phase {
+            duration: Duration::seconds(duration),
+            state: state.chars().collect(),
+            actuated: None,
+        }
+    }
+
+    fn actuated_phase(min: f64, max: f64, state: &str) -> Phase {
+        Phase {
+            duration: Duration::seconds(min),
+            state: state.chars().collect(),
+            actuated: Some((Duration::seconds(min), Duration::seconds(max))),
+        }
+    }
+
+    #[test]
+    fn test_parse_tl_logic() {
+        let logic: raw::TrafficLightLogic = quick_xml::de::from_str(
+            r#"<tlLogic id="J1" type="static" programID="0" offset="5">
+                <phase duration="30" state="Gr"/>
+                <phase duration="3" state="yr"/>
+            </tlLogic>"#,
+        )
+        .unwrap();
+        assert_eq!(logic.id, "J1");
+        assert_eq!(logic.program_id, "0");
+        assert_eq!(logic.offset, 5.0);
+        assert_eq!(logic.phases.len(), 2);
+        assert_eq!(logic.phases[1].duration, 3.0);
+        assert_eq!(logic.phases[1].state, "yr");
+        assert_eq!(logic.phases[1].min_duration, None);
+
+        let logic: raw::TrafficLightLogic = quick_xml::de::from_str(
+            r#"<tlLogic id="J1" type="actuated" programID="0" offset="0">
+                <phase duration="10" minDur="5" maxDur="40" state="Gr"/>
+            </tlLogic>"#,
+        )
+        .unwrap();
+        assert_eq!(logic.tl_type, "actuated");
+        assert_eq!(logic.phases[0].min_duration, Some(5.0));
+        assert_eq!(logic.phases[0].max_duration, Some(40.0));
+    }
+
+    #[test]
+    fn test_stages_from_phases() {
+        let links = vec![(0, turn(1)), (1, turn(2))];
+        let stages = stages_from_phases(
+            &[
+                // The cycle starts with the yellow from the previous one
+                phase(3.0, "ry"),
+                phase(30.0, "Gr"),
+                phase(3.0, "yr"),
+                phase(25.0, "rG"),
+                phase(2.0, "rr"),
+            ],
+            &links,
+        );
+        assert_eq!(stages.len(), 2);
+        assert_eq!(
+            stages[0].protected_turns,
+            vec![turn(1)].into_iter().collect()
+        );
+        assert_eq!(
+            stages[0].stage_type,
+            traffic_signal_data::StageType::Fixed(33)
+        );
+        assert_eq!(
+            stages[1].protected_turns,
+            vec![turn(2)].into_iter().collect()
+        );
+        assert_eq!(
+            stages[1].stage_type,
+            traffic_signal_data::StageType::Fixed(30)
+        );
+    }
+
+    #[test]
+    fn test_permitted_and_repeated_phases() {
+        // Two lanes of the first movement, and one of the second
+        let links = vec![(0, turn(1)), (1, turn(1)), (2, turn(2))];
+        let stages = stages_from_phases(&[phase(20.0, "Ggg"), phase(10.0, "Ggg")], &links);
+        assert_eq!(stages.len(), 1);
+        // The movement is protected if any of its lanes are
+        assert_eq!(
+            stages[0].protected_turns,
+            vec![turn(1)].into_iter().collect()
+        );
+        assert_eq!(
+            stages[0].permitted_turns,
+            vec![turn(2)].into_iter().collect()
+        );
+        assert_eq!(
+            stages[0].stage_type,
+            traffic_signal_data::StageType::Fixed(30)
+        );
+    }
+
+    #[test]
+    fn test_actuated_phases() {
+        let links = vec![(0, turn(1)), (1, turn(2))];
+        let stages = stages_from_phases(
+            &[
+                actuated_phase(5.0, 40.0, "Gr"),
+                phase(3.0, "yr"),
+                actuated_phase(10.0, 20.0, "rG"),
+                phase(3.0, "ry"),
+            ],
+            &links,
+        );
+        assert_eq!(stages.len(), 2);
+        // The yellow after each green is part of both the minimum and maximum
+        assert_eq!(
+            stages[0].stage_type,
+            traffic_signal_data::StageType::Actuated(8, 43, ACTUATED_PASSAGE_TIME_SECONDS)
+        );
+        assert_eq!(
+            stages[1].stage_type,
+            traffic_signal_data::StageType::Actuated(13, 23, ACTUATED_PASSAGE_TIME_SECONDS)
+        );
+    }
+}
@@ -2,13 +2,19 @@
 
 #[macro_use]
 extern crate anyhow;
+#[macro_use]
+extern crate log;
 
 use std::collections::BTreeMap;
 
-use geom::{Distance, PolyLine, Polygon, Pt2D, Speed};
+use geom::{Distance, Duration, PolyLine, Polygon, Pt2D, Speed};
 
+pub use self::export::{write_bus_stops, write_network, write_routes, write_signal_schedules};
+pub use self::import::{apply_traffic_signals, to_raw_map};
 pub use self::raw::{Connection, Direction, EdgeID, InternalLaneID, LaneID, NodeID};
 
+mod export;
+mod import;
 mod normalize;
 mod raw;
 
@@ -20,12 +26,16 @@ mod raw;
 /// - Internal edges are represented separately
 /// - Internal junctions are filtered out
 /// - The Y coordinate is inverted, so that Y decreases northbound
+/// - Only the first program for each traffic light is kept
 pub struct Network {
     pub location: raw::Location,
+    /// Vehicles drive on the left side of the road
+    pub lefthand: bool,
     pub normal_edges: BTreeMap<EdgeID, Edge>,
     pub internal_edges: BTreeMap<EdgeID, InternalEdge>,
     pub junctions: BTreeMap<NodeID, Junction>,
     pub connections: Vec<Connection>,
+    pub traffic_lights: BTreeMap<String, TrafficLight>,
 }
 
 pub struct Edge {
@@ -74,6 +84,22 @@ pub struct Junction {
     pub shape: Polygon,
 }
 
+/// See https://sumo.dlr.de/docs/Simulation/Traffic_Lights.html
+pub struct TrafficLight {
+    pub id: String,
+    pub offset: Duration,
+    pub phases: Vec<Phase>,
+}
+
+pub struct Phase {
+    pub duration: Duration,
+    /// One character per link index of the connections controlled by this traffic light. See
+    /// https://sumo.dlr.de/docs/Simulation/Traffic_Lights.html#signal_state_definitions
+    pub state: Vec<char>,
+    /// The minimum and maximum duration of a phase in an actuated program
+    pub actuated: Option<(Duration, Duration)>,
+}
+
 #[derive(PartialEq)]
 pub enum VehicleClass {
     Pedestrian,
@@ -9,7 +9,7 @@ use abstutil::{CmdArgs, Tags, Timer};
 use geom::{Distance, PolyLine};
 use map_model::{
     osm, raw, AccessRestrictions, Intersection, IntersectionID, IntersectionType, Lane, LaneID,
-    LaneType, Map, Road, RoadID, Turn, TurnID, TurnType,
+    LaneType, Map, ParkingPolicy, Road, RoadID, Turn, TurnID, TurnType,
 };
 
 use sumo::{Direction, InternalLaneID, Network, NodeID, VehicleClass};
@@ -123,6 +123,9 @@ fn convert(orig_path: &str, network: Network) -> Result<Map> {
                 orig_id: raw::OriginalRoad::new(123, (456, 789)),
                 speed_limit,
                 access_restrictions: AccessRestrictions::new(),
+                parking_policy: ParkingPolicy::free(),
+                turn_lanes_fwd: None,
+                turn_lanes_back: None,
                 zorder: 0,
                 percent_incline: 0.0,
 
@@ -5,10 +5,11 @@ use std::collections::BTreeMap;
 use anyhow::Result;
 
 use abstutil::Timer;
-use geom::{Distance, PolyLine, Pt2D, Ring};
+use geom::{Distance, Duration, PolyLine, Pt2D, Ring};
 
 use crate::{
-    raw, Edge, InternalEdge, InternalLane, InternalLaneID, Junction, Lane, LaneID, Network,
+    raw, Edge, InternalEdge, InternalLane, InternalLaneID, Junction, Lane, LaneID, Network, Phase,
+    TrafficLight,
 };
 
 impl Network {
@@ -24,10 +25,12 @@ impl Network {
     fn from_raw(raw: raw::Network) -> Network {
         let mut network = Network {
             location: raw.location,
+            lefthand: raw.lefthand,
             normal_edges: BTreeMap::new(),
             internal_edges: BTreeMap::new(),
             junctions: BTreeMap::new(),
             connections: raw.connections,
+            traffic_lights: BTreeMap::new(),
         };
 
         let types: BTreeMap<String, raw::Type> =
@@ -68,6 +71,10 @@ impl Network {
                     .insert(edge.id.clone(), InternalEdge { id: edge.id, lanes });
                 continue;
             }
+            // Pedestrian crossings and walking areas aren't handled yet
+            if edge.function != raw::Function::Normal {
+                continue;
+            }
 
             let from = edge.from.unwrap();
             let to = edge.to.unwrap();
@@ -116,6 +123,41 @@ impl Network {
             );
         }
 
+        for tl in raw.traffic_lights {
+            // Switching between programs by time of day needs a WAUT from a separate additional
+            // file, so there's no way to turn these into timing plans.
+            if let Some(first) = network.traffic_lights.get(&tl.id) {
+                warn!(
+                    "Traffic light {} has several programs. Only the first is used; ignoring \
+                     program {}",
+                    first.id, tl.program_id
+                );
+                continue;
+            }
+            let actuated = tl.tl_type == "actuated" || tl.tl_type == "delay_based";
+            network.traffic_lights.insert(
+                tl.id.clone(),
+                TrafficLight {
+                    id: tl.id,
+                    offset: Duration::seconds(tl.offset),
+                    phases: tl
+                        .phases
+                        .into_iter()
+                        .map(|phase| Phase {
+                            duration: Duration::seconds(phase.duration),
+                            state: phase.state.chars().collect(),
+                            actuated: match (phase.min_duration, phase.max_duration) {
+                                (Some(min), Some(max)) if actuated => {
+                                    Some((Duration::seconds(min), Duration::seconds(max)))
+                                }
+                                _ => None,
+                            },
+                        })
+                        .collect(),
+                },
+            );
+        }
+
         network.fix_coordinates();
         network
     }
@@ -11,11 +11,15 @@ use crate::VehicleClass;
 
 #[derive(Deserialize)]
 pub struct Network {
+    #[serde(default)]
+    pub lefthand: bool,
     pub location: Location,
     #[serde(rename = "type")]
     pub types: Vec<Type>,
     #[serde(rename = "edge")]
     pub edges: Vec<Edge>,
+    #[serde(rename = "tlLogic", default)]
+    pub traffic_lights: Vec<TrafficLightLogic>,
     #[serde(rename = "junction")]
     pub junctions: Vec<Junction>,
     #[serde(rename = "connection")]
@@ -44,7 +48,7 @@ impl Network {
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
 pub struct EdgeID(pub String);
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
-pub struct NodeID(String);
+pub struct NodeID(pub String);
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
 pub struct LaneID(pub String);
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
@@ -87,6 +91,10 @@ pub enum Function {
     Normal,
     #[serde(rename = "internal")]
     Internal,
+    #[serde(rename = "crossing")]
+    Crossing,
+    #[serde(rename = "walkingarea")]
+    WalkingArea,
 }
 impl std::default::Default for Function {
     fn default() -> Function {
@@ -159,6 +167,10 @@ pub struct Connection {
     pub to_lane: usize,
     pub via: Option<InternalLaneID>,
     pub dir: Direction,
+    /// The traffic light controlling this connection
+    pub tl: Option<String>,
+    #[serde(rename = "linkIndex")]
+    pub link_index: Option<usize>,
 }
 impl Connection {
     pub fn from_lane(&self) -> LaneID {
@@ -170,6 +182,30 @@ impl Connection {
     }
 }
 
+#[derive(Deserialize)]
+pub struct TrafficLightLogic {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub tl_type: String,
+    #[serde(rename = "programID")]
+    pub program_id: String,
+    pub offset: f64,
+    #[serde(rename = "phase")]
+    pub phases: Vec<Phase>,
+}
+
+#[derive(Deserialize)]
+pub struct Phase {
+    pub duration: f64,
+    /// One character per link index
+    pub state: String,
+    /// Only used by actuated and delay-based programs
+    #[serde(rename = "minDur")]
+    pub min_duration: Option<f64>,
+    #[serde(rename = "maxDur")]
+    pub max_duration: Option<f64>,
+}
+
 #[derive(Deserialize)]
 pub enum Direction {
     #[serde(rename = "s")]
@@ -13,9 +13,9 @@ use map_model::{IntersectionID, Map};
 use sim::{IndividTrip, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose};
 
 fn main() -> Result<()> {
-    test_lane_changing(&import_map(abstio::path(
-        "../tests/input/lane_selection.osm",
-    )))?;
+    let lane_selection = import_map(abstio::path("../tests/input/lane_selection.osm"));
+    test_lane_changing(&lane_selection)?;
+    test_leaving_through_border(&lane_selection)?;
     test_map_importer()?;
     check_proposals()?;
     smoke_test()?;
@@ -236,3 +236,48 @@ fn test_lane_changing(map: &Map) -> Result<()> {
 
     Ok(())
 }
+
+/// Cars that drive off the map through a border shouldn't be tracked by Analytics anymore.
+/// Otherwise savestates and multi-day runs keep growing.
+fn test_leaving_through_border(map: &Map) -> Result<()> {
+    let mut scenario = Scenario::empty(map, "leaving_through_border");
+    for idx in 0..10 {
+        scenario.people.push(PersonSpec {
+            orig_id: None,
+            origin: TripEndpoint::Border(IntersectionID(8)),
+            trips: vec![IndividTrip::new(
+                Time::START_OF_DAY + Duration::seconds(5.0 * (idx as f64)),
+                TripPurpose::Shopping,
+                TripEndpoint::Border(IntersectionID(0)),
+                TripMode::Drive,
+            )],
+        });
+    }
+
+    let mut opts = sim::SimOptions::new("test_leaving_through_border");
+    opts.alerts = sim::AlertHandler::Silence;
+    let mut sim = sim::Sim::new(map, opts);
+    let mut rng = sim::SimFlags::for_test("test_leaving_through_border").make_rng();
+    scenario.instantiate(&mut sim, map, &mut rng, &mut Timer::throwaway());
+    while !sim.is_done() {
+        sim.tiny_step(map, &mut None);
+    }
+
+    let left = sim
+        .get_analytics()
+        .finished_trips
+        .iter()
+        .filter(|(_, _, _, dt)| dt.is_some())
+        .count();
+    if left != 10 {
+        anyhow::bail!("Only {} of 10 cars left through the border", left);
+    }
+    let remaining = sim.get_analytics().num_vehicles_in_progress();
+    if remaining != 0 {
+        anyhow::bail!(
+            "{} vehicles are still tracked after every car left the map",
+            remaining
+        );
+    }
+    Ok(())
+}
@@ -15,6 +15,20 @@ pub struct TrafficSignal {
     /// order of ascending `start_time_seconds`, the first plan must begin at `0` (midnight), and
     /// the last plan must not start after 24 hours.
     pub plans: Vec<Plan>,
+    /// If present, buses and trains approaching the signal can bend its timing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub transit_priority: Option<TransitPriority>,
+}
+
+/// Transit signal priority limits how much a signal may change its timing for buses and trains.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TransitPriority {
+    /// When a stage serving an approaching bus or train is about to end, it may be held green for
+    /// up to this many more seconds.
+    pub max_extension_seconds: usize,
+    /// When a bus or train is waiting at a red light, the conflicting stage may end up to this
+    /// many seconds early.
+    pub max_truncation_seconds: usize,
 }
 
 /// A plan describes how a traffic signal is configured during some period of time. Multiple plans
@@ -60,6 +74,11 @@ pub enum StageType {
     /// is 20, and additional is 40, the maximum cycle duration is 60.
     /// If there are crosswalks, the minimum is the minimum for the maximum crosswalks
     Variable(usize, usize, usize),
+    /// Minimum, Maximum, Passage
+    /// Actuated by loop detectors on the incoming lanes. After the minimum, the stage is extended
+    /// by the passage time whenever a vehicle calls for it, up to the maximum. A stage nobody
+    /// calls for is skipped.
+    Actuated(usize, usize, usize),
 }
 
 /// A movement through an intersection.